@@ -1,8 +1,13 @@
 use std::collections::BTreeMap;
 use std::io::{stdout, Seek, SeekFrom, Write};
+use std::path::Path;
 
+use anyhow::ensure;
+use fil_proofs_tooling::synthetic_replicas::{SyntheticReplicas, MANIFEST_NAME};
 use fil_proofs_tooling::{measure, Metadata};
-use filecoin_proofs::constants::{POREP_PARTITIONS, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT};
+use filecoin_proofs::constants::{
+    DefaultTreeHasher, POREP_PARTITIONS, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT,
+};
 use filecoin_proofs::types::{
     PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, SectorSize,
     UnpaddedBytesAmount,
@@ -25,6 +30,7 @@ const SECTOR_ID: u64 = 0;
 #[serde(rename_all = "kebab-case")]
 struct Inputs {
     sector_size: usize,
+    sectors: usize,
 }
 
 #[derive(Serialize)]
@@ -52,9 +58,123 @@ impl Report {
     }
 }
 
-pub fn run(sector_size: usize) -> anyhow::Result<()> {
-    info!("Benchy Election PoSt: sector-size={}", sector_size,);
+/// Benchmarks election PoSt over the synthetic replicas in `replicas_dir` if given, or over a
+/// single freshly sealed sector otherwise.
+pub fn run(sector_size: usize, replicas_dir: Option<&Path>) -> anyhow::Result<()> {
+    info!(
+        "Benchy Election PoSt: sector-size={}, replicas={:?}",
+        sector_size, replicas_dir
+    );
+
+    // Store the replica's private and publicly facing info for proving and verifying respectively.
+    let (pub_replica_info, priv_replica_info) = match replicas_dir {
+        Some(dir) => load_synthetic_replicas(sector_size, dir)?,
+        None => seal_replica(sector_size)?,
+    };
+    let sectors = priv_replica_info.len();
+
+    // Measure PoSt generation and verification.
+    let post_config = PoStConfig {
+        sector_size: SectorSize(sector_size as u64),
+        challenge_count: POST_CHALLENGE_COUNT,
+        challenged_nodes: POST_CHALLENGED_NODES,
+        priority: true,
+    };
+
+    let gen_candidates_measurement = measure(|| {
+        generate_candidates(
+            post_config,
+            &RANDOMNESS,
+            CHALLENGE_COUNT,
+            &priv_replica_info,
+            PROVER_ID,
+        )
+    })
+    .expect("failed to generate post candidates");
+
+    let candidates = &gen_candidates_measurement.return_value;
+
+    let gen_post_measurement = measure(|| {
+        generate_post(
+            post_config,
+            &RANDOMNESS,
+            &priv_replica_info,
+            candidates
+                .iter()
+                .cloned()
+                .map(Into::into)
+                .collect::<Vec<_>>(),
+            PROVER_ID,
+        )
+    })
+    .expect("failed to generate PoSt");
+
+    let proof = &gen_post_measurement.return_value;
+
+    let verify_post_measurement = measure(|| {
+        verify_post(
+            post_config,
+            &RANDOMNESS,
+            CHALLENGE_COUNT,
+            proof,
+            &pub_replica_info,
+            &candidates
+                .iter()
+                .cloned()
+                .map(Into::into)
+                .collect::<Vec<_>>(),
+            PROVER_ID,
+        )
+    })
+    .expect("failed to verify PoSt");
+
+    // Create a JSON serializable report that we print to stdout (that will later be parsed using
+    // the CLI JSON parser `jq`).
+    let report = Report {
+        inputs: Inputs {
+            sector_size,
+            sectors,
+        },
+        outputs: Outputs {
+            candidates_cpu_time_ms: gen_candidates_measurement.cpu_time.as_millis() as u64,
+            proving_cpu_time_ms: gen_post_measurement.cpu_time.as_millis() as u64,
+            proving_wall_time_ms: gen_post_measurement.wall_time.as_millis() as u64,
+            verifying_cpu_time_ms: verify_post_measurement.cpu_time.as_millis() as u64,
+            verifying_wall_time_ms: verify_post_measurement.wall_time.as_millis() as u64,
+        },
+    };
+
+    report.print();
+    Ok(())
+}
 
+type ReplicaInfos = (
+    BTreeMap<SectorId, PublicReplicaInfo>,
+    BTreeMap<SectorId, PrivateReplicaInfo>,
+);
+
+fn load_synthetic_replicas(sector_size: usize, dir: &Path) -> anyhow::Result<ReplicaInfos> {
+    let replicas = SyntheticReplicas::load(dir.join(MANIFEST_NAME))?;
+    ensure!(
+        replicas.sector_size == sector_size as u64,
+        "synthetic replicas in {:?} are {} byte sectors, not {}",
+        dir,
+        replicas.sector_size,
+        sector_size
+    );
+
+    let mut pub_replica_info = BTreeMap::new();
+    let mut priv_replica_info = BTreeMap::new();
+    for replica in &replicas.replicas {
+        pub_replica_info.insert(replica.sector_id, replica.public_replica_info()?);
+        priv_replica_info.insert(replica.sector_id, replica.private_replica_info()?);
+    }
+
+    Ok((pub_replica_info, priv_replica_info))
+}
+
+/// Seals and commits a single sector of random data.
+fn seal_replica(sector_size: usize) -> anyhow::Result<ReplicaInfos> {
     let sector_size_unpadded_bytes_ammount =
         UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size as u64));
 
@@ -108,7 +228,7 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
     let cache_dir = tempfile::tempdir().unwrap();
     let sector_id = SectorId::from(SECTOR_ID);
 
-    let phase1_output = seal_pre_commit_phase1(
+    let phase1_output = seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
         porep_config,
         cache_dir.path(),
         staged_file.path(),
@@ -128,7 +248,7 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
     let seed = [0u8; 32];
     let comm_r = seal_pre_commit_output.comm_r;
 
-    let phase1_output = seal_commit_phase1(
+    let phase1_output = seal_commit_phase1::<DefaultTreeHasher, _>(
         porep_config,
         cache_dir.path(),
         PROVER_ID,
@@ -142,7 +262,6 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
     let _seal_commit_output =
         seal_commit_phase2(porep_config, phase1_output, PROVER_ID, sector_id)?;
 
-    // Store the replica's private and publicly facing info for proving and verifying respectively.
     let mut pub_replica_info: BTreeMap<SectorId, PublicReplicaInfo> = BTreeMap::new();
     let mut priv_replica_info: BTreeMap<SectorId, PrivateReplicaInfo> = BTreeMap::new();
 
@@ -152,75 +271,7 @@ pub fn run(sector_size: usize) -> anyhow::Result<()> {
         sector_id,
         PrivateReplicaInfo::new(sealed_path_string, comm_r, cache_dir.into_path())?,
     );
+    sealed_file.keep()?;
 
-    // Measure PoSt generation and verification.
-    let post_config = PoStConfig {
-        sector_size: SectorSize(sector_size as u64),
-        challenge_count: POST_CHALLENGE_COUNT,
-        challenged_nodes: POST_CHALLENGED_NODES,
-        priority: true,
-    };
-
-    let gen_candidates_measurement = measure(|| {
-        generate_candidates(
-            post_config,
-            &RANDOMNESS,
-            CHALLENGE_COUNT,
-            &priv_replica_info,
-            PROVER_ID,
-        )
-    })
-    .expect("failed to generate post candidates");
-
-    let candidates = &gen_candidates_measurement.return_value;
-
-    let gen_post_measurement = measure(|| {
-        generate_post(
-            post_config,
-            &RANDOMNESS,
-            &priv_replica_info,
-            candidates
-                .iter()
-                .cloned()
-                .map(Into::into)
-                .collect::<Vec<_>>(),
-            PROVER_ID,
-        )
-    })
-    .expect("failed to generate PoSt");
-
-    let proof = &gen_post_measurement.return_value;
-
-    let verify_post_measurement = measure(|| {
-        verify_post(
-            post_config,
-            &RANDOMNESS,
-            CHALLENGE_COUNT,
-            proof,
-            &pub_replica_info,
-            &candidates
-                .iter()
-                .cloned()
-                .map(Into::into)
-                .collect::<Vec<_>>(),
-            PROVER_ID,
-        )
-    })
-    .expect("failed to verify PoSt");
-
-    // Create a JSON serializable report that we print to stdout (that will later be parsed using
-    // the CLI JSON parser `jq`).
-    let report = Report {
-        inputs: Inputs { sector_size },
-        outputs: Outputs {
-            candidates_cpu_time_ms: gen_candidates_measurement.cpu_time.as_millis() as u64,
-            proving_cpu_time_ms: gen_post_measurement.cpu_time.as_millis() as u64,
-            proving_wall_time_ms: gen_post_measurement.wall_time.as_millis() as u64,
-            verifying_cpu_time_ms: verify_post_measurement.cpu_time.as_millis() as u64,
-            verifying_wall_time_ms: verify_post_measurement.wall_time.as_millis() as u64,
-        },
-    };
-
-    report.print();
-    Ok(())
+    Ok((pub_replica_info, priv_replica_info))
 }
@@ -208,7 +208,7 @@ pub fn run(
             replica_measurement.return_value.iter().zip(created.iter())
         {
             let measured = measure(|| {
-                let phase1_output = seal_commit_phase1(
+                let phase1_output = seal_commit_phase1::<FlarpHasher, _>(
                     cfg,
                     &replica_info.private_replica_info.cache_dir_path(),
                     PROVER_ID,
@@ -479,7 +479,7 @@ fn cache_porep_params(porep_config: PoRepConfig) {
     use storage_proofs::circuit::stacked::StackedCompound;
     use storage_proofs::stacked::StackedDrg;
 
-    let public_params = public_params(
+    let public_params = public_params::<FlarpHasher>(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )
@@ -1,8 +1,12 @@
 use std::io::{stdin, stdout};
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use clap::{value_t, App, Arg, SubCommand};
 
+use fil_proofs_tooling::synthetic_replicas::create_synthetic_replicas;
+use filecoin_proofs::constants::DefaultTreeHasher;
+
 use crate::flarp::FlarpInputs;
 
 mod election_post;
@@ -103,6 +107,43 @@ fn main() -> Result<()> {
                 .required(true)
                 .help("The data size in KiB")
                 .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("replicas")
+                .long("replicas")
+                .help("Prove the synthetic replicas in this directory instead of sealing a sector")
+                .takes_value(true),
+        );
+
+    let synthetic_replicas_cmd = SubCommand::with_name("synthetic-replicas")
+        .about("Create synthetic replicas to benchmark Election PoSt over many sectors")
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .required(true)
+                .help("The sector size in KiB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .required(true)
+                .help("How many replicas to create")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .required(true)
+                .help("The directory to create the replicas in")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rng-seed")
+                .long("rng-seed")
+                .default_value("5962be5d763d318d17db37325406bce5")
+                .help("The 16 byte, hex encoded, seed the replicas are generated from")
+                .takes_value(true),
         );
 
     let hash_cmd = SubCommand::with_name("hash-constraints")
@@ -157,6 +198,7 @@ fn main() -> Result<()> {
         .version("0.1")
         .subcommand(stacked_cmd)
         .subcommand(election_post_cmd)
+        .subcommand(synthetic_replicas_cmd)
         .subcommand(hash_cmd)
         .subcommand(flarp_cmd)
         .subcommand(merkleproof_cmd)
@@ -186,7 +228,24 @@ fn main() -> Result<()> {
             let sector_size_kibs = value_t!(m, "size", usize)
                 .expect("could not convert `size` CLI argument to `usize`");
             let sector_size = sector_size_kibs * 1024;
-            election_post::run(sector_size)?;
+            election_post::run(sector_size, m.value_of("replicas").map(Path::new))?;
+        }
+        ("synthetic-replicas", Some(m)) => {
+            let sector_size = value_t!(m, "size", u64)? * 1024;
+            let count = value_t!(m, "count", usize)?;
+            let output = PathBuf::from(m.value_of("output").unwrap());
+
+            let rng_seed = hex::decode(m.value_of("rng-seed").unwrap()).context("invalid seed")?;
+            ensure!(
+                rng_seed.len() == 16,
+                "the seed must be 16 bytes, got {}",
+                rng_seed.len()
+            );
+            let mut seed = [0u8; 16];
+            seed.copy_from_slice(&rng_seed);
+
+            create_synthetic_replicas::<DefaultTreeHasher>(sector_size, count, &output, seed)?;
+            println!("created {} synthetic replicas in {:?}", count, output);
         }
         ("hash-constraints", Some(_m)) => {
             hash_fns::run()?;
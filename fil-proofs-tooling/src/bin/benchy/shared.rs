@@ -6,7 +6,7 @@ use rayon::prelude::*;
 use tempfile::NamedTempFile;
 
 use fil_proofs_tooling::{measure, FuncMeasurement};
-use filecoin_proofs::constants::POREP_PARTITIONS;
+use filecoin_proofs::constants::{DefaultTreeHasher, POREP_PARTITIONS};
 use filecoin_proofs::types::{PaddedBytesAmount, PoRepConfig, SectorSize, UnpaddedBytesAmount};
 use filecoin_proofs::{
     add_piece, generate_piece_commitment, seal_pre_commit_phase1, seal_pre_commit_phase2,
@@ -147,7 +147,7 @@ pub fn create_replicas(
             .zip(piece_infos.par_iter())
             .map(
                 |((((cache_dir, staged_file), sealed_file), sector_id), piece_infos)| {
-                    seal_pre_commit_phase1(
+                    seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
                         porep_config,
                         cache_dir,
                         staged_file,
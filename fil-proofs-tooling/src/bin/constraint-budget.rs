@@ -0,0 +1,104 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use clap::{value_t, values_t, App, Arg};
+use fil_proofs_tooling::constraint_budget::{budget_report, diff_reports, BudgetReport};
+use log::info;
+
+fn main() -> Result<()> {
+    fil_logger::init();
+
+    let default_baseline = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("constraint-budget.json");
+    let default_baseline = default_baseline.to_string_lossy();
+
+    let matches = App::new("constraint-budget")
+        .version("0.1")
+        .about("Report the constraints of every production circuit and compare them against a baseline")
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .takes_value(true)
+                .default_value(&default_baseline)
+                .help("The baseline report to compare against"),
+        )
+        .arg(
+            Arg::with_name("sector-sizes")
+                .long("sector-sizes")
+                .takes_value(true)
+                .require_delimiter(true)
+                .value_delimiter(",")
+                .multiple(true)
+                .help("Comma-separated sector sizes, in bytes, to report (defaults to the baseline's)"),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .help("Namespace depth of the per-namespace breakdown (defaults to the baseline's)"),
+        )
+        .arg(
+            Arg::with_name("update")
+                .long("update")
+                .help("Overwrite the baseline with the current report instead of comparing"),
+        )
+        .get_matches();
+
+    let baseline_path = PathBuf::from(matches.value_of("baseline").unwrap());
+    let update = matches.is_present("update");
+    let baseline = if baseline_path.exists() {
+        Some(BudgetReport::load(&baseline_path)?)
+    } else {
+        None
+    };
+
+    let sector_sizes = if matches.is_present("sector-sizes") {
+        values_t!(matches, "sector-sizes", u64)?
+    } else {
+        match baseline {
+            Some(ref baseline) => baseline.sector_sizes.clone(),
+            None => vec![2048],
+        }
+    };
+    let depth = if matches.is_present("depth") {
+        value_t!(matches, "depth", usize)?
+    } else {
+        baseline
+            .as_ref()
+            .map(|baseline| baseline.depth)
+            .unwrap_or(2)
+    };
+
+    let report = budget_report(&sector_sizes, depth)?;
+
+    if update {
+        report.save(&baseline_path)?;
+        info!("wrote baseline to {:?}", baseline_path);
+        return Ok(());
+    }
+
+    serde_json::to_writer_pretty(stdout(), &report)?;
+    println!();
+
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => bail!(
+            "no baseline at {:?}, create it with --update",
+            baseline_path
+        ),
+    };
+
+    let diff = diff_reports(&baseline, &report);
+    if !diff.is_empty() {
+        for line in &diff {
+            eprintln!("{}", line);
+        }
+        bail!(
+            "constraint budget differs from the baseline at {:?}: if this is intended, bump \
+             storage_proofs::parameter_cache::VERSION and regenerate the baseline with --update",
+            baseline_path
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{stdout, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{ensure, Context, Result};
+use clap::{values_t, ArgMatches};
+use fil_proofs_tooling::daemon::PrivateReplica;
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::{Candidate, PieceInfo, PrivateReplicaInfo};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use storage_proofs::sector::SectorId;
+
+pub fn read_json<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let file = File::open(path).with_context(|| format!("could not open {}", path))?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("invalid JSON in {}", path))
+}
+
+/// Writes `value` to `--output` if given, to stdout otherwise.
+pub fn write_json<T: Serialize>(m: &ArgMatches, value: &T) -> Result<()> {
+    match m.value_of("output") {
+        Some(path) => {
+            let file = File::create(path).with_context(|| format!("could not create {}", path))?;
+            let mut writer = BufWriter::new(file);
+            serde_json::to_writer_pretty(&mut writer, value)?;
+            writer.flush()?;
+            info!("wrote {}", path);
+        }
+        None => {
+            let stdout = stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer_pretty(&mut stdout, value)?;
+            writeln!(stdout)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn path(m: &ArgMatches, name: &str) -> PathBuf {
+    PathBuf::from(m.value_of(name).expect("required argument"))
+}
+
+pub fn bytes32(m: &ArgMatches, name: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(m.value_of(name).expect("required argument"))
+        .with_context(|| format!("--{} must be hex encoded", name))?;
+    ensure!(
+        bytes.len() == 32,
+        "--{} must be 32 bytes, got {}",
+        name,
+        bytes.len()
+    );
+
+    let mut out = [0; 32];
+    out.copy_from_slice(&bytes);
+    Ok(out)
+}
+
+pub fn piece_infos(m: &ArgMatches) -> Result<Vec<PieceInfo>> {
+    values_t!(m, "piece-infos", String)?
+        .iter()
+        .map(|path| read_json(path))
+        .collect()
+}
+
+pub fn replicas(m: &ArgMatches) -> Result<Vec<PrivateReplica>> {
+    read_json(m.value_of("replicas").expect("required argument"))
+}
+
+pub fn private_replica_infos(
+    replicas: &[PrivateReplica],
+) -> Result<BTreeMap<SectorId, PrivateReplicaInfo<DefaultTreeHasher>>> {
+    replicas
+        .iter()
+        .map(|replica| {
+            Ok((
+                SectorId::from(replica.sector_id),
+                replica.private_replica_info()?,
+            ))
+        })
+        .collect()
+}
+
+pub fn read_candidates(m: &ArgMatches) -> Result<Vec<Candidate>> {
+    read_json(m.value_of("candidates").expect("required argument"))
+}
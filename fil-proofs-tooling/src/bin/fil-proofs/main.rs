@@ -0,0 +1,218 @@
+use anyhow::Result;
+use clap::{App, AppSettings, Arg, SubCommand};
+
+mod io;
+mod post;
+mod seal;
+
+/// A required `--<name> <value>` argument.
+fn arg(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .takes_value(true)
+        .required(true)
+        .help(help)
+}
+
+fn output() -> Arg<'static, 'static> {
+    arg(
+        "output",
+        "Write the JSON output to this file, instead of stdout",
+    )
+    .required(false)
+}
+
+fn sector_size() -> Arg<'static, 'static> {
+    arg("sector-size", "The sector size in bytes")
+}
+
+fn prover_id() -> Arg<'static, 'static> {
+    arg("prover-id", "The prover id, as 32 hex encoded bytes")
+}
+
+fn sector_id() -> Arg<'static, 'static> {
+    arg("sector-id", "The sector id")
+}
+
+fn ticket() -> Arg<'static, 'static> {
+    arg("ticket", "The sealing ticket, as 32 hex encoded bytes")
+}
+
+fn seed() -> Arg<'static, 'static> {
+    arg("seed", "The interactive seed, as 32 hex encoded bytes")
+}
+
+fn piece_infos() -> Arg<'static, 'static> {
+    arg(
+        "piece-infos",
+        "The pieces of the sector in order, as files written by add-piece",
+    )
+    .multiple(true)
+}
+
+fn precommit() -> Arg<'static, 'static> {
+    arg("precommit", "The commitments, as written by precommit2")
+}
+
+fn randomness() -> Arg<'static, 'static> {
+    arg("randomness", "The PoSt randomness, as 32 hex encoded bytes")
+}
+
+fn replicas() -> Arg<'static, 'static> {
+    arg(
+        "replicas",
+        "A JSON list of {sector_id, comm_r, replica_path, cache_dir} replicas",
+    )
+}
+
+fn main() -> Result<()> {
+    fil_logger::init();
+
+    let matches = App::new("fil-proofs")
+        .version("0.1")
+        .about("Run each step of sealing and PoSt, reading and writing JSON intermediates")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("comm-p")
+                .about("Compute the commitment of a piece")
+                .arg(arg("piece", "The piece file"))
+                .arg(arg("size", "The piece size, defaults to the file size").required(false))
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("add-piece")
+                .about("Append a piece to a staged sector, and compute its commitment")
+                .arg(arg("piece", "The piece file"))
+                .arg(arg("staged", "The staged sector, created if missing"))
+                .arg(arg("size", "The piece size, defaults to the file size").required(false))
+                .arg(
+                    arg(
+                        "existing-sizes",
+                        "The sizes of the pieces already in the staged sector, comma separated",
+                    )
+                    .required(false)
+                    .use_delimiter(true),
+                )
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("precommit1")
+                .about("Run the first pre-commit phase, which replicates the sector")
+                .arg(sector_size())
+                .arg(arg("cache", "The cache directory, created if missing"))
+                .arg(arg("staged", "The staged sector"))
+                .arg(arg("sealed", "Where to write the sealed sector"))
+                .arg(prover_id())
+                .arg(sector_id())
+                .arg(ticket())
+                .arg(piece_infos())
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("precommit2")
+                .about("Run the second pre-commit phase, which computes comm_r")
+                .arg(sector_size())
+                .arg(arg("phase1", "The output of precommit1"))
+                .arg(arg("cache", "The cache directory"))
+                .arg(arg("sealed", "The sealed sector"))
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("commit1")
+                .about("Run the first commit phase, which generates the vanilla proofs")
+                .arg(sector_size())
+                .arg(precommit())
+                .arg(arg("cache", "The cache directory"))
+                .arg(prover_id())
+                .arg(sector_id())
+                .arg(ticket())
+                .arg(seed())
+                .arg(piece_infos())
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("commit2")
+                .about("Run the second commit phase, which generates the SNARK proof")
+                .arg(sector_size())
+                .arg(arg("phase1", "The output of commit1"))
+                .arg(prover_id())
+                .arg(sector_id())
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-seal")
+                .about("Verify a seal proof, failing if it is invalid")
+                .arg(sector_size())
+                .arg(precommit())
+                .arg(arg("proof", "The proof, as written by commit2"))
+                .arg(prover_id())
+                .arg(sector_id())
+                .arg(ticket())
+                .arg(seed()),
+        )
+        .subcommand(
+            SubCommand::with_name("unseal")
+                .about("Unseal a range of a sealed sector")
+                .arg(sector_size())
+                .arg(arg("cache", "The cache directory"))
+                .arg(arg("sealed", "The sealed sector"))
+                .arg(precommit())
+                .arg(prover_id())
+                .arg(sector_id())
+                .arg(ticket())
+                .arg(
+                    arg("offset", "The unpadded offset of the range")
+                        .required(false)
+                        .default_value("0"),
+                )
+                .arg(arg("num-bytes", "The unpadded length of the range"))
+                .arg(arg("output", "Where to write the unsealed bytes")),
+        )
+        .subcommand(
+            SubCommand::with_name("candidates")
+                .about("Generate the election PoSt candidates")
+                .arg(sector_size())
+                .arg(randomness())
+                .arg(arg("challenge-count", "How many sectors to challenge"))
+                .arg(replicas())
+                .arg(prover_id())
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("post")
+                .about("Generate the election PoSt for the winning candidates")
+                .arg(sector_size())
+                .arg(randomness())
+                .arg(replicas())
+                .arg(arg("candidates", "The winning candidates, as JSON"))
+                .arg(prover_id())
+                .arg(output()),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-post")
+                .about("Verify an election PoSt, failing if it is invalid")
+                .arg(sector_size())
+                .arg(randomness())
+                .arg(arg("challenge-count", "How many sectors were challenged"))
+                .arg(replicas())
+                .arg(arg("candidates", "The winning candidates, as JSON"))
+                .arg(arg("proof", "The proofs, as written by post"))
+                .arg(prover_id()),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("comm-p", Some(m)) => seal::comm_p(m),
+        ("add-piece", Some(m)) => seal::add_piece(m),
+        ("precommit1", Some(m)) => seal::precommit1(m),
+        ("precommit2", Some(m)) => seal::precommit2(m),
+        ("commit1", Some(m)) => seal::commit1(m),
+        ("commit2", Some(m)) => seal::commit2(m),
+        ("verify-seal", Some(m)) => seal::verify_seal(m),
+        ("unseal", Some(m)) => seal::unseal(m),
+        ("candidates", Some(m)) => post::candidates(m),
+        ("post", Some(m)) => post::post(m),
+        ("verify-post", Some(m)) => post::verify_post(m),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use anyhow::{ensure, Result};
+use clap::{value_t, ArgMatches};
+use fil_proofs_tooling::daemon::GeneratePoStResult;
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::{post_config, PublicReplicaInfo};
+use storage_proofs::sector::SectorId;
+
+use crate::io::{bytes32, private_replica_infos, read_candidates, read_json, replicas, write_json};
+
+pub fn candidates(m: &ArgMatches) -> Result<()> {
+    let replicas = replicas(m)?;
+
+    let candidates = filecoin_proofs::generate_candidates(
+        post_config(value_t!(m, "sector-size", u64)?)?,
+        &bytes32(m, "randomness")?,
+        value_t!(m, "challenge-count", u64)?,
+        &private_replica_infos(&replicas)?,
+        bytes32(m, "prover-id")?,
+    )?;
+
+    write_json(m, &candidates)
+}
+
+pub fn post(m: &ArgMatches) -> Result<()> {
+    let replicas = replicas(m)?;
+
+    let proofs = filecoin_proofs::generate_post(
+        post_config(value_t!(m, "sector-size", u64)?)?,
+        &bytes32(m, "randomness")?,
+        &private_replica_infos(&replicas)?,
+        read_candidates(m)?,
+        bytes32(m, "prover-id")?,
+    )?;
+
+    write_json(m, &GeneratePoStResult { proofs })
+}
+
+pub fn verify_post(m: &ArgMatches) -> Result<()> {
+    let GeneratePoStResult { proofs } = read_json(m.value_of("proof").unwrap())?;
+    let replicas: BTreeMap<SectorId, PublicReplicaInfo<DefaultTreeHasher>> = replicas(m)?
+        .iter()
+        .map(|replica| {
+            Ok((
+                SectorId::from(replica.sector_id),
+                PublicReplicaInfo::new(replica.comm_r)?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    let is_valid = filecoin_proofs::verify_post(
+        post_config(value_t!(m, "sector-size", u64)?)?,
+        &bytes32(m, "randomness")?,
+        value_t!(m, "challenge-count", u64)?,
+        &proofs,
+        &replicas,
+        &read_candidates(m)?,
+        bytes32(m, "prover-id")?,
+    )?;
+
+    ensure!(is_valid, "the PoSt is invalid");
+    println!("the PoSt is valid");
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom};
+
+use anyhow::{ensure, Context, Result};
+use clap::{value_t, values_t, ArgMatches};
+use fil_proofs_tooling::daemon::SealCommitPhase2Result;
+use fil_proofs_tooling::serde_hex::hex_32;
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::types::{
+    SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output, UnpaddedByteIndex,
+    UnpaddedBytesAmount,
+};
+use filecoin_proofs::{porep_config, PieceInfo};
+use log::info;
+use serde::{Deserialize, Serialize};
+use storage_proofs::sector::SectorId;
+
+use crate::io::{bytes32, path, piece_infos, read_json, write_json};
+
+/// The output of `precommit2`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreCommit {
+    #[serde(with = "hex_32")]
+    pub comm_r: [u8; 32],
+    #[serde(with = "hex_32")]
+    pub comm_d: [u8; 32],
+}
+
+/// The piece size defaults to the length of the piece file.
+fn piece_size(m: &ArgMatches, piece: &File) -> Result<UnpaddedBytesAmount> {
+    let size = match m.value_of("size") {
+        Some(_) => value_t!(m, "size", u64)?,
+        None => piece.metadata()?.len(),
+    };
+
+    Ok(UnpaddedBytesAmount(size))
+}
+
+pub fn comm_p(m: &ArgMatches) -> Result<()> {
+    let piece = File::open(path(m, "piece")).context("could not open the piece")?;
+    let size = piece_size(m, &piece)?;
+
+    let piece_info = filecoin_proofs::generate_piece_commitment(piece, size)?;
+    write_json(m, &piece_info)
+}
+
+pub fn add_piece(m: &ArgMatches) -> Result<()> {
+    let piece = File::open(path(m, "piece")).context("could not open the piece")?;
+    let size = piece_size(m, &piece)?;
+    let existing_sizes: Vec<UnpaddedBytesAmount> = match m.value_of("existing-sizes") {
+        Some(_) => values_t!(m, "existing-sizes", u64)?
+            .into_iter()
+            .map(UnpaddedBytesAmount)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let staged_path = path(m, "staged");
+    let mut staged = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&staged_path)
+        .with_context(|| format!("could not open {:?}", staged_path))?;
+    staged.seek(SeekFrom::End(0))?;
+
+    let (aligned, commitment) = filecoin_proofs::add_piece(piece, staged, size, &existing_sizes)?;
+    info!(
+        "added {} bytes, {} of them alignment",
+        u64::from(aligned),
+        u64::from(aligned) - u64::from(size)
+    );
+
+    write_json(m, &PieceInfo::new(commitment, size)?)
+}
+
+pub fn precommit1(m: &ArgMatches) -> Result<()> {
+    let cache = path(m, "cache");
+    fs::create_dir_all(&cache).with_context(|| format!("could not create {:?}", cache))?;
+    // Sealing happens in place, over a copy of the staged sector.
+    let sealed = path(m, "sealed");
+    File::create(&sealed).with_context(|| format!("could not create {:?}", sealed))?;
+
+    let output = filecoin_proofs::seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        cache,
+        path(m, "staged"),
+        sealed,
+        bytes32(m, "prover-id")?,
+        SectorId::from(value_t!(m, "sector-id", u64)?),
+        bytes32(m, "ticket")?,
+        &piece_infos(m)?,
+    )?;
+
+    write_json(m, &output)
+}
+
+pub fn precommit2(m: &ArgMatches) -> Result<()> {
+    let phase1_output: SealPreCommitPhase1Output<DefaultTreeHasher> =
+        read_json(m.value_of("phase1").unwrap())?;
+
+    let SealPreCommitOutput { comm_r, comm_d } = filecoin_proofs::seal_pre_commit_phase2(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        phase1_output,
+        path(m, "cache"),
+        path(m, "sealed"),
+    )?;
+
+    write_json(m, &PreCommit { comm_r, comm_d })
+}
+
+pub fn commit1(m: &ArgMatches) -> Result<()> {
+    let PreCommit { comm_r, comm_d } = read_json(m.value_of("precommit").unwrap())?;
+
+    let output = filecoin_proofs::seal_commit_phase1::<DefaultTreeHasher, _>(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        path(m, "cache"),
+        bytes32(m, "prover-id")?,
+        SectorId::from(value_t!(m, "sector-id", u64)?),
+        bytes32(m, "ticket")?,
+        bytes32(m, "seed")?,
+        SealPreCommitOutput { comm_r, comm_d },
+        &piece_infos(m)?,
+    )?;
+
+    write_json(m, &output)
+}
+
+pub fn commit2(m: &ArgMatches) -> Result<()> {
+    let phase1_output: SealCommitPhase1Output<DefaultTreeHasher> =
+        read_json(m.value_of("phase1").unwrap())?;
+
+    let output = filecoin_proofs::seal_commit_phase2(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        phase1_output,
+        bytes32(m, "prover-id")?,
+        SectorId::from(value_t!(m, "sector-id", u64)?),
+    )?;
+
+    write_json(
+        m,
+        &SealCommitPhase2Result {
+            proof: output.proof,
+        },
+    )
+}
+
+pub fn verify_seal(m: &ArgMatches) -> Result<()> {
+    let PreCommit { comm_r, comm_d } = read_json(m.value_of("precommit").unwrap())?;
+    let SealCommitPhase2Result { proof } = read_json(m.value_of("proof").unwrap())?;
+
+    let is_valid = filecoin_proofs::verify_seal::<DefaultTreeHasher>(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        comm_r,
+        comm_d,
+        bytes32(m, "prover-id")?,
+        SectorId::from(value_t!(m, "sector-id", u64)?),
+        bytes32(m, "ticket")?,
+        bytes32(m, "seed")?,
+        &proof,
+    )?;
+
+    ensure!(is_valid, "the seal proof is invalid");
+    println!("the seal proof is valid");
+    Ok(())
+}
+
+pub fn unseal(m: &ArgMatches) -> Result<()> {
+    let PreCommit { comm_d, .. } = read_json(m.value_of("precommit").unwrap())?;
+
+    let written = filecoin_proofs::get_unsealed_range::<DefaultTreeHasher, _>(
+        porep_config(value_t!(m, "sector-size", u64)?)?,
+        path(m, "cache"),
+        path(m, "sealed"),
+        path(m, "output"),
+        bytes32(m, "prover-id")?,
+        SectorId::from(value_t!(m, "sector-id", u64)?),
+        comm_d,
+        bytes32(m, "ticket")?,
+        UnpaddedByteIndex(value_t!(m, "offset", u64)?),
+        UnpaddedBytesAmount(value_t!(m, "num-bytes", u64)?),
+    )?;
+
+    info!(
+        "wrote {} bytes to {}",
+        u64::from(written),
+        m.value_of("output").unwrap()
+    );
+    Ok(())
+}
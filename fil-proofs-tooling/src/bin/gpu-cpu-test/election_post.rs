@@ -3,7 +3,8 @@ use std::io::{Seek, SeekFrom, Write};
 use std::path::Path;
 
 use filecoin_proofs::constants::{
-    POREP_PARTITIONS, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT, SECTOR_SIZE_8_MIB,
+    DefaultTreeHasher, POREP_PARTITIONS, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT,
+    SECTOR_SIZE_8_MIB,
 };
 use filecoin_proofs::types::{
     PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig,
@@ -86,7 +87,7 @@ pub fn generate_seal_fixture(cache_dir_path: &Path) -> (SealPreCommitOutput, Vec
             *POREP_PARTITIONS.read().unwrap().get(&SECTOR_SIZE).unwrap(),
         ),
     };
-    let phase1_output = seal_pre_commit_phase1(
+    let phase1_output = seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
         porep_config,
         cache_dir_path,
         staged_file.path(),
@@ -119,7 +120,7 @@ pub fn do_generate_seal(
             *POREP_PARTITIONS.read().unwrap().get(&SECTOR_SIZE).unwrap(),
         ),
     };
-    let phase1_output = seal_commit_phase1(
+    let phase1_output = seal_commit_phase1::<DefaultTreeHasher, _>(
         porep_config,
         cache_dir_path,
         PROVER_ID,
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{value_t, App, Arg, ArgGroup};
+use fil_proofs_tooling::daemon::{Config, Endpoint, Server};
+use fil_proofs_tooling::metrics_exporter;
+
+fn main() -> Result<()> {
+    fil_logger::init();
+
+    let defaults = Config::default();
+    let default_max_concurrent = defaults.max_concurrent.to_string();
+    let default_max_queued = defaults.max_queued.to_string();
+
+    let matches = App::new("proving-daemon")
+        .version("0.1")
+        .about(
+            "Serve SNARK proving and verification over JSON-RPC, keeping Groth parameters loaded",
+        )
+        .arg(
+            Arg::with_name("socket")
+                .long("socket")
+                .takes_value(true)
+                .help("Listen on this Unix socket"),
+        )
+        .arg(
+            Arg::with_name("tcp")
+                .long("tcp")
+                .takes_value(true)
+                .help("Listen on this loopback address, e.g. 127.0.0.1:7777"),
+        )
+        .group(
+            ArgGroup::with_name("endpoint")
+                .args(&["socket", "tcp"])
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("max-concurrent")
+                .long("max-concurrent")
+                .takes_value(true)
+                .default_value(&default_max_concurrent)
+                .help("How many requests are proven at once"),
+        )
+        .arg(
+            Arg::with_name("max-queued")
+                .long("max-queued")
+                .takes_value(true)
+                .default_value(&default_max_queued)
+                .help("How many requests may wait, before new ones are rejected"),
+        )
+        .arg(
+            Arg::with_name("metrics")
+                .long("metrics")
+                .takes_value(true)
+                .help("Serve Prometheus metrics over HTTP on this address, e.g. 127.0.0.1:9100"),
+        )
+        .get_matches();
+
+    let endpoint = match matches.value_of("socket") {
+        Some(path) => Endpoint::Unix(PathBuf::from(path)),
+        None => {
+            let addr = matches.value_of("tcp").unwrap();
+            Endpoint::Tcp(
+                addr.parse()
+                    .with_context(|| format!("invalid address {}", addr))?,
+            )
+        }
+    };
+    let config = Config {
+        max_concurrent: value_t!(matches, "max-concurrent", usize)?,
+        max_queued: value_t!(matches, "max-queued", usize)?,
+    };
+
+    if let Some(addr) = matches.value_of("metrics") {
+        metrics_exporter::serve(
+            addr.parse()
+                .with_context(|| format!("invalid address {}", addr))?,
+        )?;
+    }
+
+    Server::bind(&endpoint, config)?.run()
+}
@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+
+use anyhow::{Context, Result};
+use bellperson::Circuit;
+use clap::{value_t, App, Arg};
+use fil_proofs_tooling::circuits::{with_blank_circuit, CircuitKind, CircuitVisitor};
+use log::info;
+use paired::bls12_381::Bls12;
+use storage_proofs::circuit::r1cs::R1CSExportCS;
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Json,
+    Binary,
+}
+
+struct Exporter<'a> {
+    format: Format,
+    output: Option<&'a str>,
+}
+
+impl<'a> CircuitVisitor for Exporter<'a> {
+    type Output = ();
+
+    fn visit<C: Circuit<Bls12>>(self, circuit: C) -> Result<()> {
+        let mut cs = R1CSExportCS::<Bls12>::new();
+        circuit
+            .synthesize(&mut cs)
+            .context("failed to synthesize circuit")?;
+
+        info!(
+            "exporting {} constraints, {} public inputs, {} private inputs",
+            cs.num_constraints(),
+            cs.num_inputs(),
+            cs.num_aux()
+        );
+
+        let writer: Box<dyn Write> = match self.output {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).with_context(|| format!("could not create {}", path))?,
+            )),
+            None => Box::new(BufWriter::new(stdout())),
+        };
+
+        match self.format {
+            Format::Json => cs.write_json(writer),
+            Format::Binary => cs.write_binary(writer),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    fil_logger::init();
+
+    let matches = App::new("r1cs-export")
+        .version("0.1")
+        .about(
+            "Export the R1CS of a blank proof circuit, for inspection outside of rust-fil-proofs",
+        )
+        .arg(
+            Arg::with_name("circuit")
+                .long("circuit")
+                .required(true)
+                .takes_value(true)
+                .possible_values(&CircuitKind::names())
+                .help("The circuit to export"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .required(true)
+                .takes_value(true)
+                .help("The sector size, in bytes, of the profile to export the circuit for"),
+        )
+        .arg(
+            Arg::with_name("challenges")
+                .long("challenges")
+                .default_value("1")
+                .takes_value(true)
+                .help("How many challenges the rational-post circuit verifies"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .default_value("json")
+                .takes_value(true)
+                .possible_values(&["json", "binary"])
+                .help("The export format, see `storage_proofs::circuit::r1cs`"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .help("The file to write to (defaults to stdout)"),
+        )
+        .get_matches();
+
+    let sector_size = value_t!(matches, "size", u64)?;
+    let challenges = value_t!(matches, "challenges", usize)?;
+    let output = matches.value_of("output");
+    let format = match matches.value_of("format").unwrap() {
+        "binary" => Format::Binary,
+        _ => Format::Json,
+    };
+
+    let kind = value_t!(matches, "circuit", CircuitKind)?;
+
+    with_blank_circuit(kind, sector_size, challenges, Exporter { format, output })
+}
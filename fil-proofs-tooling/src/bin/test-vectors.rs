@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::{values_t, App, Arg, SubCommand};
+use fil_proofs_tooling::test_vectors::{check_vector, generate_vectors, TestVectors};
+use filecoin_proofs::constants::{SECTOR_SIZE_2_KIB, SECTOR_SIZE_8_MIB};
+use log::info;
+
+/// Seed of the 2 KiB and 8 MiB vectors `generate` writes by default.
+const DEFAULT_RNG_SEED: &str = "5962be5d763d318d17db37325406bce5";
+
+fn main() -> Result<()> {
+    fil_logger::init();
+
+    let default_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-vectors.json");
+    let default_path = default_path.to_string_lossy();
+    let default_sizes = format!("{},{}", SECTOR_SIZE_2_KIB, SECTOR_SIZE_8_MIB);
+
+    let path_arg = Arg::with_name("path")
+        .long("path")
+        .takes_value(true)
+        .default_value(&default_path)
+        .help("The test vectors file");
+
+    let generate_cmd = SubCommand::with_name("generate")
+        .about("Generate test vectors from seeded inputs")
+        .arg(path_arg.clone())
+        .arg(
+            Arg::with_name("sector-sizes")
+                .long("sector-sizes")
+                .takes_value(true)
+                .require_delimiter(true)
+                .value_delimiter(",")
+                .multiple(true)
+                .default_value(&default_sizes)
+                .help("Comma-separated sector sizes, in bytes, to generate vectors for"),
+        )
+        .arg(
+            Arg::with_name("rng-seed")
+                .long("rng-seed")
+                .takes_value(true)
+                .default_value(DEFAULT_RNG_SEED)
+                .help("The 16 byte, hex encoded, seed the inputs are generated from"),
+        );
+
+    let check_cmd = SubCommand::with_name("check")
+        .about("Re-derive every value of the test vectors and report the first mismatch")
+        .arg(path_arg);
+
+    let matches = App::new("test-vectors")
+        .version("0.1")
+        .about("Generate and check conformance test vectors for sealing and PoSt")
+        .subcommand(generate_cmd)
+        .subcommand(check_cmd)
+        .get_matches();
+
+    match matches.subcommand() {
+        ("generate", Some(m)) => {
+            let path = PathBuf::from(m.value_of("path").unwrap());
+            let sector_sizes = values_t!(m, "sector-sizes", u64)?;
+
+            let rng_seed = hex::decode(m.value_of("rng-seed").unwrap()).context("invalid seed")?;
+            ensure!(
+                rng_seed.len() == 16,
+                "the seed must be 16 bytes, got {}",
+                rng_seed.len()
+            );
+            let mut seed = [0u8; 16];
+            seed.copy_from_slice(&rng_seed);
+
+            generate_vectors(&sector_sizes, seed)?.save(&path)?;
+            info!("wrote test vectors to {:?}", path);
+        }
+        ("check", Some(m)) => {
+            let path = PathBuf::from(m.value_of("path").unwrap());
+            let vectors = TestVectors::load(&path)?;
+
+            for vector in &vectors.vectors {
+                if let Some(mismatch) = check_vector(vector)? {
+                    bail!("test vectors at {:?} do not match: {}", path, mismatch);
+                }
+                info!("{} byte sector vector matches", vector.inputs.sector_size);
+            }
+            println!("{} test vectors match", vectors.vectors.len());
+        }
+        _ => bail!("a subcommand is required, see --help"),
+    }
+
+    Ok(())
+}
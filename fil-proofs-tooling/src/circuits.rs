@@ -0,0 +1,193 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bellperson::Circuit;
+use paired::bls12_381::{Bls12, Fr};
+
+use filecoin_proofs::constants::{
+    DefaultPieceHasher, DefaultTreeHasher, POREP_PARTITIONS, POST_CHALLENGED_NODES,
+    POST_CHALLENGE_COUNT,
+};
+use filecoin_proofs::parameters::{post_public_params, public_params, tree_r_last_arity};
+use filecoin_proofs::types::{PaddedBytesAmount, PoStConfig, SectorSize, OCT_ARITY, QUAD_ARITY};
+use storage_proofs::circuit::election_post::{ElectionPoStCircuit, ElectionPoStCompound};
+use storage_proofs::circuit::por::{PoRCircuit, PoRCompound};
+use storage_proofs::circuit::rational_post::{RationalPoStCircuit, RationalPoStCompound};
+use storage_proofs::circuit::stacked::StackedCompound;
+use storage_proofs::compound_proof::CompoundProof;
+use storage_proofs::election_post::ElectionPoSt;
+use storage_proofs::hasher::{PoseidonArity, PoseidonEngine};
+use storage_proofs::merklepor::{self, MerklePoR};
+use storage_proofs::rational_post::{self, RationalPoSt};
+use storage_proofs::stacked::StackedDrg;
+use storage_proofs::util::NODE_SIZE;
+
+type H = DefaultTreeHasher;
+
+/// The production circuits, as synthesized for a sector profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitKind {
+    Stacked,
+    ElectionPoSt,
+    RationalPoSt,
+    PoR,
+}
+
+impl CircuitKind {
+    pub const ALL: [CircuitKind; 4] = [
+        CircuitKind::Stacked,
+        CircuitKind::ElectionPoSt,
+        CircuitKind::RationalPoSt,
+        CircuitKind::PoR,
+    ];
+
+    pub fn names() -> Vec<&'static str> {
+        Self::ALL.iter().map(CircuitKind::name).collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CircuitKind::Stacked => "stacked",
+            CircuitKind::ElectionPoSt => "election-post",
+            CircuitKind::RationalPoSt => "rational-post",
+            CircuitKind::PoR => "por",
+        }
+    }
+}
+
+impl fmt::Display for CircuitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for CircuitKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CircuitKind::ALL
+            .iter()
+            .find(|kind| kind.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown circuit: {}", s))
+    }
+}
+
+/// Consumes a synthesizable circuit, whichever its concrete type is.
+pub trait CircuitVisitor {
+    type Output;
+
+    fn visit<C: Circuit<Bls12>>(self, circuit: C) -> Result<Self::Output>;
+}
+
+/// Builds the blank circuit of `kind` for the sector profile of `sector_size` and
+/// hands it to `visitor`. `challenges` is only used by the rational PoSt circuit.
+pub fn with_blank_circuit<V: CircuitVisitor>(
+    kind: CircuitKind,
+    sector_size: u64,
+    challenges: usize,
+    visitor: V,
+) -> Result<V::Output> {
+    if kind == CircuitKind::RationalPoSt {
+        return rational_post_circuit(sector_size, challenges, visitor);
+    }
+
+    match (kind, tree_r_last_arity(PaddedBytesAmount(sector_size))?) {
+        (CircuitKind::Stacked, QUAD_ARITY) => {
+            stacked_circuit::<typenum::U4, _>(sector_size, visitor)
+        }
+        (CircuitKind::Stacked, OCT_ARITY) => {
+            stacked_circuit::<typenum::U8, _>(sector_size, visitor)
+        }
+        (CircuitKind::ElectionPoSt, QUAD_ARITY) => {
+            election_post_circuit::<typenum::U4, _>(sector_size, visitor)
+        }
+        (CircuitKind::ElectionPoSt, OCT_ARITY) => {
+            election_post_circuit::<typenum::U8, _>(sector_size, visitor)
+        }
+        (CircuitKind::PoR, QUAD_ARITY) => por_circuit::<typenum::U4, _>(sector_size, visitor),
+        (CircuitKind::PoR, OCT_ARITY) => por_circuit::<typenum::U8, _>(sector_size, visitor),
+        (_, arity) => bail!("unsupported tree_r_last arity: {}", arity),
+    }
+}
+
+fn stacked_circuit<U, V>(sector_size: u64, visitor: V) -> Result<V::Output>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+    V: CircuitVisitor,
+{
+    let partitions = *POREP_PARTITIONS
+        .read()
+        .unwrap()
+        .get(&sector_size)
+        .with_context(|| format!("no partitions known for sector size {}", sector_size))?;
+    let public_params = public_params::<H>(PaddedBytesAmount(sector_size), partitions as usize)?;
+
+    visitor.visit(
+        <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
+            _,
+            StackedDrg<H, DefaultPieceHasher, U>,
+            _,
+        >>::blank_circuit(&public_params),
+    )
+}
+
+fn election_post_circuit<U, V>(sector_size: u64, visitor: V) -> Result<V::Output>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+    V: CircuitVisitor,
+{
+    let public_params = post_public_params(PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count: POST_CHALLENGE_COUNT,
+        challenged_nodes: POST_CHALLENGED_NODES,
+        priority: false,
+    })?;
+
+    visitor.visit(<ElectionPoStCompound<H, U> as CompoundProof<
+        Bls12,
+        ElectionPoSt<H, U>,
+        ElectionPoStCircuit<Bls12, H, U>,
+    >>::blank_circuit(&public_params))
+}
+
+fn rational_post_circuit<V: CircuitVisitor>(
+    sector_size: u64,
+    challenges_count: usize,
+    visitor: V,
+) -> Result<V::Output> {
+    let public_params = rational_post::PublicParams {
+        sector_size,
+        challenges_count,
+    };
+
+    visitor.visit(<RationalPoStCompound<H> as CompoundProof<
+        Bls12,
+        RationalPoSt<H>,
+        RationalPoStCircuit<Bls12, H>,
+    >>::blank_circuit(&public_params))
+}
+
+fn por_circuit<U, V>(sector_size: u64, visitor: V) -> Result<V::Output>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+    V: CircuitVisitor,
+{
+    let public_params = merklepor::PublicParams {
+        leaves: sector_size as usize / NODE_SIZE,
+        private: false,
+    };
+
+    visitor.visit(<PoRCompound<H, U> as CompoundProof<
+        Bls12,
+        MerklePoR<H, U>,
+        PoRCircuit<U, Bls12, H>,
+    >>::blank_circuit(&public_params))
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use bellperson::Circuit;
+use paired::bls12_381::Bls12;
+use serde::{Deserialize, Serialize};
+use storage_proofs::circuit::metric::{MetricCS, NamespaceMetrics};
+use storage_proofs::parameter_cache::VERSION;
+
+use crate::circuits::{with_blank_circuit, CircuitKind, CircuitVisitor};
+
+/// Challenges used when synthesizing the rational PoSt circuit.
+const RATIONAL_POST_CHALLENGES: usize = 1;
+
+/// Constraints and inputs of a synthesized circuit, in total and per namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBudget {
+    pub constraints: usize,
+    pub inputs: usize,
+    pub aux: usize,
+    pub namespaces: BTreeMap<String, NamespaceMetrics>,
+}
+
+/// Budget of every production circuit for a set of sector profiles, keyed by
+/// `<circuit>-<sector size>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BudgetReport {
+    /// The `storage_proofs::parameter_cache::VERSION` the report was generated with.
+    pub parameter_cache_version: usize,
+    pub sector_sizes: Vec<u64>,
+    /// Namespace depth of the per-namespace breakdown.
+    pub depth: usize,
+    pub circuits: BTreeMap<String, CircuitBudget>,
+}
+
+impl BudgetReport {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("could not open {:?}", path.as_ref()))?;
+        let report = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid budget report {:?}", path.as_ref()))?;
+
+        Ok(report)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("could not create {:?}", path.as_ref()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+}
+
+struct Metrics {
+    depth: usize,
+}
+
+impl CircuitVisitor for Metrics {
+    type Output = CircuitBudget;
+
+    fn visit<C: Circuit<Bls12>>(self, circuit: C) -> Result<CircuitBudget> {
+        let mut cs = MetricCS::<Bls12>::new();
+        circuit
+            .synthesize(&mut cs)
+            .context("failed to synthesize circuit")?;
+
+        Ok(CircuitBudget {
+            constraints: cs.num_constraints(),
+            inputs: cs.num_inputs(),
+            aux: cs.num_aux(),
+            namespaces: cs.namespace_metrics(self.depth),
+        })
+    }
+}
+
+/// Synthesizes every production circuit for each of the `sector_sizes`.
+pub fn budget_report(sector_sizes: &[u64], depth: usize) -> Result<BudgetReport> {
+    let mut circuits = BTreeMap::new();
+
+    for &sector_size in sector_sizes {
+        for &kind in CircuitKind::ALL.iter() {
+            let budget = with_blank_circuit(
+                kind,
+                sector_size,
+                RATIONAL_POST_CHALLENGES,
+                Metrics { depth },
+            )
+            .with_context(|| format!("failed to synthesize {} for {}", kind, sector_size))?;
+            circuits.insert(format!("{}-{}", kind, sector_size), budget);
+        }
+    }
+
+    Ok(BudgetReport {
+        parameter_cache_version: VERSION,
+        sector_sizes: sector_sizes.to_vec(),
+        depth,
+        circuits,
+    })
+}
+
+/// Lists, one change per line, how `current` differs from `baseline`. An empty
+/// result means the constraint budget didn't change.
+pub fn diff_reports(baseline: &BudgetReport, current: &BudgetReport) -> Vec<String> {
+    let mut diff = Vec::new();
+
+    if baseline.parameter_cache_version != current.parameter_cache_version {
+        diff.push(format!(
+            "parameter_cache::VERSION: {} -> {}",
+            baseline.parameter_cache_version, current.parameter_cache_version
+        ));
+    }
+
+    for name in union_keys(&baseline.circuits, &current.circuits) {
+        match (baseline.circuits.get(name), current.circuits.get(name)) {
+            (Some(_), None) => diff.push(format!("{}: removed", name)),
+            (None, Some(_)) => diff.push(format!("{}: added", name)),
+            (Some(old), Some(new)) => {
+                diff_count(
+                    &mut diff,
+                    name,
+                    "constraints",
+                    old.constraints,
+                    new.constraints,
+                );
+                diff_count(&mut diff, name, "inputs", old.inputs, new.inputs);
+                diff_count(&mut diff, name, "aux", old.aux, new.aux);
+
+                for namespace in union_keys(&old.namespaces, &new.namespaces) {
+                    let old = old.namespaces.get(namespace).copied().unwrap_or_default();
+                    let new = new.namespaces.get(namespace).copied().unwrap_or_default();
+                    let label = format!("{} [{}]", name, namespace);
+
+                    diff_count(
+                        &mut diff,
+                        &label,
+                        "constraints",
+                        old.constraints,
+                        new.constraints,
+                    );
+                    diff_count(&mut diff, &label, "inputs", old.inputs, new.inputs);
+                    diff_count(&mut diff, &label, "aux", old.aux, new.aux);
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    diff
+}
+
+fn union_keys<'a, V>(
+    a: &'a BTreeMap<String, V>,
+    b: &'a BTreeMap<String, V>,
+) -> BTreeSet<&'a String> {
+    a.keys().chain(b.keys()).collect()
+}
+
+fn diff_count(diff: &mut Vec<String>, label: &str, what: &str, old: usize, new: usize) {
+    if old != new {
+        diff.push(format!(
+            "{} {}: {} -> {} ({:+})",
+            label,
+            what,
+            old,
+            new,
+            new as i64 - old as i64
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(constraints: usize, namespaces: &[(&str, usize)]) -> CircuitBudget {
+        CircuitBudget {
+            constraints,
+            inputs: 2,
+            aux: 10,
+            namespaces: namespaces
+                .iter()
+                .map(|&(name, constraints)| {
+                    (
+                        name.to_string(),
+                        NamespaceMetrics {
+                            constraints,
+                            inputs: 0,
+                            aux: 0,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn report(circuits: Vec<(&str, CircuitBudget)>) -> BudgetReport {
+        BudgetReport {
+            parameter_cache_version: VERSION,
+            sector_sizes: vec![2048],
+            depth: 1,
+            circuits: circuits
+                .into_iter()
+                .map(|(name, budget)| (name.to_string(), budget))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_reports() {
+        let baseline = report(vec![
+            ("stacked-2048", budget(30, &[("a", 10), ("b", 20)])),
+            ("por-2048", budget(5, &[])),
+        ]);
+        assert!(diff_reports(&baseline, &baseline).is_empty());
+
+        let current = report(vec![
+            (
+                "stacked-2048",
+                budget(27, &[("a", 10), ("b", 15), ("c", 2)]),
+            ),
+            ("election-post-2048", budget(5, &[])),
+        ]);
+        assert_eq!(
+            diff_reports(&baseline, &current),
+            vec![
+                "election-post-2048: added",
+                "por-2048: removed",
+                "stacked-2048 constraints: 30 -> 27 (-3)",
+                "stacked-2048 [b] constraints: 20 -> 15 (-5)",
+                "stacked-2048 [c] constraints: 0 -> 2 (+2)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+
+use anyhow::{ensure, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::methods::{
+    GeneratePoStParams, GeneratePoStResult, SealCommitPhase2Params, SealCommitPhase2Result,
+    VerifyBatchSealParams, VerifyPoStParams, VerifyResult, VerifySealParams,
+};
+use super::queue::Status;
+use super::rpc::{Request, Response};
+use super::Endpoint;
+
+/// A blocking client, with one request in flight at a time. Errors returned by the daemon
+/// can be downcast to `RpcError`.
+pub struct Client {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+    next_id: u64,
+}
+
+impl Client {
+    pub fn connect(endpoint: &Endpoint) -> Result<Self> {
+        let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = match endpoint {
+            Endpoint::Unix(path) => {
+                let stream = UnixStream::connect(path)
+                    .with_context(|| format!("could not connect to {}", endpoint))?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+            Endpoint::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)
+                    .with_context(|| format!("could not connect to {}", endpoint))?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+        };
+
+        Ok(Client {
+            reader: BufReader::new(reader),
+            writer,
+            next_id: 0,
+        })
+    }
+
+    pub fn call<P: Serialize, T: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<T> {
+        self.next_id += 1;
+        let id = json!(self.next_id);
+        let request = Request::new(id.clone(), method, serde_json::to_value(params)?);
+
+        serde_json::to_writer(&mut self.writer, &request)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        ensure!(
+            self.reader.read_line(&mut line)? > 0,
+            "the daemon closed the connection"
+        );
+        let response: Response = serde_json::from_str(&line).context("invalid response")?;
+        ensure!(
+            response.id == id,
+            "expected a response to {}, got {}",
+            id,
+            response.id
+        );
+
+        if let Some(err) = response.error {
+            return Err(err.into());
+        }
+        Ok(serde_json::from_value(
+            response.result.unwrap_or(Value::Null),
+        )?)
+    }
+
+    pub fn status(&mut self) -> Result<Status> {
+        self.call("status", json!({}))
+    }
+
+    pub fn seal_commit_phase2(&mut self, params: &SealCommitPhase2Params) -> Result<Vec<u8>> {
+        let result: SealCommitPhase2Result = self.call("seal_commit_phase2", params)?;
+        Ok(result.proof)
+    }
+
+    pub fn generate_post(&mut self, params: &GeneratePoStParams) -> Result<Vec<Vec<u8>>> {
+        let result: GeneratePoStResult = self.call("generate_post", params)?;
+        Ok(result.proofs)
+    }
+
+    pub fn verify_seal(&mut self, params: &VerifySealParams) -> Result<bool> {
+        let result: VerifyResult = self.call("verify_seal", params)?;
+        Ok(result.is_valid)
+    }
+
+    pub fn verify_batch_seal(&mut self, params: &VerifyBatchSealParams) -> Result<bool> {
+        let result: VerifyResult = self.call("verify_batch_seal", params)?;
+        Ok(result.is_valid)
+    }
+
+    pub fn verify_post(&mut self, params: &VerifyPoStParams) -> Result<bool> {
+        let result: VerifyResult = self.call("verify_post", params)?;
+        Ok(result.is_valid)
+    }
+}
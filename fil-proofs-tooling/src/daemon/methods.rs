@@ -0,0 +1,296 @@
+//! The proving methods served by the daemon. 32 byte values and proofs are hex encoded.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::types::SealCommitPhase1Output;
+use filecoin_proofs::{
+    porep_config, post_config, Candidate, PrivateReplicaInfo, PublicReplicaInfo,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use storage_proofs::sector::SectorId;
+
+use crate::serde_hex::{hex_32, hex_bytes, hex_bytes_vec};
+
+use super::queue::Work;
+use super::rpc::{RpcError, INVALID_PARAMS, METHOD_NOT_FOUND};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealCommitPhase2Params {
+    pub sector_size: u64,
+    pub phase1_output: SealCommitPhase1Output<DefaultTreeHasher>,
+    #[serde(with = "hex_32")]
+    pub prover_id: [u8; 32],
+    pub sector_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealCommitPhase2Result {
+    #[serde(with = "hex_bytes")]
+    pub proof: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealProof {
+    #[serde(with = "hex_32")]
+    pub comm_r: [u8; 32],
+    #[serde(with = "hex_32")]
+    pub comm_d: [u8; 32],
+    #[serde(with = "hex_32")]
+    pub prover_id: [u8; 32],
+    pub sector_id: u64,
+    #[serde(with = "hex_32")]
+    pub ticket: [u8; 32],
+    #[serde(with = "hex_32")]
+    pub seed: [u8; 32],
+    #[serde(with = "hex_bytes")]
+    pub proof: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifySealParams {
+    pub sector_size: u64,
+    #[serde(flatten)]
+    pub seal: SealProof,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyBatchSealParams {
+    pub sector_size: u64,
+    pub seals: Vec<SealProof>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateReplica {
+    pub sector_id: u64,
+    #[serde(with = "hex_32")]
+    pub comm_r: [u8; 32],
+    pub replica_path: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+impl PrivateReplica {
+    pub fn private_replica_info(&self) -> Result<PrivateReplicaInfo<DefaultTreeHasher>> {
+        let access = self
+            .replica_path
+            .to_str()
+            .with_context(|| format!("invalid replica path {:?}", self.replica_path))?
+            .to_string();
+        PrivateReplicaInfo::new(access, self.comm_r, self.cache_dir.clone())
+            .with_context(|| format!("invalid replica for sector {}", self.sector_id))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicReplica {
+    pub sector_id: u64,
+    #[serde(with = "hex_32")]
+    pub comm_r: [u8; 32],
+}
+
+/// The replica paths must be readable by the daemon.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GeneratePoStParams {
+    pub sector_size: u64,
+    #[serde(with = "hex_32")]
+    pub randomness: [u8; 32],
+    pub replicas: Vec<PrivateReplica>,
+    pub winners: Vec<Candidate>,
+    #[serde(with = "hex_32")]
+    pub prover_id: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratePoStResult {
+    #[serde(with = "hex_bytes_vec")]
+    pub proofs: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VerifyPoStParams {
+    pub sector_size: u64,
+    #[serde(with = "hex_32")]
+    pub randomness: [u8; 32],
+    pub challenge_count: u64,
+    #[serde(with = "hex_bytes_vec")]
+    pub proofs: Vec<Vec<u8>>,
+    pub replicas: Vec<PublicReplica>,
+    pub winners: Vec<Candidate>,
+    #[serde(with = "hex_32")]
+    pub prover_id: [u8; 32],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub is_valid: bool,
+}
+
+/// Parses the params of a proving method, and defers the call itself.
+pub(crate) fn prepare(method: &str, params: Value) -> std::result::Result<Work, RpcError> {
+    match method {
+        "seal_commit_phase2" => defer(params, seal_commit_phase2),
+        "generate_post" => defer(params, generate_post),
+        "verify_seal" => defer(params, verify_seal),
+        "verify_batch_seal" => defer(params, verify_batch_seal),
+        "verify_post" => defer(params, verify_post),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+fn defer<P, T>(params: Value, method: fn(P) -> Result<T>) -> std::result::Result<Work, RpcError>
+where
+    P: 'static + DeserializeOwned + Send,
+    T: Serialize,
+{
+    let params: P = serde_json::from_value(params)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+
+    Ok(Box::new(move || -> Result<Value> {
+        Ok(serde_json::to_value(method(params)?)?)
+    }))
+}
+
+pub fn seal_commit_phase2(params: SealCommitPhase2Params) -> Result<SealCommitPhase2Result> {
+    let output = filecoin_proofs::seal_commit_phase2(
+        porep_config(params.sector_size)?,
+        params.phase1_output,
+        params.prover_id,
+        SectorId::from(params.sector_id),
+    )?;
+
+    Ok(SealCommitPhase2Result {
+        proof: output.proof,
+    })
+}
+
+pub fn verify_seal(params: VerifySealParams) -> Result<VerifyResult> {
+    let VerifySealParams { sector_size, seal } = params;
+
+    let is_valid = filecoin_proofs::verify_seal::<DefaultTreeHasher>(
+        porep_config(sector_size)?,
+        seal.comm_r,
+        seal.comm_d,
+        seal.prover_id,
+        SectorId::from(seal.sector_id),
+        seal.ticket,
+        seal.seed,
+        &seal.proof,
+    )?;
+
+    Ok(VerifyResult { is_valid })
+}
+
+pub fn verify_batch_seal(params: VerifyBatchSealParams) -> Result<VerifyResult> {
+    let seals = &params.seals;
+    let proofs: Vec<&[u8]> = seals.iter().map(|seal| &seal.proof[..]).collect();
+
+    let is_valid = filecoin_proofs::verify_batch_seal::<DefaultTreeHasher>(
+        porep_config(params.sector_size)?,
+        &seals.iter().map(|seal| seal.comm_r).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.comm_d).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.prover_id).collect::<Vec<_>>(),
+        &seals
+            .iter()
+            .map(|seal| SectorId::from(seal.sector_id))
+            .collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.ticket).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.seed).collect::<Vec<_>>(),
+        &proofs,
+    )?;
+
+    Ok(VerifyResult { is_valid })
+}
+
+pub fn generate_post(params: GeneratePoStParams) -> Result<GeneratePoStResult> {
+    let mut replicas = BTreeMap::new();
+    for replica in &params.replicas {
+        replicas.insert(
+            SectorId::from(replica.sector_id),
+            replica.private_replica_info()?,
+        );
+    }
+
+    let proofs = filecoin_proofs::generate_post(
+        post_config(params.sector_size)?,
+        &params.randomness,
+        &replicas,
+        params.winners,
+        params.prover_id,
+    )?;
+
+    Ok(GeneratePoStResult { proofs })
+}
+
+pub fn verify_post(params: VerifyPoStParams) -> Result<VerifyResult> {
+    let mut replicas = BTreeMap::new();
+    for replica in params.replicas {
+        let info = PublicReplicaInfo::<DefaultTreeHasher>::new(replica.comm_r)
+            .with_context(|| format!("invalid replica for sector {}", replica.sector_id))?;
+        replicas.insert(SectorId::from(replica.sector_id), info);
+    }
+
+    let is_valid = filecoin_proofs::verify_post(
+        post_config(params.sector_size)?,
+        &params.randomness,
+        params.challenge_count,
+        &params.proofs,
+        &replicas,
+        &params.winners,
+        params.prover_id,
+    )?;
+
+    Ok(VerifyResult { is_valid })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_params_encoding() {
+        let params = VerifySealParams {
+            sector_size: 2048,
+            seal: SealProof {
+                comm_r: [1; 32],
+                comm_d: [2; 32],
+                prover_id: [3; 32],
+                sector_id: 4,
+                ticket: [5; 32],
+                seed: [6; 32],
+                proof: vec![0xab, 0xcd],
+            },
+        };
+
+        let value = serde_json::to_value(&params).unwrap();
+        assert_eq!(value["sector_size"], json!(2048));
+        assert_eq!(value["comm_r"], json!(hex::encode([1; 32])));
+        assert_eq!(value["proof"], json!("abcd"));
+        assert_eq!(
+            serde_json::from_value::<VerifySealParams>(value).unwrap(),
+            params
+        );
+
+        let short = json!({ "sector_size": 2048, "seals": [{ "comm_r": "abcd" }] });
+        assert!(serde_json::from_value::<VerifyBatchSealParams>(short).is_err());
+    }
+
+    #[test]
+    fn test_prepare_rejects_bad_requests() {
+        let err = prepare("seal_pre_commit_phase1", json!({})).err().unwrap();
+        assert_eq!(err.code, METHOD_NOT_FOUND);
+
+        let err = prepare("verify_seal", json!({ "sector_size": 2048 }))
+            .err()
+            .unwrap();
+        assert_eq!(err.code, INVALID_PARAMS);
+    }
+}
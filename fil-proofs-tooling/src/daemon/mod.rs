@@ -0,0 +1,363 @@
+//! A long-running proving daemon.
+//!
+//! `filecoin_proofs` keeps Groth parameters in a process-wide cache once they are loaded, which
+//! takes a while and several GB of memory. The daemon serves the SNARK heavy calls
+//! (`seal_commit_phase2`, `generate_post`, `verify_seal`, `verify_batch_seal` and
+//! `verify_post`) over JSON-RPC 2.0, so sealing workers can hand it their
+//! `SealCommitPhase1Output` instead of each loading the parameters themselves.
+//!
+//! Requests and responses are JSON objects on a single line each, over a Unix socket or a
+//! localhost TCP connection. A connection may have several requests in flight, answered as
+//! they complete and matched up by `id`. Besides the proving methods, there are:
+//!
+//! - `status`: the number of queued and running requests, and the configured limits.
+//! - `cancel`, with params `{ "id": <id> }`: cancels a request of the same connection.
+//!
+//! Proving requests wait in a bounded queue for one of `max_concurrent` workers. A cancelled
+//! request is answered with a `REQUEST_CANCELLED` error right away. If it was running
+//! already, it still runs to completion, since proving cannot be interrupted, but its result
+//! is dropped. Requests are dropped as well when their connection closes.
+
+mod client;
+mod methods;
+mod queue;
+mod rpc;
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{ensure, Context, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use self::queue::{Queue, Reply};
+
+pub use self::client::Client;
+pub use self::methods::{
+    GeneratePoStParams, GeneratePoStResult, PrivateReplica, PublicReplica, SealCommitPhase2Params,
+    SealCommitPhase2Result, SealProof, VerifyBatchSealParams, VerifyPoStParams, VerifyResult,
+    VerifySealParams,
+};
+pub use self::queue::Status;
+pub use self::rpc::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// How many requests are proven at once. Each uses all cores, and the GPU if any.
+    pub max_concurrent: usize,
+    /// How many requests may wait for a worker, before new ones are rejected.
+    pub max_queued: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_concurrent: 1,
+            max_queued: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Unix(PathBuf),
+    /// Must be a loopback address, as requests carry local paths and are not authenticated.
+    Tcp(SocketAddr),
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+            Endpoint::Tcp(addr) => write!(f, "tcp:{}", addr),
+        }
+    }
+}
+
+enum Listener {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+type Connection = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+pub struct Server {
+    listener: Listener,
+    queue: Arc<Queue>,
+}
+
+impl Server {
+    /// Binds `endpoint`, replacing a stale Unix socket, and starts the workers.
+    pub fn bind(endpoint: &Endpoint, config: Config) -> Result<Self> {
+        let listener = match endpoint {
+            Endpoint::Unix(path) => {
+                if let Ok(metadata) = std::fs::symlink_metadata(path) {
+                    ensure!(
+                        metadata.file_type().is_socket(),
+                        "{:?} exists and is not a socket",
+                        path
+                    );
+                    std::fs::remove_file(path)?;
+                }
+                Listener::Unix(
+                    UnixListener::bind(path)
+                        .with_context(|| format!("could not listen on {}", endpoint))?,
+                )
+            }
+            Endpoint::Tcp(addr) => {
+                ensure!(
+                    addr.ip().is_loopback(),
+                    "only loopback addresses are supported, got {}",
+                    addr
+                );
+                Listener::Tcp(
+                    TcpListener::bind(addr)
+                        .with_context(|| format!("could not listen on {}", endpoint))?,
+                )
+            }
+        };
+
+        Ok(Server {
+            listener,
+            queue: Queue::start(config)?,
+        })
+    }
+
+    /// The endpoint actually bound, e.g. to learn the port picked for `127.0.0.1:0`.
+    pub fn local_endpoint(&self) -> Result<Endpoint> {
+        match &self.listener {
+            Listener::Unix(listener) => {
+                let addr = listener.local_addr()?;
+                let path = addr.as_pathname().context("unnamed socket")?;
+                Ok(Endpoint::Unix(path.to_path_buf()))
+            }
+            Listener::Tcp(listener) => Ok(Endpoint::Tcp(listener.local_addr()?)),
+        }
+    }
+
+    /// Serves connections, each on its own thread. Never returns, unless it fails to start.
+    pub fn run(self) -> Result<()> {
+        info!("listening on {}", self.local_endpoint()?);
+
+        let mut connection = 0u64;
+        loop {
+            let (reader, writer) = match self.accept() {
+                Ok(streams) => streams,
+                Err(err) => {
+                    warn!("could not accept a connection: {:?}", err);
+                    continue;
+                }
+            };
+
+            let queue = self.queue.clone();
+            thread::Builder::new()
+                .name(format!("connection-{}", connection))
+                .spawn(move || serve_connection(&queue, connection, reader, writer))?;
+            connection += 1;
+        }
+    }
+
+    fn accept(&self) -> Result<Connection> {
+        let streams: Connection = match &self.listener {
+            Listener::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                (Box::new(stream.try_clone()?), Box::new(stream))
+            }
+        };
+
+        Ok(streams)
+    }
+}
+
+#[derive(Deserialize)]
+struct CancelParams {
+    id: Value,
+}
+
+fn serve_connection(
+    queue: &Queue,
+    connection: u64,
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+) {
+    info!("connection {}: open", connection);
+
+    let writer = Mutex::new(writer);
+    let reply: Reply = Arc::new(move |response: Response| {
+        let mut writer = writer.lock().unwrap();
+        let written = serde_json::to_writer(&mut *writer, &response)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| {
+                writer.write_all(b"\n")?;
+                writer.flush()?;
+                Ok(())
+            });
+        if let Err(err) = written {
+            warn!("connection {}: could not reply: {:?}", connection, err);
+        }
+    });
+
+    for line in BufReader::new(reader).lines() {
+        match line {
+            Ok(line) if line.trim().is_empty() => {}
+            Ok(line) => handle_request(queue, connection, &line, &reply),
+            Err(err) => {
+                warn!("connection {}: could not read: {:?}", connection, err);
+                break;
+            }
+        }
+    }
+
+    queue.cancel_connection(connection);
+    info!("connection {}: closed", connection);
+}
+
+fn handle_request(queue: &Queue, connection: u64, line: &str, reply: &Reply) {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(err) => {
+            return reply(Response::error(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, err.to_string()),
+            ))
+        }
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+
+    let request = match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == JSONRPC_VERSION && !request.id.is_null() => request,
+        _ => {
+            return reply(Response::error(
+                id,
+                RpcError::new(
+                    INVALID_REQUEST,
+                    "expected a JSON-RPC 2.0 request with an id",
+                ),
+            ))
+        }
+    };
+    let Request {
+        id, method, params, ..
+    } = request;
+
+    match method.as_str() {
+        "status" => reply(Response::result(id, json!(queue.status()))),
+        "cancel" => match serde_json::from_value::<CancelParams>(params) {
+            Ok(CancelParams { id: target }) => {
+                let cancelled = queue.cancel(connection, &target);
+                reply(Response::result(id, json!({ "cancelled": cancelled })))
+            }
+            Err(err) => reply(Response::error(
+                id,
+                RpcError::new(INVALID_PARAMS, err.to_string()),
+            )),
+        },
+        method => {
+            let submitted = methods::prepare(method, params)
+                .and_then(|work| queue.submit(connection, id.clone(), work, reply.clone()));
+            if let Err(err) = submitted {
+                reply(Response::error(id, err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::net::UnixStream;
+
+    fn start(dir: &tempfile::TempDir) -> Endpoint {
+        let endpoint = Endpoint::Unix(dir.path().join("daemon.sock"));
+        let server = Server::bind(&endpoint, Config::default()).unwrap();
+        thread::spawn(move || server.run());
+        endpoint
+    }
+
+    #[test]
+    fn test_daemon_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = start(&dir);
+        let mut client = Client::connect(&endpoint).unwrap();
+
+        let status = client.status().unwrap();
+        assert_eq!(status.queued, 0);
+        assert_eq!(status.max_concurrent, 1);
+
+        let cancelled: Value = client.call("cancel", json!({ "id": 42 })).unwrap();
+        assert_eq!(cancelled, json!({ "cancelled": false }));
+
+        let err = client.call::<_, Value>("nope", json!({})).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RpcError>().unwrap().code,
+            METHOD_NOT_FOUND
+        );
+
+        let err = client
+            .call::<_, Value>("verify_seal", json!({ "sector_size": 2048 }))
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<RpcError>().unwrap().code, INVALID_PARAMS);
+
+        let params = VerifySealParams {
+            sector_size: 1234,
+            seal: SealProof {
+                comm_r: [1; 32],
+                comm_d: [1; 32],
+                prover_id: [0; 32],
+                sector_id: 0,
+                ticket: [0; 32],
+                seed: [0; 32],
+                proof: vec![0; 192],
+            },
+        };
+        let err = client.verify_seal(&params).unwrap_err();
+        assert_eq!(err.downcast_ref::<RpcError>().unwrap().code, PROOF_ERROR);
+    }
+
+    #[test]
+    fn test_daemon_malformed_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = start(&dir);
+        let path = match &endpoint {
+            Endpoint::Unix(path) => path.clone(),
+            _ => unreachable!(),
+        };
+
+        let mut stream = UnixStream::connect(path).unwrap();
+        stream.write_all(b"{not json\n").unwrap();
+        stream
+            .write_all(b"{\"jsonrpc\": \"2.0\", \"method\": \"status\"}\n")
+            .unwrap();
+
+        let mut lines = BufReader::new(stream).lines();
+        let mut next =
+            || serde_json::from_str::<Response>(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(next().error.unwrap().code, PARSE_ERROR);
+        assert_eq!(next().error.unwrap().code, INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_daemon_rejects_remote_addresses() {
+        let endpoint = Endpoint::Tcp("0.0.0.0:0".parse().unwrap());
+        assert!(Server::bind(&endpoint, Config::default()).is_err());
+
+        let endpoint = Endpoint::Tcp("127.0.0.1:0".parse().unwrap());
+        let server = Server::bind(&endpoint, Config::default()).unwrap();
+        match server.local_endpoint().unwrap() {
+            Endpoint::Tcp(addr) => assert_ne!(addr.port(), 0),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use anyhow::{ensure, Result};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::rpc::{Response, RpcError, INTERNAL_ERROR, INVALID_REQUEST, PROOF_ERROR, QUEUE_FULL};
+use super::Config;
+
+/// The deferred proving call of a request.
+pub(crate) type Work = Box<dyn FnOnce() -> Result<Value> + Send>;
+
+/// Writes a response back to the connection a request came from.
+pub(crate) type Reply = Arc<dyn Fn(Response) + Send + Sync>;
+
+/// A snapshot of the queue, as returned by the `status` method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    pub queued: usize,
+    pub running: usize,
+    pub max_concurrent: usize,
+    pub max_queued: usize,
+}
+
+struct Job {
+    connection: u64,
+    id: Value,
+    work: Work,
+    reply: Reply,
+    /// Set by whoever replies first: the worker on completion, or a cancellation.
+    replied: Arc<AtomicBool>,
+}
+
+struct Running {
+    connection: u64,
+    id: Value,
+    reply: Reply,
+    replied: Arc<AtomicBool>,
+    /// Set once the cancellation has been answered, after which the id may be reused while
+    /// the work runs to completion.
+    cancelled: bool,
+}
+
+#[derive(Default)]
+struct State {
+    queued: VecDeque<Job>,
+    running: Vec<Running>,
+}
+
+impl State {
+    fn contains(&self, connection: u64, id: &Value) -> bool {
+        self.queued
+            .iter()
+            .any(|job| job.connection == connection && &job.id == id)
+            || self.find_running(connection, id).is_some()
+    }
+
+    fn find_running(&self, connection: u64, id: &Value) -> Option<&Running> {
+        self.running
+            .iter()
+            .find(|job| !job.cancelled && job.connection == connection && &job.id == id)
+    }
+}
+
+/// Runs submitted work on `max_concurrent` worker threads, in submission order.
+pub(crate) struct Queue {
+    config: Config,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+impl Queue {
+    pub(crate) fn start(config: Config) -> Result<Arc<Self>> {
+        ensure!(config.max_concurrent > 0, "max_concurrent must be > 0");
+
+        let queue = Arc::new(Queue {
+            config,
+            state: Default::default(),
+            available: Condvar::new(),
+        });
+
+        for i in 0..config.max_concurrent {
+            let queue = queue.clone();
+            thread::Builder::new()
+                .name(format!("proving-worker-{}", i))
+                .spawn(move || queue.work())?;
+        }
+
+        Ok(queue)
+    }
+
+    /// Queues `work`. Request ids must be unique among a connection's pending requests.
+    pub(crate) fn submit(
+        &self,
+        connection: u64,
+        id: Value,
+        work: Work,
+        reply: Reply,
+    ) -> std::result::Result<(), RpcError> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.contains(connection, &id) {
+            return Err(RpcError::new(
+                INVALID_REQUEST,
+                format!("request {} is already pending", id),
+            ));
+        }
+        if state.queued.len() >= self.config.max_queued {
+            return Err(RpcError::new(
+                QUEUE_FULL,
+                format!("{} requests are queued already", state.queued.len()),
+            ));
+        }
+
+        state.queued.push_back(Job {
+            connection,
+            id,
+            work,
+            reply,
+            replied: Arc::new(AtomicBool::new(false)),
+        });
+        self.available.notify_one();
+
+        Ok(())
+    }
+
+    /// Cancels a pending request, which is answered with a cancellation error. Returns
+    /// whether the request was still pending.
+    ///
+    /// Running work cannot be interrupted, so it runs to completion and its result is dropped.
+    /// Its id can be reused as soon as the cancellation is answered.
+    pub(crate) fn cancel(&self, connection: u64, id: &Value) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(pos) = state
+            .queued
+            .iter()
+            .position(|job| job.connection == connection && &job.id == id)
+        {
+            let job = state.queued.remove(pos).expect("position is in bounds");
+            drop(state);
+            job.replied.store(true, Ordering::SeqCst);
+            (job.reply)(Response::error(job.id, RpcError::cancelled()));
+            return true;
+        }
+
+        // Mark the job cancelled before replying, so its id is free once the reply is out.
+        let reply = match state
+            .running
+            .iter_mut()
+            .find(|job| !job.cancelled && job.connection == connection && &job.id == id)
+        {
+            Some(job) if !job.replied.swap(true, Ordering::SeqCst) => {
+                job.cancelled = true;
+                job.reply.clone()
+            }
+            _ => return false,
+        };
+        drop(state);
+        reply(Response::error(id.clone(), RpcError::cancelled()));
+
+        true
+    }
+
+    /// Drops everything a closed connection had pending, without replying.
+    pub(crate) fn cancel_connection(&self, connection: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        state.queued.retain(|job| job.connection != connection);
+        for job in state.running.iter() {
+            if job.connection == connection {
+                job.replied.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    pub(crate) fn status(&self) -> Status {
+        let state = self.state.lock().unwrap();
+
+        Status {
+            queued: state.queued.len(),
+            running: state.running.len(),
+            max_concurrent: self.config.max_concurrent,
+            max_queued: self.config.max_queued,
+        }
+    }
+
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut state = self.state.lock().unwrap();
+                while state.queued.is_empty() {
+                    state = self.available.wait(state).unwrap();
+                }
+
+                let job = state.queued.pop_front().expect("queue is not empty");
+                state.running.push(Running {
+                    connection: job.connection,
+                    id: job.id.clone(),
+                    reply: job.reply.clone(),
+                    replied: job.replied.clone(),
+                    cancelled: false,
+                });
+                job
+            };
+
+            let Job {
+                connection,
+                id,
+                work,
+                reply,
+                replied,
+            } = job;
+
+            info!("request {} from connection {}: start", id, connection);
+            let result = match panic::catch_unwind(AssertUnwindSafe(work)) {
+                Ok(Ok(result)) => Response::result(id.clone(), result),
+                Ok(Err(err)) => {
+                    error!("request {} from connection {}: {:?}", id, connection, err);
+                    Response::error(id.clone(), RpcError::new(PROOF_ERROR, format!("{:#}", err)))
+                }
+                Err(_) => {
+                    error!("request {} from connection {}: panicked", id, connection);
+                    Response::error(
+                        id.clone(),
+                        RpcError::new(INTERNAL_ERROR, "request panicked"),
+                    )
+                }
+            };
+            info!("request {} from connection {}: finish", id, connection);
+
+            self.state
+                .lock()
+                .unwrap()
+                .running
+                .retain(|job| !Arc::ptr_eq(&job.replied, &replied));
+            if !replied.swap(true, Ordering::SeqCst) {
+                reply(result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    fn collect() -> (Reply, mpsc::Receiver<Response>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        (
+            Arc::new(move |response| tx.lock().unwrap().send(response).unwrap()),
+            rx,
+        )
+    }
+
+    /// Work that only completes once the returned sender is used.
+    fn blocked() -> (Work, mpsc::Sender<()>) {
+        let (tx, rx) = mpsc::channel::<()>();
+        let work: Work = Box::new(move || -> Result<Value> {
+            rx.recv()?;
+            Ok(json!("done"))
+        });
+        (work, tx)
+    }
+
+    fn wait_for_running(queue: &Queue, running: usize) {
+        while queue.status().running != running {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_queue_limits_and_cancellation() {
+        let queue = Queue::start(Config {
+            max_concurrent: 1,
+            max_queued: 1,
+        })
+        .unwrap();
+        let (reply, responses) = collect();
+
+        let (running, unblock_running) = blocked();
+        queue.submit(0, json!(1), running, reply.clone()).unwrap();
+        wait_for_running(&queue, 1);
+
+        let (queued, _unblock_queued) = blocked();
+        queue.submit(0, json!(2), queued, reply.clone()).unwrap();
+
+        let (extra, _) = blocked();
+        let err = queue.submit(0, json!(3), extra, reply.clone()).unwrap_err();
+        assert_eq!(err.code, QUEUE_FULL);
+
+        let (duplicate, _) = blocked();
+        let err = queue
+            .submit(0, json!(2), duplicate, reply.clone())
+            .unwrap_err();
+        assert_eq!(err.code, INVALID_REQUEST);
+
+        // Ids are scoped to their connection.
+        assert!(!queue.cancel(1, &json!(2)));
+
+        assert!(queue.cancel(0, &json!(2)));
+        let response = responses.recv().unwrap();
+        assert_eq!(response.id, json!(2));
+        assert_eq!(response.error, Some(RpcError::cancelled()));
+
+        assert!(queue.cancel(0, &json!(1)));
+        let response = responses.recv().unwrap();
+        assert_eq!(response.id, json!(1));
+        assert_eq!(response.error, Some(RpcError::cancelled()));
+        assert!(!queue.cancel(0, &json!(1)), "already cancelled");
+
+        // The id of cancelled work can be reused while that work still runs.
+        let (reused, _) = blocked();
+        queue.submit(0, json!(1), reused, reply.clone()).unwrap();
+        assert!(queue.cancel(0, &json!(1)));
+        let response = responses.recv().unwrap();
+        assert_eq!(response.id, json!(1));
+        assert_eq!(response.error, Some(RpcError::cancelled()));
+
+        // The cancelled work still completes, but is not answered a second time.
+        unblock_running.send(()).unwrap();
+        wait_for_running(&queue, 0);
+
+        let work: Work = Box::new(|| -> Result<Value> { Ok(json!("ok")) });
+        queue.submit(0, json!(4), work, reply).unwrap();
+        let response = responses.recv().unwrap();
+        assert_eq!(response.id, json!(4));
+        assert_eq!(response.result, Some(json!("ok")));
+        assert!(responses.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_queue_reports_failures() {
+        let queue = Queue::start(Config::default()).unwrap();
+        let (reply, responses) = collect();
+
+        let work: Work = Box::new(|| -> Result<Value> { anyhow::bail!("invalid proof") });
+        queue.submit(0, json!("a"), work, reply.clone()).unwrap();
+        let error = responses.recv().unwrap().error.unwrap();
+        assert_eq!(error.code, PROOF_ERROR);
+        assert_eq!(error.message, "invalid proof");
+
+        let work: Work = Box::new(|| -> Result<Value> { panic!("boom") });
+        queue.submit(0, json!("b"), work, reply).unwrap();
+        let error = responses.recv().unwrap().error.unwrap();
+        assert_eq!(error.code, INTERNAL_ERROR);
+
+        assert_eq!(queue.status().queued, 0);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod circuits;
+pub mod constraint_budget;
+pub mod daemon;
 pub mod measure;
 pub mod metadata;
+pub mod metrics_exporter;
+pub mod serde_hex;
+pub mod synthetic_replicas;
+pub mod test_vectors;
 
 pub use measure::{measure, FuncMeasurement};
 pub use metadata::Metadata;
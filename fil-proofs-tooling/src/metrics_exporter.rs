@@ -0,0 +1,112 @@
+//! A tiny HTTP server exposing `storage_proofs::metrics` at `/metrics`, for Prometheus to
+//! scrape. Each connection is answered once and closed.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use storage_proofs::metrics;
+
+/// Requests are a request line and some headers, which are ignored.
+const MAX_REQUEST_LEN: u64 = 8 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the metrics on `addr` from a background thread, and returns the address bound,
+/// e.g. to learn the port picked for `127.0.0.1:0`.
+pub fn serve(addr: SocketAddr) -> Result<SocketAddr> {
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("could not listen on {}", addr))?;
+    let local_addr = listener.local_addr()?;
+    info!("serving metrics on http://{}/metrics", local_addr);
+
+    thread::Builder::new()
+        .name("metrics-exporter".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.map_err(Into::into).and_then(serve_connection);
+                if let Err(err) = result {
+                    warn!("metrics exporter: {:?}", err);
+                }
+            }
+        })?;
+
+    Ok(local_addr)
+}
+
+fn serve_connection(stream: TcpStream) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let mut request_line = String::new();
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_LEN));
+    reader.read_line(&mut request_line)?;
+    // Drain the headers, so that closing the connection does not reset it.
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && !line.trim_end().is_empty() {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", metrics::render()),
+        (Some("GET"), Some(_)) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        body.len(),
+        body
+    )?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_metrics_exporter() {
+        metrics::measure_verification("exporter-test", || Ok(true)).unwrap();
+        let addr = serve("127.0.0.1:0".parse().unwrap()).unwrap();
+
+        let response = get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains(
+            "fil_proofs_verifications_total{proof=\"exporter-test\",result=\"valid\"} 1\n"
+        ));
+
+        let (head, body) = response.split_at(response.find("\r\n\r\n").unwrap() + 4);
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+
+        let response = get(addr, "GET / HTTP/1.1\r\n\r\n");
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        let response = get(addr, "POST /metrics HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405"), "{}", response);
+    }
+}
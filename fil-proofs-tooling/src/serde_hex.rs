@@ -0,0 +1,125 @@
+//! Serde helpers encoding byte strings as hex, for `#[serde(with = "...")]`.
+
+pub mod hex_32 {
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let bytes = super::hex_bytes::deserialize(deserializer)?;
+        if bytes.len() != 32 {
+            return Err(de::Error::invalid_length(bytes.len(), &"32 bytes"));
+        }
+
+        let mut out = [0; 32];
+        out.copy_from_slice(&bytes);
+        Ok(out)
+    }
+}
+
+pub mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        hex::decode(&encoded).map_err(de::Error::custom)
+    }
+}
+
+pub mod hex_bytes_vec {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[Vec<u8>], serializer: S) -> Result<S::Ok, S::Error> {
+        values
+            .iter()
+            .map(hex::encode)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|encoded| hex::decode(encoded).map_err(de::Error::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Encoded {
+        #[serde(with = "super::hex_32")]
+        commitment: [u8; 32],
+        #[serde(with = "super::hex_bytes")]
+        proof: Vec<u8>,
+        #[serde(with = "super::hex_bytes_vec")]
+        proofs: Vec<Vec<u8>>,
+    }
+
+    fn decode(commitment: &str, proof: &str, proofs: &[&str]) -> serde_json::Result<Encoded> {
+        serde_json::from_value(json!({
+            "commitment": commitment,
+            "proof": proof,
+            "proofs": proofs,
+        }))
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let encoded = Encoded {
+            commitment: [0xab; 32],
+            proof: vec![1, 2, 3],
+            proofs: vec![vec![], vec![0xff]],
+        };
+
+        let value = serde_json::to_value(&encoded).unwrap();
+        assert_eq!(value["commitment"], json!("ab".repeat(32)));
+        assert_eq!(value["proof"], json!("010203"));
+        assert_eq!(value["proofs"], json!(["", "ff"]));
+        assert_eq!(serde_json::from_value::<Encoded>(value).unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_hex_rejects_bad_lengths() {
+        let ok = "00".repeat(32);
+        assert!(decode(&ok, "", &[]).is_ok());
+
+        let err = decode(&"00".repeat(31), "", &[]).unwrap_err();
+        assert!(err.to_string().contains("32 bytes"), "{}", err);
+        assert!(decode(&"00".repeat(33), "", &[]).is_err());
+        assert!(decode("", "", &[]).is_err());
+
+        // Odd numbers of digits are not whole bytes.
+        assert!(decode(&ok, "012", &[]).is_err());
+        assert!(decode(&ok, "", &["01", "f"]).is_err());
+    }
+
+    #[test]
+    fn test_hex_rejects_bad_hex() {
+        let ok = "00".repeat(32);
+
+        assert!(decode(&"zz".repeat(32), "", &[]).is_err());
+        assert!(decode(&ok, "0x01", &[]).is_err());
+        assert!(decode(&ok, "", &["01", "gg"]).is_err());
+
+        // Only strings are hex.
+        let err = serde_json::from_value::<Encoded>(json!({
+            "commitment": ok,
+            "proof": [1, 2, 3],
+            "proofs": [],
+        }));
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Synthetic replicas, for benchmarking PoSt over many more sectors than could be sealed.
+//!
+//! A synthetic replica is random replica data, with everything election PoSt reads about
+//! it derived from that data: the compacted `tree-r-last`, a `p_aux` holding its root and a
+//! random `comm_c` (standing in for the root of `tree-c`), a `t_aux` whose labels, `tree-d`
+//! and `tree-c` are already discarded, and `comm_r = H(comm_c || comm_r_last)`. Candidates
+//! and PoSt proofs generated over synthetic replicas verify, but the replicas don't encode
+//! any data, so they can't stand in for sealed sectors anywhere else.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use filecoin_proofs::constants::{DefaultPieceHasher, LAYERS};
+use filecoin_proofs::parameters::tree_r_last_arity;
+use filecoin_proofs::types::{Commitment, PaddedBytesAmount, BINARY_ARITY, OCT_ARITY, QUAD_ARITY};
+use filecoin_proofs::{PrivateReplicaInfo, PublicReplicaInfo};
+use log::info;
+use merkletree::merkle::FromIndexedParallelIterator;
+use merkletree::store::{StoreConfig, StoreConfigDataVersion};
+use paired::bls12_381::Bls12;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use storage_proofs::fr32::fr_into_bytes;
+use storage_proofs::hasher::{Domain, HashFunction, Hasher};
+use storage_proofs::merkle::MerkleTree;
+use storage_proofs::sector::SectorId;
+use storage_proofs::stacked::{CacheKey, Labels, PersistentAux, TemporaryAux};
+
+/// Name of the manifest `create_synthetic_replicas` writes next to the replicas.
+pub const MANIFEST_NAME: &str = "replicas.json";
+
+/// A synthetic replica and its cache directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntheticReplica {
+    pub sector_id: SectorId,
+    pub comm_r: Commitment,
+    pub replica_path: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+impl SyntheticReplica {
+    pub fn private_replica_info<H: Hasher>(&self) -> Result<PrivateReplicaInfo<H>> {
+        PrivateReplicaInfo::new(
+            self.replica_path.to_string_lossy().into_owned(),
+            self.comm_r,
+            self.cache_dir.clone(),
+        )
+    }
+
+    pub fn public_replica_info<H: Hasher>(&self) -> Result<PublicReplicaInfo<H>> {
+        PublicReplicaInfo::new(self.comm_r)
+    }
+}
+
+/// The manifest of a directory of synthetic replicas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntheticReplicas {
+    pub sector_size: u64,
+    pub replicas: Vec<SyntheticReplica>,
+}
+
+impl SyntheticReplicas {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("could not open {:?}", path.as_ref()))?;
+        let replicas = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid synthetic replicas manifest {:?}", path.as_ref()))?;
+
+        Ok(replicas)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("could not create {:?}", path.as_ref()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+}
+
+/// Creates `count` synthetic replicas of `sector_size` bytes, with sector ids `0..count`, in
+/// `sector-<id>` subdirectories of `dir`, and writes their manifest to `dir/replicas.json`.
+///
+/// Replicas are created in parallel, each holding its `sector_size` bytes in memory.
+pub fn create_synthetic_replicas<H: 'static + Hasher>(
+    sector_size: u64,
+    count: usize,
+    dir: &Path,
+    rng_seed: [u8; 16],
+) -> Result<SyntheticReplicas> {
+    info!(
+        "creating {} synthetic replicas of {} bytes in {:?}",
+        count, sector_size, dir
+    );
+
+    let replicas = (0..count as u64)
+        .into_par_iter()
+        .map(|id| {
+            let sector_dir = dir.join(format!("sector-{}", id));
+            let cache_dir = sector_dir.join("cache");
+            fs::create_dir_all(&cache_dir)
+                .with_context(|| format!("could not create {:?}", cache_dir))?;
+            let replica_path = sector_dir.join("replica");
+
+            let rng = &mut XorShiftRng::from_seed(replica_seed(rng_seed, id));
+            let comm_r =
+                create_synthetic_replica::<H, _>(sector_size, &replica_path, &cache_dir, rng)?;
+
+            Ok(SyntheticReplica {
+                sector_id: SectorId::from(id),
+                comm_r,
+                replica_path,
+                cache_dir,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let replicas = SyntheticReplicas {
+        sector_size,
+        replicas,
+    };
+    replicas.save(dir.join(MANIFEST_NAME))?;
+
+    Ok(replicas)
+}
+
+/// Writes a synthetic replica of `sector_size` bytes to `replica_path` and its cache to
+/// `cache_dir`, and returns its `comm_r`.
+pub fn create_synthetic_replica<H: 'static + Hasher, R: Rng>(
+    sector_size: u64,
+    replica_path: &Path,
+    cache_dir: &Path,
+    rng: &mut R,
+) -> Result<Commitment> {
+    match tree_r_last_arity(PaddedBytesAmount(sector_size))? {
+        QUAD_ARITY => create_synthetic_replica_inner::<H, typenum::U4, _>(
+            sector_size,
+            replica_path,
+            cache_dir,
+            rng,
+        ),
+        OCT_ARITY => create_synthetic_replica_inner::<H, typenum::U8, _>(
+            sector_size,
+            replica_path,
+            cache_dir,
+            rng,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }
+}
+
+fn create_synthetic_replica_inner<H, U, R>(
+    sector_size: u64,
+    replica_path: &Path,
+    cache_dir: &Path,
+    rng: &mut R,
+) -> Result<Commitment>
+where
+    H: 'static + Hasher,
+    U: typenum::Unsigned,
+    R: Rng,
+{
+    let nodes = sector_size as usize / H::Domain::byte_len();
+    let layers = *LAYERS
+        .read()
+        .unwrap()
+        .get(&sector_size)
+        .with_context(|| format!("unknown sector size {}", sector_size))?;
+
+    // The replica is the leaves of tree_r_last.
+    let leaves: Vec<H::Domain> = (0..nodes).map(|_| H::Domain::random(rng)).collect();
+    let replica: Vec<u8> = leaves.iter().flat_map(|leaf| leaf.into_bytes()).collect();
+    fs::write(replica_path, &replica)
+        .with_context(|| format!("could not write replica {:?}", replica_path))?;
+
+    // The store configs replication uses, see `StackedDrg::transform_and_replicate_layers_inner`.
+    let config = StoreConfig::new(
+        cache_dir,
+        CacheKey::CommDTree.to_string(),
+        StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+    );
+    let labels = Labels::<H>::new(
+        (1..=layers)
+            .map(|layer| {
+                StoreConfig::from_config(&config, CacheKey::label_layer(layer), Some(nodes))
+            })
+            .collect(),
+    );
+    let tree_d_config = StoreConfig::from_config(
+        &config,
+        CacheKey::CommDTree.to_string(),
+        Some(StoreConfig::default_cached_above_base_layer(
+            nodes,
+            BINARY_ARITY,
+        )),
+    );
+    let mut tree_r_last_config = StoreConfig::from_config(
+        &config,
+        CacheKey::CommRLastTree.to_string(),
+        Some(StoreConfig::default_cached_above_base_layer(
+            nodes,
+            U::to_usize(),
+        )),
+    );
+    let tree_c_config = StoreConfig::from_config(
+        &config,
+        CacheKey::CommCTree.to_string(),
+        Some(StoreConfig::default_cached_above_base_layer(
+            nodes, QUAD_ARITY,
+        )),
+    );
+
+    let mut tree_r_last = MerkleTree::<H::Domain, H::Function, U>::from_par_iter_with_config(
+        leaves.into_par_iter(),
+        tree_r_last_config.clone(),
+    )?;
+    tree_r_last_config.size = Some(tree_r_last.len());
+    let comm_r_last = tree_r_last.root();
+
+    // Leave tree_r_last as `TemporaryAux::compact` does once sealing is done.
+    tree_r_last.compact(
+        tree_r_last_config.clone(),
+        StoreConfigDataVersion::One as u32,
+    )?;
+
+    let comm_c = H::Domain::random(rng);
+    let comm_r = H::Function::hash2(&comm_c, &comm_r_last);
+
+    let p_aux = PersistentAux {
+        comm_c,
+        comm_r_last,
+    };
+    // None of the labels, tree_d and tree_c exist, so `TemporaryAux::compact` leaves the
+    // cache as is.
+    let t_aux = TemporaryAux::<H, DefaultPieceHasher> {
+        labels,
+        tree_d_config,
+        tree_r_last_config,
+        tree_c_config,
+        _g: PhantomData,
+    };
+    fs::write(
+        cache_dir.join(CacheKey::PAux.to_string()),
+        bincode::serialize(&p_aux)?,
+    )?;
+    fs::write(
+        cache_dir.join(CacheKey::TAux.to_string()),
+        bincode::serialize(&t_aux)?,
+    )?;
+
+    let mut commitment = [0; 32];
+    commitment.copy_from_slice(&fr_into_bytes::<Bls12>(&comm_r.into()));
+
+    Ok(commitment)
+}
+
+/// The seed of the replica of sector `id`, so that replicas don't depend on the order they
+/// are created in.
+fn replica_seed(rng_seed: [u8; 16], id: u64) -> [u8; 16] {
+    let mut seed = rng_seed;
+    for (byte, id_byte) in seed.iter_mut().zip(&id.to_le_bytes()) {
+        *byte ^= id_byte;
+    }
+
+    seed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    use filecoin_proofs::constants::{
+        DefaultTreeHasher, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT, SECTOR_SIZE_2_KIB,
+    };
+    use filecoin_proofs::generate_candidates;
+    use filecoin_proofs::parameters::post_setup_params;
+    use filecoin_proofs::types::{PoStConfig, SectorSize};
+    use filecoin_proofs::Candidate;
+    use merkletree::merkle::{get_merkle_tree_leafs, get_merkle_tree_len};
+    use storage_proofs::election_post::{self, ElectionPoSt};
+    use storage_proofs::proof::ProofScheme;
+
+    type Replicas = BTreeMap<SectorId, PrivateReplicaInfo<DefaultTreeHasher>>;
+
+    const RANDOMNESS: [u8; 32] = [3; 32];
+    const PROVER_ID: [u8; 32] = [4; 32];
+
+    fn post_config() -> PoStConfig {
+        PoStConfig {
+            sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+            challenge_count: POST_CHALLENGE_COUNT,
+            challenged_nodes: POST_CHALLENGED_NODES,
+            priority: false,
+        }
+    }
+
+    /// Proves and verifies a vanilla election PoSt for every candidate.
+    fn assert_valid_post<U: 'static + typenum::Unsigned>(
+        replicas: &Replicas,
+        candidates: &[Candidate],
+    ) {
+        let pub_params =
+            ElectionPoSt::<DefaultTreeHasher, U>::setup(&post_setup_params(post_config())).unwrap();
+        let nodes = SECTOR_SIZE_2_KIB as usize / 32;
+        let tree_len = get_merkle_tree_len(nodes, U::to_usize());
+        let domain = |bytes: &[u8; 32]| {
+            <DefaultTreeHasher as Hasher>::Domain::try_from_bytes(bytes).unwrap()
+        };
+
+        for candidate in candidates {
+            let replica = &replicas[&candidate.sector_id];
+            let pub_inputs = election_post::PublicInputs {
+                randomness: domain(&RANDOMNESS),
+                comm_r: replica.safe_comm_r().unwrap(),
+                sector_id: candidate.sector_id,
+                partial_ticket: candidate.partial_ticket,
+                sector_challenge_index: candidate.sector_challenge_index,
+                prover_id: domain(&PROVER_ID),
+            };
+            let priv_inputs = election_post::PrivateInputs::<DefaultTreeHasher, U> {
+                tree: replica
+                    .merkle_tree::<U>(tree_len, get_merkle_tree_leafs(tree_len, U::to_usize()))
+                    .unwrap(),
+                comm_c: replica.safe_comm_c().unwrap(),
+                comm_r_last: replica.safe_comm_r_last().unwrap(),
+            };
+
+            let proof = ElectionPoSt::prove(&pub_params, &pub_inputs, &priv_inputs).unwrap();
+            assert!(ElectionPoSt::verify(&pub_params, &pub_inputs, &proof).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_synthetic_replicas_prove() {
+        let dir = tempfile::tempdir().unwrap();
+        let replicas = create_synthetic_replicas::<DefaultTreeHasher>(
+            SECTOR_SIZE_2_KIB,
+            3,
+            dir.path(),
+            [7; 16],
+        )
+        .unwrap();
+        assert_eq!(
+            SyntheticReplicas::load(dir.path().join(MANIFEST_NAME)).unwrap(),
+            replicas
+        );
+
+        let private_replicas = replicas
+            .replicas
+            .iter()
+            .map(|replica| Ok((replica.sector_id, replica.private_replica_info()?)))
+            .collect::<Result<Replicas>>()
+            .unwrap();
+        let candidates =
+            generate_candidates(post_config(), &RANDOMNESS, 2, &private_replicas, PROVER_ID)
+                .unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        match tree_r_last_arity(PaddedBytesAmount(SECTOR_SIZE_2_KIB)).unwrap() {
+            QUAD_ARITY => assert_valid_post::<typenum::U4>(&private_replicas, &candidates),
+            OCT_ARITY => assert_valid_post::<typenum::U8>(&private_replicas, &candidates),
+            arity => panic!("unsupported tree_r_last arity: {}", arity),
+        }
+    }
+
+    #[test]
+    fn test_replica_seed() {
+        assert_eq!(replica_seed([7; 16], 0), [7; 16]);
+        assert_ne!(replica_seed([7; 16], 1), replica_seed([7; 16], 2));
+    }
+}
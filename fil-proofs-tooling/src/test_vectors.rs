@@ -0,0 +1,414 @@
+//! Conformance test vectors: every value sealing and PoSt candidate generation derive for a
+//! sector, from inputs generated by a seeded RNG, so that other implementations can check
+//! they compute the same.
+//!
+//! Inputs are drawn, in this order, from `XorShiftRng::from_seed(rng_seed)`: the prover id,
+//! the sector id, the ticket, the seed, the PoSt randomness and finally the bytes of the
+//! sector's single piece, which fills it. They are recorded too, except for the piece, of
+//! which only the Blake2s digest is.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use filecoin_proofs::constants::{
+    DefaultPieceHasher, DefaultTreeHasher, POREP_PARTITIONS, POST_CHALLENGED_NODES,
+    POST_CHALLENGE_COUNT,
+};
+use filecoin_proofs::parameters::setup_params;
+use filecoin_proofs::types::{
+    PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, SectorSize,
+    UnpaddedBytesAmount,
+};
+use filecoin_proofs::{
+    add_piece, deserialize_aux, generate_candidates, generate_piece_commitment,
+    seal_pre_commit_phase1, seal_pre_commit_phase2, PrivateReplicaInfo,
+};
+use log::info;
+use merkletree::store::Store;
+use paired::bls12_381::Bls12;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use storage_proofs::fr32::fr_into_bytes;
+use storage_proofs::hasher::{Domain, Hasher};
+use storage_proofs::sector::SectorId;
+use storage_proofs::stacked::{self, generate_replica_id, CacheKey};
+use tempfile::NamedTempFile;
+
+type TreeDomain = <DefaultTreeHasher as Hasher>::Domain;
+
+/// How many sectors the PoSt candidates are generated for.
+const POST_CHALLENGE_SECTORS: u64 = 1;
+
+/// The inputs of a test vector, see the module documentation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorInputs {
+    pub sector_size: u64,
+    pub rng_seed: String,
+    pub prover_id: String,
+    pub sector_id: u64,
+    pub ticket: String,
+    pub seed: String,
+    pub post_randomness: String,
+    pub piece_digest: String,
+}
+
+/// The labels of a layer, summarized by its first label and the Blake2s digest of all its
+/// labels, in node order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerLabels {
+    pub first: String,
+    pub digest: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoStCandidate {
+    pub sector_challenge_index: u64,
+    pub partial_ticket: String,
+}
+
+/// The values derived from a test vector's inputs, in the order they are computed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorOutputs {
+    pub comm_p: String,
+    pub comm_d: String,
+    pub replica_id: String,
+    pub labels: Vec<LayerLabels>,
+    pub comm_c: String,
+    pub comm_r_last: String,
+    pub comm_r: String,
+    /// The challenged nodes of each partition.
+    pub challenges: Vec<Vec<usize>>,
+    pub post_candidates: Vec<PoStCandidate>,
+}
+
+impl VectorOutputs {
+    fn fields(&self) -> Result<Vec<(&'static str, Value)>> {
+        Ok(vec![
+            ("comm_p", serde_json::to_value(&self.comm_p)?),
+            ("comm_d", serde_json::to_value(&self.comm_d)?),
+            ("replica_id", serde_json::to_value(&self.replica_id)?),
+            ("labels", serde_json::to_value(&self.labels)?),
+            ("comm_c", serde_json::to_value(&self.comm_c)?),
+            ("comm_r_last", serde_json::to_value(&self.comm_r_last)?),
+            ("comm_r", serde_json::to_value(&self.comm_r)?),
+            ("challenges", serde_json::to_value(&self.challenges)?),
+            (
+                "post_candidates",
+                serde_json::to_value(&self.post_candidates)?,
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub inputs: VectorInputs,
+    pub outputs: VectorOutputs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub vectors: Vec<TestVector>,
+}
+
+impl TestVectors {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("could not open {:?}", path.as_ref()))?;
+        let vectors = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid test vectors {:?}", path.as_ref()))?;
+
+        Ok(vectors)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = File::create(path.as_ref())
+            .with_context(|| format!("could not create {:?}", path.as_ref()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+}
+
+/// Seals a sector of `sector_size` from the inputs `rng_seed` generates, and records every
+/// value derived on the way.
+pub fn generate_vector(sector_size: u64, rng_seed: [u8; 16]) -> Result<TestVector> {
+    info!("generating test vector for {} byte sectors", sector_size);
+
+    let rng = &mut XorShiftRng::from_seed(rng_seed);
+    let prover_id: [u8; 32] = rng.gen();
+    let sector_id = SectorId::from(rng.gen::<u64>());
+    let ticket: [u8; 32] = rng.gen();
+    let seed: [u8; 32] = rng.gen();
+    let post_randomness: [u8; 32] = rng.gen();
+
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+    let mut piece_bytes = vec![0u8; u64::from(piece_size) as usize];
+    rng.fill_bytes(&mut piece_bytes);
+
+    let inputs = VectorInputs {
+        sector_size,
+        rng_seed: hex::encode(&rng_seed),
+        prover_id: hex::encode(&prover_id),
+        sector_id: u64::from(sector_id),
+        ticket: hex::encode(&ticket),
+        seed: hex::encode(&seed),
+        post_randomness: hex::encode(&post_randomness),
+        piece_digest: hex::encode(blake2s_simd::blake2s(&piece_bytes).as_bytes()),
+    };
+
+    let partitions = *POREP_PARTITIONS
+        .read()
+        .unwrap()
+        .get(&sector_size)
+        .with_context(|| format!("no partitions known for sector size {}", sector_size))?;
+    let porep_config = PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(partitions),
+    };
+
+    let piece_info = generate_piece_commitment(&piece_bytes[..], piece_size)?;
+
+    let mut staged_file = NamedTempFile::new()?;
+    add_piece(&piece_bytes[..], &mut staged_file, piece_size, &[])?;
+
+    let sealed_file = NamedTempFile::new()?;
+    let cache_dir = tempfile::tempdir()?;
+    let piece_infos = vec![piece_info.clone()];
+
+    let phase1_output = seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
+        porep_config,
+        cache_dir.path(),
+        staged_file.path(),
+        sealed_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+    )?;
+    let pre_commit = seal_pre_commit_phase2(
+        porep_config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_file.path(),
+    )?;
+
+    let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
+        &prover_id,
+        sector_id.into(),
+        &ticket,
+        pre_commit.comm_d,
+    );
+
+    let p_aux: stacked::PersistentAux<TreeDomain> = deserialize_aux(&std::fs::read(
+        cache_dir.path().join(CacheKey::PAux.to_string()),
+    )?)?;
+    let t_aux: stacked::TemporaryAux<DefaultTreeHasher, DefaultPieceHasher> = deserialize_aux(
+        &std::fs::read(cache_dir.path().join(CacheKey::TAux.to_string()))?,
+    )?;
+
+    let nodes = sector_size as usize / TreeDomain::byte_len();
+    let labels = (1..=t_aux.labels.len())
+        .map(|layer| {
+            let labels = t_aux.labels.labels_for_layer(layer)?.read_range(0..nodes)?;
+            let mut hasher = blake2s_simd::State::new();
+            for label in &labels {
+                hasher.update(&label.into_bytes());
+            }
+
+            Ok(LayerLabels {
+                first: hex::encode(labels[0].into_bytes()),
+                digest: hex::encode(hasher.finalize().as_bytes()),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let layer_challenges =
+        setup_params(PaddedBytesAmount(sector_size), partitions as usize)?.layer_challenges;
+    let challenges = (0..partitions)
+        .map(|k| layer_challenges.derive::<TreeDomain>(nodes, &replica_id, &seed, k))
+        .collect();
+
+    let post_config = PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count: POST_CHALLENGE_COUNT,
+        challenged_nodes: POST_CHALLENGED_NODES,
+        priority: false,
+    };
+    let sealed_path = sealed_file
+        .path()
+        .to_str()
+        .context("file name is not a UTF-8 string")?
+        .to_string();
+    let mut replicas = BTreeMap::new();
+    replicas.insert(
+        sector_id,
+        PrivateReplicaInfo::<DefaultTreeHasher>::new(
+            sealed_path,
+            pre_commit.comm_r,
+            cache_dir.path().to_path_buf(),
+        )?,
+    );
+    let post_candidates = generate_candidates(
+        post_config,
+        &post_randomness,
+        POST_CHALLENGE_SECTORS,
+        &replicas,
+        prover_id,
+    )?
+    .into_iter()
+    .map(|candidate| PoStCandidate {
+        sector_challenge_index: candidate.sector_challenge_index,
+        partial_ticket: hex::encode(fr_into_bytes::<Bls12>(&candidate.partial_ticket)),
+    })
+    .collect();
+
+    let outputs = VectorOutputs {
+        comm_p: hex::encode(&piece_info.commitment),
+        comm_d: hex::encode(&pre_commit.comm_d),
+        replica_id: hex::encode(replica_id.into_bytes()),
+        labels,
+        comm_c: hex::encode(p_aux.comm_c.into_bytes()),
+        comm_r_last: hex::encode(p_aux.comm_r_last.into_bytes()),
+        comm_r: hex::encode(&pre_commit.comm_r),
+        challenges,
+        post_candidates,
+    };
+
+    Ok(TestVector { inputs, outputs })
+}
+
+/// Generates a test vector for each of `sector_sizes`, the rng seed of each derived from
+/// `rng_seed` and its sector size.
+pub fn generate_vectors(sector_sizes: &[u64], rng_seed: [u8; 16]) -> Result<TestVectors> {
+    let vectors = sector_sizes
+        .iter()
+        .map(|&sector_size| generate_vector(sector_size, vector_seed(rng_seed, sector_size)))
+        .collect::<Result<_>>()?;
+
+    Ok(TestVectors { vectors })
+}
+
+fn vector_seed(rng_seed: [u8; 16], sector_size: u64) -> [u8; 16] {
+    let mut seed = rng_seed;
+    for (byte, size_byte) in seed.iter_mut().zip(sector_size.to_le_bytes().iter()) {
+        *byte ^= size_byte;
+    }
+
+    seed
+}
+
+/// Re-derives `vector` from its inputs, and describes the first value that differs, if any.
+pub fn check_vector(vector: &TestVector) -> Result<Option<String>> {
+    let rng_seed = hex::decode(&vector.inputs.rng_seed).context("invalid rng_seed")?;
+    ensure!(
+        rng_seed.len() == 16,
+        "rng_seed must be 16 bytes, got {}",
+        rng_seed.len()
+    );
+    let mut seed = [0u8; 16];
+    seed.copy_from_slice(&rng_seed);
+
+    let actual = generate_vector(vector.inputs.sector_size, seed)?;
+    let label = format!("{} byte sector", vector.inputs.sector_size);
+
+    if actual.inputs != vector.inputs {
+        return Ok(Some(format!(
+            "{}: inputs differ, expected {:?}, got {:?}",
+            label, vector.inputs, actual.inputs
+        )));
+    }
+
+    let expected = vector.outputs.fields()?;
+    let actual = actual.outputs.fields()?;
+    for ((name, expected), (_, actual)) in expected.iter().zip(actual.iter()) {
+        if let Some(mismatch) = first_mismatch(name, expected, actual) {
+            return Ok(Some(format!("{}: {}", label, mismatch)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Describes the first difference between `expected` and `actual`, walking arrays and
+/// objects depth first.
+fn first_mismatch(path: &str, expected: &Value, actual: &Value) -> Option<String> {
+    match (expected, actual) {
+        (Value::Array(expected), Value::Array(actual)) => {
+            for (i, (expected, actual)) in expected.iter().zip(actual.iter()).enumerate() {
+                let mismatch = first_mismatch(&format!("{}[{}]", path, i), expected, actual);
+                if mismatch.is_some() {
+                    return mismatch;
+                }
+            }
+            if expected.len() != actual.len() {
+                return Some(format!(
+                    "{}: expected {} entries, got {}",
+                    path,
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+
+            None
+        }
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected_value) in expected {
+                let path = format!("{}.{}", path, key);
+                match actual.get(key) {
+                    Some(actual_value) => {
+                        let mismatch = first_mismatch(&path, expected_value, actual_value);
+                        if mismatch.is_some() {
+                            return mismatch;
+                        }
+                    }
+                    None => return Some(format!("{}: missing", path)),
+                }
+            }
+
+            None
+        }
+        (expected, actual) if expected != actual => {
+            Some(format!("{}: expected {}, got {}", path, expected, actual))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_first_mismatch() {
+        let expected =
+            json!([{ "first": "aa", "digest": "bb" }, { "first": "cc", "digest": "dd" }]);
+        assert_eq!(first_mismatch("labels", &expected, &expected), None);
+
+        let actual = json!([{ "first": "aa", "digest": "bb" }, { "first": "cc", "digest": "ee" }]);
+        assert_eq!(
+            first_mismatch("labels", &expected, &actual),
+            Some(r#"labels[1].digest: expected "dd", got "ee""#.to_string())
+        );
+
+        let actual = json!([{ "first": "aa", "digest": "bb" }]);
+        assert_eq!(
+            first_mismatch("labels", &expected, &actual),
+            Some("labels: expected 2 entries, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_vector_seed() {
+        let seed = [0u8; 16];
+        assert_ne!(vector_seed(seed, 2048), vector_seed(seed, 1 << 23));
+        assert_eq!(vector_seed(seed, 2048), vector_seed(seed, 2048));
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use fil_proofs_tooling::constraint_budget::{budget_report, diff_reports, BudgetReport};
+
+#[test]
+fn constraint_budget_matches_baseline() {
+    let baseline_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("constraint-budget.json");
+    if !baseline_path.exists() {
+        eprintln!(
+            "skipping, no baseline at {:?}: generate it with `constraint-budget --update` and commit it",
+            baseline_path
+        );
+        return;
+    }
+    let baseline = BudgetReport::load(&baseline_path).expect("failed to load baseline");
+
+    let report =
+        budget_report(&baseline.sector_sizes, baseline.depth).expect("failed to build report");
+
+    for (name, budget) in &report.circuits {
+        let constraints: usize = budget.namespaces.values().map(|m| m.constraints).sum();
+        let inputs: usize = budget.namespaces.values().map(|m| m.inputs).sum();
+        let aux: usize = budget.namespaces.values().map(|m| m.aux).sum();
+        assert_eq!(constraints, budget.constraints, "{}", name);
+        assert_eq!(inputs, budget.inputs, "{}", name);
+        assert_eq!(aux, budget.aux, "{}", name);
+    }
+
+    let diff = diff_reports(&baseline, &report);
+    assert!(
+        diff.is_empty(),
+        "constraint budget differs from the baseline (bump parameter_cache::VERSION and \
+         regenerate it with `constraint-budget --update` if this is intended):\n{}",
+        diff.join("\n")
+    );
+}
@@ -0,0 +1,96 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use filecoin_proofs::constants::SECTOR_SIZE_2_KIB;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+const ID: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+/// The path of the `fil-proofs` binary built next to this test.
+fn fil_proofs_bin() -> PathBuf {
+    let mut path = env::current_exe().expect("no test executable");
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join(format!("fil-proofs{}", env::consts::EXE_SUFFIX))
+}
+
+/// Runs `fil-proofs` in `dir` with the whitespace separated `args`.
+fn fil_proofs(dir: &Path, args: &str) -> Output {
+    Command::new(fil_proofs_bin())
+        .current_dir(dir)
+        .args(args.split_whitespace())
+        .output()
+        .expect("failed to run fil-proofs")
+}
+
+fn assert_success(args: &str, output: &Output) {
+    assert!(
+        output.status.success(),
+        "fil-proofs {} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_seal_through_json_outputs() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let size = SECTOR_SIZE_2_KIB;
+
+    let rng = &mut XorShiftRng::from_seed([0x3d; 16]);
+    let piece: Vec<u8> = (0..2032).map(|_| rng.gen()).collect();
+    fs::write(dir.path().join("piece.dat"), &piece).expect("failed to write piece");
+
+    // Each step reads the JSON outputs of the previous ones.
+    let steps = [
+        "add-piece --piece piece.dat --staged staged --output piece.json".to_string(),
+        format!(
+            "precommit1 --sector-size {} --cache cache --staged staged --sealed sealed \
+             --prover-id {} --sector-id 1 --ticket {} --piece-infos piece.json --output pc1.json",
+            size, ID, ID
+        ),
+        format!(
+            "precommit2 --sector-size {} --phase1 pc1.json --cache cache --sealed sealed \
+             --output precommit.json",
+            size
+        ),
+        format!(
+            "commit1 --sector-size {} --precommit precommit.json --cache cache --prover-id {} \
+             --sector-id 1 --ticket {} --seed {} --piece-infos piece.json --output c1.json",
+            size, ID, ID, ID
+        ),
+        format!(
+            "commit2 --sector-size {} --phase1 c1.json --prover-id {} --sector-id 1 \
+             --output proof.json",
+            size, ID
+        ),
+    ];
+    for args in &steps {
+        assert_success(args, &fil_proofs(dir.path(), args));
+    }
+
+    let verify_seal = |seed: &str| {
+        format!(
+            "verify-seal --sector-size {} --precommit precommit.json --proof proof.json \
+             --prover-id {} --sector-id 1 --ticket {} --seed {}",
+            size, ID, ID, seed
+        )
+    };
+
+    let args = verify_seal(ID);
+    let output = fil_proofs(dir.path(), &args);
+    assert_success(&args, &output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "the seal proof is valid"
+    );
+
+    // The proof doesn't hold for another seed.
+    let output = fil_proofs(dir.path(), &verify_seal(&"02".repeat(32)));
+    assert!(!output.status.success());
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use fil_proofs_tooling::test_vectors::{check_vector, generate_vector, TestVectors};
+use filecoin_proofs::constants::SECTOR_SIZE_2_KIB;
+
+#[test]
+fn test_vectors_are_deterministic() {
+    let mut vector =
+        generate_vector(SECTOR_SIZE_2_KIB, [7; 16]).expect("failed to generate vector");
+    assert_eq!(check_vector(&vector).expect("failed to check vector"), None);
+
+    vector.outputs.comm_r = vector.outputs.comm_c.clone();
+    let mismatch = check_vector(&vector)
+        .expect("failed to check vector")
+        .expect("mismatch not reported");
+    assert!(mismatch.contains("comm_r:"), "{}", mismatch);
+}
+
+#[test]
+fn test_vectors_match() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-vectors.json");
+    if !path.exists() {
+        eprintln!(
+            "skipping, no test vectors at {:?}: generate them with `test-vectors generate`",
+            path
+        );
+        return;
+    }
+    let vectors = TestVectors::load(&path).expect("failed to load test vectors");
+
+    for vector in &vectors.vectors {
+        if let Some(mismatch) = check_vector(vector).expect("failed to check vector") {
+            panic!("test vectors at {:?} do not match: {}", path, mismatch);
+        }
+    }
+}
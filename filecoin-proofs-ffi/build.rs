@@ -0,0 +1,16 @@
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set");
+    let header = Path::new(&crate_dir)
+        .join("include")
+        .join("filecoin_proofs.h");
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::generate(&crate_dir)
+        .expect("could not generate header")
+        .write_to_file(header);
+}
@@ -0,0 +1,291 @@
+/* Generated by cbindgen from filecoin-proofs-ffi, do not edit. */
+
+#ifndef FILECOIN_PROOFS_H
+#define FILECOIN_PROOFS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * How a call went. Anything but `FCPNoError` comes with an `error_msg`.
+ */
+typedef enum FCPResponseStatus {
+  FCPNoError = 0,
+  /**
+   * The call panicked.
+   */
+  FCPUnclassifiedError = 1,
+  /**
+   * The arguments were invalid, e.g. a null pointer or an unsupported sector size.
+   */
+  FCPCallerError = 2,
+  /**
+   * The operation itself failed.
+   */
+  FCPReceiverError = 3,
+} FCPResponseStatus;
+
+/**
+ * `num_bytes_aligned` includes the alignment padding written before the piece.
+ */
+typedef struct FFIAddPieceResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  uint8_t comm_p[32];
+  uint64_t num_bytes_aligned;
+} FFIAddPieceResponse;
+
+typedef struct FFIGeneratePieceCommitmentResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  uint8_t comm_p[32];
+  uint64_t num_bytes;
+} FFIGeneratePieceCommitmentResponse;
+
+typedef struct FFICandidate {
+  uint64_t sector_id;
+  uint8_t partial_ticket[32];
+  uint8_t ticket[32];
+  uint64_t sector_challenge_index;
+} FFICandidate;
+
+typedef struct FFIGenerateCandidatesResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  const FFICandidate *candidates_ptr;
+  size_t candidates_len;
+} FFIGenerateCandidatesResponse;
+
+/**
+ * A borrowed byte buffer when passed in, an owned one when found in a response.
+ */
+typedef struct FFIBytes {
+  const uint8_t *ptr;
+  size_t len;
+} FFIBytes;
+
+/**
+ * One proof per partition, each owned by the response.
+ */
+typedef struct FFIGeneratePoStResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  const FFIBytes *proofs_ptr;
+  size_t proofs_len;
+} FFIGeneratePoStResponse;
+
+typedef struct FFIGetUnsealedRangeResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  uint64_t num_bytes_written;
+} FFIGetUnsealedRangeResponse;
+
+/**
+ * The phase 1 output is JSON, to be passed back unchanged to `seal_commit_phase2`.
+ */
+typedef struct FFISealCommitPhase1Response {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  const uint8_t *seal_commit_phase1_output_ptr;
+  size_t seal_commit_phase1_output_len;
+} FFISealCommitPhase1Response;
+
+typedef struct FFISealCommitPhase2Response {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  const uint8_t *proof_ptr;
+  size_t proof_len;
+} FFISealCommitPhase2Response;
+
+/**
+ * The phase 1 output is JSON, to be passed back unchanged to `seal_pre_commit_phase2`.
+ */
+typedef struct FFISealPreCommitPhase1Response {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  const uint8_t *seal_pre_commit_phase1_output_ptr;
+  size_t seal_pre_commit_phase1_output_len;
+} FFISealPreCommitPhase1Response;
+
+typedef struct FFISealPreCommitPhase2Response {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  uint8_t comm_r[32];
+  uint8_t comm_d[32];
+} FFISealPreCommitPhase2Response;
+
+/**
+ * Returned by `verify_seal`, `verify_batch_seal` and `verify_post`.
+ */
+typedef struct FFIVerifyResponse {
+  FCPResponseStatus status_code;
+  const char *error_msg;
+  bool is_valid;
+} FFIVerifyResponse;
+
+/**
+ * A 32 byte value (commitment, prover id, ticket, seed or randomness), passed by value.
+ */
+typedef struct FFI32ByteArray {
+  uint8_t inner[32];
+} FFI32ByteArray;
+
+typedef struct FFIPrivateReplicaInfo {
+  uint64_t sector_id;
+  uint8_t comm_r[32];
+  const char *cache_dir_path;
+  const char *replica_path;
+} FFIPrivateReplicaInfo;
+
+typedef struct FFIPublicPieceInfo {
+  uint64_t num_bytes;
+  uint8_t comm_p[32];
+} FFIPublicPieceInfo;
+
+typedef struct FFIPublicReplicaInfo {
+  uint64_t sector_id;
+  uint8_t comm_r[32];
+} FFIPublicReplicaInfo;
+
+/**
+ * Appends the first `piece_bytes` bytes of the file at `piece_path` to the staged sector,
+ * which is created if needed. `existing_piece_sizes` lists the pieces already in it.
+ */
+FFIAddPieceResponse *add_piece(const char *piece_path,
+                               const char *staged_sector_path,
+                               uint64_t piece_bytes,
+                               const uint64_t *existing_piece_sizes_ptr,
+                               size_t existing_piece_sizes_len);
+
+void destroy_add_piece_response(FFIAddPieceResponse *ptr);
+
+void destroy_generate_candidates_response(FFIGenerateCandidatesResponse *ptr);
+
+void destroy_generate_piece_commitment_response(FFIGeneratePieceCommitmentResponse *ptr);
+
+void destroy_generate_post_response(FFIGeneratePoStResponse *ptr);
+
+void destroy_get_unsealed_range_response(FFIGetUnsealedRangeResponse *ptr);
+
+void destroy_seal_commit_phase1_response(FFISealCommitPhase1Response *ptr);
+
+void destroy_seal_commit_phase2_response(FFISealCommitPhase2Response *ptr);
+
+void destroy_seal_pre_commit_phase1_response(FFISealPreCommitPhase1Response *ptr);
+
+void destroy_seal_pre_commit_phase2_response(FFISealPreCommitPhase2Response *ptr);
+
+void destroy_verify_response(FFIVerifyResponse *ptr);
+
+FFIGenerateCandidatesResponse *generate_candidates(uint64_t sector_size,
+                                                   FFI32ByteArray randomness,
+                                                   uint64_t challenge_count,
+                                                   const FFIPrivateReplicaInfo *replicas_ptr,
+                                                   size_t replicas_len,
+                                                   FFI32ByteArray prover_id);
+
+/**
+ * Computes the commitment of the first `num_bytes` bytes of the file at `piece_path`.
+ */
+FFIGeneratePieceCommitmentResponse *generate_piece_commitment(const char *piece_path,
+                                                              uint64_t num_bytes);
+
+FFIGeneratePoStResponse *generate_post(uint64_t sector_size,
+                                       FFI32ByteArray randomness,
+                                       const FFIPrivateReplicaInfo *replicas_ptr,
+                                       size_t replicas_len,
+                                       const FFICandidate *winners_ptr,
+                                       size_t winners_len,
+                                       FFI32ByteArray prover_id);
+
+/**
+ * Unseals `num_bytes` bytes starting at `offset`, and writes them to `unseal_output_path`.
+ */
+FFIGetUnsealedRangeResponse *get_unsealed_range(uint64_t sector_size,
+                                                const char *cache_dir_path,
+                                                const char *sealed_sector_path,
+                                                const char *unseal_output_path,
+                                                FFI32ByteArray prover_id,
+                                                uint64_t sector_id,
+                                                FFI32ByteArray comm_d,
+                                                FFI32ByteArray ticket,
+                                                uint64_t offset,
+                                                uint64_t num_bytes);
+
+/**
+ * Generates the vanilla proofs for `seed`, and returns the serialized
+ * `SealCommitPhase1Output`.
+ */
+FFISealCommitPhase1Response *seal_commit_phase1(uint64_t sector_size,
+                                                FFI32ByteArray comm_r,
+                                                FFI32ByteArray comm_d,
+                                                const char *cache_dir_path,
+                                                FFI32ByteArray prover_id,
+                                                uint64_t sector_id,
+                                                FFI32ByteArray ticket,
+                                                FFI32ByteArray seed,
+                                                const FFIPublicPieceInfo *pieces_ptr,
+                                                size_t pieces_len);
+
+FFISealCommitPhase2Response *seal_commit_phase2(uint64_t sector_size,
+                                                const uint8_t *seal_commit_phase1_output_ptr,
+                                                size_t seal_commit_phase1_output_len,
+                                                FFI32ByteArray prover_id,
+                                                uint64_t sector_id);
+
+/**
+ * Writes the replica of the data at `staged_sector_path` to `sealed_sector_path`, and
+ * returns the serialized `SealPreCommitPhase1Output`.
+ */
+FFISealPreCommitPhase1Response *seal_pre_commit_phase1(uint64_t sector_size,
+                                                       const char *cache_dir_path,
+                                                       const char *staged_sector_path,
+                                                       const char *sealed_sector_path,
+                                                       FFI32ByteArray prover_id,
+                                                       uint64_t sector_id,
+                                                       FFI32ByteArray ticket,
+                                                       const FFIPublicPieceInfo *pieces_ptr,
+                                                       size_t pieces_len);
+
+FFISealPreCommitPhase2Response *seal_pre_commit_phase2(uint64_t sector_size,
+                                                       const uint8_t *seal_pre_commit_phase1_output_ptr,
+                                                       size_t seal_pre_commit_phase1_output_len,
+                                                       const char *cache_dir_path,
+                                                       const char *sealed_sector_path);
+
+/**
+ * Verifies `len` seal proofs at once. Every array holds one element per proof.
+ */
+FFIVerifyResponse *verify_batch_seal(uint64_t sector_size,
+                                     const FFI32ByteArray *comm_rs_ptr,
+                                     const FFI32ByteArray *comm_ds_ptr,
+                                     const FFI32ByteArray *prover_ids_ptr,
+                                     const uint64_t *sector_ids_ptr,
+                                     const FFI32ByteArray *tickets_ptr,
+                                     const FFI32ByteArray *seeds_ptr,
+                                     const FFIBytes *proofs_ptr,
+                                     size_t len);
+
+FFIVerifyResponse *verify_post(uint64_t sector_size,
+                               FFI32ByteArray randomness,
+                               uint64_t challenge_count,
+                               const FFIBytes *proofs_ptr,
+                               size_t proofs_len,
+                               const FFIPublicReplicaInfo *replicas_ptr,
+                               size_t replicas_len,
+                               const FFICandidate *winners_ptr,
+                               size_t winners_len,
+                               FFI32ByteArray prover_id);
+
+FFIVerifyResponse *verify_seal(uint64_t sector_size,
+                               FFI32ByteArray comm_r,
+                               FFI32ByteArray comm_d,
+                               FFI32ByteArray prover_id,
+                               uint64_t sector_id,
+                               FFI32ByteArray ticket,
+                               FFI32ByteArray seed,
+                               const uint8_t *proof_ptr,
+                               size_t proof_len);
+
+#endif /* FILECOIN_PROOFS_H */
@@ -0,0 +1,670 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom};
+
+use anyhow::{Context, Result};
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::types::{
+    SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output, UnpaddedByteIndex,
+    UnpaddedBytesAmount,
+};
+use filecoin_proofs::{Candidate, PieceInfo, PrivateReplicaInfo, PublicReplicaInfo};
+use libc::c_char;
+use storage_proofs::sector::SectorId;
+
+use crate::types::*;
+use crate::util::*;
+
+unsafe fn piece_infos(ptr: *const FFIPublicPieceInfo, len: usize) -> Result<Vec<PieceInfo>> {
+    from_raw_slice(ptr, len, "pieces_ptr")?
+        .iter()
+        .map(to_piece_info)
+        .collect()
+}
+
+unsafe fn candidates(ptr: *const FFICandidate, len: usize) -> Result<Vec<Candidate>> {
+    from_raw_slice(ptr, len, "winners_ptr")?
+        .iter()
+        .map(|candidate| candidate.try_into_candidate())
+        .collect()
+}
+
+unsafe fn private_replicas(
+    ptr: *const FFIPrivateReplicaInfo,
+    len: usize,
+) -> Result<BTreeMap<SectorId, PrivateReplicaInfo<DefaultTreeHasher>>> {
+    let mut replicas = BTreeMap::new();
+    for info in from_raw_slice(ptr, len, "replicas_ptr")? {
+        let sector_id = SectorId::from(info.sector_id);
+        let replica = PrivateReplicaInfo::new(
+            c_str_to_string(info.replica_path, "replica_path")?,
+            info.comm_r,
+            c_str_to_pathbuf(info.cache_dir_path, "cache_dir_path")?,
+        )
+        .with_context(|| format!("invalid replica for sector {}", info.sector_id))?;
+
+        if replicas.insert(sector_id, replica).is_some() {
+            return Err(caller_error(format!(
+                "duplicate replica for sector {}",
+                info.sector_id
+            )));
+        }
+    }
+
+    Ok(replicas)
+}
+
+unsafe fn public_replicas(
+    ptr: *const FFIPublicReplicaInfo,
+    len: usize,
+) -> Result<BTreeMap<SectorId, PublicReplicaInfo<DefaultTreeHasher>>> {
+    let mut replicas = BTreeMap::new();
+    for info in from_raw_slice(ptr, len, "replicas_ptr")? {
+        let replica = PublicReplicaInfo::new(info.comm_r)
+            .map_err(|err| caller_error(format!("{:#}", err)))
+            .with_context(|| format!("invalid replica for sector {}", info.sector_id))?;
+
+        if replicas
+            .insert(SectorId::from(info.sector_id), replica)
+            .is_some()
+        {
+            return Err(caller_error(format!(
+                "duplicate replica for sector {}",
+                info.sector_id
+            )));
+        }
+    }
+
+    Ok(replicas)
+}
+
+/// Borrows every buffer of `proofs`.
+unsafe fn proof_slices<'a>(ptr: *const FFIBytes, len: usize) -> Result<Vec<&'a [u8]>> {
+    from_raw_slice(ptr, len, "proofs_ptr")?
+        .iter()
+        .map(|proof| from_raw_slice(proof.ptr, proof.len, "proof ptr"))
+        .collect()
+}
+
+/// Writes the replica of the data at `staged_sector_path` to `sealed_sector_path`, and
+/// returns the serialized `SealPreCommitPhase1Output`.
+#[no_mangle]
+pub unsafe extern "C" fn seal_pre_commit_phase1(
+    sector_size: u64,
+    cache_dir_path: *const c_char,
+    staged_sector_path: *const c_char,
+    sealed_sector_path: *const c_char,
+    prover_id: FFI32ByteArray,
+    sector_id: u64,
+    ticket: FFI32ByteArray,
+    pieces_ptr: *const FFIPublicPieceInfo,
+    pieces_len: usize,
+) -> *mut FFISealPreCommitPhase1Response {
+    respond("seal_pre_commit_phase1", |response| {
+        let output = filecoin_proofs::seal_pre_commit_phase1::<DefaultTreeHasher, _, _, _>(
+            porep_config(sector_size)?,
+            c_str_to_pathbuf(cache_dir_path, "cache_dir_path")?,
+            c_str_to_pathbuf(staged_sector_path, "staged_sector_path")?,
+            c_str_to_pathbuf(sealed_sector_path, "sealed_sector_path")?,
+            prover_id.inner,
+            SectorId::from(sector_id),
+            ticket.inner,
+            &piece_infos(pieces_ptr, pieces_len)?,
+        )?;
+
+        let (ptr, len) = into_raw_slice(serde_json::to_vec(&output)?);
+        response.seal_pre_commit_phase1_output_ptr = ptr;
+        response.seal_pre_commit_phase1_output_len = len;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn seal_pre_commit_phase2(
+    sector_size: u64,
+    seal_pre_commit_phase1_output_ptr: *const u8,
+    seal_pre_commit_phase1_output_len: usize,
+    cache_dir_path: *const c_char,
+    sealed_sector_path: *const c_char,
+) -> *mut FFISealPreCommitPhase2Response {
+    respond("seal_pre_commit_phase2", |response| {
+        let phase1_output: SealPreCommitPhase1Output<DefaultTreeHasher> =
+            serde_json::from_slice(from_raw_slice(
+                seal_pre_commit_phase1_output_ptr,
+                seal_pre_commit_phase1_output_len,
+                "seal_pre_commit_phase1_output_ptr",
+            )?)
+            .map_err(|err| caller_error(format!("invalid phase 1 output: {}", err)))?;
+
+        let SealPreCommitOutput { comm_r, comm_d } = filecoin_proofs::seal_pre_commit_phase2(
+            porep_config(sector_size)?,
+            phase1_output,
+            c_str_to_pathbuf(cache_dir_path, "cache_dir_path")?,
+            c_str_to_pathbuf(sealed_sector_path, "sealed_sector_path")?,
+        )?;
+
+        response.comm_r = comm_r;
+        response.comm_d = comm_d;
+
+        Ok(())
+    })
+}
+
+/// Generates the vanilla proofs for `seed`, and returns the serialized
+/// `SealCommitPhase1Output`.
+#[no_mangle]
+pub unsafe extern "C" fn seal_commit_phase1(
+    sector_size: u64,
+    comm_r: FFI32ByteArray,
+    comm_d: FFI32ByteArray,
+    cache_dir_path: *const c_char,
+    prover_id: FFI32ByteArray,
+    sector_id: u64,
+    ticket: FFI32ByteArray,
+    seed: FFI32ByteArray,
+    pieces_ptr: *const FFIPublicPieceInfo,
+    pieces_len: usize,
+) -> *mut FFISealCommitPhase1Response {
+    respond("seal_commit_phase1", |response| {
+        let output = filecoin_proofs::seal_commit_phase1::<DefaultTreeHasher, _>(
+            porep_config(sector_size)?,
+            c_str_to_pathbuf(cache_dir_path, "cache_dir_path")?,
+            prover_id.inner,
+            SectorId::from(sector_id),
+            ticket.inner,
+            seed.inner,
+            SealPreCommitOutput {
+                comm_r: comm_r.inner,
+                comm_d: comm_d.inner,
+            },
+            &piece_infos(pieces_ptr, pieces_len)?,
+        )?;
+
+        let (ptr, len) = into_raw_slice(serde_json::to_vec(&output)?);
+        response.seal_commit_phase1_output_ptr = ptr;
+        response.seal_commit_phase1_output_len = len;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn seal_commit_phase2(
+    sector_size: u64,
+    seal_commit_phase1_output_ptr: *const u8,
+    seal_commit_phase1_output_len: usize,
+    prover_id: FFI32ByteArray,
+    sector_id: u64,
+) -> *mut FFISealCommitPhase2Response {
+    respond("seal_commit_phase2", |response| {
+        let phase1_output: SealCommitPhase1Output<DefaultTreeHasher> =
+            serde_json::from_slice(from_raw_slice(
+                seal_commit_phase1_output_ptr,
+                seal_commit_phase1_output_len,
+                "seal_commit_phase1_output_ptr",
+            )?)
+            .map_err(|err| caller_error(format!("invalid phase 1 output: {}", err)))?;
+
+        let output = filecoin_proofs::seal_commit_phase2(
+            porep_config(sector_size)?,
+            phase1_output,
+            prover_id.inner,
+            SectorId::from(sector_id),
+        )?;
+
+        let (ptr, len) = into_raw_slice(output.proof);
+        response.proof_ptr = ptr;
+        response.proof_len = len;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn verify_seal(
+    sector_size: u64,
+    comm_r: FFI32ByteArray,
+    comm_d: FFI32ByteArray,
+    prover_id: FFI32ByteArray,
+    sector_id: u64,
+    ticket: FFI32ByteArray,
+    seed: FFI32ByteArray,
+    proof_ptr: *const u8,
+    proof_len: usize,
+) -> *mut FFIVerifyResponse {
+    respond("verify_seal", |response| {
+        response.is_valid = filecoin_proofs::verify_seal::<DefaultTreeHasher>(
+            porep_config(sector_size)?,
+            comm_r.inner,
+            comm_d.inner,
+            prover_id.inner,
+            SectorId::from(sector_id),
+            ticket.inner,
+            seed.inner,
+            from_raw_slice(proof_ptr, proof_len, "proof_ptr")?,
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Verifies `len` seal proofs at once. Every array holds one element per proof.
+#[no_mangle]
+pub unsafe extern "C" fn verify_batch_seal(
+    sector_size: u64,
+    comm_rs_ptr: *const FFI32ByteArray,
+    comm_ds_ptr: *const FFI32ByteArray,
+    prover_ids_ptr: *const FFI32ByteArray,
+    sector_ids_ptr: *const u64,
+    tickets_ptr: *const FFI32ByteArray,
+    seeds_ptr: *const FFI32ByteArray,
+    proofs_ptr: *const FFIBytes,
+    len: usize,
+) -> *mut FFIVerifyResponse {
+    respond("verify_batch_seal", |response| {
+        let inners = |ptr: *const FFI32ByteArray, name: &str| -> Result<Vec<[u8; 32]>> {
+            Ok(from_raw_slice(ptr, len, name)?
+                .iter()
+                .map(|value| value.inner)
+                .collect())
+        };
+
+        let sector_ids: Vec<SectorId> = from_raw_slice(sector_ids_ptr, len, "sector_ids_ptr")?
+            .iter()
+            .copied()
+            .map(SectorId::from)
+            .collect();
+
+        response.is_valid = filecoin_proofs::verify_batch_seal::<DefaultTreeHasher>(
+            porep_config(sector_size)?,
+            &inners(comm_rs_ptr, "comm_rs_ptr")?,
+            &inners(comm_ds_ptr, "comm_ds_ptr")?,
+            &inners(prover_ids_ptr, "prover_ids_ptr")?,
+            &sector_ids,
+            &inners(tickets_ptr, "tickets_ptr")?,
+            &inners(seeds_ptr, "seeds_ptr")?,
+            &proof_slices(proofs_ptr, len)?,
+        )?;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn generate_candidates(
+    sector_size: u64,
+    randomness: FFI32ByteArray,
+    challenge_count: u64,
+    replicas_ptr: *const FFIPrivateReplicaInfo,
+    replicas_len: usize,
+    prover_id: FFI32ByteArray,
+) -> *mut FFIGenerateCandidatesResponse {
+    respond("generate_candidates", |response| {
+        let candidates = filecoin_proofs::generate_candidates(
+            post_config(sector_size)?,
+            &randomness.inner,
+            challenge_count,
+            &private_replicas(replicas_ptr, replicas_len)?,
+            prover_id.inner,
+        )?;
+
+        let (ptr, len) = into_raw_slice(candidates.iter().map(FFICandidate::from).collect());
+        response.candidates_ptr = ptr;
+        response.candidates_len = len;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn generate_post(
+    sector_size: u64,
+    randomness: FFI32ByteArray,
+    replicas_ptr: *const FFIPrivateReplicaInfo,
+    replicas_len: usize,
+    winners_ptr: *const FFICandidate,
+    winners_len: usize,
+    prover_id: FFI32ByteArray,
+) -> *mut FFIGeneratePoStResponse {
+    respond("generate_post", |response| {
+        let proofs = filecoin_proofs::generate_post(
+            post_config(sector_size)?,
+            &randomness.inner,
+            &private_replicas(replicas_ptr, replicas_len)?,
+            candidates(winners_ptr, winners_len)?,
+            prover_id.inner,
+        )?;
+
+        let proofs: Vec<FFIBytes> = proofs
+            .into_iter()
+            .map(|proof| {
+                let (ptr, len) = into_raw_slice(proof);
+                FFIBytes { ptr, len }
+            })
+            .collect();
+        let (ptr, len) = into_raw_slice(proofs);
+        response.proofs_ptr = ptr;
+        response.proofs_len = len;
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn verify_post(
+    sector_size: u64,
+    randomness: FFI32ByteArray,
+    challenge_count: u64,
+    proofs_ptr: *const FFIBytes,
+    proofs_len: usize,
+    replicas_ptr: *const FFIPublicReplicaInfo,
+    replicas_len: usize,
+    winners_ptr: *const FFICandidate,
+    winners_len: usize,
+    prover_id: FFI32ByteArray,
+) -> *mut FFIVerifyResponse {
+    respond("verify_post", |response| {
+        let proofs: Vec<Vec<u8>> = proof_slices(proofs_ptr, proofs_len)?
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect();
+
+        response.is_valid = filecoin_proofs::verify_post(
+            post_config(sector_size)?,
+            &randomness.inner,
+            challenge_count,
+            &proofs,
+            &public_replicas(replicas_ptr, replicas_len)?,
+            &candidates(winners_ptr, winners_len)?,
+            prover_id.inner,
+        )?;
+
+        Ok(())
+    })
+}
+
+/// Computes the commitment of the first `num_bytes` bytes of the file at `piece_path`.
+#[no_mangle]
+pub unsafe extern "C" fn generate_piece_commitment(
+    piece_path: *const c_char,
+    num_bytes: u64,
+) -> *mut FFIGeneratePieceCommitmentResponse {
+    respond("generate_piece_commitment", |response| {
+        let piece_path = c_str_to_pathbuf(piece_path, "piece_path")?;
+        let piece_file = File::open(&piece_path)
+            .with_context(|| format!("could not open piece {:?}", piece_path))?;
+
+        let PieceInfo { commitment, size } =
+            filecoin_proofs::generate_piece_commitment(piece_file, UnpaddedBytesAmount(num_bytes))?;
+
+        response.comm_p = commitment;
+        response.num_bytes = size.into();
+
+        Ok(())
+    })
+}
+
+/// Appends the first `piece_bytes` bytes of the file at `piece_path` to the staged sector,
+/// which is created if needed. `existing_piece_sizes` lists the pieces already in it.
+#[no_mangle]
+pub unsafe extern "C" fn add_piece(
+    piece_path: *const c_char,
+    staged_sector_path: *const c_char,
+    piece_bytes: u64,
+    existing_piece_sizes_ptr: *const u64,
+    existing_piece_sizes_len: usize,
+) -> *mut FFIAddPieceResponse {
+    respond("add_piece", |response| {
+        let piece_path = c_str_to_pathbuf(piece_path, "piece_path")?;
+        let staged_sector_path = c_str_to_pathbuf(staged_sector_path, "staged_sector_path")?;
+        let existing_piece_sizes: Vec<UnpaddedBytesAmount> = from_raw_slice(
+            existing_piece_sizes_ptr,
+            existing_piece_sizes_len,
+            "existing_piece_sizes_ptr",
+        )?
+        .iter()
+        .copied()
+        .map(UnpaddedBytesAmount)
+        .collect();
+
+        let piece_file = File::open(&piece_path)
+            .with_context(|| format!("could not open piece {:?}", piece_path))?;
+        let mut staged_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&staged_sector_path)
+            .with_context(|| format!("could not open staged sector {:?}", staged_sector_path))?;
+        staged_file.seek(SeekFrom::End(0))?;
+
+        let (aligned, comm_p) = filecoin_proofs::add_piece(
+            piece_file,
+            staged_file,
+            UnpaddedBytesAmount(piece_bytes),
+            &existing_piece_sizes,
+        )?;
+
+        response.comm_p = comm_p;
+        response.num_bytes_aligned = aligned.into();
+
+        Ok(())
+    })
+}
+
+/// Unseals `num_bytes` bytes starting at `offset`, and writes them to `unseal_output_path`.
+#[no_mangle]
+pub unsafe extern "C" fn get_unsealed_range(
+    sector_size: u64,
+    cache_dir_path: *const c_char,
+    sealed_sector_path: *const c_char,
+    unseal_output_path: *const c_char,
+    prover_id: FFI32ByteArray,
+    sector_id: u64,
+    comm_d: FFI32ByteArray,
+    ticket: FFI32ByteArray,
+    offset: u64,
+    num_bytes: u64,
+) -> *mut FFIGetUnsealedRangeResponse {
+    respond("get_unsealed_range", |response| {
+        let written = filecoin_proofs::get_unsealed_range::<DefaultTreeHasher, _>(
+            porep_config(sector_size)?,
+            c_str_to_pathbuf(cache_dir_path, "cache_dir_path")?,
+            c_str_to_pathbuf(sealed_sector_path, "sealed_sector_path")?,
+            c_str_to_pathbuf(unseal_output_path, "unseal_output_path")?,
+            prover_id.inner,
+            SectorId::from(sector_id),
+            comm_d.inner,
+            ticket.inner,
+            UnpaddedByteIndex(offset),
+            UnpaddedBytesAmount(num_bytes),
+        )?;
+
+        response.num_bytes_written = written.into();
+
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_seal_pre_commit_phase1_response(
+    ptr: *mut FFISealPreCommitPhase1Response,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_seal_pre_commit_phase2_response(
+    ptr: *mut FFISealPreCommitPhase2Response,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_seal_commit_phase1_response(
+    ptr: *mut FFISealCommitPhase1Response,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_seal_commit_phase2_response(
+    ptr: *mut FFISealCommitPhase2Response,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_verify_response(ptr: *mut FFIVerifyResponse) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_generate_candidates_response(
+    ptr: *mut FFIGenerateCandidatesResponse,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_generate_post_response(ptr: *mut FFIGeneratePoStResponse) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_generate_piece_commitment_response(
+    ptr: *mut FFIGeneratePieceCommitmentResponse,
+) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_add_piece_response(ptr: *mut FFIAddPieceResponse) {
+    destroy(ptr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn destroy_get_unsealed_range_response(
+    ptr: *mut FFIGetUnsealedRangeResponse,
+) {
+    destroy(ptr)
+}
+
+/// Frees a response, together with everything it owns. Null is ignored.
+unsafe fn destroy<R>(ptr: *mut R) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::{CStr, CString};
+    use std::io::Write;
+    use std::ptr;
+
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    fn c_path(path: &std::path::Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_null_pointers_are_caller_errors() {
+        unsafe {
+            let response = generate_piece_commitment(ptr::null(), 127);
+            assert_eq!((*response).status_code, FCPResponseStatus::FCPCallerError);
+            let msg = CStr::from_ptr((*response).error_msg).to_str().unwrap();
+            assert!(msg.contains("piece_path"), "unexpected message: {}", msg);
+            destroy_generate_piece_commitment_response(response);
+
+            let response = verify_seal(
+                2048,
+                FFI32ByteArray { inner: [1; 32] },
+                FFI32ByteArray { inner: [1; 32] },
+                FFI32ByteArray::default(),
+                0,
+                FFI32ByteArray::default(),
+                FFI32ByteArray::default(),
+                ptr::null(),
+                192,
+            );
+            assert_eq!((*response).status_code, FCPResponseStatus::FCPCallerError);
+            assert!(!(*response).is_valid);
+            destroy_verify_response(response);
+
+            let response = verify_seal(
+                1234,
+                FFI32ByteArray { inner: [1; 32] },
+                FFI32ByteArray { inner: [1; 32] },
+                FFI32ByteArray::default(),
+                0,
+                FFI32ByteArray::default(),
+                FFI32ByteArray::default(),
+                ptr::null(),
+                0,
+            );
+            assert_eq!((*response).status_code, FCPResponseStatus::FCPCallerError);
+            destroy_verify_response(response);
+
+            // Destroying null is a no-op.
+            destroy_verify_response(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_add_piece_matches_piece_commitment() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let dir = tempfile::tempdir().unwrap();
+
+        let piece_path = dir.path().join("piece");
+        let staged_path = dir.path().join("staged");
+        let piece: Vec<u8> = (0..508).map(|_| rng.gen()).collect();
+        File::create(&piece_path)
+            .unwrap()
+            .write_all(&piece)
+            .unwrap();
+
+        let piece_path = c_path(&piece_path);
+        let staged_path = c_path(&staged_path);
+
+        unsafe {
+            let commitment = generate_piece_commitment(piece_path.as_ptr(), 508);
+            assert_eq!((*commitment).status_code, FCPResponseStatus::FCPNoError);
+            assert!((*commitment).error_msg.is_null());
+            assert_eq!((*commitment).num_bytes, 508);
+
+            let first = add_piece(
+                piece_path.as_ptr(),
+                staged_path.as_ptr(),
+                508,
+                ptr::null(),
+                0,
+            );
+            assert_eq!((*first).status_code, FCPResponseStatus::FCPNoError);
+            assert_eq!((*first).comm_p, (*commitment).comm_p);
+            assert_eq!((*first).num_bytes_aligned, 508);
+
+            let existing = [508u64];
+            let second = add_piece(
+                piece_path.as_ptr(),
+                staged_path.as_ptr(),
+                508,
+                existing.as_ptr(),
+                existing.len(),
+            );
+            assert_eq!((*second).status_code, FCPResponseStatus::FCPNoError);
+            assert_eq!((*second).comm_p, (*commitment).comm_p);
+
+            destroy_generate_piece_commitment_response(commitment);
+            destroy_add_piece_response(first);
+            destroy_add_piece_response(second);
+        }
+
+        let staged_len = std::fs::metadata(dir.path().join("staged")).unwrap().len();
+        assert_eq!(staged_len, 2 * 512, "both pieces must have been appended");
+    }
+}
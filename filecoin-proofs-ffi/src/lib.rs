@@ -0,0 +1,28 @@
+//! C bindings for the `filecoin-proofs` API.
+//!
+//! Every exported function takes its inputs by value or as borrowed pointers and returns a
+//! heap-allocated response struct. The response always carries a `status_code` and, when the
+//! status is not `FCPNoError`, a NUL-terminated `error_msg`. Any buffer hanging off a response
+//! is owned by that response, and is released together with it by the matching
+//! `destroy_*_response` function. Callers must not free those buffers themselves, and must not
+//! use them after the response has been destroyed.
+//!
+//! Input pointers are only read for the duration of the call. A pointer/length pair may be
+//! null when its length is zero; any other null pointer is rejected with `FCPCallerError`.
+//!
+//! The C declarations live in `include/filecoin_proofs.h`, which is regenerated by `build.rs`.
+
+#![deny(clippy::all, clippy::perf, clippy::correctness)]
+#![allow(clippy::missing_safety_doc)]
+
+mod api;
+mod types;
+mod util;
+
+pub use self::api::*;
+pub use self::types::*;
+
+#[cfg(test)]
+pub(crate) const TEST_SEED: [u8; 16] = [
+    0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
+];
@@ -0,0 +1,426 @@
+use std::ptr;
+
+use anyhow::{Context, Result};
+use filecoin_proofs::Candidate;
+use libc::c_char;
+use paired::bls12_381::Bls12;
+use storage_proofs::fr32::{bytes_into_fr, fr_into_bytes};
+use storage_proofs::sector::SectorId;
+
+use crate::util::{caller_error, free_c_str, free_raw_slice, into_c_str};
+
+/// How a call went. Anything but `FCPNoError` comes with an `error_msg`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FCPResponseStatus {
+    FCPNoError = 0,
+    /// The call panicked.
+    FCPUnclassifiedError = 1,
+    /// The arguments were invalid, e.g. a null pointer or an unsupported sector size.
+    FCPCallerError = 2,
+    /// The operation itself failed.
+    FCPReceiverError = 3,
+}
+
+/// A 32 byte value (commitment, prover id, ticket, seed or randomness), passed by value.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FFI32ByteArray {
+    pub inner: [u8; 32],
+}
+
+/// A borrowed byte buffer when passed in, an owned one when found in a response.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFIBytes {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFIPublicPieceInfo {
+    pub num_bytes: u64,
+    pub comm_p: [u8; 32],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFICandidate {
+    pub sector_id: u64,
+    pub partial_ticket: [u8; 32],
+    pub ticket: [u8; 32],
+    pub sector_challenge_index: u64,
+}
+
+impl FFICandidate {
+    pub(crate) fn try_into_candidate(self) -> Result<Candidate> {
+        let partial_ticket = bytes_into_fr::<Bls12>(&self.partial_ticket)
+            .map_err(|_| caller_error("invalid partial_ticket"))?;
+
+        Ok(Candidate {
+            sector_id: SectorId::from(self.sector_id),
+            partial_ticket,
+            ticket: self.ticket,
+            sector_challenge_index: self.sector_challenge_index,
+        })
+    }
+}
+
+impl From<&Candidate> for FFICandidate {
+    fn from(candidate: &Candidate) -> Self {
+        let mut partial_ticket = [0; 32];
+        partial_ticket.copy_from_slice(&fr_into_bytes::<Bls12>(&candidate.partial_ticket));
+
+        FFICandidate {
+            sector_id: u64::from(candidate.sector_id),
+            partial_ticket,
+            ticket: candidate.ticket,
+            sector_challenge_index: candidate.sector_challenge_index,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFIPrivateReplicaInfo {
+    pub sector_id: u64,
+    pub comm_r: [u8; 32],
+    pub cache_dir_path: *const c_char,
+    pub replica_path: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FFIPublicReplicaInfo {
+    pub sector_id: u64,
+    pub comm_r: [u8; 32],
+}
+
+/// Implemented by all responses, so `util::respond` can report a failure.
+pub(crate) trait Response: Default {
+    fn set_error(&mut self, status_code: FCPResponseStatus, error_msg: String);
+}
+
+macro_rules! impl_response {
+    ($($response:ty),*) => {
+        $(
+            impl Response for $response {
+                fn set_error(&mut self, status_code: FCPResponseStatus, error_msg: String) {
+                    self.status_code = status_code;
+                    self.error_msg = into_c_str(error_msg);
+                }
+            }
+        )*
+    };
+}
+
+impl_response!(
+    FFISealPreCommitPhase1Response,
+    FFISealPreCommitPhase2Response,
+    FFISealCommitPhase1Response,
+    FFISealCommitPhase2Response,
+    FFIVerifyResponse,
+    FFIGenerateCandidatesResponse,
+    FFIGeneratePoStResponse,
+    FFIGeneratePieceCommitmentResponse,
+    FFIAddPieceResponse,
+    FFIGetUnsealedRangeResponse
+);
+
+/// The phase 1 output is JSON, to be passed back unchanged to `seal_pre_commit_phase2`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFISealPreCommitPhase1Response {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub seal_pre_commit_phase1_output_ptr: *const u8,
+    pub seal_pre_commit_phase1_output_len: usize,
+}
+
+impl Default for FFISealPreCommitPhase1Response {
+    fn default() -> Self {
+        FFISealPreCommitPhase1Response {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            seal_pre_commit_phase1_output_ptr: ptr::null(),
+            seal_pre_commit_phase1_output_len: 0,
+        }
+    }
+}
+
+impl Drop for FFISealPreCommitPhase1Response {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg);
+            free_raw_slice(
+                self.seal_pre_commit_phase1_output_ptr,
+                self.seal_pre_commit_phase1_output_len,
+            );
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFISealPreCommitPhase2Response {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub comm_r: [u8; 32],
+    pub comm_d: [u8; 32],
+}
+
+impl Default for FFISealPreCommitPhase2Response {
+    fn default() -> Self {
+        FFISealPreCommitPhase2Response {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            comm_r: [0; 32],
+            comm_d: [0; 32],
+        }
+    }
+}
+
+impl Drop for FFISealPreCommitPhase2Response {
+    fn drop(&mut self) {
+        unsafe { free_c_str(self.error_msg) };
+    }
+}
+
+/// The phase 1 output is JSON, to be passed back unchanged to `seal_commit_phase2`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFISealCommitPhase1Response {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub seal_commit_phase1_output_ptr: *const u8,
+    pub seal_commit_phase1_output_len: usize,
+}
+
+impl Default for FFISealCommitPhase1Response {
+    fn default() -> Self {
+        FFISealCommitPhase1Response {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            seal_commit_phase1_output_ptr: ptr::null(),
+            seal_commit_phase1_output_len: 0,
+        }
+    }
+}
+
+impl Drop for FFISealCommitPhase1Response {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg);
+            free_raw_slice(
+                self.seal_commit_phase1_output_ptr,
+                self.seal_commit_phase1_output_len,
+            );
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFISealCommitPhase2Response {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub proof_ptr: *const u8,
+    pub proof_len: usize,
+}
+
+impl Default for FFISealCommitPhase2Response {
+    fn default() -> Self {
+        FFISealCommitPhase2Response {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            proof_ptr: ptr::null(),
+            proof_len: 0,
+        }
+    }
+}
+
+impl Drop for FFISealCommitPhase2Response {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg);
+            free_raw_slice(self.proof_ptr, self.proof_len);
+        }
+    }
+}
+
+/// Returned by `verify_seal`, `verify_batch_seal` and `verify_post`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIVerifyResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub is_valid: bool,
+}
+
+impl Default for FFIVerifyResponse {
+    fn default() -> Self {
+        FFIVerifyResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            is_valid: false,
+        }
+    }
+}
+
+impl Drop for FFIVerifyResponse {
+    fn drop(&mut self) {
+        unsafe { free_c_str(self.error_msg) };
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIGenerateCandidatesResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub candidates_ptr: *const FFICandidate,
+    pub candidates_len: usize,
+}
+
+impl Default for FFIGenerateCandidatesResponse {
+    fn default() -> Self {
+        FFIGenerateCandidatesResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            candidates_ptr: ptr::null(),
+            candidates_len: 0,
+        }
+    }
+}
+
+impl Drop for FFIGenerateCandidatesResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg);
+            free_raw_slice(self.candidates_ptr, self.candidates_len);
+        }
+    }
+}
+
+/// One proof per partition, each owned by the response.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIGeneratePoStResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub proofs_ptr: *const FFIBytes,
+    pub proofs_len: usize,
+}
+
+impl Default for FFIGeneratePoStResponse {
+    fn default() -> Self {
+        FFIGeneratePoStResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            proofs_ptr: ptr::null(),
+            proofs_len: 0,
+        }
+    }
+}
+
+impl Drop for FFIGeneratePoStResponse {
+    fn drop(&mut self) {
+        unsafe {
+            free_c_str(self.error_msg);
+            if !self.proofs_ptr.is_null() {
+                for proof in std::slice::from_raw_parts(self.proofs_ptr, self.proofs_len) {
+                    free_raw_slice(proof.ptr, proof.len);
+                }
+            }
+            free_raw_slice(self.proofs_ptr, self.proofs_len);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIGeneratePieceCommitmentResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub comm_p: [u8; 32],
+    pub num_bytes: u64,
+}
+
+impl Default for FFIGeneratePieceCommitmentResponse {
+    fn default() -> Self {
+        FFIGeneratePieceCommitmentResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            comm_p: [0; 32],
+            num_bytes: 0,
+        }
+    }
+}
+
+impl Drop for FFIGeneratePieceCommitmentResponse {
+    fn drop(&mut self) {
+        unsafe { free_c_str(self.error_msg) };
+    }
+}
+
+/// `num_bytes_aligned` includes the alignment padding written before the piece.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIAddPieceResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub comm_p: [u8; 32],
+    pub num_bytes_aligned: u64,
+}
+
+impl Default for FFIAddPieceResponse {
+    fn default() -> Self {
+        FFIAddPieceResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            comm_p: [0; 32],
+            num_bytes_aligned: 0,
+        }
+    }
+}
+
+impl Drop for FFIAddPieceResponse {
+    fn drop(&mut self) {
+        unsafe { free_c_str(self.error_msg) };
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FFIGetUnsealedRangeResponse {
+    pub status_code: FCPResponseStatus,
+    pub error_msg: *const c_char,
+    pub num_bytes_written: u64,
+}
+
+impl Default for FFIGetUnsealedRangeResponse {
+    fn default() -> Self {
+        FFIGetUnsealedRangeResponse {
+            status_code: FCPResponseStatus::FCPNoError,
+            error_msg: ptr::null(),
+            num_bytes_written: 0,
+        }
+    }
+}
+
+impl Drop for FFIGetUnsealedRangeResponse {
+    fn drop(&mut self) {
+        unsafe { free_c_str(self.error_msg) };
+    }
+}
+
+/// Converts a caller supplied piece info, rejecting the all zero commitment.
+pub(crate) fn to_piece_info(info: &FFIPublicPieceInfo) -> Result<filecoin_proofs::PieceInfo> {
+    filecoin_proofs::PieceInfo::new(
+        info.comm_p,
+        filecoin_proofs::UnpaddedBytesAmount(info.num_bytes),
+    )
+    .map_err(|err| caller_error(format!("{:#}", err)))
+    .context("invalid piece info")
+}
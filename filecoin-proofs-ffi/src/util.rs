@@ -0,0 +1,234 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::slice;
+use std::sync::Once;
+
+use anyhow::Result;
+use filecoin_proofs::constants::POREP_PARTITIONS;
+use filecoin_proofs::{PoRepConfig, PoStConfig};
+use libc::c_char;
+use log::{error, info};
+
+use crate::types::{FCPResponseStatus, Response};
+
+static LOGGER: Once = Once::new();
+
+/// Marks an error as the caller's fault, reported as `FCPCallerError`.
+#[derive(Debug)]
+pub(crate) struct CallerError(String);
+
+impl fmt::Display for CallerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CallerError {}
+
+pub(crate) fn caller_error<S: Into<String>>(msg: S) -> anyhow::Error {
+    CallerError(msg.into()).into()
+}
+
+/// Runs `f` against a fresh response and hands the response over to the caller.
+///
+/// Errors and panics discard whatever `f` had filled in, so a failed response only ever
+/// carries a status and a message.
+pub(crate) fn respond<R, F>(name: &str, f: F) -> *mut R
+where
+    R: Response,
+    F: FnOnce(&mut R) -> Result<()>,
+{
+    LOGGER.call_once(fil_logger::init);
+    info!("{}: start", name);
+
+    let mut response = R::default();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut response)));
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            error!("{}: {:?}", name, err);
+
+            let status_code = if err.downcast_ref::<CallerError>().is_some() {
+                FCPResponseStatus::FCPCallerError
+            } else {
+                FCPResponseStatus::FCPReceiverError
+            };
+            response = R::default();
+            response.set_error(status_code, format!("{:#}", err));
+        }
+        Err(panic) => {
+            let msg = if let Some(msg) = panic.downcast_ref::<&str>() {
+                (*msg).to_string()
+            } else if let Some(msg) = panic.downcast_ref::<String>() {
+                msg.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            error!("{}: panicked: {}", name, msg);
+
+            response = R::default();
+            response.set_error(
+                FCPResponseStatus::FCPUnclassifiedError,
+                format!("panicked: {}", msg),
+            );
+        }
+    }
+
+    info!("{}: finish", name);
+    Box::into_raw(Box::new(response))
+}
+
+/// Borrows a NUL-terminated, UTF-8 C string.
+pub(crate) unsafe fn c_str_to_string(ptr: *const c_char, name: &str) -> Result<String> {
+    if ptr.is_null() {
+        return Err(caller_error(format!("{} must not be null", name)));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(ToString::to_string)
+        .map_err(|_| caller_error(format!("{} is not valid UTF-8", name)))
+}
+
+pub(crate) unsafe fn c_str_to_pathbuf(ptr: *const c_char, name: &str) -> Result<PathBuf> {
+    c_str_to_string(ptr, name).map(PathBuf::from)
+}
+
+/// Borrows `len` elements at `ptr`, which may only be null if `len` is zero.
+pub(crate) unsafe fn from_raw_slice<'a, T>(
+    ptr: *const T,
+    len: usize,
+    name: &str,
+) -> Result<&'a [T]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(caller_error(format!("{} must not be null", name)));
+    }
+
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+/// Leaks `values`, to be reclaimed by `free_raw_slice`. Empty vectors become a null pointer.
+pub(crate) fn into_raw_slice<T>(values: Vec<T>) -> (*const T, usize) {
+    if values.is_empty() {
+        return (ptr::null(), 0);
+    }
+
+    let len = values.len();
+    let ptr = Box::into_raw(values.into_boxed_slice()) as *const T;
+    (ptr, len)
+}
+
+pub(crate) unsafe fn free_raw_slice<T>(ptr: *const T, len: usize) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(slice::from_raw_parts_mut(ptr as *mut T, len)));
+    }
+}
+
+/// Leaks `s` as a C string, to be reclaimed by `free_c_str`. Interior NULs are escaped.
+pub(crate) fn into_c_str(s: String) -> *const c_char {
+    CString::new(s.replace('\0', "\\0"))
+        .expect("interior NUL bytes were escaped")
+        .into_raw()
+}
+
+pub(crate) unsafe fn free_c_str(ptr: *const c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr as *mut c_char));
+    }
+}
+
+/// Fails with a caller error if `sector_size` has no sector profile, and thus no partitions.
+fn ensure_supported_sector_size(sector_size: u64) -> Result<()> {
+    if POREP_PARTITIONS.read().unwrap().contains_key(&sector_size) {
+        Ok(())
+    } else {
+        Err(caller_error(format!(
+            "unsupported sector size {}",
+            sector_size
+        )))
+    }
+}
+
+/// `filecoin_proofs::porep_config`, failing with a caller error for unsupported sector sizes.
+/// Any other error is an internal one.
+pub(crate) fn porep_config(sector_size: u64) -> Result<PoRepConfig> {
+    ensure_supported_sector_size(sector_size)?;
+    filecoin_proofs::porep_config(sector_size)
+}
+
+/// `filecoin_proofs::post_config`, failing with a caller error for unsupported sector sizes.
+/// Any other error is an internal one.
+pub(crate) fn post_config(sector_size: u64) -> Result<PoStConfig> {
+    ensure_supported_sector_size(sector_size)?;
+    filecoin_proofs::post_config(sector_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::types::FFIVerifyResponse;
+
+    #[test]
+    fn test_raw_slice_roundtrip() {
+        let (ptr, len) = into_raw_slice(vec![1u8, 2, 3]);
+        let borrowed = unsafe { from_raw_slice(ptr, len, "values") }.unwrap();
+        assert_eq!(borrowed, &[1, 2, 3]);
+        unsafe { free_raw_slice(ptr, len) };
+
+        let (ptr, len) = into_raw_slice(Vec::<u8>::new());
+        assert!(ptr.is_null());
+        assert!(unsafe { from_raw_slice(ptr, len, "values") }
+            .unwrap()
+            .is_empty());
+        assert!(unsafe { from_raw_slice(ptr, 1, "values") }.is_err());
+    }
+
+    #[test]
+    fn test_respond_classifies_failures() {
+        let status = |response: *mut FFIVerifyResponse| {
+            let response = unsafe { Box::from_raw(response) };
+            assert!(!response.is_valid, "failures must reset the response");
+            if response.status_code != FCPResponseStatus::FCPNoError {
+                assert!(!response.error_msg.is_null());
+            }
+            response.status_code
+        };
+
+        let caller = respond("test", |response: &mut FFIVerifyResponse| {
+            response.is_valid = true;
+            Err(caller_error("bad input"))
+        });
+        assert_eq!(status(caller), FCPResponseStatus::FCPCallerError);
+
+        let receiver = respond("test", |response: &mut FFIVerifyResponse| {
+            response.is_valid = true;
+            anyhow::bail!("failed")
+        });
+        assert_eq!(status(receiver), FCPResponseStatus::FCPReceiverError);
+
+        let unclassified = respond("test", |response: &mut FFIVerifyResponse| {
+            response.is_valid = true;
+            panic!("boom")
+        });
+        assert_eq!(
+            status(unclassified),
+            FCPResponseStatus::FCPUnclassifiedError
+        );
+    }
+
+    #[test]
+    fn test_unsupported_sector_size() {
+        let err = porep_config(1234).unwrap_err();
+        assert!(err.downcast_ref::<CallerError>().is_some());
+        let err = post_config(1234).unwrap_err();
+        assert!(err.downcast_ref::<CallerError>().is_some());
+    }
+}
@@ -0,0 +1,199 @@
+/*
+ * Seals, proves and unseals a 2KiB sector through the C API.
+ *
+ * Needs the 2KiB Groth parameters, see `paramfetch`. Build and run with `make` from this
+ * directory, after `cargo build --release -p filecoin-proofs-ffi`.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
+
+#include "filecoin_proofs.h"
+
+#define SECTOR_SIZE 2048
+#define PIECE_SIZE 2032
+
+#define CHECK(response, name)                                                                  \
+  do {                                                                                         \
+    if ((response)->status_code != FCPNoError) {                                               \
+      fprintf(stderr, "%s failed (%d): %s\n", name, (response)->status_code,                  \
+              (response)->error_msg);                                                          \
+      exit(1);                                                                                 \
+    }                                                                                          \
+  } while (0)
+
+#define ASSERT(cond, msg)                                                                      \
+  do {                                                                                         \
+    if (!(cond)) {                                                                             \
+      fprintf(stderr, "assertion failed: %s\n", msg);                                          \
+      exit(1);                                                                                 \
+    }                                                                                          \
+  } while (0)
+
+static FFI32ByteArray filled(uint8_t value) {
+  FFI32ByteArray array;
+  memset(array.inner, value, sizeof(array.inner));
+  return array;
+}
+
+static FFI32ByteArray from_bytes(const uint8_t bytes[32]) {
+  FFI32ByteArray array;
+  memcpy(array.inner, bytes, sizeof(array.inner));
+  return array;
+}
+
+static void join(char *out, const char *dir, const char *name) {
+  snprintf(out, 4096, "%s/%s", dir, name);
+}
+
+static void test_caller_error(void) {
+  FFIGeneratePieceCommitmentResponse *response = generate_piece_commitment(NULL, PIECE_SIZE);
+  ASSERT(response->status_code == FCPCallerError, "null path must be a caller error");
+  ASSERT(response->error_msg != NULL, "failures carry a message");
+  destroy_generate_piece_commitment_response(response);
+}
+
+int main(void) {
+  char dir[] = "/tmp/filecoin-proofs-ffi-XXXXXX";
+  char piece_path[4096], staged_path[4096], sealed_path[4096], cache_path[4096],
+      unsealed_path[4096];
+
+  ASSERT(mkdtemp(dir) != NULL, "could not create a temporary directory");
+  join(piece_path, dir, "piece");
+  join(staged_path, dir, "staged");
+  join(sealed_path, dir, "sealed");
+  join(cache_path, dir, "cache");
+  join(unsealed_path, dir, "unsealed");
+  ASSERT(mkdir(cache_path, 0700) == 0, "could not create the cache directory");
+
+  uint8_t piece[PIECE_SIZE];
+  for (size_t i = 0; i < PIECE_SIZE; i++) {
+    piece[i] = (uint8_t)(rand() % 256);
+  }
+  FILE *piece_file = fopen(piece_path, "wb");
+  ASSERT(piece_file != NULL, "could not create the piece");
+  ASSERT(fwrite(piece, 1, PIECE_SIZE, piece_file) == PIECE_SIZE, "could not write the piece");
+  fclose(piece_file);
+  FILE *sealed_file = fopen(sealed_path, "wb");
+  ASSERT(sealed_file != NULL, "could not create the sealed sector");
+  fclose(sealed_file);
+
+  test_caller_error();
+
+  FFI32ByteArray prover_id = filled(1);
+  FFI32ByteArray ticket = filled(2);
+  FFI32ByteArray seed = filled(3);
+  FFI32ByteArray randomness = filled(4);
+  uint64_t sector_id = 42;
+
+  FFIGeneratePieceCommitmentResponse *comm_p_resp =
+      generate_piece_commitment(piece_path, PIECE_SIZE);
+  CHECK(comm_p_resp, "generate_piece_commitment");
+
+  FFIAddPieceResponse *add_piece_resp = add_piece(piece_path, staged_path, PIECE_SIZE, NULL, 0);
+  CHECK(add_piece_resp, "add_piece");
+  ASSERT(memcmp(add_piece_resp->comm_p, comm_p_resp->comm_p, 32) == 0,
+         "add_piece and generate_piece_commitment disagree");
+
+  FFIPublicPieceInfo piece_info;
+  piece_info.num_bytes = PIECE_SIZE;
+  memcpy(piece_info.comm_p, add_piece_resp->comm_p, 32);
+
+  FFISealPreCommitPhase1Response *p1_resp =
+      seal_pre_commit_phase1(SECTOR_SIZE, cache_path, staged_path, sealed_path, prover_id,
+                             sector_id, ticket, &piece_info, 1);
+  CHECK(p1_resp, "seal_pre_commit_phase1");
+
+  FFISealPreCommitPhase2Response *p2_resp = seal_pre_commit_phase2(
+      SECTOR_SIZE, p1_resp->seal_pre_commit_phase1_output_ptr,
+      p1_resp->seal_pre_commit_phase1_output_len, cache_path, sealed_path);
+  CHECK(p2_resp, "seal_pre_commit_phase2");
+  FFI32ByteArray comm_r = from_bytes(p2_resp->comm_r);
+  FFI32ByteArray comm_d = from_bytes(p2_resp->comm_d);
+
+  FFISealCommitPhase1Response *c1_resp =
+      seal_commit_phase1(SECTOR_SIZE, comm_r, comm_d, cache_path, prover_id, sector_id, ticket,
+                         seed, &piece_info, 1);
+  CHECK(c1_resp, "seal_commit_phase1");
+
+  FFISealCommitPhase2Response *c2_resp =
+      seal_commit_phase2(SECTOR_SIZE, c1_resp->seal_commit_phase1_output_ptr,
+                         c1_resp->seal_commit_phase1_output_len, prover_id, sector_id);
+  CHECK(c2_resp, "seal_commit_phase2");
+
+  FFIVerifyResponse *verify_resp =
+      verify_seal(SECTOR_SIZE, comm_r, comm_d, prover_id, sector_id, ticket, seed,
+                  c2_resp->proof_ptr, c2_resp->proof_len);
+  CHECK(verify_resp, "verify_seal");
+  ASSERT(verify_resp->is_valid, "seal proof must verify");
+  destroy_verify_response(verify_resp);
+
+  FFIBytes proof = {c2_resp->proof_ptr, c2_resp->proof_len};
+  verify_resp = verify_batch_seal(SECTOR_SIZE, &comm_r, &comm_d, &prover_id, &sector_id, &ticket,
+                                  &seed, &proof, 1);
+  CHECK(verify_resp, "verify_batch_seal");
+  ASSERT(verify_resp->is_valid, "batched seal proof must verify");
+  destroy_verify_response(verify_resp);
+
+  verify_resp = verify_seal(SECTOR_SIZE, comm_r, comm_d, prover_id, sector_id + 1, ticket, seed,
+                            c2_resp->proof_ptr, c2_resp->proof_len);
+  CHECK(verify_resp, "verify_seal");
+  ASSERT(!verify_resp->is_valid, "seal proof must not verify for another sector");
+  destroy_verify_response(verify_resp);
+
+  FFIGetUnsealedRangeResponse *unseal_resp =
+      get_unsealed_range(SECTOR_SIZE, cache_path, sealed_path, unsealed_path, prover_id,
+                         sector_id, comm_d, ticket, 0, PIECE_SIZE);
+  CHECK(unseal_resp, "get_unsealed_range");
+  ASSERT(unseal_resp->num_bytes_written == PIECE_SIZE, "unexpected unsealed length");
+  destroy_get_unsealed_range_response(unseal_resp);
+
+  uint8_t unsealed[PIECE_SIZE];
+  FILE *unsealed_file = fopen(unsealed_path, "rb");
+  ASSERT(unsealed_file != NULL, "could not open the unsealed piece");
+  ASSERT(fread(unsealed, 1, PIECE_SIZE, unsealed_file) == PIECE_SIZE, "short unsealed piece");
+  fclose(unsealed_file);
+  ASSERT(memcmp(unsealed, piece, PIECE_SIZE) == 0, "unsealed bytes differ from the piece");
+
+  FFIPrivateReplicaInfo private_replica;
+  private_replica.sector_id = sector_id;
+  memcpy(private_replica.comm_r, comm_r.inner, 32);
+  private_replica.cache_dir_path = cache_path;
+  private_replica.replica_path = sealed_path;
+
+  FFIGenerateCandidatesResponse *candidates_resp =
+      generate_candidates(SECTOR_SIZE, randomness, 1, &private_replica, 1, prover_id);
+  CHECK(candidates_resp, "generate_candidates");
+  ASSERT(candidates_resp->candidates_len == 1, "expected one candidate");
+
+  FFIGeneratePoStResponse *post_resp =
+      generate_post(SECTOR_SIZE, randomness, &private_replica, 1,
+                    candidates_resp->candidates_ptr, candidates_resp->candidates_len, prover_id);
+  CHECK(post_resp, "generate_post");
+
+  FFIPublicReplicaInfo public_replica;
+  public_replica.sector_id = sector_id;
+  memcpy(public_replica.comm_r, comm_r.inner, 32);
+
+  verify_resp = verify_post(SECTOR_SIZE, randomness, 1, post_resp->proofs_ptr,
+                            post_resp->proofs_len, &public_replica, 1,
+                            candidates_resp->candidates_ptr, candidates_resp->candidates_len,
+                            prover_id);
+  CHECK(verify_resp, "verify_post");
+  ASSERT(verify_resp->is_valid, "PoSt must verify");
+  destroy_verify_response(verify_resp);
+
+  destroy_generate_post_response(post_resp);
+  destroy_generate_candidates_response(candidates_resp);
+  destroy_seal_commit_phase2_response(c2_resp);
+  destroy_seal_commit_phase1_response(c1_resp);
+  destroy_seal_pre_commit_phase2_response(p2_resp);
+  destroy_seal_pre_commit_phase1_response(p1_resp);
+  destroy_add_piece_response(add_piece_resp);
+  destroy_generate_piece_commitment_response(comm_p_resp);
+
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,153 @@
+//! Deterministic fake SNARK proofs, for testing sealing and PoSt orchestration without
+//! Groth parameters.
+//!
+//! With the `mock-proofs` feature, `seal_commit_phase2` and `generate_post` still run every
+//! vanilla step, but emit a mock proof instead of proving the circuits: `MOCK_PROOF_MAGIC`
+//! followed by a digest of the public inputs. `verify_seal`, `verify_batch_seal` and
+//! `verify_post` then accept exactly those. Builds without the feature refuse mock proofs,
+//! and the feature can't be enabled in release builds.
+
+use anyhow::{ensure, Result};
+use blake2b_simd::{Params as Blake2b, State};
+use paired::bls12_381::Bls12;
+use storage_proofs::election_post::Candidate;
+use storage_proofs::fr32::fr_into_bytes;
+use storage_proofs::sector::SectorId;
+
+use crate::types::{
+    ChallengeSeed, Commitment, PoRepConfig, PoRepProofPartitions, PoStConfig, ProverId, SectorSize,
+    Ticket,
+};
+
+/// Whether this build emits, and only accepts, mock proofs.
+pub const MOCK_PROOFS: bool = cfg!(feature = "mock-proofs");
+
+/// Prefix of every mock proof, which no real (raw or enveloped) proof starts with.
+pub const MOCK_PROOF_MAGIC: [u8; 8] = *b"FILMOCK\0";
+
+/// Returns true if `proof` is a mock proof.
+pub fn is_mock_proof(proof: &[u8]) -> bool {
+    proof.starts_with(&MOCK_PROOF_MAGIC)
+}
+
+pub(crate) fn seal_proof(
+    porep_config: PoRepConfig,
+    comm_r: &Commitment,
+    comm_d: &Commitment,
+    prover_id: &ProverId,
+    sector_id: SectorId,
+    ticket: &Ticket,
+    seed: &Ticket,
+) -> Vec<u8> {
+    let mut hasher = mock_hasher("seal", porep_config.sector_size);
+    update(
+        &mut hasher,
+        &(usize::from(PoRepProofPartitions::from(porep_config)) as u64).to_le_bytes(),
+    );
+    for input in &[comm_r, comm_d, prover_id] {
+        update(&mut hasher, &input[..]);
+    }
+    update(&mut hasher, &u64::from(sector_id).to_le_bytes());
+    update(&mut hasher, ticket);
+    update(&mut hasher, seed);
+
+    finish(hasher)
+}
+
+pub(crate) fn post_proof(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    comm_r: &[u8],
+    winner: &Candidate,
+    prover_id: &ProverId,
+) -> Vec<u8> {
+    let mut hasher = mock_hasher("post", post_config.sector_size);
+    update(&mut hasher, randomness);
+    update(&mut hasher, comm_r);
+    update(&mut hasher, &u64::from(winner.sector_id).to_le_bytes());
+    update(&mut hasher, &fr_into_bytes::<Bls12>(&winner.partial_ticket));
+    update(&mut hasher, &winner.sector_challenge_index.to_le_bytes());
+    update(&mut hasher, prover_id);
+
+    finish(hasher)
+}
+
+/// Checks `proof` against the mock proof the verifier `expected`. Fails in builds without
+/// the `mock-proofs` feature, so that mock proofs are never silently accepted.
+pub(crate) fn verify(expected: &[u8], proof: &[u8]) -> Result<bool> {
+    ensure!(
+        MOCK_PROOFS,
+        "refusing to verify a mock proof: filecoin-proofs was built without the mock-proofs feature"
+    );
+
+    Ok(expected == proof)
+}
+
+fn mock_hasher(kind: &str, sector_size: SectorSize) -> State {
+    let mut hasher = Blake2b::new().hash_length(32).to_state();
+    update(&mut hasher, &MOCK_PROOF_MAGIC);
+    update(&mut hasher, kind.as_bytes());
+    update(&mut hasher, &u64::from(sector_size).to_le_bytes());
+
+    hasher
+}
+
+/// Hashes `input` prefixed by its length, so that inputs can't run into each other.
+fn update(hasher: &mut State, input: &[u8]) {
+    hasher.update(&(input.len() as u64).to_le_bytes());
+    hasher.update(input);
+}
+
+fn finish(hasher: State) -> Vec<u8> {
+    let mut proof = MOCK_PROOF_MAGIC.to_vec();
+    proof.extend_from_slice(hasher.finalize().as_bytes());
+
+    proof
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn porep_config(partitions: u8) -> PoRepConfig {
+        PoRepConfig {
+            sector_size: SectorSize(2048),
+            partitions: PoRepProofPartitions(partitions),
+        }
+    }
+
+    #[test]
+    fn test_mock_seal_proof() {
+        let proof = |config, sector_id: u64, seed| {
+            seal_proof(
+                config,
+                &[1; 32],
+                &[2; 32],
+                &[3; 32],
+                SectorId::from(sector_id),
+                &[4; 32],
+                seed,
+            )
+        };
+
+        let expected = proof(porep_config(2), 7, &[5; 32]);
+        assert!(is_mock_proof(&expected));
+        assert_eq!(expected.len(), MOCK_PROOF_MAGIC.len() + 32);
+        assert_eq!(expected, proof(porep_config(2), 7, &[5; 32]));
+
+        assert_ne!(expected, proof(porep_config(1), 7, &[5; 32]));
+        assert_ne!(expected, proof(porep_config(2), 8, &[5; 32]));
+        assert_ne!(expected, proof(porep_config(2), 7, &[6; 32]));
+
+        // A real proof starts with a compressed G1 point, whose first byte has its most
+        // significant bit set.
+        assert!(!is_mock_proof(&[0x80; 192]));
+
+        if MOCK_PROOFS {
+            assert!(verify(&expected, &expected).unwrap());
+            assert!(!verify(&expected, &proof(porep_config(1), 7, &[5; 32])).unwrap());
+        } else {
+            assert!(verify(&expected, &expected).is_err());
+        }
+    }
+}
@@ -1,269 +1,65 @@
 use std::collections::{BTreeMap, HashSet};
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::{Path, PathBuf};
-
-use anyhow::{anyhow, ensure, Context, Result};
-use bincode::deserialize;
-use log::{info, trace};
-use merkletree::merkle::get_merkle_tree_leafs;
-use merkletree::store::{LevelCacheStore, Store, StoreConfig};
-use paired::bls12_381::Bls12;
-use rayon::prelude::*;
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use log::info;
+use paired::bls12_381::{Bls12, Fr};
 use storage_proofs::circuit::election_post::ElectionPoStCompound;
 use storage_proofs::circuit::multi_proof::MultiProof;
 use storage_proofs::compound_proof::{self, CompoundProof};
 use storage_proofs::election_post;
 pub use storage_proofs::election_post::Candidate;
 use storage_proofs::fr32::bytes_into_fr;
-use storage_proofs::hasher::Hasher;
-use storage_proofs::merkle::QuadLCMerkleTree;
+use storage_proofs::hasher::{Domain, Hasher, PoseidonArity, PoseidonEngine};
+use storage_proofs::metrics;
 use storage_proofs::proof::NoRequirements;
 use storage_proofs::sector::*;
-use storage_proofs::stacked::CacheKey;
+use storage_proofs::trace;
 
-use crate::api::util::{as_safe_commitment, get_tree_size};
-use crate::caches::{get_post_params, get_post_verifying_key};
+use crate::api::mock::{self, is_mock_proof, MOCK_PROOFS};
+use crate::api::util::as_safe_commitment;
+use crate::caches::get_post_verifying_key;
 use crate::constants::DefaultTreeHasher;
-use crate::parameters::post_setup_params;
+use crate::parameters::{post_setup_params, tree_r_last_arity};
 use crate::types::{
-    ChallengeSeed, Commitment, LCTree, PersistentAux, PoStConfig, ProverId, TemporaryAux,
-    QUAD_ARITY,
+    ChallengeSeed, Commitment, PaddedBytesAmount, PoStConfig, ProverId, OCT_ARITY, QUAD_ARITY,
 };
 
-/// The minimal information required about a replica, in order to be able to generate
-/// a PoSt over it.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct PrivateReplicaInfo {
-    /// Path to the replica.
-    access: String,
-    /// The replica commitment.
-    comm_r: Commitment,
-    /// Persistent Aux.
-    aux: PersistentAux,
-    /// Contains sector-specific (e.g. merkle trees) assets
-    cache_dir: PathBuf,
-}
-
-impl std::cmp::Ord for PrivateReplicaInfo {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.comm_r.as_ref().cmp(other.comm_r.as_ref())
-    }
-}
-
-impl std::cmp::PartialOrd for PrivateReplicaInfo {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl PrivateReplicaInfo {
-    pub fn new(access: String, comm_r: Commitment, cache_dir: PathBuf) -> Result<Self> {
-        ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
-
-        let aux = {
-            let mut aux_bytes = vec![];
-            let f_aux_path = cache_dir.join(CacheKey::PAux.to_string());
-            let mut f_aux = File::open(&f_aux_path)
-                .with_context(|| format!("could not open path={:?}", f_aux_path))?;
-            f_aux
-                .read_to_end(&mut aux_bytes)
-                .with_context(|| format!("could not read from path={:?}", f_aux_path))?;
-
-            deserialize(&aux_bytes)
-        }?;
-
-        Ok(PrivateReplicaInfo {
-            access,
-            comm_r,
-            aux,
-            cache_dir,
-        })
-    }
-
-    pub fn cache_dir_path(&self) -> &Path {
-        self.cache_dir.as_path()
-    }
-
-    pub fn safe_comm_r(&self) -> Result<<DefaultTreeHasher as Hasher>::Domain> {
-        as_safe_commitment(&self.comm_r, "comm_r")
-    }
-
-    pub fn safe_comm_c(&self) -> Result<<DefaultTreeHasher as Hasher>::Domain> {
-        Ok(self.aux.comm_c)
-    }
-
-    pub fn safe_comm_r_last(&self) -> Result<<DefaultTreeHasher as Hasher>::Domain> {
-        Ok(self.aux.comm_r_last)
-    }
-
-    /// Generate the merkle tree of this particular replica.
-    pub fn merkle_tree(&self, tree_size: usize, tree_leafs: usize) -> Result<LCTree> {
-        trace!(
-            "post: tree size {}, tree leafs {}, cached above base {}",
-            tree_size,
-            tree_leafs,
-            StoreConfig::default_cached_above_base_layer(tree_leafs, QUAD_ARITY)
-        );
-        let mut config = StoreConfig::new(
-            self.cache_dir_path(),
-            CacheKey::CommRLastTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(tree_leafs, QUAD_ARITY),
-        );
-        config.size = Some(tree_size);
-
-        let tree_r_last_store: LevelCacheStore<<DefaultTreeHasher as Hasher>::Domain, _> =
-            LevelCacheStore::new_from_disk(tree_size, QUAD_ARITY, &config)?;
-        let tree_r_last = QuadLCMerkleTree::from_data_store(tree_r_last_store, tree_leafs)?;
-
-        Ok(tree_r_last)
-    }
-}
-
 /// The minimal information required about a replica, in order to be able to verify
 /// a PoSt over it.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct PublicReplicaInfo {
+pub struct PublicReplicaInfo<H: Hasher = DefaultTreeHasher> {
     /// The replica commitment.
     comm_r: Commitment,
+    _h: PhantomData<H>,
 }
 
-impl std::cmp::Ord for PublicReplicaInfo {
+impl<H: Hasher> std::cmp::Ord for PublicReplicaInfo<H> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.comm_r.as_ref().cmp(other.comm_r.as_ref())
     }
 }
 
-impl std::cmp::PartialOrd for PublicReplicaInfo {
+impl<H: Hasher> std::cmp::PartialOrd for PublicReplicaInfo<H> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl PublicReplicaInfo {
+impl<H: Hasher> PublicReplicaInfo<H> {
     pub fn new(comm_r: Commitment) -> Result<Self> {
         ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
-        Ok(PublicReplicaInfo { comm_r })
+        Ok(PublicReplicaInfo {
+            comm_r,
+            _h: PhantomData,
+        })
     }
 
-    pub fn safe_comm_r(&self) -> Result<<DefaultTreeHasher as Hasher>::Domain> {
+    pub fn safe_comm_r(&self) -> Result<H::Domain> {
         as_safe_commitment(&self.comm_r, "comm_r")
     }
 }
 
-/// Generates proof-of-spacetime candidates for ElectionPoSt.
-///
-/// # Arguments
-///
-/// * `post_config` - post config that contains the sector size of each sector that we are
-/// generating this post for.
-/// * `randomness` - randomness used to generate sector challenges.
-/// * `challenge_count` - the number sector challenges in this post.
-/// * `replicas` - each sector's sector-id and associated replica info.
-/// * `prover_id` - the prover-id that is generating this post.
-pub fn generate_candidates(
-    post_config: PoStConfig,
-    randomness: &ChallengeSeed,
-    challenge_count: u64,
-    replicas: &BTreeMap<SectorId, PrivateReplicaInfo>,
-    prover_id: ProverId,
-) -> Result<Vec<Candidate>> {
-    info!("generate_candidates:start");
-
-    ensure!(!replicas.is_empty(), "Replicas must not be empty");
-    ensure!(challenge_count > 0, "Challenge count must be > 0");
-
-    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe = as_safe_commitment(&prover_id, "randomness")?;
-
-    let vanilla_params = post_setup_params(post_config);
-    let setup_params = compound_proof::SetupParams {
-        vanilla_params,
-        partitions: None,
-        priority: false,
-    };
-    let public_params: compound_proof::PublicParams<
-        election_post::ElectionPoSt<DefaultTreeHasher>,
-    > = ElectionPoStCompound::setup(&setup_params)?;
-
-    let sector_count = replicas.len() as u64;
-    ensure!(sector_count > 0, "Must supply at least one replica");
-
-    let sectors = replicas.keys().copied().collect();
-
-    let challenged_sectors =
-        election_post::generate_sector_challenges(randomness_safe, challenge_count, &sectors)?;
-
-    // Match the replicas to the challenges, as these are the only ones required.
-    let challenged_replicas: Vec<_> = challenged_sectors
-        .iter()
-        .map(|c| {
-            if let Some(replica) = replicas.get(c) {
-                Ok((c, replica))
-            } else {
-                Err(anyhow!(
-                    "Invalid challenge generated: {}, only {} sectors are being proven",
-                    c,
-                    sector_count
-                ))
-            }
-        })
-        .collect::<Result<_, _>>()?;
-
-    // Generate merkle trees for the challenged replicas.
-    // Merkle trees should be generated only once, not multiple times if the same sector is challenged
-    // multiple times, so we build a HashMap of trees.
-
-    let mut unique_challenged_replicas = challenged_replicas.clone();
-    unique_challenged_replicas.sort_unstable(); // dedup requires a sorted list
-    unique_challenged_replicas.dedup();
-
-    let tree_size =
-        get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size, QUAD_ARITY);
-    let tree_leafs = get_merkle_tree_leafs(tree_size, QUAD_ARITY);
-
-    let unique_trees_res: Vec<_> = unique_challenged_replicas
-        .into_par_iter()
-        .map(|(id, replica)| {
-            // Ensure that any associated cached data persisted is
-            // discarded and our tree is compacted by this point.
-            let t_aux = {
-                let mut aux_bytes = vec![];
-                let f_aux_path = replica.cache_dir_path().join(CacheKey::TAux.to_string());
-                let mut f_aux = File::open(&f_aux_path)
-                    .with_context(|| format!("could not open path={:?}", f_aux_path))?;
-                f_aux
-                    .read_to_end(&mut aux_bytes)
-                    .with_context(|| format!("could not read from path={:?}", f_aux_path))?;
-
-                deserialize(&aux_bytes)
-            }?;
-
-            TemporaryAux::compact(t_aux)?;
-
-            replica
-                .merkle_tree(tree_size, tree_leafs)
-                .map(|tree| (*id, tree))
-        })
-        .collect();
-
-    // resolve results
-    let trees: BTreeMap<SectorId, LCTree> =
-        unique_trees_res.into_iter().collect::<Result<_, _>>()?;
-
-    let candidates = election_post::generate_candidates::<DefaultTreeHasher>(
-        &public_params.vanilla_params,
-        &challenged_sectors,
-        &trees,
-        prover_id_safe,
-        randomness_safe,
-    )?;
-
-    info!("generate_candidates:finish");
-
-    Ok(candidates)
-}
-
 pub type SnarkProof = Vec<u8>;
 
 /// Generates a ticket from a partial_ticket.
@@ -273,90 +69,6 @@ pub fn finalize_ticket(partial_ticket: &[u8; 32]) -> Result<[u8; 32]> {
     Ok(election_post::finalize_ticket(&partial_ticket))
 }
 
-/// Generates a proof-of-spacetime.
-///
-/// # Arguments
-///
-/// * `post_config` - post config that contains the sector size of each sector that we are
-/// generating this post for.
-/// * `randomness` - randomness used to generate sector challenges.
-/// * `replicas` - each sector's sector-id and associated replica info.
-/// * `winners` - a vector containing each winning ticket.
-/// * `prover_id` - the prover-id that is generating this post.
-pub fn generate_post(
-    post_config: PoStConfig,
-    randomness: &ChallengeSeed,
-    replicas: &BTreeMap<SectorId, PrivateReplicaInfo>,
-    winners: Vec<Candidate>,
-    prover_id: ProverId,
-) -> Result<Vec<SnarkProof>> {
-    info!("generate_post:start");
-
-    let sector_count = replicas.len() as u64;
-    ensure!(sector_count > 0, "Must supply at least one replica");
-    ensure!(!winners.is_empty(), "Winners must not be empty");
-    ensure!(!replicas.is_empty(), "Replicas must not be empty");
-
-    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe = as_safe_commitment(&prover_id, "randomness")?;
-
-    let vanilla_params = post_setup_params(post_config);
-    let setup_params = compound_proof::SetupParams {
-        vanilla_params,
-        partitions: None,
-        priority: post_config.priority,
-    };
-    let pub_params: compound_proof::PublicParams<election_post::ElectionPoSt<DefaultTreeHasher>> =
-        ElectionPoStCompound::setup(&setup_params)?;
-    let groth_params = get_post_params(post_config)?;
-
-    let tree_size =
-        get_tree_size::<<DefaultTreeHasher as Hasher>::Domain>(post_config.sector_size, QUAD_ARITY);
-    let tree_leafs = get_merkle_tree_leafs(tree_size, QUAD_ARITY);
-
-    let mut proofs = Vec::with_capacity(winners.len());
-
-    let inputs: Vec<_> = winners
-        .par_iter()
-        .map(|winner| {
-            let replica = replicas
-                .get(&winner.sector_id)
-                .with_context(|| format!("Missing replica for sector: {}", winner.sector_id))?;
-            let tree = replica.merkle_tree(tree_size, tree_leafs)?;
-
-            let comm_r = replica.safe_comm_r()?;
-            let pub_inputs = election_post::PublicInputs {
-                randomness: randomness_safe,
-                comm_r,
-                sector_id: winner.sector_id,
-                partial_ticket: winner.partial_ticket,
-                sector_challenge_index: winner.sector_challenge_index,
-                prover_id: prover_id_safe,
-            };
-
-            let comm_c = replica.safe_comm_c()?;
-            let comm_r_last = replica.safe_comm_r_last()?;
-            let priv_inputs = election_post::PrivateInputs::<DefaultTreeHasher> {
-                tree,
-                comm_c,
-                comm_r_last,
-            };
-
-            Ok((pub_inputs, priv_inputs))
-        })
-        .collect::<Result<_>>()?;
-
-    for (pub_inputs, priv_inputs) in &inputs {
-        let proof =
-            ElectionPoStCompound::prove(&pub_params, &pub_inputs, &priv_inputs, &groth_params)?;
-        proofs.push(proof.to_vec()?);
-    }
-
-    info!("generate_post:finish");
-
-    Ok(proofs)
-}
-
 /// Verifies a proof-of-spacetime.
 ///
 /// # Arguments
@@ -369,17 +81,39 @@ pub fn generate_post(
 /// * `replicas` - each sector's sector-id and associated replica info.
 /// * `winners` - a vector containing each winning ticket.
 /// * `prover_id` - the prover-id that generated this post.
-pub fn verify_post(
+pub fn verify_post<H: 'static + Hasher>(
     post_config: PoStConfig,
     randomness: &ChallengeSeed,
     challenge_count: u64,
     proofs: &[Vec<u8>],
-    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo<H>>,
     winners: &[Candidate],
     prover_id: ProverId,
 ) -> Result<bool> {
     info!("verify_post:start");
+    let _span = trace::span("verify_post").arg("sectors", replicas.len());
+    metrics::measure_verification("post", || {
+        verify_post_inner::<H>(
+            post_config,
+            randomness,
+            challenge_count,
+            proofs,
+            replicas,
+            winners,
+            prover_id,
+        )
+    })
+}
 
+fn verify_post_inner<H: 'static + Hasher>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    challenge_count: u64,
+    proofs: &[Vec<u8>],
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo<H>>,
+    winners: &[Candidate],
+    prover_id: ProverId,
+) -> Result<bool> {
     let mut challenge_indexes: HashSet<_> = HashSet::new();
 
     // Fail early if any sector_challenge_index is duplicated.
@@ -404,6 +138,48 @@ pub fn verify_post(
         "Mismatch between winners and proofs"
     );
 
+    let is_valid = match tree_r_last_arity(PaddedBytesAmount::from(post_config))? {
+        QUAD_ARITY => verify_post_with_arity::<H, typenum::U4>(
+            post_config,
+            randomness,
+            challenge_count,
+            proofs,
+            replicas,
+            winners,
+            prover_id,
+        ),
+        OCT_ARITY => verify_post_with_arity::<H, typenum::U8>(
+            post_config,
+            randomness,
+            challenge_count,
+            proofs,
+            replicas,
+            winners,
+            prover_id,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }?;
+
+    info!("verify_post:finish");
+
+    Ok(is_valid)
+}
+
+fn verify_post_with_arity<H, U>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    challenge_count: u64,
+    proofs: &[Vec<u8>],
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo<H>>,
+    winners: &[Candidate],
+    prover_id: ProverId,
+) -> Result<bool>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let randomness_safe = as_safe_commitment(randomness, "randomness")?;
     let prover_id_safe = as_safe_commitment(&prover_id, "randomness")?;
 
@@ -414,10 +190,16 @@ pub fn verify_post(
         partitions: None,
         priority: false,
     };
-    let pub_params: compound_proof::PublicParams<election_post::ElectionPoSt<DefaultTreeHasher>> =
-        ElectionPoStCompound::setup(&setup_params)?;
-
-    let verifying_key = get_post_verifying_key(post_config)?;
+    let pub_params: compound_proof::PublicParams<election_post::ElectionPoSt<H, U>> =
+        ElectionPoStCompound::<H, U>::setup(&setup_params)?;
+
+    // Mock proofs are checked without a verifying key, see `api::mock`.
+    let verifying_key = if MOCK_PROOFS || proofs.iter().any(|proof| is_mock_proof(proof)) {
+        None
+    } else {
+        Some(get_post_verifying_key::<H, U>(post_config)?)
+    };
+    let proof_header = post_config.get_proof_header::<H>()?;
 
     for (proof, winner) in proofs.iter().zip(winners.iter()) {
         let replica = replicas
@@ -441,7 +223,24 @@ pub fn verify_post(
             return Ok(false);
         }
 
-        let proof = MultiProof::new_from_reader(None, &proof[..], &verifying_key)?;
+        let verifying_key = match verifying_key {
+            Some(ref verifying_key) => verifying_key,
+            None => {
+                let expected = mock::post_proof(
+                    post_config,
+                    randomness,
+                    &comm_r.into_bytes(),
+                    winner,
+                    &prover_id,
+                );
+                if !mock::verify(&expected, proof)? {
+                    return Ok(false);
+                }
+                continue;
+            }
+        };
+
+        let proof = MultiProof::new_from_envelope_reader(&proof_header, &proof[..], verifying_key)?;
         let pub_inputs = election_post::PublicInputs {
             randomness: randomness_safe,
             comm_r,
@@ -451,14 +250,139 @@ pub fn verify_post(
             prover_id: prover_id_safe,
         };
 
-        let is_valid =
-            ElectionPoStCompound::verify(&pub_params, &pub_inputs, &proof, &NoRequirements)?;
+        let is_valid = ElectionPoStCompound::<H, U>::verify(
+            &pub_params,
+            &pub_inputs,
+            &proof,
+            &NoRequirements,
+        )?;
         if !is_valid {
             return Ok(false);
         }
     }
 
-    info!("verify_post:finish");
-
     Ok(true)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Once;
+
+    use ff::Field;
+
+    use crate::constants::{SECTOR_SIZE_2_KIB, SINGLE_PARTITION_PROOF_LEN};
+    use crate::types::SectorSize;
+
+    static INIT_LOGGER: Once = Once::new();
+    fn init_logger() {
+        INIT_LOGGER.call_once(|| {
+            fil_logger::init();
+        });
+    }
+
+    #[test]
+    #[ignore]
+    fn test_verify_post_fr32_validation() {
+        init_logger();
+
+        let not_convertible_to_fr_bytes = [255; 32];
+        let out = bytes_into_fr::<Bls12>(&not_convertible_to_fr_bytes);
+        assert!(out.is_err(), "tripwire");
+        let mut replicas = BTreeMap::new();
+        replicas.insert(
+            1.into(),
+            PublicReplicaInfo::<DefaultTreeHasher>::new(not_convertible_to_fr_bytes).unwrap(),
+        );
+        let winner = Candidate {
+            sector_id: 1.into(),
+            partial_ticket: Fr::zero(),
+            ticket: [0; 32],
+            sector_challenge_index: 0,
+        };
+
+        let result = verify_post(
+            PoStConfig {
+                sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+                challenge_count: crate::constants::POST_CHALLENGE_COUNT,
+                challenged_nodes: crate::constants::POST_CHALLENGED_NODES,
+                priority: false,
+            },
+            &[0; 32],
+            1,
+            &[vec![0u8; SINGLE_PARTITION_PROOF_LEN]][..],
+            &replicas,
+            &[winner][..],
+            [0; 32],
+        );
+
+        if let Err(err) = result {
+            let needle = "Invalid commitment (comm_r)";
+            let haystack = format!("{}", err);
+
+            assert!(
+                haystack.contains(needle),
+                format!("\"{}\" did not contain \"{}\"", haystack, needle)
+            );
+        } else {
+            panic!("should have failed comm_r to Fr32 conversion");
+        }
+    }
+
+    #[test]
+    fn test_verify_post_duplicate_checking() {
+        init_logger();
+
+        let mut fr_bytes = [1; 32];
+        fr_bytes[31] = 0;
+
+        let out = bytes_into_fr::<Bls12>(&fr_bytes);
+        let mut replicas = BTreeMap::new();
+        replicas.insert(
+            1.into(),
+            PublicReplicaInfo::<DefaultTreeHasher>::new(fr_bytes).unwrap(),
+        );
+        let winner = Candidate {
+            sector_id: 1.into(),
+            partial_ticket: Fr::zero(),
+            ticket: [0; 32],
+            sector_challenge_index: 0,
+        };
+
+        let result = verify_post(
+            PoStConfig {
+                sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+                challenge_count: crate::constants::POST_CHALLENGE_COUNT,
+                challenged_nodes: crate::constants::POST_CHALLENGED_NODES,
+                priority: false,
+            },
+            &[0; 32],
+            1,
+            &[
+                vec![0u8; SINGLE_PARTITION_PROOF_LEN],
+                vec![0u8; SINGLE_PARTITION_PROOF_LEN],
+            ][..],
+            &replicas,
+            &[winner.clone(), winner][..],
+            [0; 32],
+        );
+
+        assert!(
+            result.is_err(),
+            "expected error when passing duplicate winner"
+        );
+
+        if let Err(err) = result {
+            let message = "duplicate sector_challenge_index";
+            let error_string = format!("{}", err);
+
+            assert!(
+                error_string.contains(message),
+                format!("\"{}\" did not contain \"{}\"", error_string, message)
+            );
+        } else {
+            panic!("should have failed comm_r to Fr32 conversion");
+        }
+    }
+}
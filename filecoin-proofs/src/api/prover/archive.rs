@@ -0,0 +1,607 @@
+//! Archives for moving a sealed sector between machines.
+//!
+//! An archive is a tar stream which starts with a `manifest.json`, followed by the replica and
+//! the cache files listed in it. Every file is listed with its size and blake2b digest, which
+//! `import_sector` checks before the sector is used.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use bincode::serialize;
+use blake2b_simd::Params as Blake2b;
+use log::info;
+use merkletree::store::StoreConfig;
+use serde::{Deserialize, Serialize};
+use storage_proofs::hasher::{HashFunction, Hasher};
+use storage_proofs::stacked::{CacheKey, PersistentAux, TemporaryAux};
+
+use crate::api::util::{as_safe_commitment, deserialize_aux};
+use crate::constants::DefaultPieceHasher;
+use crate::types::Commitment;
+
+/// Version of the archive layout, bumped on incompatible changes.
+pub const SECTOR_ARCHIVE_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const REPLICA_FILE: &str = "replica";
+const CACHE_DIR: &str = "cache";
+
+/// Manifests are small, anything bigger is not one.
+const MAX_MANIFEST_LEN: u64 = 1 << 20;
+
+/// Which cache files to include in an archive.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SectorArtifacts {
+    /// Only what generating a PoSt needs: `p_aux`, `t_aux` and the compacted `tree-r-last`.
+    PoSt,
+    /// Also the layer labels and `tree-d` and `tree-c`, which `seal_commit_phase1` needs.
+    /// Only available until the cache was compacted by the first PoSt.
+    Commit,
+}
+
+/// Where `import_sector` placed a sector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedSector {
+    pub comm_r: Commitment,
+    pub artifacts: SectorArtifacts,
+    pub replica_path: PathBuf,
+    pub cache_path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    comm_r: String,
+    artifacts: SectorArtifacts,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    name: String,
+    size: u64,
+    digest: String,
+}
+
+/// Writes an archive of the sector sealed into `replica_path`, with the cache files in
+/// `cache_path` that `artifacts` needs, to `writer`.
+///
+/// # Arguments
+///
+/// * `cache_path` - path to the cache directory of the sector.
+/// * `replica_path` - path to the sealed sector.
+/// * `comm_r` - the replica commitment, which must match the one in `p_aux`.
+/// * `artifacts` - which cache files to include.
+/// * `writer` - where to write the archive.
+pub fn export_sector<H: Hasher, W: Write>(
+    cache_path: &Path,
+    replica_path: &Path,
+    comm_r: Commitment,
+    artifacts: SectorArtifacts,
+    writer: W,
+) -> Result<()> {
+    info!("export_sector:start");
+
+    check_comm_r::<H>(cache_path, comm_r)?;
+    let t_aux = read_t_aux::<H>(cache_path)?;
+
+    let mut files = vec![(REPLICA_FILE.to_string(), replica_path.to_path_buf())];
+    for name in cache_file_names(&t_aux, artifacts) {
+        let path = cache_path.join(&name);
+        ensure!(
+            path.exists(),
+            "{:?} is missing, the sector can't be exported with {:?} artifacts",
+            path,
+            artifacts
+        );
+        files.push((format!("{}/{}", CACHE_DIR, name), path));
+    }
+
+    if artifacts == SectorArtifacts::PoSt {
+        // PoSt only reads the compacted tree-r-last, which the first PoSt compacts in place
+        // while deleting tree-d. Without tree-d, an uncompacted tree-r-last is never compacted.
+        ensure!(
+            !data_path(&t_aux.tree_d_config).exists(),
+            "{:?} is not compacted yet, export it with commit artifacts instead",
+            cache_path
+        );
+    }
+
+    let mut manifest_files = Vec::with_capacity(files.len());
+    for (name, path) in &files {
+        let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+        let (size, digest) = copy_hashed(BufReader::new(file), io::sink())
+            .with_context(|| format!("could not read {:?}", path))?;
+        manifest_files.push(ManifestFile {
+            name: name.clone(),
+            size,
+            digest,
+        });
+    }
+
+    let manifest = Manifest {
+        version: SECTOR_ARCHIVE_VERSION,
+        comm_r: hex::encode(comm_r),
+        artifacts,
+        files: manifest_files,
+    };
+    let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+
+    let mut builder = tar::Builder::new(writer);
+    builder.append_data(
+        &mut file_header(manifest_bytes.len() as u64),
+        MANIFEST_FILE,
+        &manifest_bytes[..],
+    )?;
+    for ((name, path), manifest_file) in files.iter().zip(&manifest.files) {
+        let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+        // The archive would no longer match the digests in the manifest.
+        ensure!(
+            file.metadata()?.len() == manifest_file.size,
+            "{:?} changed while exporting",
+            path
+        );
+        builder
+            .append_data(
+                &mut file_header(manifest_file.size),
+                name,
+                BufReader::new(file),
+            )
+            .with_context(|| format!("could not archive {:?}", path))?;
+    }
+    builder.into_inner()?.flush()?;
+
+    info!("export_sector:finish");
+    Ok(())
+}
+
+/// Reads an archive written by `export_sector` from `reader` into `dest`, which must be a
+/// missing or empty directory. The replica is written to `dest/replica` and the cache files
+/// to `dest/cache`, whose `t_aux` is then rewritten to point at its new location.
+///
+/// Fails, and removes what it wrote, if a file does not match its digest in the manifest
+/// or `p_aux` does not match the archived `comm_r`.
+pub fn import_sector<H: Hasher, R: Read>(reader: R, dest: &Path) -> Result<ImportedSector> {
+    info!("import_sector:start");
+
+    fs::create_dir_all(dest).with_context(|| format!("could not create {:?}", dest))?;
+    ensure!(
+        fs::read_dir(dest)?.next().is_none(),
+        "{:?} is not empty, refusing to import into it",
+        dest
+    );
+
+    let replica_path = dest.join(REPLICA_FILE);
+    let cache_path = dest.join(CACHE_DIR);
+    let result = import_sector_inner::<H, R>(reader, dest, &cache_path);
+    if result.is_err() {
+        let _ = fs::remove_file(&replica_path);
+        let _ = fs::remove_dir_all(&cache_path);
+    }
+
+    let (comm_r, artifacts) = result?;
+
+    info!("import_sector:finish");
+    Ok(ImportedSector {
+        comm_r,
+        artifacts,
+        replica_path,
+        cache_path,
+    })
+}
+
+fn import_sector_inner<H: Hasher, R: Read>(
+    reader: R,
+    dest: &Path,
+    cache_path: &Path,
+) -> Result<(Commitment, SectorArtifacts)> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = archive.entries()?;
+
+    let manifest: Manifest = {
+        let entry = entries.next().context("the archive is empty")??;
+        ensure!(
+            entry.path()?.as_ref() == Path::new(MANIFEST_FILE),
+            "the archive does not start with {}",
+            MANIFEST_FILE
+        );
+        serde_json::from_reader(entry.take(MAX_MANIFEST_LEN)).context("invalid manifest")?
+    };
+    ensure!(
+        manifest.version == SECTOR_ARCHIVE_VERSION,
+        "unsupported sector archive version {}, expected {}",
+        manifest.version,
+        SECTOR_ARCHIVE_VERSION
+    );
+
+    let comm_r_bytes = hex::decode(&manifest.comm_r).context("invalid comm_r in manifest")?;
+    ensure!(comm_r_bytes.len() == 32, "invalid comm_r in manifest");
+    let mut comm_r = [0; 32];
+    comm_r.copy_from_slice(&comm_r_bytes);
+
+    let mut expected: BTreeMap<&str, &ManifestFile> = BTreeMap::new();
+    for file in &manifest.files {
+        ensure!(
+            expected.insert(&file.name, file).is_none(),
+            "{} is listed twice in the manifest",
+            file.name
+        );
+    }
+    for name in &[
+        REPLICA_FILE.to_string(),
+        format!("{}/{}", CACHE_DIR, CacheKey::PAux),
+        format!("{}/{}", CACHE_DIR, CacheKey::TAux),
+    ] {
+        ensure!(
+            expected.contains_key(name.as_str()),
+            "the manifest does not list {}",
+            name
+        );
+    }
+
+    fs::create_dir(cache_path).with_context(|| format!("could not create {:?}", cache_path))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let name = entry
+            .path()?
+            .to_str()
+            .context("invalid file name in archive")?
+            .to_string();
+        let file = expected
+            .remove(name.as_str())
+            .with_context(|| format!("{} is not listed in the manifest, or repeated", name))?;
+        ensure!(
+            entry.header().entry_type().is_file(),
+            "{} is not a regular file",
+            name
+        );
+        ensure!(
+            entry.header().size()? == file.size,
+            "{} has the wrong size",
+            name
+        );
+
+        let path = dest_path(dest, &name)?;
+        let out = File::create(&path).with_context(|| format!("could not create {:?}", path))?;
+        let mut out = BufWriter::new(out);
+        let (size, digest) =
+            copy_hashed(entry, &mut out).with_context(|| format!("could not write {:?}", path))?;
+        out.into_inner()?.sync_all()?;
+        ensure!(
+            size == file.size && digest == file.digest,
+            "{} does not match its digest",
+            name
+        );
+    }
+    ensure!(
+        expected.is_empty(),
+        "the archive is missing {:?}",
+        expected.keys().collect::<Vec<_>>()
+    );
+
+    check_comm_r::<H>(cache_path, comm_r)?;
+
+    // `read_t_aux` points the stores at `cache_path`.
+    let t_aux = read_t_aux::<H>(cache_path)?;
+    let t_aux_path = cache_path.join(CacheKey::TAux.to_string());
+    fs::write(&t_aux_path, serialize(&t_aux)?)
+        .with_context(|| format!("could not write to file t_aux={:?}", t_aux_path))?;
+
+    Ok((comm_r, manifest.artifacts))
+}
+
+/// Checks that `comm_r` is the commitment of `comm_c` and `comm_r_last` in `p_aux`.
+fn check_comm_r<H: Hasher>(cache_path: &Path, comm_r: Commitment) -> Result<()> {
+    let p_aux_path = cache_path.join(CacheKey::PAux.to_string());
+    let p_aux_bytes =
+        fs::read(&p_aux_path).with_context(|| format!("could not read p_aux={:?}", p_aux_path))?;
+    let p_aux: PersistentAux<H::Domain> =
+        deserialize_aux(&p_aux_bytes).with_context(|| format!("invalid p_aux={:?}", p_aux_path))?;
+
+    let comm_r_safe: H::Domain = as_safe_commitment(&comm_r, "comm_r")?;
+    ensure!(
+        H::Function::hash2(&p_aux.comm_c, &p_aux.comm_r_last) == comm_r_safe,
+        "comm_r does not match p_aux={:?}",
+        p_aux_path
+    );
+
+    Ok(())
+}
+
+/// Reads `t_aux`, pointed at `cache_path`.
+fn read_t_aux<H: Hasher>(cache_path: &Path) -> Result<TemporaryAux<H, DefaultPieceHasher>> {
+    let t_aux_path = cache_path.join(CacheKey::TAux.to_string());
+    let t_aux_bytes =
+        fs::read(&t_aux_path).with_context(|| format!("could not read t_aux={:?}", t_aux_path))?;
+    let mut t_aux: TemporaryAux<H, DefaultPieceHasher> =
+        deserialize_aux(&t_aux_bytes).with_context(|| format!("invalid t_aux={:?}", t_aux_path))?;
+    t_aux.set_cache_path(cache_path);
+
+    Ok(t_aux)
+}
+
+/// The names of the cache files `artifacts` needs.
+fn cache_file_names<H: Hasher>(
+    t_aux: &TemporaryAux<H, DefaultPieceHasher>,
+    artifacts: SectorArtifacts,
+) -> Vec<String> {
+    let mut configs = vec![&t_aux.tree_r_last_config];
+    if artifacts == SectorArtifacts::Commit {
+        configs.push(&t_aux.tree_d_config);
+        configs.push(&t_aux.tree_c_config);
+        configs.extend(t_aux.labels.labels.iter());
+    }
+
+    let mut names = vec![CacheKey::PAux.to_string(), CacheKey::TAux.to_string()];
+    names.extend(configs.into_iter().map(|config| {
+        data_path(config)
+            .file_name()
+            .expect("store data paths name a file")
+            .to_string_lossy()
+            .into_owned()
+    }));
+
+    names
+}
+
+fn data_path(config: &StoreConfig) -> PathBuf {
+    StoreConfig::data_path(&config.path, &config.id)
+}
+
+/// Maps an archived file name to its path below `dest`, rejecting names which would escape it.
+fn dest_path(dest: &Path, name: &str) -> Result<PathBuf> {
+    let components: Vec<_> = Path::new(name).components().collect();
+    match components.as_slice() {
+        [Component::Normal(file)] if *file == REPLICA_FILE => Ok(dest.join(REPLICA_FILE)),
+        [Component::Normal(dir), Component::Normal(file)] if *dir == CACHE_DIR => {
+            Ok(dest.join(CACHE_DIR).join(file))
+        }
+        _ => bail!("invalid file name in archive: {}", name),
+    }
+}
+
+fn file_header(size: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    header
+}
+
+/// Copies `reader` to `writer`, returning the number of bytes copied and their hex encoded
+/// blake2b digest.
+fn copy_hashed<R: Read, W: Write>(mut reader: R, mut writer: W) -> io::Result<(u64, String)> {
+    let mut state = Blake2b::new().hash_length(32).to_state();
+    let mut buf = vec![0; 1 << 20];
+    let mut size = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        state.update(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+
+    Ok((size, hex::encode(state.finalize().as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::marker::PhantomData;
+
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+    use storage_proofs::hasher::Domain;
+    use storage_proofs::stacked::Labels;
+
+    use crate::constants::DefaultTreeHasher;
+
+    type H = DefaultTreeHasher;
+
+    /// Writes a cache with `p_aux`, `t_aux` and random store files, returning its `comm_r`.
+    fn fake_cache(cache_path: &Path, replica_path: &Path, compacted: bool) -> Commitment {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let p_aux = PersistentAux {
+            comm_c: <H as Hasher>::Domain::random(rng),
+            comm_r_last: <H as Hasher>::Domain::random(rng),
+        };
+        let comm_r = <H as Hasher>::Function::hash2(&p_aux.comm_c, &p_aux.comm_r_last);
+        fs::write(
+            cache_path.join(CacheKey::PAux.to_string()),
+            serialize(&p_aux).unwrap(),
+        )
+        .unwrap();
+
+        let config = |id: String| StoreConfig::new(cache_path, id, 0);
+        let t_aux = TemporaryAux::<H, DefaultPieceHasher> {
+            labels: Labels::new(
+                (1..=2)
+                    .map(|layer| config(CacheKey::label_layer(layer)))
+                    .collect(),
+            ),
+            tree_d_config: config(CacheKey::CommDTree.to_string()),
+            tree_r_last_config: config(CacheKey::CommRLastTree.to_string()),
+            tree_c_config: config(CacheKey::CommCTree.to_string()),
+            _g: PhantomData,
+        };
+        fs::write(
+            cache_path.join(CacheKey::TAux.to_string()),
+            serialize(&t_aux).unwrap(),
+        )
+        .unwrap();
+
+        let mut stores = vec![&t_aux.tree_r_last_config];
+        if !compacted {
+            stores.extend(&[&t_aux.tree_d_config, &t_aux.tree_c_config]);
+            stores.extend(t_aux.labels.labels.iter());
+        }
+        for config in stores {
+            let data: Vec<u8> = (0..1024).map(|_| rng.gen()).collect();
+            fs::write(data_path(config), data).unwrap();
+        }
+
+        let replica: Vec<u8> = (0..2048).map(|_| rng.gen()).collect();
+        fs::write(replica_path, replica).unwrap();
+
+        let mut commitment = [0; 32];
+        commitment.copy_from_slice(&comm_r.into_bytes());
+        commitment
+    }
+
+    fn assert_same_file(a: &Path, b: &Path) {
+        assert_eq!(fs::read(a).unwrap(), fs::read(b).unwrap(), "{:?}", b);
+    }
+
+    #[test]
+    fn test_export_import_sector() {
+        for &(artifacts, compacted) in &[
+            (SectorArtifacts::PoSt, true),
+            (SectorArtifacts::Commit, false),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let cache_path = dir.path().join("cache");
+            fs::create_dir(&cache_path).unwrap();
+            let replica_path = dir.path().join("sealed");
+            let comm_r = fake_cache(&cache_path, &replica_path, compacted);
+
+            let mut archive = Vec::new();
+            export_sector::<H, _>(&cache_path, &replica_path, comm_r, artifacts, &mut archive)
+                .unwrap();
+
+            let dest = dir.path().join("imported");
+            let imported = import_sector::<H, _>(Cursor::new(&archive), &dest).unwrap();
+            assert_eq!(imported.comm_r, comm_r);
+            assert_eq!(imported.artifacts, artifacts);
+            assert_same_file(&replica_path, &imported.replica_path);
+
+            let t_aux = read_t_aux::<H>(&cache_path).unwrap();
+            let imported_t_aux: TemporaryAux<H, DefaultPieceHasher> = deserialize_aux(
+                &fs::read(imported.cache_path.join(CacheKey::TAux.to_string())).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(imported_t_aux.tree_r_last_config.path, imported.cache_path);
+            assert_eq!(imported_t_aux.labels.labels[1].path, imported.cache_path);
+
+            let names = cache_file_names(&t_aux, artifacts);
+            assert_eq!(
+                fs::read_dir(&imported.cache_path).unwrap().count(),
+                names.len()
+            );
+            for name in names
+                .iter()
+                .filter(|name| **name != CacheKey::TAux.to_string())
+            {
+                assert_same_file(&cache_path.join(name), &imported.cache_path.join(name));
+            }
+
+            // Importing twice into the same directory is refused.
+            assert!(import_sector::<H, _>(Cursor::new(&archive), &dest).is_err());
+        }
+    }
+
+    #[test]
+    fn test_export_sector_checks_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let replica_path = dir.path().join("sealed");
+        let comm_r = fake_cache(dir.path(), &replica_path, false);
+        let export = |comm_r, artifacts| {
+            export_sector::<H, _>(dir.path(), &replica_path, comm_r, artifacts, io::sink())
+        };
+
+        assert!(export(comm_r, SectorArtifacts::Commit).is_ok());
+        // tree-r-last is not compacted yet.
+        assert!(export(comm_r, SectorArtifacts::PoSt).is_err());
+
+        let mut wrong_comm_r = comm_r;
+        wrong_comm_r[0] ^= 1;
+        assert!(export(wrong_comm_r, SectorArtifacts::Commit).is_err());
+
+        let compacted = tempfile::tempdir().unwrap();
+        let comm_r = fake_cache(compacted.path(), &replica_path, true);
+        let export = |artifacts| {
+            export_sector::<H, _>(
+                compacted.path(),
+                &replica_path,
+                comm_r,
+                artifacts,
+                io::sink(),
+            )
+        };
+        assert!(export(SectorArtifacts::PoSt).is_ok());
+        // The labels, tree-d and tree-c are gone.
+        assert!(export(SectorArtifacts::Commit).is_err());
+    }
+
+    #[test]
+    fn test_import_sector_rejects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let replica_path = dir.path().join("sealed");
+        let cache_path = dir.path().join("cache");
+        fs::create_dir(&cache_path).unwrap();
+        let comm_r = fake_cache(&cache_path, &replica_path, true);
+
+        let mut archive = Vec::new();
+        export_sector::<H, _>(
+            &cache_path,
+            &replica_path,
+            comm_r,
+            SectorArtifacts::PoSt,
+            &mut archive,
+        )
+        .unwrap();
+
+        // Flip a byte of the replica.
+        let replica = fs::read(&replica_path).unwrap();
+        let offset = archive
+            .windows(replica.len())
+            .position(|window| window == &replica[..])
+            .unwrap();
+        archive[offset + 100] ^= 1;
+
+        let dest = dir.path().join("imported");
+        let err = import_sector::<H, _>(Cursor::new(&archive), &dest).unwrap_err();
+        assert!(err.to_string().contains("digest"), "{:?}", err);
+        // What was written is removed again.
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+
+        // A truncated archive is missing files.
+        archive[offset + 100] ^= 1;
+        assert!(import_sector::<H, _>(Cursor::new(&archive[..offset]), &dest).is_err());
+        assert!(import_sector::<H, _>(Cursor::new(&archive), &dest).is_ok());
+    }
+
+    #[test]
+    fn test_dest_path() {
+        let dest = Path::new("/dest");
+        assert_eq!(
+            dest_path(dest, "replica").unwrap(),
+            PathBuf::from("/dest/replica")
+        );
+        assert_eq!(
+            dest_path(dest, "cache/p_aux").unwrap(),
+            PathBuf::from("/dest/cache/p_aux")
+        );
+        for name in &[
+            "cache",
+            "sealed",
+            "cache/../replica",
+            "/replica",
+            "cache/a/b",
+        ] {
+            assert!(dest_path(dest, name).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use log::{info, trace};
+use merkletree::merkle::get_merkle_tree_leafs;
+use merkletree::store::{LevelCacheStore, Store, StoreConfig};
+use paired::bls12_381::{Bls12, Fr};
+use rayon::prelude::*;
+use storage_proofs::circuit::election_post::ElectionPoStCompound;
+use storage_proofs::compound_proof::{self, CompoundProof};
+use storage_proofs::election_post::{self, Candidate};
+use storage_proofs::hasher::{Domain, Hasher, PoseidonArity, PoseidonEngine};
+use storage_proofs::merkle::LCMerkleTree;
+use storage_proofs::proof::ProofScheme;
+use storage_proofs::sector::*;
+use storage_proofs::settings;
+use storage_proofs::stacked::CacheKey;
+use storage_proofs::trace;
+
+use crate::api::mock::{self, MOCK_PROOFS};
+use crate::api::util::{as_safe_commitment, deserialize_aux, get_tree_size};
+use crate::caches::get_post_params;
+use crate::constants::DefaultTreeHasher;
+use crate::parameters::{post_setup_params, tree_r_last_arity};
+use crate::types::{
+    ChallengeSeed, Commitment, PaddedBytesAmount, PersistentAux, PoStConfig, ProverId,
+    TemporaryAux, OCT_ARITY, QUAD_ARITY,
+};
+
+/// The minimal information required about a replica, in order to be able to generate
+/// a PoSt over it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PrivateReplicaInfo<H: Hasher = DefaultTreeHasher> {
+    /// Path to the replica.
+    access: String,
+    /// The replica commitment.
+    comm_r: Commitment,
+    /// Persistent Aux.
+    aux: PersistentAux<H>,
+    /// Contains sector-specific (e.g. merkle trees) assets
+    cache_dir: PathBuf,
+}
+
+impl<H: Hasher> std::cmp::Ord for PrivateReplicaInfo<H> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.comm_r.as_ref().cmp(other.comm_r.as_ref())
+    }
+}
+
+impl<H: Hasher> std::cmp::PartialOrd for PrivateReplicaInfo<H> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<H: Hasher> PrivateReplicaInfo<H> {
+    pub fn new(access: String, comm_r: Commitment, cache_dir: PathBuf) -> Result<Self> {
+        ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+
+        let aux = {
+            let mut aux_bytes = vec![];
+            let f_aux_path = cache_dir.join(CacheKey::PAux.to_string());
+            let mut f_aux = File::open(&f_aux_path)
+                .with_context(|| format!("could not open path={:?}", f_aux_path))?;
+            f_aux
+                .read_to_end(&mut aux_bytes)
+                .with_context(|| format!("could not read from path={:?}", f_aux_path))?;
+
+            deserialize_aux(&aux_bytes)
+                .with_context(|| format!("invalid p_aux at path={:?}", f_aux_path))
+        }?;
+
+        Ok(PrivateReplicaInfo {
+            access,
+            comm_r,
+            aux,
+            cache_dir,
+        })
+    }
+
+    pub fn cache_dir_path(&self) -> &Path {
+        self.cache_dir.as_path()
+    }
+
+    pub fn safe_comm_r(&self) -> Result<H::Domain> {
+        as_safe_commitment(&self.comm_r, "comm_r")
+    }
+
+    pub fn safe_comm_c(&self) -> Result<H::Domain> {
+        Ok(self.aux.comm_c)
+    }
+
+    pub fn safe_comm_r_last(&self) -> Result<H::Domain> {
+        Ok(self.aux.comm_r_last)
+    }
+
+    /// Generate the merkle tree of this particular replica, which has arity `U`.
+    pub fn merkle_tree<U: typenum::Unsigned>(
+        &self,
+        tree_size: usize,
+        tree_leafs: usize,
+    ) -> Result<LCMerkleTree<H::Domain, H::Function, U>> {
+        let arity = U::to_usize();
+        trace!(
+            "post: tree size {}, tree leafs {}, arity {}, cached above base {}",
+            tree_size,
+            tree_leafs,
+            arity,
+            StoreConfig::default_cached_above_base_layer(tree_leafs, arity)
+        );
+        let mut config = StoreConfig::new(
+            self.cache_dir_path(),
+            CacheKey::CommRLastTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(tree_leafs, arity),
+        );
+        config.size = Some(tree_size);
+
+        let tree_r_last_store: LevelCacheStore<H::Domain, _> =
+            LevelCacheStore::new_from_disk(tree_size, arity, &config)?;
+        let tree_r_last = LCMerkleTree::from_data_store(tree_r_last_store, tree_leafs)?;
+
+        Ok(tree_r_last)
+    }
+}
+
+/// Generates proof-of-spacetime candidates for ElectionPoSt.
+///
+/// # Arguments
+///
+/// * `post_config` - post config that contains the sector size of each sector that we are
+/// generating this post for.
+/// * `randomness` - randomness used to generate sector challenges.
+/// * `challenge_count` - the number sector challenges in this post.
+/// * `replicas` - each sector's sector-id and associated replica info.
+/// * `prover_id` - the prover-id that is generating this post.
+pub fn generate_candidates<H: 'static + Hasher>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    challenge_count: u64,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<H>>,
+    prover_id: ProverId,
+) -> Result<Vec<Candidate>> {
+    info!("generate_candidates:start");
+    let _span = trace::span("generate_candidates").arg("sectors", replicas.len());
+
+    ensure!(!replicas.is_empty(), "Replicas must not be empty");
+    ensure!(challenge_count > 0, "Challenge count must be > 0");
+
+    let candidates = match tree_r_last_arity(PaddedBytesAmount::from(post_config))? {
+        QUAD_ARITY => generate_candidates_inner::<H, typenum::U4>(
+            post_config,
+            randomness,
+            challenge_count,
+            replicas,
+            prover_id,
+        ),
+        OCT_ARITY => generate_candidates_inner::<H, typenum::U8>(
+            post_config,
+            randomness,
+            challenge_count,
+            replicas,
+            prover_id,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }?;
+
+    info!("generate_candidates:finish");
+
+    Ok(candidates)
+}
+
+fn generate_candidates_inner<H, U>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    challenge_count: u64,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<H>>,
+    prover_id: ProverId,
+) -> Result<Vec<Candidate>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe = as_safe_commitment(&prover_id, "randomness")?;
+
+    let vanilla_params = post_setup_params(post_config);
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params,
+        partitions: None,
+        priority: false,
+    };
+    let public_params: compound_proof::PublicParams<election_post::ElectionPoSt<H, U>> =
+        ElectionPoStCompound::<H, U>::setup(&setup_params)?;
+
+    let sector_count = replicas.len() as u64;
+    ensure!(sector_count > 0, "Must supply at least one replica");
+
+    let sectors = replicas.keys().copied().collect();
+
+    let challenged_sectors =
+        election_post::generate_sector_challenges(randomness_safe, challenge_count, &sectors)?;
+
+    // Match the replicas to the challenges, as these are the only ones required.
+    let challenged_replicas: Vec<_> = challenged_sectors
+        .iter()
+        .map(|c| {
+            if let Some(replica) = replicas.get(c) {
+                Ok((c, replica))
+            } else {
+                Err(anyhow!(
+                    "Invalid challenge generated: {}, only {} sectors are being proven",
+                    c,
+                    sector_count
+                ))
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Generate merkle trees for the challenged replicas.
+    // Merkle trees should be generated only once, not multiple times if the same sector is challenged
+    // multiple times, so we build a HashMap of trees.
+
+    let mut unique_challenged_replicas = challenged_replicas.clone();
+    unique_challenged_replicas.sort_unstable(); // dedup requires a sorted list
+    unique_challenged_replicas.dedup();
+
+    let tree_size = get_tree_size::<H::Domain>(post_config.sector_size, U::to_usize());
+    let tree_leafs = get_merkle_tree_leafs(tree_size, U::to_usize());
+
+    let unique_trees_res: Vec<_> = unique_challenged_replicas
+        .into_par_iter()
+        .map(|(id, replica)| {
+            // Ensure that any associated cached data persisted is
+            // discarded and our tree is compacted by this point.
+            let t_aux = {
+                let mut aux_bytes = vec![];
+                let f_aux_path = replica.cache_dir_path().join(CacheKey::TAux.to_string());
+                let mut f_aux = File::open(&f_aux_path)
+                    .with_context(|| format!("could not open path={:?}", f_aux_path))?;
+                f_aux
+                    .read_to_end(&mut aux_bytes)
+                    .with_context(|| format!("could not read from path={:?}", f_aux_path))?;
+
+                deserialize_aux(&aux_bytes)
+                    .with_context(|| format!("invalid t_aux at path={:?}", f_aux_path))
+            }?;
+
+            TemporaryAux::<H>::compact::<U>(t_aux)?;
+
+            replica
+                .merkle_tree::<U>(tree_size, tree_leafs)
+                .map(|tree| (*id, tree))
+        })
+        .collect();
+
+    // resolve results
+    let trees: BTreeMap<SectorId, _> = unique_trees_res.into_iter().collect::<Result<_, _>>()?;
+
+    election_post::generate_candidates::<H, U>(
+        &public_params.vanilla_params,
+        &challenged_sectors,
+        &trees,
+        prover_id_safe,
+        randomness_safe,
+    )
+}
+
+/// Generates a proof-of-spacetime.
+///
+/// # Arguments
+///
+/// * `post_config` - post config that contains the sector size of each sector that we are
+/// generating this post for.
+/// * `randomness` - randomness used to generate sector challenges.
+/// * `replicas` - each sector's sector-id and associated replica info.
+/// * `winners` - a vector containing each winning ticket.
+/// * `prover_id` - the prover-id that is generating this post.
+pub fn generate_post<H: 'static + Hasher>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<H>>,
+    winners: Vec<Candidate>,
+    prover_id: ProverId,
+) -> Result<Vec<SnarkProof>> {
+    info!("generate_post:start");
+    let _span = trace::span("generate_post").arg("sectors", replicas.len());
+
+    let sector_count = replicas.len() as u64;
+    ensure!(sector_count > 0, "Must supply at least one replica");
+    ensure!(!winners.is_empty(), "Winners must not be empty");
+    ensure!(!replicas.is_empty(), "Replicas must not be empty");
+
+    let proofs = match tree_r_last_arity(PaddedBytesAmount::from(post_config))? {
+        QUAD_ARITY => generate_post_inner::<H, typenum::U4>(
+            post_config,
+            randomness,
+            replicas,
+            winners,
+            prover_id,
+        ),
+        OCT_ARITY => generate_post_inner::<H, typenum::U8>(
+            post_config,
+            randomness,
+            replicas,
+            winners,
+            prover_id,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }?;
+
+    info!("generate_post:finish");
+
+    Ok(proofs)
+}
+
+fn generate_post_inner<H, U>(
+    post_config: PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<H>>,
+    winners: Vec<Candidate>,
+    prover_id: ProverId,
+) -> Result<Vec<SnarkProof>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe = as_safe_commitment(&prover_id, "randomness")?;
+
+    let vanilla_params = post_setup_params(post_config);
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params,
+        partitions: None,
+        priority: post_config.priority,
+    };
+    let pub_params: compound_proof::PublicParams<election_post::ElectionPoSt<H, U>> =
+        ElectionPoStCompound::<H, U>::setup(&setup_params)?;
+
+    let tree_size = get_tree_size::<H::Domain>(post_config.sector_size, U::to_usize());
+    let tree_leafs = get_merkle_tree_leafs(tree_size, U::to_usize());
+
+    let mut proofs = Vec::with_capacity(winners.len());
+    let proof_header = if settings::current().envelope_proofs() {
+        Some(post_config.get_proof_header::<H>()?)
+    } else {
+        None
+    };
+
+    let inputs: Vec<_> = winners
+        .par_iter()
+        .map(|winner| {
+            let replica = replicas
+                .get(&winner.sector_id)
+                .with_context(|| format!("Missing replica for sector: {}", winner.sector_id))?;
+            let tree = replica.merkle_tree::<U>(tree_size, tree_leafs)?;
+
+            let comm_r = replica.safe_comm_r()?;
+            let pub_inputs = election_post::PublicInputs {
+                randomness: randomness_safe,
+                comm_r,
+                sector_id: winner.sector_id,
+                partial_ticket: winner.partial_ticket,
+                sector_challenge_index: winner.sector_challenge_index,
+                prover_id: prover_id_safe,
+            };
+
+            let comm_c = replica.safe_comm_c()?;
+            let comm_r_last = replica.safe_comm_r_last()?;
+            let priv_inputs = election_post::PrivateInputs::<H, U> {
+                tree,
+                comm_c,
+                comm_r_last,
+            };
+
+            Ok((pub_inputs, priv_inputs))
+        })
+        .collect::<Result<_>>()?;
+
+    if MOCK_PROOFS {
+        return inputs
+            .iter()
+            .zip(winners.iter())
+            .map(|((pub_inputs, priv_inputs), winner)| {
+                let vanilla_proof = election_post::ElectionPoSt::<H, U>::prove(
+                    &pub_params.vanilla_params,
+                    pub_inputs,
+                    priv_inputs,
+                )?;
+                ensure!(
+                    election_post::ElectionPoSt::<H, U>::verify(
+                        &pub_params.vanilla_params,
+                        pub_inputs,
+                        &vanilla_proof
+                    )?,
+                    "Invalid vanilla proof generated"
+                );
+
+                Ok(mock::post_proof(
+                    post_config,
+                    randomness,
+                    &pub_inputs.comm_r.into_bytes(),
+                    winner,
+                    &prover_id,
+                ))
+            })
+            .collect();
+    }
+
+    let groth_params = get_post_params::<H, U>(post_config)?;
+
+    for (pub_inputs, priv_inputs) in &inputs {
+        let proof = ElectionPoStCompound::<H, U>::prove(
+            &pub_params,
+            &pub_inputs,
+            &priv_inputs,
+            &groth_params,
+        )?;
+        if let Some(ref header) = proof_header {
+            proofs.push(proof.to_envelope_vec(header)?);
+        } else {
+            proofs.push(proof.to_vec()?);
+        }
+    }
+
+    Ok(proofs)
+}
@@ -0,0 +1,624 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use bincode::serialize;
+use log::{info, trace};
+use memmap::MmapOptions;
+use merkletree::merkle::get_merkle_tree_leafs;
+use merkletree::store::{DiskStore, Store, StoreConfig};
+use paired::bls12_381::{Bls12, Fr};
+use storage_proofs::circuit::multi_proof::MultiProof;
+use storage_proofs::circuit::stacked::StackedCompound;
+use storage_proofs::compound_proof::{self, CompoundProof};
+use storage_proofs::drgraph::Graph;
+use storage_proofs::hasher::{Domain, Hasher, PoseidonArity, PoseidonEngine};
+use storage_proofs::measurements::{measure_op, Operation::CommD};
+use storage_proofs::merkle::{create_merkle_tree, BinaryMerkleTree};
+use storage_proofs::proof::ProofScheme;
+use storage_proofs::sector::SectorId;
+use storage_proofs::settings;
+use storage_proofs::stacked::{
+    self, generate_replica_id, CacheKey, LabelingMode, StackedDrg, TemporaryAux, TemporaryAuxCache,
+};
+use storage_proofs::trace;
+
+use crate::api::mock::{self, MOCK_PROOFS};
+use crate::api::util::{as_safe_commitment, commitment_from_fr, deserialize_aux, get_tree_size};
+use crate::caches::get_stacked_params;
+use crate::constants::{DefaultPieceHasher, SINGLE_PARTITION_PROOF_LEN};
+use crate::parameters::{setup_params, tree_r_last_arity};
+use crate::pieces::verify_pieces;
+use crate::types::{
+    PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId, SealCommitOutput,
+    SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output, Ticket,
+    VanillaSealProof, VanillaSealProofs, BINARY_ARITY, OCT_ARITY, QUAD_ARITY,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1<H, R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+) -> Result<SealPreCommitPhase1Output<H>>
+where
+    H: 'static + Hasher,
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_with_mode::<H, _, _, _>(
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+        LabelingMode::default(),
+    )
+}
+
+/// Same as `seal_pre_commit_phase1`, but generates the layer labels using the given
+/// `labeling_mode`, e.g. `LabelingMode::Bounded` to seal on machines with less memory
+/// than twice the sector size.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_mode<H, R, S, T>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    labeling_mode: LabelingMode,
+) -> Result<SealPreCommitPhase1Output<H>>
+where
+    H: 'static + Hasher,
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    info!("seal_pre_commit_phase1: start");
+    let _span = trace::span("seal_pre_commit_phase1").arg("sector_id", u64::from(sector_id));
+
+    let sector_bytes = usize::from(PaddedBytesAmount::from(porep_config));
+    fs::metadata(&in_path)
+        .with_context(|| format!("could not read in_path={:?})", in_path.as_ref().display()))?;
+
+    fs::metadata(&out_path)
+        .with_context(|| format!("could not read out_path={:?}", out_path.as_ref().display()))?;
+
+    // Copy unsealed data to output location, where it will be sealed in place.
+    fs::copy(&in_path, &out_path).with_context(|| {
+        format!(
+            "could not copy in_path={:?} to out_path={:?}",
+            in_path.as_ref().display(),
+            out_path.as_ref().display()
+        )
+    })?;
+
+    let f_data = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&out_path)
+        .with_context(|| format!("could not open out_path={:?}", out_path.as_ref().display()))?;
+
+    // Zero-pad the data to the requested size by extending the underlying file if needed.
+    f_data.set_len(sector_bytes as u64)?;
+
+    let data = unsafe {
+        MmapOptions::new()
+            .map_mut(&f_data)
+            .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
+    };
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: setup_params(
+            PaddedBytesAmount::from(porep_config),
+            usize::from(PoRepProofPartitions::from(porep_config)),
+        )?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params = <StackedCompound<H, DefaultPieceHasher> as CompoundProof<
+        _,
+        StackedDrg<H, DefaultPieceHasher>,
+        _,
+    >>::setup(&compound_setup_params)?;
+
+    info!("building merkle tree for the original data");
+    let (config, comm_d) = measure_op(CommD, || -> Result<_> {
+        let tree_size = get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(
+            porep_config.sector_size,
+            BINARY_ARITY,
+        );
+        let tree_leafs = get_merkle_tree_leafs(tree_size, BINARY_ARITY);
+        ensure!(
+            compound_public_params.vanilla_params.graph.size() == tree_leafs,
+            "graph size and leaf size don't match"
+        );
+
+        trace!(
+            "seal phase 1: sector_size {}, tree size {}, tree leafs {}, cached above base {}",
+            u64::from(porep_config.sector_size),
+            tree_size,
+            tree_leafs,
+            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY)
+        );
+
+        // MT for original data is always named tree-d, and it will be
+        // referenced later in the process as such.
+        let config = StoreConfig::new(
+            cache_path.as_ref(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY),
+        );
+        let data_tree = create_merkle_tree::<DefaultPieceHasher, typenum::U2>(
+            Some(config.clone()),
+            tree_leafs,
+            &data,
+        )?;
+        drop(data);
+
+        let comm_d_root: Fr = data_tree.root().into();
+        let comm_d = commitment_from_fr::<Bls12>(comm_d_root);
+        drop(data_tree);
+
+        Ok((config, comm_d))
+    })?;
+
+    info!("verifying pieces");
+
+    ensure!(
+        verify_pieces(&comm_d, piece_infos, porep_config.into())?,
+        "pieces and comm_d do not match"
+    );
+
+    let replica_id = generate_replica_id::<H, _>(&prover_id, sector_id.into(), &ticket, comm_d);
+
+    let labels = StackedDrg::<H, DefaultPieceHasher>::replicate_phase1_with_mode(
+        &compound_public_params.vanilla_params,
+        &replica_id,
+        config.clone(),
+        labeling_mode,
+    )?;
+
+    Ok(SealPreCommitPhase1Output {
+        labels,
+        config,
+        comm_d,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase2<H, R, S>(
+    porep_config: PoRepConfig,
+    phase1_output: SealPreCommitPhase1Output<H>,
+    cache_path: S,
+    out_path: R,
+) -> Result<SealPreCommitOutput>
+where
+    H: 'static + Hasher,
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    info!("seal_pre_commit_phase2: start");
+    let _span = trace::span("seal_pre_commit_phase2")
+        .arg("sector_size", u64::from(porep_config.sector_size));
+
+    let SealPreCommitPhase1Output {
+        mut labels,
+        config,
+        comm_d,
+        ..
+    } = phase1_output;
+
+    labels.update_root(cache_path.as_ref());
+
+    let f_data = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&out_path)
+        .with_context(|| format!("could not open out_path={:?}", out_path.as_ref().display()))?;
+    let data = unsafe {
+        MmapOptions::new()
+            .map_mut(&f_data)
+            .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
+    };
+    let data: storage_proofs::porep::Data<'_> = (data, PathBuf::from(out_path.as_ref())).into();
+
+    // Load data tree from disk
+    let data_tree = {
+        let tree_size = get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(
+            porep_config.sector_size,
+            BINARY_ARITY,
+        );
+        let tree_leafs = get_merkle_tree_leafs(tree_size, BINARY_ARITY);
+
+        trace!(
+            "seal phase 2: tree size {}, tree leafs {}, cached above base {}",
+            tree_size,
+            tree_leafs,
+            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY)
+        );
+        let config = StoreConfig::new(
+            cache_path.as_ref(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY),
+        );
+
+        let store: DiskStore<<DefaultPieceHasher as Hasher>::Domain> =
+            DiskStore::new_from_disk(tree_size, BINARY_ARITY, &config)?;
+        BinaryMerkleTree::from_data_store(store, tree_leafs)
+    }?;
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: setup_params(
+            PaddedBytesAmount::from(porep_config),
+            usize::from(PoRepProofPartitions::from(porep_config)),
+        )?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params = <StackedCompound<H, DefaultPieceHasher> as CompoundProof<
+        _,
+        StackedDrg<H, DefaultPieceHasher>,
+        _,
+    >>::setup(&compound_setup_params)?;
+
+    let (tau, (p_aux, t_aux)) = match tree_r_last_arity(PaddedBytesAmount::from(porep_config))? {
+        QUAD_ARITY => StackedDrg::<H, DefaultPieceHasher, typenum::U4>::replicate_phase2(
+            &compound_public_params.vanilla_params,
+            labels,
+            data,
+            data_tree,
+            config,
+        ),
+        OCT_ARITY => StackedDrg::<H, DefaultPieceHasher, typenum::U8>::replicate_phase2(
+            &compound_public_params.vanilla_params,
+            labels,
+            data,
+            data_tree,
+            config,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }?;
+
+    let comm_r = commitment_from_fr::<Bls12>(tau.comm_r.into());
+
+    // Persist p_aux and t_aux here
+    let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
+    let mut f_p_aux = File::create(&p_aux_path)
+        .with_context(|| format!("could not create file p_aux={:?}", p_aux_path))?;
+    let p_aux_bytes = serialize(&p_aux)?;
+    f_p_aux
+        .write_all(&p_aux_bytes)
+        .with_context(|| format!("could not write to file p_aux={:?}", p_aux_path))?;
+
+    let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
+    let mut f_t_aux = File::create(&t_aux_path)
+        .with_context(|| format!("could not create file t_aux={:?}", t_aux_path))?;
+    let t_aux_bytes = serialize(&t_aux)?;
+    f_t_aux
+        .write_all(&t_aux_bytes)
+        .with_context(|| format!("could not write to file t_aux={:?}", t_aux_path))?;
+
+    Ok(SealPreCommitOutput { comm_r, comm_d })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_commit_phase1<H: 'static + Hasher, T: AsRef<Path>>(
+    porep_config: PoRepConfig,
+    cache_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    pre_commit: SealPreCommitOutput,
+    piece_infos: &[PieceInfo],
+) -> Result<SealCommitPhase1Output<H>> {
+    info!("seal_commit_phase1:start");
+    let _span = trace::span("seal_commit_phase1").arg("sector_id", u64::from(sector_id));
+
+    let SealPreCommitOutput { comm_d, comm_r } = pre_commit;
+
+    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+    ensure!(
+        verify_pieces(&comm_d, piece_infos, porep_config.into())?,
+        "pieces and comm_d do not match"
+    );
+
+    let p_aux = {
+        let mut p_aux_bytes = vec![];
+        let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
+        let mut f_p_aux = File::open(&p_aux_path)
+            .with_context(|| format!("could not open file p_aux={:?}", p_aux_path))?;
+        f_p_aux.read_to_end(&mut p_aux_bytes)?;
+
+        deserialize_aux(&p_aux_bytes)
+    }
+    .with_context(|| format!("invalid p_aux in {:?}", cache_path.as_ref()))?;
+
+    let t_aux = {
+        let mut t_aux_bytes = vec![];
+        let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
+        let mut f_t_aux = File::open(&t_aux_path)
+            .with_context(|| format!("could not open file t_aux={:?}", t_aux_path))?;
+        f_t_aux.read_to_end(&mut t_aux_bytes)?;
+
+        let mut res: TemporaryAux<_, _> = deserialize_aux(&t_aux_bytes)
+            .with_context(|| format!("invalid t_aux={:?}", t_aux_path))?;
+
+        // Switch t_aux to the passed in cache_path
+        res.set_cache_path(cache_path);
+        res
+    };
+
+    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
+    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
+
+    let replica_id =
+        generate_replica_id::<H, _>(&prover_id, sector_id.into(), &ticket, comm_d_safe);
+
+    let public_inputs = stacked::PublicInputs {
+        replica_id,
+        tau: Some(stacked::Tau {
+            comm_d: comm_d_safe,
+            comm_r: comm_r_safe,
+        }),
+        k: None,
+        seed,
+    };
+
+    let vanilla_proofs: VanillaSealProofs<H> =
+        match tree_r_last_arity(PaddedBytesAmount::from(porep_config))? {
+            QUAD_ARITY => prove_vanilla_partitions::<H, typenum::U4>(
+                porep_config,
+                &public_inputs,
+                p_aux,
+                t_aux,
+            )?
+            .into(),
+            OCT_ARITY => prove_vanilla_partitions::<H, typenum::U8>(
+                porep_config,
+                &public_inputs,
+                p_aux,
+                t_aux,
+            )?
+            .into(),
+            arity => bail!("unsupported tree_r_last arity: {}", arity),
+        };
+
+    info!("seal_commit_phase1:end");
+
+    Ok(SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_r,
+        comm_d,
+        replica_id,
+        seed,
+        ticket,
+    })
+}
+
+/// Generates and sanity checks the vanilla proofs of all partitions, for a `tree_r_last` of
+/// arity `U`.
+fn prove_vanilla_partitions<H, U>(
+    porep_config: PoRepConfig,
+    public_inputs: &stacked::PublicInputs<
+        <H as Hasher>::Domain,
+        <DefaultPieceHasher as Hasher>::Domain,
+    >,
+    p_aux: stacked::PersistentAux<<H as Hasher>::Domain>,
+    t_aux: TemporaryAux<H, DefaultPieceHasher>,
+) -> Result<Vec<Vec<VanillaSealProof<H, U>>>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
+    // elements based on the configs stored in TemporaryAux.
+    let t_aux_cache: TemporaryAuxCache<H, DefaultPieceHasher, U> =
+        TemporaryAuxCache::new(&t_aux).context("failed to restore contents of t_aux")?;
+
+    let private_inputs = stacked::PrivateInputs::<H, DefaultPieceHasher, U> {
+        p_aux,
+        t_aux: t_aux_cache,
+    };
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: setup_params(
+            PaddedBytesAmount::from(porep_config),
+            usize::from(PoRepProofPartitions::from(porep_config)),
+        )?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params = <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
+        _,
+        StackedDrg<H, DefaultPieceHasher, U>,
+        _,
+    >>::setup(&compound_setup_params)?;
+
+    let vanilla_proofs = StackedDrg::<H, DefaultPieceHasher, U>::prove_all_partitions(
+        &compound_public_params.vanilla_params,
+        public_inputs,
+        &private_inputs,
+        StackedCompound::partition_count(&compound_public_params),
+    )?;
+
+    let sanity_check = StackedDrg::<H, DefaultPieceHasher, U>::verify_all_partitions(
+        &compound_public_params.vanilla_params,
+        public_inputs,
+        &vanilla_proofs,
+    )?;
+    ensure!(sanity_check, "Invalid vanilla proof generated");
+
+    // Discard or compact cached MTs that are no longer needed.
+    TemporaryAux::<H, DefaultPieceHasher>::compact::<U>(t_aux)?;
+
+    Ok(vanilla_proofs)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_commit_phase2<H: 'static + Hasher>(
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output<H>,
+    prover_id: ProverId,
+    sector_id: SectorId,
+) -> Result<SealCommitOutput> {
+    info!("seal_commit_phase2:start");
+    let _span = trace::span("seal_commit_phase2").arg("sector_id", u64::from(sector_id));
+
+    let SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_d,
+        comm_r,
+        replica_id,
+        seed,
+        ticket,
+    } = phase1_output;
+
+    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+
+    if MOCK_PROOFS {
+        // The vanilla proofs were generated and checked by `seal_commit_phase1`.
+        let proof = mock::seal_proof(
+            porep_config,
+            &comm_r,
+            &comm_d,
+            &prover_id,
+            sector_id,
+            &ticket,
+            &seed,
+        );
+        info!("seal_commit_phase2:end (mock)");
+
+        return Ok(SealCommitOutput { proof });
+    }
+
+    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
+    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
+
+    let public_inputs = stacked::PublicInputs {
+        replica_id,
+        tau: Some(stacked::Tau {
+            comm_d: comm_d_safe,
+            comm_r: comm_r_safe,
+        }),
+        k: None,
+        seed,
+    };
+
+    let buf = match (
+        tree_r_last_arity(PaddedBytesAmount::from(porep_config))?,
+        vanilla_proofs,
+    ) {
+        (QUAD_ARITY, VanillaSealProofs::Quad(vanilla_proofs)) => {
+            prove_circuit_partitions::<H, typenum::U4>(porep_config, &public_inputs, vanilla_proofs)
+        }
+        (OCT_ARITY, VanillaSealProofs::Oct(vanilla_proofs)) => {
+            prove_circuit_partitions::<H, typenum::U8>(porep_config, &public_inputs, vanilla_proofs)
+        }
+        (arity, _) => bail!(
+            "vanilla proofs were not generated for a tree_r_last of arity {}",
+            arity
+        ),
+    }?;
+
+    // Verification is cheap when parameters are cached,
+    // and it is never correct to return a proof which does not verify.
+    verify_seal::<H>(
+        porep_config,
+        comm_r,
+        comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        &buf,
+    )
+    .context("post-seal verification sanity check failed")?;
+
+    info!("seal_commit_phase2:end");
+
+    Ok(SealCommitOutput { proof: buf })
+}
+
+/// Generates the serialized circuit proofs of all partitions, for a `tree_r_last` of arity `U`.
+fn prove_circuit_partitions<H, U>(
+    porep_config: PoRepConfig,
+    public_inputs: &stacked::PublicInputs<
+        <H as Hasher>::Domain,
+        <DefaultPieceHasher as Hasher>::Domain,
+    >,
+    vanilla_proofs: Vec<Vec<VanillaSealProof<H, U>>>,
+) -> Result<Vec<u8>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let groth_params = get_stacked_params::<H, U>(porep_config)?;
+
+    info!(
+        "got groth params ({}) while sealing",
+        u64::from(PaddedBytesAmount::from(porep_config))
+    );
+
+    let compound_setup_params = compound_proof::SetupParams {
+        vanilla_params: setup_params(
+            PaddedBytesAmount::from(porep_config),
+            usize::from(PoRepProofPartitions::from(porep_config)),
+        )?,
+        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        priority: false,
+    };
+
+    let compound_public_params = <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
+        _,
+        StackedDrg<H, DefaultPieceHasher, U>,
+        _,
+    >>::setup(&compound_setup_params)?;
+
+    info!("snark_proof:start");
+    let groth_proofs = StackedCompound::<H, DefaultPieceHasher, U>::circuit_proofs(
+        public_inputs,
+        vanilla_proofs,
+        &compound_public_params.vanilla_params,
+        &groth_params,
+        compound_public_params.priority,
+    )?;
+    info!("snark_proof:finish");
+
+    let proof = MultiProof::new(groth_proofs, &groth_params.vk);
+
+    let mut buf = Vec::with_capacity(
+        SINGLE_PARTITION_PROOF_LEN * usize::from(PoRepProofPartitions::from(porep_config)),
+    );
+
+    if settings::current().envelope_proofs() {
+        proof.write_envelope(&porep_config.get_proof_header::<H>()?, &mut buf)?;
+    } else {
+        proof.write(&mut buf)?;
+    }
+
+    Ok(buf)
+}
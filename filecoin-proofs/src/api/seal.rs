@@ -1,492 +1,27 @@
-use std::fs::{self, File, OpenOptions};
-use std::io::prelude::*;
-use std::path::{Path, PathBuf};
-
-use anyhow::{ensure, Context, Result};
-use bincode::{deserialize, serialize};
-use log::{info, trace};
-use memmap::MmapOptions;
-use merkletree::merkle::get_merkle_tree_leafs;
-use merkletree::store::{DiskStore, Store, StoreConfig};
+use anyhow::{bail, ensure, Context, Result};
+use log::info;
 use paired::bls12_381::{Bls12, Fr};
 use storage_proofs::circuit::multi_proof::MultiProof;
 use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::{self, CompoundProof};
-use storage_proofs::drgraph::Graph;
-use storage_proofs::hasher::{Domain, Hasher};
-use storage_proofs::measurements::{measure_op, Operation::CommD};
-use storage_proofs::merkle::{create_merkle_tree, BinaryMerkleTree};
-use storage_proofs::proof::ProofScheme;
+use storage_proofs::hasher::{Hasher, PoseidonArity, PoseidonEngine};
+use storage_proofs::metrics;
 use storage_proofs::sector::SectorId;
-use storage_proofs::stacked::{
-    self, generate_replica_id, CacheKey, ChallengeRequirements, StackedDrg, Tau, TemporaryAux,
-    TemporaryAuxCache,
-};
-
-use crate::api::util::{as_safe_commitment, commitment_from_fr, get_tree_size};
-use crate::caches::{get_stacked_params, get_stacked_verifying_key};
-use crate::constants::{
-    DefaultPieceHasher, DefaultTreeHasher, POREP_MINIMUM_CHALLENGES, SINGLE_PARTITION_PROOF_LEN,
-};
-use crate::parameters::setup_params;
+use storage_proofs::stacked::{self, generate_replica_id, ChallengeRequirements, StackedDrg, Tau};
+use storage_proofs::trace;
+
+use crate::api::mock::{self, is_mock_proof, MOCK_PROOFS};
+use crate::api::util::as_safe_commitment;
+use crate::caches::get_stacked_verifying_key;
+use crate::constants::{DefaultPieceHasher, POREP_MINIMUM_CHALLENGES};
+use crate::parameters::{setup_params, tree_r_last_arity};
 pub use crate::pieces;
 pub use crate::pieces::verify_pieces;
 use crate::types::{
     Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId,
-    SealCommitOutput, SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output,
-    SectorSize, Ticket, BINARY_ARITY,
+    SectorSize, Ticket, OCT_ARITY, QUAD_ARITY,
 };
 
-#[allow(clippy::too_many_arguments)]
-pub fn seal_pre_commit_phase1<R, S, T>(
-    porep_config: PoRepConfig,
-    cache_path: R,
-    in_path: S,
-    out_path: T,
-    prover_id: ProverId,
-    sector_id: SectorId,
-    ticket: Ticket,
-    piece_infos: &[PieceInfo],
-) -> Result<SealPreCommitPhase1Output>
-where
-    R: AsRef<Path>,
-    S: AsRef<Path>,
-    T: AsRef<Path>,
-{
-    info!("seal_pre_commit_phase1: start");
-
-    let sector_bytes = usize::from(PaddedBytesAmount::from(porep_config));
-    fs::metadata(&in_path)
-        .with_context(|| format!("could not read in_path={:?})", in_path.as_ref().display()))?;
-
-    fs::metadata(&out_path)
-        .with_context(|| format!("could not read out_path={:?}", out_path.as_ref().display()))?;
-
-    // Copy unsealed data to output location, where it will be sealed in place.
-    fs::copy(&in_path, &out_path).with_context(|| {
-        format!(
-            "could not copy in_path={:?} to out_path={:?}",
-            in_path.as_ref().display(),
-            out_path.as_ref().display()
-        )
-    })?;
-
-    let f_data = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(&out_path)
-        .with_context(|| format!("could not open out_path={:?}", out_path.as_ref().display()))?;
-
-    // Zero-pad the data to the requested size by extending the underlying file if needed.
-    f_data.set_len(sector_bytes as u64)?;
-
-    let data = unsafe {
-        MmapOptions::new()
-            .map_mut(&f_data)
-            .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
-    };
-
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
-
-    let compound_public_params =
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::setup(&compound_setup_params)?;
-
-    info!("building merkle tree for the original data");
-    let (config, comm_d) = measure_op(CommD, || -> Result<_> {
-        let tree_size = get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(
-            porep_config.sector_size,
-            BINARY_ARITY,
-        );
-        let tree_leafs = get_merkle_tree_leafs(tree_size, BINARY_ARITY);
-        ensure!(
-            compound_public_params.vanilla_params.graph.size() == tree_leafs,
-            "graph size and leaf size don't match"
-        );
-
-        trace!(
-            "seal phase 1: sector_size {}, tree size {}, tree leafs {}, cached above base {}",
-            u64::from(porep_config.sector_size),
-            tree_size,
-            tree_leafs,
-            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY)
-        );
-
-        // MT for original data is always named tree-d, and it will be
-        // referenced later in the process as such.
-        let config = StoreConfig::new(
-            cache_path.as_ref(),
-            CacheKey::CommDTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY),
-        );
-        let data_tree = create_merkle_tree::<DefaultPieceHasher, typenum::U2>(
-            Some(config.clone()),
-            tree_leafs,
-            &data,
-        )?;
-        drop(data);
-
-        let comm_d_root: Fr = data_tree.root().into();
-        let comm_d = commitment_from_fr::<Bls12>(comm_d_root);
-        drop(data_tree);
-
-        Ok((config, comm_d))
-    })?;
-
-    info!("verifying pieces");
-
-    ensure!(
-        verify_pieces(&comm_d, piece_infos, porep_config.into())?,
-        "pieces and comm_d do not match"
-    );
-
-    let replica_id =
-        generate_replica_id::<DefaultTreeHasher, _>(&prover_id, sector_id.into(), &ticket, comm_d);
-
-    let labels = StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::replicate_phase1(
-        &compound_public_params.vanilla_params,
-        &replica_id,
-        config.clone(),
-    )?;
-
-    Ok(SealPreCommitPhase1Output {
-        labels,
-        config,
-        comm_d,
-    })
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn seal_pre_commit_phase2<R, S>(
-    porep_config: PoRepConfig,
-    phase1_output: SealPreCommitPhase1Output,
-    cache_path: S,
-    out_path: R,
-) -> Result<SealPreCommitOutput>
-where
-    R: AsRef<Path>,
-    S: AsRef<Path>,
-{
-    info!("seal_pre_commit_phase2: start");
-
-    let SealPreCommitPhase1Output {
-        mut labels,
-        config,
-        comm_d,
-        ..
-    } = phase1_output;
-
-    labels.update_root(cache_path.as_ref());
-
-    let f_data = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(&out_path)
-        .with_context(|| format!("could not open out_path={:?}", out_path.as_ref().display()))?;
-    let data = unsafe {
-        MmapOptions::new()
-            .map_mut(&f_data)
-            .with_context(|| format!("could not mmap out_path={:?}", out_path.as_ref().display()))?
-    };
-    let data: storage_proofs::porep::Data<'_> = (data, PathBuf::from(out_path.as_ref())).into();
-
-    // Load data tree from disk
-    let data_tree = {
-        let tree_size = get_tree_size::<<DefaultPieceHasher as Hasher>::Domain>(
-            porep_config.sector_size,
-            BINARY_ARITY,
-        );
-        let tree_leafs = get_merkle_tree_leafs(tree_size, BINARY_ARITY);
-
-        trace!(
-            "seal phase 2: tree size {}, tree leafs {}, cached above base {}",
-            tree_size,
-            tree_leafs,
-            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY)
-        );
-        let config = StoreConfig::new(
-            cache_path.as_ref(),
-            CacheKey::CommDTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(tree_leafs, BINARY_ARITY),
-        );
-
-        let store: DiskStore<<DefaultPieceHasher as Hasher>::Domain> =
-            DiskStore::new_from_disk(tree_size, BINARY_ARITY, &config)?;
-        BinaryMerkleTree::from_data_store(store, tree_leafs)
-    }?;
-
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
-
-    let compound_public_params =
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::setup(&compound_setup_params)?;
-
-    let (tau, (p_aux, t_aux)) =
-        StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::replicate_phase2(
-            &compound_public_params.vanilla_params,
-            labels,
-            data,
-            data_tree,
-            config,
-        )?;
-
-    let comm_r = commitment_from_fr::<Bls12>(tau.comm_r.into());
-
-    // Persist p_aux and t_aux here
-    let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
-    let mut f_p_aux = File::create(&p_aux_path)
-        .with_context(|| format!("could not create file p_aux={:?}", p_aux_path))?;
-    let p_aux_bytes = serialize(&p_aux)?;
-    f_p_aux
-        .write_all(&p_aux_bytes)
-        .with_context(|| format!("could not write to file p_aux={:?}", p_aux_path))?;
-
-    let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
-    let mut f_t_aux = File::create(&t_aux_path)
-        .with_context(|| format!("could not create file t_aux={:?}", t_aux_path))?;
-    let t_aux_bytes = serialize(&t_aux)?;
-    f_t_aux
-        .write_all(&t_aux_bytes)
-        .with_context(|| format!("could not write to file t_aux={:?}", t_aux_path))?;
-
-    Ok(SealPreCommitOutput { comm_r, comm_d })
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn seal_commit_phase1<T: AsRef<Path>>(
-    porep_config: PoRepConfig,
-    cache_path: T,
-    prover_id: ProverId,
-    sector_id: SectorId,
-    ticket: Ticket,
-    seed: Ticket,
-    pre_commit: SealPreCommitOutput,
-    piece_infos: &[PieceInfo],
-) -> Result<SealCommitPhase1Output> {
-    info!("seal_commit_phase1:start");
-
-    let SealPreCommitOutput { comm_d, comm_r } = pre_commit;
-
-    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
-    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
-    ensure!(
-        verify_pieces(&comm_d, piece_infos, porep_config.into())?,
-        "pieces and comm_d do not match"
-    );
-
-    let p_aux = {
-        let mut p_aux_bytes = vec![];
-        let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
-        let mut f_p_aux = File::open(&p_aux_path)
-            .with_context(|| format!("could not open file p_aux={:?}", p_aux_path))?;
-        f_p_aux.read_to_end(&mut p_aux_bytes)?;
-
-        deserialize(&p_aux_bytes)
-    }?;
-
-    let t_aux = {
-        let mut t_aux_bytes = vec![];
-        let t_aux_path = cache_path.as_ref().join(CacheKey::TAux.to_string());
-        let mut f_t_aux = File::open(&t_aux_path)
-            .with_context(|| format!("could not open file t_aux={:?}", t_aux_path))?;
-        f_t_aux.read_to_end(&mut t_aux_bytes)?;
-
-        let mut res: TemporaryAux<_, _> = deserialize(&t_aux_bytes)?;
-
-        // Switch t_aux to the passed in cache_path
-        res.set_cache_path(cache_path);
-        res
-    };
-
-    // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
-    // elements based on the configs stored in TemporaryAux.
-    let t_aux_cache: TemporaryAuxCache<DefaultTreeHasher, DefaultPieceHasher> =
-        TemporaryAuxCache::new(&t_aux).context("failed to restore contents of t_aux")?;
-
-    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
-    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
-
-    let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
-        &prover_id,
-        sector_id.into(),
-        &ticket,
-        comm_d_safe,
-    );
-
-    let public_inputs = stacked::PublicInputs {
-        replica_id,
-        tau: Some(stacked::Tau {
-            comm_d: comm_d_safe,
-            comm_r: comm_r_safe,
-        }),
-        k: None,
-        seed,
-    };
-
-    let private_inputs = stacked::PrivateInputs::<DefaultTreeHasher, DefaultPieceHasher> {
-        p_aux,
-        t_aux: t_aux_cache,
-    };
-
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
-
-    let compound_public_params =
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::setup(&compound_setup_params)?;
-
-    let vanilla_proofs = StackedDrg::prove_all_partitions(
-        &compound_public_params.vanilla_params,
-        &public_inputs,
-        &private_inputs,
-        StackedCompound::partition_count(&compound_public_params),
-    )?;
-
-    let sanity_check = StackedDrg::verify_all_partitions(
-        &compound_public_params.vanilla_params,
-        &public_inputs,
-        &vanilla_proofs,
-    )?;
-    ensure!(sanity_check, "Invalid vanilla proof generated");
-
-    // Discard or compact cached MTs that are no longer needed.
-    TemporaryAux::<DefaultTreeHasher, DefaultPieceHasher>::compact(t_aux)?;
-
-    info!("seal_commit_phase1:end");
-
-    Ok(SealCommitPhase1Output {
-        vanilla_proofs,
-        comm_r,
-        comm_d,
-        replica_id,
-        seed,
-        ticket,
-    })
-}
-
-#[allow(clippy::too_many_arguments)]
-pub fn seal_commit_phase2(
-    porep_config: PoRepConfig,
-    phase1_output: SealCommitPhase1Output,
-    prover_id: ProverId,
-    sector_id: SectorId,
-) -> Result<SealCommitOutput> {
-    info!("seal_commit_phase2:start");
-
-    let SealCommitPhase1Output {
-        vanilla_proofs,
-        comm_d,
-        comm_r,
-        replica_id,
-        seed,
-        ticket,
-    } = phase1_output;
-
-    ensure!(comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
-    ensure!(comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
-
-    let comm_r_safe = as_safe_commitment(&comm_r, "comm_r")?;
-    let comm_d_safe = <DefaultPieceHasher as Hasher>::Domain::try_from_bytes(&comm_d)?;
-
-    let public_inputs = stacked::PublicInputs {
-        replica_id,
-        tau: Some(stacked::Tau {
-            comm_d: comm_d_safe,
-            comm_r: comm_r_safe,
-        }),
-        k: None,
-        seed,
-    };
-
-    let groth_params = get_stacked_params(porep_config)?;
-
-    info!(
-        "got groth params ({}) while sealing",
-        u64::from(PaddedBytesAmount::from(porep_config))
-    );
-
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
-
-    let compound_public_params =
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
-            _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
-            _,
-        >>::setup(&compound_setup_params)?;
-
-    info!("snark_proof:start");
-    let groth_proofs = StackedCompound::circuit_proofs(
-        &public_inputs,
-        vanilla_proofs,
-        &compound_public_params.vanilla_params,
-        &groth_params,
-        compound_public_params.priority,
-    )?;
-    info!("snark_proof:finish");
-
-    let proof = MultiProof::new(groth_proofs, &groth_params.vk);
-
-    let mut buf = Vec::with_capacity(
-        SINGLE_PARTITION_PROOF_LEN * usize::from(PoRepProofPartitions::from(porep_config)),
-    );
-
-    proof.write(&mut buf)?;
-
-    // Verification is cheap when parameters are cached,
-    // and it is never correct to return a proof which does not verify.
-    verify_seal(
-        porep_config,
-        comm_r,
-        comm_d,
-        prover_id,
-        sector_id,
-        ticket,
-        seed,
-        &buf,
-    )
-    .context("post-seal verification sanity check failed")?;
-
-    info!("seal_commit_phase2:end");
-
-    Ok(SealCommitOutput { proof: buf })
-}
-
 /// Computes a sectors's `comm_d` given its pieces.
 ///
 /// # Arguments
@@ -510,7 +45,33 @@ pub fn compute_comm_d(sector_size: SectorSize, piece_infos: &[PieceInfo]) -> Res
 /// * `seed` - the seed used to derive the porep challenges.
 /// * `proof_vec` - the porep circuit proof serialized into a vector of bytes.
 #[allow(clippy::too_many_arguments)]
-pub fn verify_seal(
+pub fn verify_seal<H: 'static + Hasher>(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    let _span = trace::span("verify_seal").arg("sector_id", u64::from(sector_id));
+    metrics::measure_verification("seal", || {
+        verify_seal_inner::<H>(
+            porep_config,
+            comm_r_in,
+            comm_d_in,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            proof_vec,
+        )
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_seal_inner<H: 'static + Hasher>(
     porep_config: PoRepConfig,
     comm_r_in: Commitment,
     comm_d_in: Commitment,
@@ -523,12 +84,67 @@ pub fn verify_seal(
     ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
     ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
 
+    if MOCK_PROOFS || is_mock_proof(proof_vec) {
+        let expected = mock::seal_proof(
+            porep_config,
+            &comm_r_in,
+            &comm_d_in,
+            &prover_id,
+            sector_id,
+            &ticket,
+            &seed,
+        );
+
+        return mock::verify(&expected, proof_vec);
+    }
+
+    match tree_r_last_arity(PaddedBytesAmount::from(porep_config))? {
+        QUAD_ARITY => verify_seal_with_arity::<H, typenum::U4>(
+            porep_config,
+            comm_r_in,
+            comm_d_in,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            proof_vec,
+        ),
+        OCT_ARITY => verify_seal_with_arity::<H, typenum::U8>(
+            porep_config,
+            comm_r_in,
+            comm_d_in,
+            prover_id,
+            sector_id,
+            ticket,
+            seed,
+            proof_vec,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_seal_with_arity<H, U>(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let sector_bytes = PaddedBytesAmount::from(porep_config);
     let comm_r = as_safe_commitment(&comm_r_in, "comm_r")?;
     let comm_d = as_safe_commitment(&comm_d_in, "comm_d")?;
 
-    let replica_id =
-        generate_replica_id::<DefaultTreeHasher, _>(&prover_id, sector_id.into(), &ticket, comm_d);
+    let replica_id = generate_replica_id::<H, _>(&prover_id, sector_id.into(), &ticket, comm_d);
 
     let compound_setup_params = compound_proof::SetupParams {
         vanilla_params: setup_params(
@@ -541,33 +157,31 @@ pub fn verify_seal(
 
     let compound_public_params: compound_proof::PublicParams<
         '_,
-        StackedDrg<'_, DefaultTreeHasher, DefaultPieceHasher>,
+        StackedDrg<'_, H, DefaultPieceHasher, U>,
     > = StackedCompound::setup(&compound_setup_params)?;
 
-    let public_inputs = stacked::PublicInputs::<
-        <DefaultTreeHasher as Hasher>::Domain,
-        <DefaultPieceHasher as Hasher>::Domain,
-    > {
-        replica_id,
-        tau: Some(Tau { comm_r, comm_d }),
-        seed,
-        k: None,
-    };
+    let public_inputs =
+        stacked::PublicInputs::<<H as Hasher>::Domain, <DefaultPieceHasher as Hasher>::Domain> {
+            replica_id,
+            tau: Some(Tau { comm_r, comm_d }),
+            seed,
+            k: None,
+        };
 
-    let verifying_key = get_stacked_verifying_key(porep_config)?;
+    let verifying_key = get_stacked_verifying_key::<H, U>(porep_config)?;
 
     info!(
         "got verifying key ({}) while verifying seal",
         u64::from(sector_bytes)
     );
 
-    let proof = MultiProof::new_from_reader(
-        Some(usize::from(PoRepProofPartitions::from(porep_config))),
+    let proof = MultiProof::new_from_envelope_reader(
+        &porep_config.get_proof_header::<H>()?,
         proof_vec,
         &verifying_key,
     )?;
 
-    StackedCompound::verify(
+    StackedCompound::<H, DefaultPieceHasher, U>::verify(
         &compound_public_params,
         &public_inputs,
         &proof,
@@ -576,7 +190,7 @@ pub fn verify_seal(
                 .read()
                 .unwrap()
                 .get(&u64::from(SectorSize::from(porep_config)))
-                .expect("unknown sector size") as usize,
+                .context("unknown sector size")? as usize,
         },
     )
     .map_err(Into::into)
@@ -595,7 +209,33 @@ pub fn verify_seal(
 /// * `[seeds]` - list of seeds used to derive the porep challenges.
 /// * `[proof_vecs]` - list of porep circuit proofs serialized into a vector of bytes.
 #[allow(clippy::too_many_arguments)]
-pub fn verify_batch_seal(
+pub fn verify_batch_seal<H: 'static + Hasher>(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    prover_ids: &[ProverId],
+    sector_ids: &[SectorId],
+    tickets: &[Ticket],
+    seeds: &[Ticket],
+    proof_vecs: &[&[u8]],
+) -> Result<bool> {
+    let _span = trace::span("verify_batch_seal").arg("sectors", comm_r_ins.len());
+    metrics::measure_verification("seal", || {
+        verify_batch_seal_inner::<H>(
+            porep_config,
+            comm_r_ins,
+            comm_d_ins,
+            prover_ids,
+            sector_ids,
+            tickets,
+            seeds,
+            proof_vecs,
+        )
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_batch_seal_inner<H: 'static + Hasher>(
     porep_config: PoRepConfig,
     comm_r_ins: &[Commitment],
     comm_d_ins: &[Commitment],
@@ -628,14 +268,78 @@ pub fn verify_batch_seal(
         );
     }
 
+    if MOCK_PROOFS || proof_vecs.iter().any(|proof| is_mock_proof(proof)) {
+        for i in 0..l {
+            let expected = mock::seal_proof(
+                porep_config,
+                &comm_r_ins[i],
+                &comm_d_ins[i],
+                &prover_ids[i],
+                sector_ids[i],
+                &tickets[i],
+                &seeds[i],
+            );
+            if !mock::verify(&expected, proof_vecs[i])? {
+                return Ok(false);
+            }
+        }
+
+        return Ok(true);
+    }
+
+    match tree_r_last_arity(PaddedBytesAmount::from(porep_config))? {
+        QUAD_ARITY => verify_batch_seal_with_arity::<H, typenum::U4>(
+            porep_config,
+            comm_r_ins,
+            comm_d_ins,
+            prover_ids,
+            sector_ids,
+            tickets,
+            seeds,
+            proof_vecs,
+        ),
+        OCT_ARITY => verify_batch_seal_with_arity::<H, typenum::U8>(
+            porep_config,
+            comm_r_ins,
+            comm_d_ins,
+            prover_ids,
+            sector_ids,
+            tickets,
+            seeds,
+            proof_vecs,
+        ),
+        arity => bail!("unsupported tree_r_last arity: {}", arity),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify_batch_seal_with_arity<H, U>(
+    porep_config: PoRepConfig,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    prover_ids: &[ProverId],
+    sector_ids: &[SectorId],
+    tickets: &[Ticket],
+    seeds: &[Ticket],
+    proof_vecs: &[&[u8]],
+) -> Result<bool>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let l = comm_r_ins.len();
     let sector_bytes = PaddedBytesAmount::from(porep_config);
 
-    let verifying_key = get_stacked_verifying_key(porep_config)?;
+    let verifying_key = get_stacked_verifying_key::<H, U>(porep_config)?;
     info!(
         "got verifying key ({}) while verifying seal",
         u64::from(sector_bytes)
     );
 
+    let proof_header = porep_config.get_proof_header::<H>()?;
+
     let compound_setup_params = compound_proof::SetupParams {
         vanilla_params: setup_params(
             PaddedBytesAmount::from(porep_config),
@@ -647,7 +351,7 @@ pub fn verify_batch_seal(
 
     let compound_public_params: compound_proof::PublicParams<
         '_,
-        StackedDrg<'_, DefaultTreeHasher, DefaultPieceHasher>,
+        StackedDrg<'_, H, DefaultPieceHasher, U>,
     > = StackedCompound::setup(&compound_setup_params)?;
 
     let mut public_inputs = Vec::with_capacity(l);
@@ -657,15 +361,11 @@ pub fn verify_batch_seal(
         let comm_r = as_safe_commitment(&comm_r_ins[i], "comm_r")?;
         let comm_d = as_safe_commitment(&comm_d_ins[i], "comm_d")?;
 
-        let replica_id = generate_replica_id::<DefaultTreeHasher, _>(
-            &prover_ids[i],
-            sector_ids[i].into(),
-            &tickets[i],
-            comm_d,
-        );
+        let replica_id =
+            generate_replica_id::<H, _>(&prover_ids[i], sector_ids[i].into(), &tickets[i], comm_d);
 
         public_inputs.push(stacked::PublicInputs::<
-            <DefaultTreeHasher as Hasher>::Domain,
+            <H as Hasher>::Domain,
             <DefaultPieceHasher as Hasher>::Domain,
         > {
             replica_id,
@@ -673,14 +373,14 @@ pub fn verify_batch_seal(
             seed: seeds[i],
             k: None,
         });
-        proofs.push(MultiProof::new_from_reader(
-            Some(usize::from(PoRepProofPartitions::from(porep_config))),
+        proofs.push(MultiProof::new_from_envelope_reader(
+            &proof_header,
             proof_vecs[i],
             &verifying_key,
         )?);
     }
 
-    StackedCompound::batch_verify(
+    StackedCompound::<H, DefaultPieceHasher, U>::batch_verify(
         &compound_public_params,
         &public_inputs,
         &proofs,
@@ -689,8 +389,96 @@ pub fn verify_batch_seal(
                 .read()
                 .unwrap()
                 .get(&u64::from(SectorSize::from(porep_config)))
-                .expect("unknown sector size") as usize,
+                .context("unknown sector size")? as usize,
         },
     )
     .map_err(Into::into)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use storage_proofs::fr32::bytes_into_fr;
+
+    use crate::constants::{DefaultTreeHasher, POREP_PARTITIONS, SECTOR_SIZE_2_KIB};
+
+    #[test]
+    fn test_verify_seal_fr32_validation() {
+        let convertible_to_fr_bytes = [0; 32];
+        let out = bytes_into_fr::<Bls12>(&convertible_to_fr_bytes);
+        assert!(out.is_ok(), "tripwire");
+
+        let not_convertible_to_fr_bytes = [255; 32];
+        let out = bytes_into_fr::<Bls12>(&not_convertible_to_fr_bytes);
+        assert!(out.is_err(), "tripwire");
+
+        {
+            let result = verify_seal::<DefaultTreeHasher>(
+                PoRepConfig {
+                    sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+                    partitions: PoRepProofPartitions(
+                        *POREP_PARTITIONS
+                            .read()
+                            .unwrap()
+                            .get(&SECTOR_SIZE_2_KIB)
+                            .unwrap(),
+                    ),
+                },
+                not_convertible_to_fr_bytes,
+                convertible_to_fr_bytes,
+                [0; 32],
+                SectorId::from(0),
+                [0; 32],
+                [0; 32],
+                &[],
+            );
+
+            if let Err(err) = result {
+                let needle = "Invalid all zero commitment";
+                let haystack = format!("{}", err);
+
+                assert!(
+                    haystack.contains(needle),
+                    format!("\"{}\" did not contain \"{}\"", haystack, needle)
+                );
+            } else {
+                panic!("should have failed comm_r to Fr32 conversion");
+            }
+        }
+
+        {
+            let result = verify_seal::<DefaultTreeHasher>(
+                PoRepConfig {
+                    sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+                    partitions: PoRepProofPartitions(
+                        *POREP_PARTITIONS
+                            .read()
+                            .unwrap()
+                            .get(&SECTOR_SIZE_2_KIB)
+                            .unwrap(),
+                    ),
+                },
+                convertible_to_fr_bytes,
+                not_convertible_to_fr_bytes,
+                [0; 32],
+                SectorId::from(0),
+                [0; 32],
+                [0; 32],
+                &[],
+            );
+
+            if let Err(err) = result {
+                let needle = "Invalid all zero commitment";
+                let haystack = format!("{}", err);
+
+                assert!(
+                    haystack.contains(needle),
+                    format!("\"{}\" did not contain \"{}\"", haystack, needle)
+                );
+            } else {
+                panic!("should have failed comm_d to Fr32 conversion");
+            }
+        }
+    }
+}
@@ -1,10 +1,17 @@
 use anyhow::{Context, Result};
 use paired::bls12_381::Bls12;
+#[cfg(any(test, feature = "prover"))]
 use paired::Engine;
-use storage_proofs::fr32::{bytes_into_fr, fr_into_bytes};
+use serde::de::DeserializeOwned;
+use storage_proofs::fr32::bytes_into_fr;
+#[cfg(any(test, feature = "prover"))]
+use storage_proofs::fr32::fr_into_bytes;
 use storage_proofs::hasher::Domain;
 
-use crate::types::{Commitment, SectorSize};
+#[cfg(any(test, feature = "prover"))]
+use crate::types::Commitment;
+#[cfg(feature = "prover")]
+use crate::types::SectorSize;
 
 pub(crate) fn as_safe_commitment<H: Domain, T: AsRef<str>>(
     comm: &[u8; 32],
@@ -15,6 +22,7 @@ pub(crate) fn as_safe_commitment<H: Domain, T: AsRef<str>>(
         .with_context(|| format!("Invalid commitment ({})", commitment_name.as_ref(),))
 }
 
+#[cfg(any(test, feature = "prover"))]
 pub(crate) fn commitment_from_fr<E: Engine>(fr: E::Fr) -> Commitment {
     let mut commitment = [0; 32];
     for (i, b) in fr_into_bytes::<E>(&fr).iter().enumerate() {
@@ -23,7 +31,38 @@ pub(crate) fn commitment_from_fr<E: Engine>(fr: E::Fr) -> Commitment {
     commitment
 }
 
+#[cfg(feature = "prover")]
 pub(crate) fn get_tree_size<D: Domain>(sector_size: SectorSize, arity: usize) -> usize {
     let leafs = u64::from(sector_size) as usize / D::byte_len();
     merkletree::merkle::get_merkle_tree_len(leafs, arity)
 }
+
+/// Deserializes bincode encoded `bytes`, such as the contents of `p_aux` and `t_aux`.
+///
+/// Reads are limited to `bytes.len()`, so that a corrupted length prefix fails instead of
+/// allocating up to the size it claims.
+pub fn deserialize_aux<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    bincode::config()
+        .limit(bytes.len() as u64)
+        .deserialize(bytes)
+        .context("invalid bincode encoding")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_aux_bounded() {
+        let bytes = bincode::serialize(&("p_aux".to_string(), 7u64)).unwrap();
+        let (name, value): (String, u64) = deserialize_aux(&bytes).unwrap();
+        assert_eq!((name.as_str(), value), ("p_aux", 7));
+
+        // A string claiming u64::MAX bytes fails instead of trying to allocate them.
+        let mut huge = bytes.clone();
+        huge[..8].copy_from_slice(&u64::max_value().to_le_bytes());
+        assert!(deserialize_aux::<(String, u64)>(&huge).is_err());
+
+        assert!(deserialize_aux::<(String, u64)>(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
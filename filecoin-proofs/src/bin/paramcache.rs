@@ -1,15 +1,19 @@
 use clap::{values_t, App, Arg};
 use log::info;
-use paired::bls12_381::Bls12;
+use paired::bls12_381::{Bls12, Fr};
 
 use filecoin_proofs::constants::*;
-use filecoin_proofs::parameters::{post_public_params, public_params};
+use filecoin_proofs::parameters::{post_public_params, public_params, tree_r_last_arity};
 use filecoin_proofs::types::*;
 use std::collections::HashSet;
 use storage_proofs::circuit::election_post::{ElectionPoStCircuit, ElectionPoStCompound};
 use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::CompoundProof;
 use storage_proofs::election_post::ElectionPoSt;
+use storage_proofs::hasher::{
+    Blake2sHasher, Hasher, PedersenHasher, PoseidonArity, PoseidonEngine, PoseidonHasher,
+    Sha256Hasher,
+};
 use storage_proofs::parameter_cache::CacheableParameters;
 use storage_proofs::stacked::StackedDrg;
 
@@ -20,58 +24,64 @@ const PUBLISHED_SECTOR_SIZES: [u64; 4] = [
     SECTOR_SIZE_32_GIB,
 ];
 
-fn cache_porep_params(porep_config: PoRepConfig) {
+fn cache_porep_params<H, U>(porep_config: PoRepConfig)
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let n = u64::from(PaddedBytesAmount::from(porep_config));
     info!(
         "begin PoRep parameter-cache check/populate routine for {}-byte sectors",
         n
     );
 
-    let public_params = public_params(
+    let public_params = public_params::<H>(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )
     .unwrap();
 
     {
-        let circuit = <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        let circuit = <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
             _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            StackedDrg<H, DefaultPieceHasher, U>,
             _,
         >>::blank_circuit(&public_params);
-        let _ = StackedCompound::<DefaultTreeHasher, DefaultPieceHasher>::get_param_metadata(
+        let _ = StackedCompound::<H, DefaultPieceHasher, U>::get_param_metadata(
             circuit,
             &public_params,
         );
     }
     {
-        let circuit = <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        let circuit = <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
             _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            StackedDrg<H, DefaultPieceHasher, U>,
             _,
         >>::blank_circuit(&public_params);
-        StackedCompound::<DefaultTreeHasher, DefaultPieceHasher>::get_groth_params(
-            circuit,
-            &public_params,
-        )
-        .expect("failed to get groth params");
+        StackedCompound::<H, DefaultPieceHasher, U>::get_groth_params(circuit, &public_params)
+            .expect("failed to get groth params");
     }
     {
-        let circuit = <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        let circuit = <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
             _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            StackedDrg<H, DefaultPieceHasher, U>,
             _,
         >>::blank_circuit(&public_params);
 
-        StackedCompound::<DefaultTreeHasher, DefaultPieceHasher>::get_verifying_key(
-            circuit,
-            &public_params,
-        )
-        .expect("failed to get verifying key");
+        StackedCompound::<H, DefaultPieceHasher, U>::get_verifying_key(circuit, &public_params)
+            .expect("failed to get verifying key");
     }
 }
 
-fn cache_post_params(post_config: PoStConfig) {
+fn cache_post_params<H, U>(post_config: PoStConfig)
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let n = u64::from(PaddedBytesAmount::from(post_config));
     info!(
         "begin PoSt parameter-cache check/populate routine for {}-byte sectors",
@@ -81,44 +91,57 @@ fn cache_post_params(post_config: PoStConfig) {
     let post_public_params = post_public_params(post_config).unwrap();
 
     {
-        let post_circuit: ElectionPoStCircuit<Bls12, DefaultTreeHasher> =
-            <ElectionPoStCompound<DefaultTreeHasher> as CompoundProof<
+        let post_circuit: ElectionPoStCircuit<Bls12, H, U> =
+            <ElectionPoStCompound<H, U> as CompoundProof<
                 Bls12,
-                ElectionPoSt<DefaultTreeHasher>,
-                ElectionPoStCircuit<Bls12, DefaultTreeHasher>,
+                ElectionPoSt<H, U>,
+                ElectionPoStCircuit<Bls12, H, U>,
             >>::blank_circuit(&post_public_params);
-        let _ = <ElectionPoStCompound<DefaultTreeHasher>>::get_param_metadata(
-            post_circuit,
-            &post_public_params,
-        )
-        .expect("failed to get metadata");
+        let _ = <ElectionPoStCompound<H, U>>::get_param_metadata(post_circuit, &post_public_params)
+            .expect("failed to get metadata");
     }
     {
-        let post_circuit: ElectionPoStCircuit<Bls12, DefaultTreeHasher> =
-            <ElectionPoStCompound<DefaultTreeHasher> as CompoundProof<
+        let post_circuit: ElectionPoStCircuit<Bls12, H, U> =
+            <ElectionPoStCompound<H, U> as CompoundProof<
                 Bls12,
-                ElectionPoSt<DefaultTreeHasher>,
-                ElectionPoStCircuit<Bls12, DefaultTreeHasher>,
+                ElectionPoSt<H, U>,
+                ElectionPoStCircuit<Bls12, H, U>,
             >>::blank_circuit(&post_public_params);
-        <ElectionPoStCompound<DefaultTreeHasher>>::get_groth_params(
-            post_circuit,
-            &post_public_params,
-        )
-        .expect("failed to get groth params");
+        <ElectionPoStCompound<H, U>>::get_groth_params(post_circuit, &post_public_params)
+            .expect("failed to get groth params");
     }
     {
-        let post_circuit: ElectionPoStCircuit<Bls12, DefaultTreeHasher> =
-            <ElectionPoStCompound<DefaultTreeHasher> as CompoundProof<
+        let post_circuit: ElectionPoStCircuit<Bls12, H, U> =
+            <ElectionPoStCompound<H, U> as CompoundProof<
                 Bls12,
-                ElectionPoSt<DefaultTreeHasher>,
-                ElectionPoStCircuit<Bls12, DefaultTreeHasher>,
+                ElectionPoSt<H, U>,
+                ElectionPoStCircuit<Bls12, H, U>,
             >>::blank_circuit(&post_public_params);
 
-        <ElectionPoStCompound<DefaultTreeHasher>>::get_verifying_key(
-            post_circuit,
-            &post_public_params,
-        )
-        .expect("failed to get verifying key");
+        <ElectionPoStCompound<H, U>>::get_verifying_key(post_circuit, &post_public_params)
+            .expect("failed to get verifying key");
+    }
+}
+
+fn cache_params<H: 'static + Hasher>(
+    porep_config: PoRepConfig,
+    post_config: PoStConfig,
+    only_election_post: bool,
+) {
+    match tree_r_last_arity(PaddedBytesAmount::from(porep_config)).expect("unknown sector size") {
+        QUAD_ARITY => {
+            cache_post_params::<H, typenum::U4>(post_config);
+            if !only_election_post {
+                cache_porep_params::<H, typenum::U4>(porep_config);
+            }
+        }
+        OCT_ARITY => {
+            cache_post_params::<H, typenum::U8>(post_config);
+            if !only_election_post {
+                cache_porep_params::<H, typenum::U8>(porep_config);
+            }
+        }
+        arity => panic!("unsupported tree_r_last arity: {}", arity),
     }
 }
 
@@ -144,6 +167,14 @@ pub fn main() {
                 .long("only-election-post")
                 .help("Only generate parameters for election-post")
         )
+        .arg(
+            Arg::with_name("tree-hasher")
+                .long("tree-hasher")
+                .takes_value(true)
+                .possible_values(&["poseidon", "pedersen", "sha256", "blake2s"])
+                .default_value("poseidon")
+                .help("The hasher used to build tree_r_last")
+        )
         .get_matches();
 
     let sizes: HashSet<u64> = if matches.is_present("params-for-sector-sizes") {
@@ -156,26 +187,38 @@ pub fn main() {
     };
 
     let only_election_post = matches.is_present("only-election-post");
+    let tree_hasher = matches.value_of("tree-hasher").unwrap();
 
     for sector_size in sizes {
-        cache_post_params(PoStConfig {
+        let post_config = PoStConfig {
             sector_size: SectorSize(sector_size),
             challenge_count: POST_CHALLENGE_COUNT,
             challenged_nodes: POST_CHALLENGED_NODES,
             priority: true,
-        });
-
-        if !only_election_post {
-            cache_porep_params(PoRepConfig {
-                sector_size: SectorSize(sector_size),
-                partitions: PoRepProofPartitions(
-                    *POREP_PARTITIONS
-                        .read()
-                        .unwrap()
-                        .get(&sector_size)
-                        .expect("missing sector size"),
-                ),
-            });
+        };
+        let porep_config = PoRepConfig {
+            sector_size: SectorSize(sector_size),
+            partitions: PoRepProofPartitions(
+                *POREP_PARTITIONS
+                    .read()
+                    .unwrap()
+                    .get(&sector_size)
+                    .expect("missing sector size"),
+            ),
+        };
+
+        match tree_hasher {
+            "poseidon" => {
+                cache_params::<PoseidonHasher>(porep_config, post_config, only_election_post)
+            }
+            "pedersen" => {
+                cache_params::<PedersenHasher>(porep_config, post_config, only_election_post)
+            }
+            "sha256" => cache_params::<Sha256Hasher>(porep_config, post_config, only_election_post),
+            "blake2s" => {
+                cache_params::<Blake2sHasher>(porep_config, post_config, only_election_post)
+            }
+            other => panic!("unsupported tree hasher: {}", other),
         }
     }
 }
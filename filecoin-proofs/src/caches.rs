@@ -1,169 +1,557 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
 
+#[cfg(feature = "prover")]
+use anyhow::bail;
 use anyhow::Result;
 use bellperson::groth16;
 use lazy_static::lazy_static;
-use log::info;
-use paired::bls12_381::Bls12;
+use log::{info, warn};
+use paired::bls12_381::{Bls12, Fr};
 use storage_proofs::circuit::election_post::ElectionPoStCircuit;
 use storage_proofs::circuit::election_post::ElectionPoStCompound;
 use storage_proofs::circuit::stacked::StackedCompound;
 use storage_proofs::compound_proof::CompoundProof;
 use storage_proofs::election_post::ElectionPoSt;
+use storage_proofs::hasher::{Hasher, PoseidonArity, PoseidonEngine};
+use storage_proofs::metrics;
+use storage_proofs::parameter_cache::parameter_cache_dir;
+use storage_proofs::settings::{self, Settings};
 use storage_proofs::stacked::StackedDrg;
 
-use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::constants::DefaultPieceHasher;
+#[cfg(feature = "prover")]
+use crate::parameters::tree_r_last_arity;
 use crate::parameters::{post_public_params, public_params};
 use crate::types::*;
 
+#[cfg(feature = "prover")]
 type Bls12GrothParams = groth16::MappedParameters<Bls12>;
 pub type Bls12VerifyingKey = groth16::VerifyingKey<Bls12>;
 
-type Cache<G> = HashMap<String, Arc<G>>;
-type GrothMemCache = Cache<Bls12GrothParams>;
-type VerifyingKeyMemCache = Cache<Bls12VerifyingKey>;
+#[cfg(feature = "prover")]
+type GrothMemCache = MemoryCache<Bls12GrothParams>;
+type VerifyingKeyMemCache = MemoryCache<Bls12VerifyingKey>;
 
 lazy_static! {
-    static ref GROTH_PARAM_MEMORY_CACHE: Mutex<GrothMemCache> = Default::default();
-    static ref VERIFYING_KEY_MEMORY_CACHE: Mutex<VerifyingKeyMemCache> = Default::default();
+    #[cfg(feature = "prover")]
+    static ref GROTH_PARAM_MEMORY_CACHE: Mutex<GrothMemCache> = Mutex::new(MemoryCache::new(
+        "groth_params",
+        Settings::groth_param_cache_max_bytes,
+    ));
+    static ref VERIFYING_KEY_MEMORY_CACHE: Mutex<VerifyingKeyMemCache> = Mutex::new(
+        MemoryCache::new("verifying_key", Settings::verifying_key_cache_max_bytes)
+    );
 }
 
+/// The circuit whose parameters to preload or evict, see `preload_params`.
+#[derive(Clone, Copy, Debug)]
+pub enum ParamsConfig {
+    PoRep(PoRepConfig),
+    PoSt(PoStConfig),
+}
+
+impl From<PoRepConfig> for ParamsConfig {
+    fn from(config: PoRepConfig) -> Self {
+        ParamsConfig::PoRep(config)
+    }
+}
+
+impl From<PoStConfig> for ParamsConfig {
+    fn from(config: PoStConfig) -> Self {
+        ParamsConfig::PoSt(config)
+    }
+}
+
+/// The usage of a parameter memory cache, see `param_cache_stats`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    /// `groth_params` or `verifying_key`, as in the `metrics`.
+    pub name: &'static str,
+    pub entries: usize,
+    /// The size of the cached entries, which is that of their files in the parameter cache, or
+    /// an estimate if a file could not be read.
+    pub bytes: u64,
+    /// The memory budget, or 0 if unbounded.
+    pub max_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct CacheEntry<G> {
+    value: Arc<G>,
+    bytes: u64,
+    last_used: u64,
+}
+
+/// Parameters kept in memory by identifier. Once the entries exceed the budget `max_bytes` of
+/// the process-wide settings, the least recently used are evicted, skipping those still in use:
+/// evicting them would not free their memory, so the cache may be over budget until they are
+/// dropped.
+pub struct MemoryCache<G> {
+    name: &'static str,
+    entries: HashMap<String, CacheEntry<G>>,
+    max_bytes: fn(&Settings) -> u64,
+    bytes: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<G> MemoryCache<G> {
+    fn new(name: &'static str, max_bytes: fn(&Settings) -> u64) -> Self {
+        MemoryCache {
+            name,
+            entries: HashMap::new(),
+            max_bytes,
+            bytes: 0,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    fn get(&mut self, identifier: &str) -> Option<Arc<G>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let value = self.entries.get_mut(identifier).map(|entry| {
+            entry.last_used = clock;
+            entry.value.clone()
+        });
+
+        if value.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        // Entries which were in use at the last insertion may have been released since.
+        self.evict_to_budget();
+
+        value
+    }
+
+    fn insert(&mut self, identifier: String, value: Arc<G>, bytes: u64) {
+        self.clock += 1;
+        let entry = CacheEntry {
+            value,
+            bytes,
+            last_used: self.clock,
+        };
+        if let Some(previous) = self.entries.insert(identifier, entry) {
+            self.bytes -= previous.bytes;
+        }
+        self.bytes += bytes;
+
+        self.evict_to_budget();
+    }
+
+    fn remove(&mut self, identifier: &str) -> bool {
+        match self.entries.remove(identifier) {
+            Some(entry) => {
+                info!(
+                    "evicting {} from the {} memory cache",
+                    identifier, self.name
+                );
+                self.bytes -= entry.bytes;
+                self.evictions += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn evict_to_budget(&mut self) {
+        let max_bytes = (self.max_bytes)(&settings::global());
+        while max_bytes > 0 && self.bytes > max_bytes {
+            // Entries are only handed out under the cache lock, so a count of one cannot race.
+            let lru = self
+                .entries
+                .iter()
+                .filter(|(_, entry)| Arc::strong_count(&entry.value) == 1)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(identifier, _)| identifier.clone());
+
+            match lru {
+                Some(identifier) => {
+                    self.remove(&identifier);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name,
+            entries: self.entries.len(),
+            bytes: self.bytes,
+            max_bytes: (self.max_bytes)(&settings::global()),
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+}
+
+/// Returns the entry for `identifier`, generating it and its size in bytes on a miss.
 pub fn cache_lookup<F, G>(
-    cache_ref: &Mutex<Cache<G>>,
+    cache_ref: &Mutex<MemoryCache<G>>,
     identifier: String,
     generator: F,
 ) -> Result<Arc<G>>
 where
-    F: FnOnce() -> Result<G>,
+    F: FnOnce() -> Result<(G, u64)>,
     G: Send + Sync,
 {
     info!("trying parameters memory cache for: {}", &identifier);
     {
-        let cache = (*cache_ref).lock().unwrap();
+        let mut cache = (*cache_ref).lock().unwrap();
 
         if let Some(entry) = cache.get(&identifier) {
             info!("found params in memory cache for {}", &identifier);
-            return Ok(entry.clone());
+            metrics::record_cache_lookup(cache.name, true);
+            return Ok(entry);
         }
+        metrics::record_cache_lookup(cache.name, false);
     }
 
     info!("no params in memory cache for {}", &identifier);
 
-    let new_entry = Arc::new(generator()?);
+    let (new_entry, bytes) = generator()?;
+    let new_entry = Arc::new(new_entry);
     let res = new_entry.clone();
     {
         let cache = &mut (*cache_ref).lock().unwrap();
-        cache.insert(identifier, new_entry);
+        cache.insert(identifier, new_entry, bytes);
     }
 
     Ok(res)
 }
 
+/// Uncompressed sizes of the curve points of a verifying key file.
+const G1_BYTES: u64 = 96;
+const G2_BYTES: u64 = 192;
+
+/// Returns the size of the file at `path`, or `estimate` if it can't be read. Sizes only count
+/// against the memory budgets, which an estimate keeps working.
+fn file_size_or(path: &Path, estimate: u64) -> u64 {
+    match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) => {
+            warn!(
+                "could not stat {:?}, estimating its size as {} bytes: {}",
+                path, estimate, err
+            );
+            estimate
+        }
+    }
+}
+
+/// The size of `vk` in its file: the alpha, beta, gamma and delta points, and the `ic` points
+/// after their count.
+fn verifying_key_bytes(vk: &Bls12VerifyingKey) -> u64 {
+    3 * G1_BYTES + 3 * G2_BYTES + 4 + vk.ic.len() as u64 * G1_BYTES
+}
+
+#[cfg(feature = "prover")]
 #[inline]
-pub fn lookup_groth_params<F>(identifier: String, generator: F) -> Result<Arc<Bls12GrothParams>>
+pub fn lookup_groth_params<F>(
+    identifier: String,
+    params_path: PathBuf,
+    generator: F,
+) -> Result<Arc<Bls12GrothParams>>
 where
     F: FnOnce() -> Result<Bls12GrothParams>,
 {
-    cache_lookup(&*GROTH_PARAM_MEMORY_CACHE, identifier, generator)
+    // The parameters are mapped from `params_path`, so its size bounds their resident memory.
+    cache_lookup(&*GROTH_PARAM_MEMORY_CACHE, identifier, || {
+        let params = generator()?;
+        let bytes = file_size_or(&params_path, params.params.len() as u64);
+        Ok((params, bytes))
+    })
 }
 
 #[inline]
-pub fn lookup_verifying_key<F>(identifier: String, generator: F) -> Result<Arc<Bls12VerifyingKey>>
+pub fn lookup_verifying_key<F>(
+    identifier: String,
+    vk_path: PathBuf,
+    generator: F,
+) -> Result<Arc<Bls12VerifyingKey>>
 where
     F: FnOnce() -> Result<Bls12VerifyingKey>,
 {
     let vk_identifier = format!("{}-verifying-key", &identifier);
-    cache_lookup(&*VERIFYING_KEY_MEMORY_CACHE, vk_identifier, generator)
+    cache_lookup(&*VERIFYING_KEY_MEMORY_CACHE, vk_identifier, || {
+        let vk = generator()?;
+        let bytes = file_size_or(&vk_path, verifying_key_bytes(&vk));
+        Ok((vk, bytes))
+    })
 }
 
-pub fn get_stacked_params(porep_config: PoRepConfig) -> Result<Arc<Bls12GrothParams>> {
-    let public_params = public_params(
+// The identifiers include the parameter cache the parameters are read from, which the settings
+// of the calling thread choose.
+
+fn stacked_identifier<H: 'static + Hasher>(porep_config: PoRepConfig, arity: usize) -> String {
+    format!(
+        "STACKED[{}-{}-{}-{}]@{}",
+        usize::from(PaddedBytesAmount::from(porep_config)),
+        H::name(),
+        DefaultPieceHasher::name(),
+        arity,
+        parameter_cache_dir().display()
+    )
+}
+
+fn post_identifier<H: 'static + Hasher>(post_config: PoStConfig, arity: usize) -> String {
+    format!(
+        "POST[{}-{}-{}]@{}",
+        usize::from(PaddedBytesAmount::from(post_config)),
+        H::name(),
+        arity,
+        parameter_cache_dir().display()
+    )
+}
+
+#[cfg(feature = "prover")]
+pub fn get_stacked_params<H, U>(porep_config: PoRepConfig) -> Result<Arc<Bls12GrothParams>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let public_params = public_params::<H>(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )?;
 
     let parameters_generator = || {
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
             _,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            StackedDrg<H, DefaultPieceHasher, U>,
             _,
         >>::groth_params(&public_params)
         .map_err(Into::into)
     };
 
     Ok(lookup_groth_params(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        stacked_identifier::<H>(porep_config, U::to_usize()),
+        porep_config.get_cache_params_path::<H>()?,
         parameters_generator,
     )?)
 }
 
-pub fn get_post_params(post_config: PoStConfig) -> Result<Arc<Bls12GrothParams>> {
+#[cfg(feature = "prover")]
+pub fn get_post_params<H, U>(post_config: PoStConfig) -> Result<Arc<Bls12GrothParams>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let post_public_params = post_public_params(post_config)?;
 
     let parameters_generator = || {
-        <ElectionPoStCompound<DefaultTreeHasher> as CompoundProof<
+        <ElectionPoStCompound<H, U> as CompoundProof<
             Bls12,
-            ElectionPoSt<DefaultTreeHasher>,
-            ElectionPoStCircuit<Bls12, DefaultTreeHasher>,
+            ElectionPoSt<H, U>,
+            ElectionPoStCircuit<Bls12, H, U>,
         >>::groth_params(&post_public_params)
         .map_err(Into::into)
     };
 
     Ok(lookup_groth_params(
-        format!(
-            "POST[{}]",
-            usize::from(PaddedBytesAmount::from(post_config))
-        ),
+        post_identifier::<H>(post_config, U::to_usize()),
+        post_config.get_cache_params_path::<H>()?,
         parameters_generator,
     )?)
 }
 
-pub fn get_stacked_verifying_key(porep_config: PoRepConfig) -> Result<Arc<Bls12VerifyingKey>> {
-    let public_params = public_params(
+pub fn get_stacked_verifying_key<H, U>(porep_config: PoRepConfig) -> Result<Arc<Bls12VerifyingKey>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    let public_params = public_params::<H>(
         PaddedBytesAmount::from(porep_config),
         usize::from(PoRepProofPartitions::from(porep_config)),
     )?;
 
     let vk_generator = || {
-        <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CompoundProof<
+        <StackedCompound<H, DefaultPieceHasher, U> as CompoundProof<
             Bls12,
-            StackedDrg<DefaultTreeHasher, DefaultPieceHasher>,
+            StackedDrg<H, DefaultPieceHasher, U>,
             _,
         >>::verifying_key(&public_params)
         .map_err(Into::into)
     };
 
     Ok(lookup_verifying_key(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        stacked_identifier::<H>(porep_config, U::to_usize()),
+        porep_config.get_cache_verifying_key_path::<H>()?,
         vk_generator,
     )?)
 }
 
-pub fn get_post_verifying_key(post_config: PoStConfig) -> Result<Arc<Bls12VerifyingKey>> {
+pub fn get_post_verifying_key<H, U>(post_config: PoStConfig) -> Result<Arc<Bls12VerifyingKey>>
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     let post_public_params = post_public_params(post_config)?;
 
     let vk_generator = || {
-        <ElectionPoStCompound<DefaultTreeHasher> as CompoundProof<
+        <ElectionPoStCompound<H, U> as CompoundProof<
             Bls12,
-            ElectionPoSt<DefaultTreeHasher>,
-            ElectionPoStCircuit<Bls12, DefaultTreeHasher>,
+            ElectionPoSt<H, U>,
+            ElectionPoStCircuit<Bls12, H, U>,
         >>::verifying_key(&post_public_params)
         .map_err(Into::into)
     };
 
     Ok(lookup_verifying_key(
-        format!(
-            "POST[{}]",
-            usize::from(PaddedBytesAmount::from(post_config))
-        ),
+        post_identifier::<H>(post_config, U::to_usize()),
+        post_config.get_cache_verifying_key_path::<H>()?,
         vk_generator,
     )?)
 }
+
+/// Loads the Groth parameters of each circuit in `configs` into memory, for a scheduler to pay
+/// for the loading before proving. Parameters beyond the memory budget of
+/// `Settings::groth_param_cache_max_bytes` evict those preloaded before them.
+#[cfg(feature = "prover")]
+pub fn preload_params<H: 'static + Hasher>(configs: &[ParamsConfig]) -> Result<()> {
+    for config in configs {
+        let arity = tree_r_last_arity(params_sector_bytes(*config))?;
+        match (*config, arity) {
+            (ParamsConfig::PoRep(c), QUAD_ARITY) => get_stacked_params::<H, typenum::U4>(c)?,
+            (ParamsConfig::PoRep(c), OCT_ARITY) => get_stacked_params::<H, typenum::U8>(c)?,
+            (ParamsConfig::PoSt(c), QUAD_ARITY) => get_post_params::<H, typenum::U4>(c)?,
+            (ParamsConfig::PoSt(c), OCT_ARITY) => get_post_params::<H, typenum::U8>(c)?,
+            (_, arity) => bail!("unsupported tree_r_last arity: {}", arity),
+        };
+    }
+
+    Ok(())
+}
+
+/// Drops the Groth parameters of the circuit for `config` from memory, and returns whether they
+/// were cached. Proofs still using them keep them until they finish.
+#[cfg(feature = "prover")]
+pub fn evict_params<H: 'static + Hasher>(config: ParamsConfig) -> Result<bool> {
+    let arity = tree_r_last_arity(params_sector_bytes(config))?;
+    let identifier = match config {
+        ParamsConfig::PoRep(c) => stacked_identifier::<H>(c, arity),
+        ParamsConfig::PoSt(c) => post_identifier::<H>(c, arity),
+    };
+
+    Ok(GROTH_PARAM_MEMORY_CACHE.lock().unwrap().remove(&identifier))
+}
+
+#[cfg(feature = "prover")]
+fn params_sector_bytes(config: ParamsConfig) -> PaddedBytesAmount {
+    match config {
+        ParamsConfig::PoRep(c) => PaddedBytesAmount::from(c),
+        ParamsConfig::PoSt(c) => PaddedBytesAmount::from(c),
+    }
+}
+
+/// Returns the usage of the parameter memory caches: the Groth parameters (in `prover` builds)
+/// and the verifying keys.
+pub fn param_cache_stats() -> Vec<CacheStats> {
+    let mut stats = Vec::new();
+    #[cfg(feature = "prover")]
+    stats.push(GROTH_PARAM_MEMORY_CACHE.lock().unwrap().stats());
+    stats.push(VERIFYING_KEY_MEMORY_CACHE.lock().unwrap().stats());
+
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(cache: &Mutex<MemoryCache<String>>, identifier: &str, bytes: u64) -> Arc<String> {
+        cache_lookup(cache, identifier.to_string(), || {
+            Ok((identifier.to_string(), bytes))
+        })
+        .unwrap()
+    }
+
+    fn cached(cache: &Mutex<MemoryCache<String>>) -> Vec<String> {
+        let mut identifiers: Vec<_> = cache.lock().unwrap().entries.keys().cloned().collect();
+        identifiers.sort();
+        identifiers
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let cache = Mutex::new(MemoryCache::new("test", |_| 100));
+
+        lookup(&cache, "a", 40);
+        lookup(&cache, "b", 40);
+        // Using `a` makes `b` the least recently used.
+        lookup(&cache, "a", 40);
+        lookup(&cache, "c", 40);
+        assert_eq!(cached(&cache), vec!["a", "c"]);
+
+        let stats = cache.lock().unwrap().stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.bytes, 80);
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 3, 1));
+    }
+
+    #[test]
+    fn test_memory_cache_keeps_entries_in_use() {
+        let cache = Mutex::new(MemoryCache::new("test", |_| 100));
+
+        let a = lookup(&cache, "a", 60);
+        // Entries are in use while they are inserted, so `b` is only evicted by the next
+        // lookup. `a` is in use, so `b` is evicted although it was used last.
+        lookup(&cache, "b", 60);
+        assert_eq!(cached(&cache), vec!["a", "b"]);
+        lookup(&cache, "a", 60);
+        assert_eq!(cached(&cache), vec!["a"]);
+
+        let _c = lookup(&cache, "c", 60);
+        // Neither is evictable, so the cache is over budget until `a` is dropped.
+        assert_eq!(cached(&cache), vec!["a", "c"]);
+        drop(a);
+        lookup(&cache, "c", 60);
+        assert_eq!(cached(&cache), vec!["c"]);
+
+        assert!(cache.lock().unwrap().remove("c"));
+        assert!(!cache.lock().unwrap().remove("c"));
+        assert_eq!(cache.lock().unwrap().stats().bytes, 0);
+    }
+
+    #[test]
+    fn test_file_size_falls_back_to_estimate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("v24-stacked.vk");
+        assert_eq!(file_size_or(&path, 42), 42);
+
+        std::fs::write(&path, [0; 10]).unwrap();
+        assert_eq!(file_size_or(&path, 42), 10);
+    }
+
+    #[test]
+    fn test_memory_cache_unbounded() {
+        let cache = Mutex::new(MemoryCache::new("test", |_| 0));
+
+        for identifier in &["a", "b", "c"] {
+            lookup(&cache, identifier, u64::from(u32::max_value()));
+        }
+        assert_eq!(cached(&cache).len(), 3);
+        assert_eq!(cache.lock().unwrap().stats().evictions, 0);
+    }
+}
@@ -56,6 +56,17 @@ lazy_static! {
         .copied()
         .collect()
     );
+    pub static ref TREE_R_LAST_ARITY: RwLock<HashMap<u64, usize>> = RwLock::new(
+        [
+            (SECTOR_SIZE_2_KIB, 4),
+            (SECTOR_SIZE_8_MIB, 4),
+            (SECTOR_SIZE_512_MIB, 4),
+            (SECTOR_SIZE_32_GIB, 4)
+        ]
+        .iter()
+        .copied()
+        .collect()
+    );
 }
 
 /// The size of a single snark proof.
@@ -70,11 +81,13 @@ pub const MINIMUM_RESERVED_BYTES_FOR_PIECE_IN_FULLY_ALIGNED_SECTOR: u64 =
 /// The minimum size a single piece must have before padding.
 pub const MIN_PIECE_SIZE: UnpaddedBytesAmount = UnpaddedBytesAmount(127);
 
-/// The hasher used for creating comm_d.
+/// The hasher used for creating comm_d. This is fixed, since piece commitments are computed
+/// with it before a sector is sealed.
 pub type DefaultPieceHasher = storage_proofs::hasher::Sha256Hasher;
 pub type DefaultPieceDomain = <DefaultPieceHasher as Hasher>::Domain;
 
-/// The default hasher for merkle trees currently in use.
+/// The default hasher for merkle trees currently in use. The seal, PoSt and unseal entry points
+/// are generic over the tree hasher; this is what callers use unless they pick another one.
 pub type DefaultTreeHasher = storage_proofs::hasher::PoseidonHasher;
 pub type DefaultTreeDomain = <DefaultTreeHasher as Hasher>::Domain;
 
@@ -649,6 +649,113 @@ pub fn clear_right_bits(byte: &mut u8, offset: usize) {
     *(byte) &= !((1 << offset) - 1)
 }
 
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Byte-aligned fast path.
+//
+// Groups of 4 elements are byte-aligned both in the raw data and in the padded
+// layout (see `PaddingMap#alignment`): every 127 raw bytes map to exactly 128
+// padded bytes. Whole groups can then be (un)padded with word-level shifts,
+// leaving the bit-level functions to handle only the unaligned edges.
+
+const FR32_GROUP_RAW_BYTES: usize = 127;
+const FR32_GROUP_PADDED_BYTES: usize = 128;
+
+// Position of each element of a group in the raw data, as the byte it starts
+// in and the bit-offset inside it (`254 * i` bits).
+const FR32_ELEMENT_OFFSETS: [(usize, u32); 4] = [(0, 0), (31, 6), (63, 4), (95, 2)];
+
+// Clears the 2 padding bits at the end of the last word of an element.
+const FR32_LAST_WORD_MASK: u64 = 0x3fff_ffff_ffff_ffff;
+
+fn is_fr32(padding_map: &PaddingMap) -> bool {
+    padding_map.data_bits == FR32_PADDING_MAP.data_bits
+        && padding_map.element_bits == FR32_PADDING_MAP.element_bits
+}
+
+#[inline]
+fn read_u64_le(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+// Pad a `source` of whole groups of raw data.
+fn pad_fr32_groups(source: &[u8]) -> Vec<u8> {
+    debug_assert_eq!(source.len() % FR32_GROUP_RAW_BYTES, 0);
+
+    let mut padded = vec![0u8; source.len() / FR32_GROUP_RAW_BYTES * FR32_GROUP_PADDED_BYTES];
+    for (raw, out) in source
+        .chunks_exact(FR32_GROUP_RAW_BYTES)
+        .zip(padded.chunks_exact_mut(FR32_GROUP_PADDED_BYTES))
+    {
+        pad_fr32_group(raw, out);
+    }
+
+    padded
+}
+
+#[inline]
+fn pad_fr32_group(raw: &[u8], padded: &mut [u8]) {
+    // Zero-extend the group so the last element can read a whole word (and
+    // the byte after it) without going out of bounds.
+    let mut input = [0u8; FR32_GROUP_RAW_BYTES + 9];
+    input[..FR32_GROUP_RAW_BYTES].copy_from_slice(raw);
+
+    for (element, &(offset, shift)) in padded.chunks_exact_mut(32).zip(&FR32_ELEMENT_OFFSETS) {
+        for (i, out) in element.chunks_exact_mut(8).enumerate() {
+            let start = offset + i * 8;
+            let mut word = read_u64_le(&input[start..]) >> shift;
+            if shift != 0 {
+                word |= u64::from(input[start + 8]) << (64 - shift);
+            }
+            if i == 3 {
+                word &= FR32_LAST_WORD_MASK;
+            }
+            out.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+}
+
+// Unpad a `source` of whole groups of padded data.
+fn unpad_fr32_groups(source: &[u8]) -> Vec<u8> {
+    debug_assert_eq!(source.len() % FR32_GROUP_PADDED_BYTES, 0);
+
+    let mut raw = vec![0u8; source.len() / FR32_GROUP_PADDED_BYTES * FR32_GROUP_RAW_BYTES];
+    for (padded, out) in source
+        .chunks_exact(FR32_GROUP_PADDED_BYTES)
+        .zip(raw.chunks_exact_mut(FR32_GROUP_RAW_BYTES))
+    {
+        unpad_fr32_group(padded, out);
+    }
+
+    raw
+}
+
+#[inline]
+fn unpad_fr32_group(padded: &[u8], raw: &mut [u8]) {
+    // Same extension as in `pad_fr32_group`, for the bits spilling over the
+    // last word written.
+    let mut output = [0u8; FR32_GROUP_RAW_BYTES + 9];
+
+    for (element, &(offset, shift)) in padded.chunks_exact(32).zip(&FR32_ELEMENT_OFFSETS) {
+        for (i, chunk) in element.chunks_exact(8).enumerate() {
+            let mut word = read_u64_le(chunk);
+            // Drop the padding bits, they are not guaranteed to be zero.
+            if i == 3 {
+                word &= FR32_LAST_WORD_MASK;
+            }
+            let start = offset + i * 8;
+            let current = read_u64_le(&output[start..]);
+            output[start..start + 8].copy_from_slice(&(current | (word << shift)).to_le_bytes());
+            if shift != 0 {
+                output[start + 8] |= (word >> (64 - shift)) as u8;
+            }
+        }
+    }
+
+    raw.copy_from_slice(&output[..FR32_GROUP_RAW_BYTES]);
+}
+
 // In order to optimize alignment in the common case of writing from an aligned
 // start, we should make the chunk a multiple of 127 (4 full elements, see
 // `PaddingMap#alignment`). N was hand-tuned to do reasonably well in the
@@ -707,6 +814,43 @@ need to handle the potential bit-level misalignments:
 // TODO: Change name, this is the real write padded function, the previous one
 // just partition data in chunks.
 fn write_padded_aux<W>(padding_map: &PaddingMap, source: &[u8], mut target: W) -> io::Result<usize>
+where
+    W: Read + Write + Seek,
+{
+    if !is_fr32(padding_map) {
+        return write_padded_bits(padding_map, source, target);
+    }
+
+    // Use the bit-level padding only to reach the next group boundary (if the
+    // `target` isn't already there), pad all the whole groups in `source` with
+    // the fast path and leave again the incomplete group at the end (if any) to
+    // the bit-level padding.
+    let (_, raw_data_bytes, _) = padding_map.target_offsets(&mut target)?;
+    let misalignment = raw_data_bytes as usize % FR32_GROUP_RAW_BYTES;
+    let head = if misalignment == 0 {
+        0
+    } else {
+        min(FR32_GROUP_RAW_BYTES - misalignment, source.len())
+    };
+    let groups_end = head + (source.len() - head) / FR32_GROUP_RAW_BYTES * FR32_GROUP_RAW_BYTES;
+
+    if head > 0 {
+        write_padded_bits(padding_map, &source[..head], &mut target)?;
+    }
+    if groups_end > head {
+        // The `target` is positioned at its end (either by `target_offsets` or the
+        // previous write).
+        target.write_all(&pad_fr32_groups(&source[head..groups_end]))?;
+    }
+    if groups_end < source.len() {
+        write_padded_bits(padding_map, &source[groups_end..], &mut target)?;
+    }
+
+    Ok(source.len())
+}
+
+// Bit-level padding, see `write_padded_aux`.
+fn write_padded_bits<W>(padding_map: &PaddingMap, source: &[u8], mut target: W) -> io::Result<usize>
 where
     W: Read + Write + Seek,
 {
@@ -898,6 +1042,65 @@ pub fn write_unpadded_aux<W: ?Sized>(
     write_pos: usize,
     max_write_size: usize,
 ) -> io::Result<usize>
+where
+    W: Write,
+{
+    if !is_fr32(padding_map) {
+        return write_unpadded_bits(padding_map, source, target, write_pos, max_write_size);
+    }
+
+    let mut written = 0;
+    let mut write_pos = write_pos;
+    let mut max_write_size = max_write_size;
+
+    // Unpad with bit precision up to the next group boundary.
+    let misalignment = write_pos % FR32_GROUP_RAW_BYTES;
+    if misalignment != 0 {
+        let head = min(FR32_GROUP_RAW_BYTES - misalignment, max_write_size);
+        let head_written = write_unpadded_bits(padding_map, source, target, write_pos, head)?;
+        written += head_written;
+        write_pos += head_written;
+        max_write_size -= head_written;
+
+        if head_written < head {
+            // We ran out of `source`.
+            return Ok(written);
+        }
+    }
+
+    // Unpad all the whole groups available (and requested) with the fast path.
+    let read_pos = write_pos / FR32_GROUP_RAW_BYTES * FR32_GROUP_PADDED_BYTES;
+    if read_pos < source.len() {
+        let groups = min(
+            max_write_size / FR32_GROUP_RAW_BYTES,
+            (source.len() - read_pos) / FR32_GROUP_PADDED_BYTES,
+        );
+        let raw_data =
+            unpad_fr32_groups(&source[read_pos..read_pos + groups * FR32_GROUP_PADDED_BYTES]);
+        target.write_all(&raw_data)?;
+        written += raw_data.len();
+        write_pos += raw_data.len();
+        max_write_size -= raw_data.len();
+    }
+
+    // Unpad the rest with bit precision (if there is still something to read).
+    if max_write_size > 0
+        && padding_map.transform_bit_offset(write_pos * 8, true) < source.len() * 8
+    {
+        written += write_unpadded_bits(padding_map, source, target, write_pos, max_write_size)?;
+    }
+
+    Ok(written)
+}
+
+// Bit-level unpadding, see `write_unpadded_aux`.
+fn write_unpadded_bits<W: ?Sized>(
+    padding_map: &PaddingMap,
+    source: &[u8],
+    target: &mut W,
+    write_pos: usize,
+    max_write_size: usize,
+) -> io::Result<usize>
 where
     W: Write,
 {
@@ -1006,16 +1209,236 @@ where
     Ok(raw_data.len())
 }
 
+/** Unpadding reader.
+
+Presents a `source` of Fr32 padded data as the byte-aligned raw data stream
+that generated it, supporting random access through `Seek`. All positions
+(and the stream length) are expressed in raw (unpadded) bytes, so a reader
+positioned with `seek(SeekFrom::Start(n))` will return the `n`-th byte of
+the original data. The padded `source` is never loaded in full, only the
+groups of elements covering the requested range are read.
+**/
+#[derive(Debug)]
+pub struct Fr32Reader<R> {
+    source: R,
+    /// Size of the padded `source`.
+    padded_len: u64,
+    /// Size of the raw data encoded in `source`.
+    raw_len: u64,
+    /// Position of the reader in the raw data stream.
+    raw_pos: u64,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read + Seek> Fr32Reader<R> {
+    pub fn new(mut source: R) -> io::Result<Self> {
+        let (padded_len, raw_len, _) = FR32_PADDING_MAP.target_offsets(&mut source)?;
+
+        Ok(Fr32Reader {
+            source,
+            padded_len,
+            raw_len,
+            raw_pos: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Size of the raw data encoded in the padded source.
+    pub fn unpadded_len(&self) -> u64 {
+        self.raw_len
+    }
+
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read + Seek> Read for Fr32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.raw_pos >= self.raw_len || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let len = min(
+            min(buf.len(), CHUNK_SIZE) as u64,
+            self.raw_len - self.raw_pos,
+        ) as usize;
+
+        // Start reading from the group containing `raw_pos`, that way the offset
+        // inside the padded chunk can be expressed in whole raw bytes.
+        let group = self.raw_pos / FR32_GROUP_RAW_BYTES as u64;
+        let group_offset = (self.raw_pos % FR32_GROUP_RAW_BYTES as u64) as usize;
+        let padded_start = group * FR32_GROUP_PADDED_BYTES as u64;
+        let padded_end = min(
+            padded_start + padded_bytes(group_offset + len) as u64,
+            self.padded_len,
+        );
+
+        self.buffer.resize((padded_end - padded_start) as usize, 0);
+        self.source.seek(SeekFrom::Start(padded_start))?;
+        self.source.read_exact(&mut self.buffer)?;
+
+        let mut target = &mut buf[..len];
+        let read = write_unpadded_aux(
+            &FR32_PADDING_MAP,
+            &self.buffer,
+            &mut target,
+            group_offset,
+            len,
+        )?;
+        self.raw_pos += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for Fr32Reader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => offset_position(self.raw_len, offset),
+            SeekFrom::Current(offset) => offset_position(self.raw_pos, offset),
+        };
+
+        match new_pos {
+            Some(new_pos) => {
+                self.raw_pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+fn offset_position(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.wrapping_neg() as u64)
+    }
+}
+
+/** Padding writer.
+
+Incrementally pads the raw data written to it, producing in `target` the same
+layout `write_padded` would for the whole stream. Unlike `write_padded` the
+`target` only needs to implement `Write`: raw data is buffered until a full
+group of elements is available, so the incomplete tail is only written when
+the writer is `finish`ed (or, ignoring any error, dropped).
+**/
+#[derive(Debug)]
+pub struct Fr32Writer<W: Write> {
+    target: Option<W>,
+    /// Raw data not yet padded (always less than a full group).
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Fr32Writer<W> {
+    pub fn new(target: W) -> Self {
+        Fr32Writer {
+            target: Some(target),
+            pending: Vec::with_capacity(FR32_GROUP_RAW_BYTES),
+        }
+    }
+
+    /// Pads and writes the incomplete tail of the stream, returning the `target`.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_tail()?;
+        let mut target = self.target.take().expect("target already taken");
+        target.flush()?;
+
+        Ok(target)
+    }
+
+    fn write_tail(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let padded = pad_partial_group(&self.pending);
+        self.pending.clear();
+
+        self.target
+            .as_mut()
+            .expect("target already taken")
+            .write_all(&padded)
+    }
+}
+
+impl<W: Write> Write for Fr32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let target = self.target.as_mut().expect("target already taken");
+        let mut source = buf;
+
+        // Complete the group left over by the previous write, if any.
+        if !self.pending.is_empty() {
+            let missing = min(FR32_GROUP_RAW_BYTES - self.pending.len(), source.len());
+            self.pending.extend_from_slice(&source[..missing]);
+            source = &source[missing..];
+
+            if self.pending.len() < FR32_GROUP_RAW_BYTES {
+                return Ok(buf.len());
+            }
+            target.write_all(&pad_fr32_groups(&self.pending))?;
+            self.pending.clear();
+        }
+
+        let groups_end = source.len() / FR32_GROUP_RAW_BYTES * FR32_GROUP_RAW_BYTES;
+        target.write_all(&pad_fr32_groups(&source[..groups_end]))?;
+
+        self.pending.extend_from_slice(&source[groups_end..]);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.target.as_mut().expect("target already taken").flush()
+    }
+}
+
+impl<W: Write> Drop for Fr32Writer<W> {
+    fn drop(&mut self) {
+        if self.target.is_some() {
+            let _ = self.write_tail();
+        }
+    }
+}
+
+// Pad an incomplete group of raw data, which always starts at an element boundary.
+fn pad_partial_group(source: &[u8]) -> Vec<u8> {
+    debug_assert!(source.len() < FR32_GROUP_RAW_BYTES);
+
+    let source_bits = source.len() * 8;
+    let mut padded = Vec::with_capacity(padded_bytes(source.len()));
+    let mut read_pos = 0;
+    while read_pos < source_bits {
+        padded.append(&mut extract_bits_and_shift(
+            source,
+            read_pos,
+            min(FR32_PADDING_MAP.data_bits, source_bits - read_pos),
+            0,
+        ));
+        read_pos += FR32_PADDING_MAP.data_bits;
+    }
+
+    padded
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use itertools::Itertools;
     use paired::bls12_381::Bls12;
+    use proptest::{proptest, proptest_helper};
     use rand::{Rng, SeedableRng};
     use rand_xorshift::XorShiftRng;
     use std::io::Cursor;
     use storage_proofs::fr32::bytes_into_fr;
 
+    use crate::types::UnpaddedByteIndex;
+
     #[test]
     fn test_position() {
         let mut bits = 0;
@@ -1366,6 +1789,128 @@ mod tests {
         }
     }
 
+    // `Fr32Writer` fed random data in chunks of random sizes must produce the
+    // same layout as padding it all at once with `write_padded`.
+    #[test]
+    fn test_fr32_writer() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for &len in &[0, 1, 31, 32, 40, 127, 128, 254, 1016, 2000] {
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let mut cursor = Cursor::new(Vec::new());
+            write_padded(&mut data[..].as_ref(), &mut cursor).unwrap();
+            let expected = cursor.into_inner();
+
+            let mut writer = Fr32Writer::new(Vec::new());
+            let mut written = 0;
+            while written < len {
+                let chunk = min(rng.gen_range(1, 300), len - written);
+                writer.write_all(&data[written..written + chunk]).unwrap();
+                written += chunk;
+            }
+            let padded = writer.finish().unwrap();
+
+            assert_eq!(expected, padded, "len {}", len);
+        }
+    }
+
+    // `Fr32Reader` over a padded source, check the whole stream and random
+    // ranges of raw data read after seeking.
+    #[test]
+    fn test_fr32_reader() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        for &len in &[1, 40, 127, 128, 1016, 2000] {
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            let mut cursor = Cursor::new(Vec::new());
+            write_padded(&mut data[..].as_ref(), &mut cursor).unwrap();
+
+            let mut reader = Fr32Reader::new(cursor).unwrap();
+            assert_eq!(reader.unpadded_len(), len as u64);
+
+            let mut unpadded = Vec::new();
+            reader.read_to_end(&mut unpadded).unwrap();
+            assert_eq!(data, unpadded);
+
+            for _ in 0..50 {
+                let start = rng.gen_range(0, len);
+                let end = rng.gen_range(start + 1, len + 1);
+
+                let mut unpadded = vec![0; end - start];
+                reader.seek(UnpaddedByteIndex(start as u64).into()).unwrap();
+                reader.read_exact(&mut unpadded).unwrap();
+                assert_eq!(&data[start..end], &unpadded[..]);
+            }
+
+            assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), len as u64);
+            assert_eq!(reader.read(&mut [0; 8]).unwrap(), 0);
+            assert!(reader.seek(SeekFrom::Current(-(len as i64) - 1)).is_err());
+        }
+    }
+
+    // Pad with the bit-level implementation only, used as reference for the fast path.
+    fn bit_padding(prefix: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_padded_bits(&FR32_PADDING_MAP, prefix, &mut cursor).unwrap();
+        write_padded_bits(&FR32_PADDING_MAP, data, &mut cursor).unwrap();
+        cursor.into_inner()
+    }
+
+    proptest! {
+        // Padding `data` after an arbitrary `prefix` (to start at an unaligned
+        // position) must match the bit-level implementation.
+        #[test]
+        fn write_padded_fast_path(
+            prefix in proptest::collection::vec(proptest::num::u8::ANY, 0..300),
+            data in proptest::collection::vec(proptest::num::u8::ANY, 0..2000)
+        ) {
+            let mut cursor = Cursor::new(Vec::new());
+            write_padded_aux(&FR32_PADDING_MAP, &prefix, &mut cursor).unwrap();
+            write_padded_aux(&FR32_PADDING_MAP, &data, &mut cursor).unwrap();
+
+            assert_eq!(cursor.into_inner(), bit_padding(&prefix, &data));
+        }
+
+        #[test]
+        fn write_unpadded_fast_path(
+            data in proptest::collection::vec(proptest::num::u8::ANY, 1..2000),
+            start in 0usize..2000,
+            len in 0usize..2100
+        ) {
+            let start = start % data.len();
+            let padded = bit_padding(&[], &data);
+
+            let mut unpadded = Vec::new();
+            let written =
+                write_unpadded_aux(&FR32_PADDING_MAP, &padded, &mut unpadded, start, len).unwrap();
+            let mut expected = Vec::new();
+            let expected_written =
+                write_unpadded_bits(&FR32_PADDING_MAP, &padded, &mut expected, start, len).unwrap();
+
+            assert_eq!(written, expected_written);
+            assert_eq!(unpadded, expected);
+        }
+    }
+
+    // The padding bits are ignored when unpadding, even if they aren't zero.
+    #[test]
+    fn test_unpad_ignores_padding_bits() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let len = 1016;
+        let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        let mut padded = bit_padding(&[], &data);
+        for element in padded.chunks_exact_mut(32) {
+            element[31] |= 0b1100_0000;
+        }
+
+        let mut unpadded = Vec::new();
+        write_unpadded(&padded, &mut unpadded, 0, len).unwrap();
+        assert_eq!(data, unpadded);
+    }
+
     // TODO: Add a test that drops the last part of an element and tries to recover
     // the rest of the data (may already be present in some form in the above tests).
 }
@@ -1,5 +1,9 @@
 #![deny(clippy::all, clippy::perf, clippy::correctness)]
 
+// Mock proofs can be forged by anyone, see `api::mock`.
+#[cfg(all(feature = "mock-proofs", not(debug_assertions)))]
+compile_error!("the mock-proofs feature must not be enabled in release builds");
+
 mod api;
 mod caches;
 
@@ -8,13 +12,21 @@ pub mod fr32;
 pub mod param;
 pub mod parameters;
 pub mod pieces;
+#[cfg(feature = "prover")]
+pub mod sector_manager;
 pub mod serde_big_array;
 pub mod singletons;
 pub mod types;
 
 pub use self::api::*;
+#[cfg(feature = "prover")]
+pub use self::caches::{evict_params, preload_params};
+pub use self::caches::{param_cache_stats, CacheStats, ParamsConfig};
 pub use self::constants::SINGLE_PARTITION_PROOF_LEN;
 pub use self::param::{ParameterData, ParameterMap};
+pub use self::parameters::{porep_config, post_config};
+#[cfg(feature = "prover")]
+pub use self::sector_manager::SectorManager;
 pub use self::types::*;
 
 pub use storage_proofs;
@@ -1,14 +1,17 @@
 use std::sync::atomic::Ordering;
 
-use anyhow::{ensure, Result};
+use anyhow::{ensure, Context, Result};
 use storage_proofs::election_post::{self, ElectionPoSt};
+use storage_proofs::hasher::Hasher;
 use storage_proofs::proof::ProofScheme;
 use storage_proofs::stacked::{self, LayerChallenges, StackedDrg};
 
 use crate::constants::{
-    DefaultPieceHasher, DefaultTreeHasher, DRG_DEGREE, EXP_DEGREE, LAYERS, POREP_MINIMUM_CHALLENGES,
+    DefaultPieceHasher, DefaultTreeHasher, DRG_DEGREE, EXP_DEGREE, LAYERS,
+    POREP_MINIMUM_CHALLENGES, POREP_PARTITIONS, POST_CHALLENGED_NODES, POST_CHALLENGE_COUNT,
+    TREE_R_LAST_ARITY,
 };
-use crate::types::{PaddedBytesAmount, PoStConfig};
+use crate::types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, SectorSize};
 
 const DRG_SEED: [u8; 28] = [
     0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25,
@@ -18,14 +21,11 @@ const DRG_SEED: [u8; 28] = [
 type PostSetupParams = election_post::SetupParams;
 pub type PostPublicParams = election_post::PublicParams;
 
-pub fn public_params(
+pub fn public_params<H: 'static + Hasher>(
     sector_bytes: PaddedBytesAmount,
     partitions: usize,
-) -> Result<stacked::PublicParams<DefaultTreeHasher>> {
-    StackedDrg::<DefaultTreeHasher, DefaultPieceHasher>::setup(&setup_params(
-        sector_bytes,
-        partitions,
-    )?)
+) -> Result<stacked::PublicParams<H>> {
+    StackedDrg::<H, DefaultPieceHasher>::setup(&setup_params(sector_bytes, partitions)?)
 }
 
 pub fn post_public_params(post_config: PoStConfig) -> Result<PostPublicParams> {
@@ -42,6 +42,44 @@ pub fn post_setup_params(post_config: PoStConfig) -> PostSetupParams {
     }
 }
 
+/// Returns the PoRep config of the sector profile of the given sector size.
+pub fn porep_config(sector_size: u64) -> Result<PoRepConfig> {
+    let partitions = POREP_PARTITIONS
+        .read()
+        .unwrap()
+        .get(&sector_size)
+        .copied()
+        .with_context(|| format!("unknown sector size {}", sector_size))?;
+
+    Ok(PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(partitions),
+    })
+}
+
+/// Returns the high priority PoSt config of the given sector size, which must have a sector
+/// profile as PoSt is only set up for sealed sectors.
+pub fn post_config(sector_size: u64) -> Result<PoStConfig> {
+    let PoRepConfig { sector_size, .. } = porep_config(sector_size)?;
+
+    Ok(PoStConfig {
+        sector_size,
+        challenge_count: POST_CHALLENGE_COUNT,
+        challenged_nodes: POST_CHALLENGED_NODES,
+        priority: true,
+    })
+}
+
+/// Returns the arity of `tree_r_last` in the sector profile of the given sector size.
+pub fn tree_r_last_arity(sector_bytes: PaddedBytesAmount) -> Result<usize> {
+    TREE_R_LAST_ARITY
+        .read()
+        .unwrap()
+        .get(&u64::from(sector_bytes))
+        .copied()
+        .with_context(|| format!("unknown sector size {}", u64::from(sector_bytes)))
+}
+
 pub fn setup_params(
     sector_bytes: PaddedBytesAmount,
     partitions: usize,
@@ -52,12 +90,13 @@ pub fn setup_params(
             .read()
             .unwrap()
             .get(&u64::from(sector_bytes))
-            .expect("unknown sector size") as usize,
+            .with_context(|| format!("unknown sector size {}", u64::from(sector_bytes)))?
+            as usize,
         *LAYERS
             .read()
             .unwrap()
             .get(&u64::from(sector_bytes))
-            .expect("unknown sector size"),
+            .with_context(|| format!("unknown sector size {}", u64::from(sector_bytes)))?,
     )?;
     let sector_bytes = u64::from(sector_bytes);
 
@@ -95,6 +134,8 @@ fn select_challenges(
 mod tests {
     use super::*;
 
+    use crate::constants::SECTOR_SIZE_2_KIB;
+
     #[test]
     fn partition_layer_challenges_test() {
         let f = |partitions| {
@@ -109,4 +150,17 @@ mod tests {
         assert_eq!(6, f(2));
         assert_eq!(3, f(4));
     }
+
+    #[test]
+    fn test_configs_of_sector_profiles() {
+        let porep = porep_config(SECTOR_SIZE_2_KIB).unwrap();
+        assert_eq!(u64::from(porep.sector_size), SECTOR_SIZE_2_KIB);
+
+        let post = post_config(SECTOR_SIZE_2_KIB).unwrap();
+        assert_eq!(u64::from(post.sector_size), SECTOR_SIZE_2_KIB);
+        assert!(post.priority);
+
+        assert!(porep_config(1234).is_err());
+        assert!(post_config(1234).is_err());
+    }
 }
@@ -292,7 +292,7 @@ pub fn get_aligned_source<T: Read>(
     )
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod tests {
     use super::*;
     use crate::api::util::commitment_from_fr;
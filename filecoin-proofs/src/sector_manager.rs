@@ -0,0 +1,799 @@
+//! Bookkeeping for sectors going through sealing.
+//!
+//! A `SectorManager` owns a directory in which it keeps, for each sector, the staged and sealed
+//! files, the cache directory, and the outputs of the phases sealing is split into. It tracks
+//! every sector through
+//!
+//! ```text
+//! Staging -> PreCommitting -> PreCommitPhase1 -> PreCommitted
+//!         -> Committing -> CommitPhase1 -> Proving
+//! ```
+//!
+//! and rejects calls that do not fit the state of their sector. The state of all sectors is
+//! stored in `state.json`, which is replaced atomically after every transition.
+//!
+//! `PreCommitting` and `Committing` are entered before the first phase of the step runs, and the
+//! `*Phase1` states once its output is written to disk. A sector left in one of them, because
+//! the process died or a phase failed, has its step picked up where it stopped by `resume`, or
+//! by calling `pre_commit` or `commit` again with the same ticket or seed.
+//!
+//! Only one `SectorManager` may use a directory at a time, which it ensures with an exclusive
+//! lock on the `lock` file in it. It can be shared between threads, which may work on different
+//! sectors concurrently.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{bail, ensure, Context, Result};
+use fs2::FileExt;
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use storage_proofs::hasher::Hasher;
+use storage_proofs::sector::SectorId;
+
+use crate::api::{
+    add_piece, seal_commit_phase1, seal_commit_phase2, seal_pre_commit_phase1,
+    seal_pre_commit_phase2, PrivateReplicaInfo,
+};
+use crate::constants::DefaultTreeHasher;
+use crate::pieces::sum_piece_bytes_with_alignment;
+use crate::types::{
+    Commitment, PieceInfo, PoRepConfig, ProverId, SealCommitOutput, SealCommitPhase1Output,
+    SealPreCommitOutput, SealPreCommitPhase1Output, Ticket, UnpaddedBytesAmount,
+};
+
+const LOCK_FILE: &str = "lock";
+const STATE_FILE: &str = "state.json";
+const PRE_COMMIT_PHASE1_FILE: &str = "pre-commit-phase1.json";
+const COMMIT_PHASE1_FILE: &str = "commit-phase1.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SectorState {
+    /// Accepting pieces.
+    Staging,
+    /// Running `seal_pre_commit_phase1`.
+    PreCommitting { ticket: Ticket },
+    /// Running `seal_pre_commit_phase2`.
+    PreCommitPhase1 { ticket: Ticket },
+    /// Waiting for the seed to commit with.
+    PreCommitted {
+        ticket: Ticket,
+        comm_r: Commitment,
+        comm_d: Commitment,
+    },
+    /// Running `seal_commit_phase1`.
+    Committing {
+        ticket: Ticket,
+        seed: Ticket,
+        comm_r: Commitment,
+        comm_d: Commitment,
+    },
+    /// Running `seal_commit_phase2`.
+    CommitPhase1 {
+        ticket: Ticket,
+        seed: Ticket,
+        comm_r: Commitment,
+        comm_d: Commitment,
+    },
+    /// Sealed and committed, ready for PoSt.
+    Proving {
+        ticket: Ticket,
+        seed: Ticket,
+        comm_r: Commitment,
+        comm_d: Commitment,
+        proof: Vec<u8>,
+    },
+}
+
+impl SectorState {
+    /// Whether a step was started, but did not finish.
+    pub fn is_interrupted(&self) -> bool {
+        match self {
+            SectorState::PreCommitting { .. }
+            | SectorState::PreCommitPhase1 { .. }
+            | SectorState::Committing { .. }
+            | SectorState::CommitPhase1 { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SectorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SectorState::Staging => "staging",
+            SectorState::PreCommitting { .. } => "pre-committing",
+            SectorState::PreCommitPhase1 { .. } => "in pre-commit phase 2",
+            SectorState::PreCommitted { .. } => "pre-committed",
+            SectorState::Committing { .. } => "committing",
+            SectorState::CommitPhase1 { .. } => "in commit phase 2",
+            SectorState::Proving { .. } => "proving",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sector {
+    pub id: SectorId,
+    /// The pieces added so far, in order.
+    pub pieces: Vec<PieceInfo>,
+    pub state: SectorState,
+    /// The length of the staged file after the last complete `add_piece`.
+    staged_len: u64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    next_id: u64,
+    sectors: BTreeMap<u64, Sector>,
+}
+
+struct Inner {
+    state: State,
+    /// Sectors a step is running for, which must not be touched by other threads.
+    busy: HashSet<u64>,
+}
+
+pub struct SectorManager<H: 'static + Hasher = DefaultTreeHasher> {
+    root: PathBuf,
+    porep_config: PoRepConfig,
+    prover_id: ProverId,
+    inner: Mutex<Inner>,
+    /// Holds the exclusive lock on `root` until the manager is dropped.
+    _lock: File,
+    _h: PhantomData<fn() -> H>,
+}
+
+/// Releases a sector marked busy by `SectorManager::acquire`.
+struct BusyGuard<'a> {
+    inner: &'a Mutex<Inner>,
+    sector_id: u64,
+}
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.busy.remove(&self.sector_id);
+        }
+    }
+}
+
+impl<H: 'static + Hasher> SectorManager<H>
+where
+    SealPreCommitPhase1Output<H>: Serialize + DeserializeOwned,
+    SealCommitPhase1Output<H>: Serialize + DeserializeOwned,
+{
+    /// Opens the manager stored in `root`, creating it if needed. Fails if another manager has
+    /// `root` open. Interrupted sectors are not resumed until `resume` is called.
+    pub fn open<P: AsRef<Path>>(
+        root: P,
+        porep_config: PoRepConfig,
+        prover_id: ProverId,
+    ) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        for dir in &["staged", "sealed", "cache"] {
+            fs::create_dir_all(root.join(dir))
+                .with_context(|| format!("could not create {:?}", root.join(dir)))?;
+        }
+
+        let lock_path = root.join(LOCK_FILE);
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&lock_path)
+            .with_context(|| format!("could not open {:?}", lock_path))?;
+        lock.try_lock_exclusive()
+            .with_context(|| format!("{:?} is in use by another sector manager", root))?;
+
+        let state_path = root.join(STATE_FILE);
+        let state = if state_path.exists() {
+            read_json(&state_path)?
+        } else {
+            State::default()
+        };
+
+        let interrupted = state
+            .sectors
+            .values()
+            .filter(|sector| sector.state.is_interrupted())
+            .count();
+        info!(
+            "opened {:?} with {} sectors, {} interrupted",
+            root,
+            state.sectors.len(),
+            interrupted
+        );
+
+        Ok(SectorManager {
+            root,
+            porep_config,
+            prover_id,
+            inner: Mutex::new(Inner {
+                state,
+                busy: HashSet::new(),
+            }),
+            _lock: lock,
+            _h: PhantomData,
+        })
+    }
+
+    pub fn sector(&self, sector_id: SectorId) -> Option<Sector> {
+        let inner = self.inner.lock().unwrap();
+        inner.state.sectors.get(&u64::from(sector_id)).cloned()
+    }
+
+    pub fn sectors(&self) -> Vec<Sector> {
+        let inner = self.inner.lock().unwrap();
+        inner.state.sectors.values().cloned().collect()
+    }
+
+    /// Creates an empty sector, in the `Staging` state.
+    pub fn new_sector(&self) -> Result<SectorId> {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.state.next_id;
+        let sector = Sector {
+            id: SectorId::from(id),
+            pieces: Vec::new(),
+            state: SectorState::Staging,
+            staged_len: 0,
+        };
+
+        inner.state.next_id += 1;
+        inner.state.sectors.insert(id, sector);
+        if let Err(err) = self.persist(&inner.state) {
+            inner.state.sectors.remove(&id);
+            inner.state.next_id = id;
+            return Err(err);
+        }
+
+        Ok(SectorId::from(id))
+    }
+
+    /// Appends `piece_size` bytes read from `source` to the staged file of a `Staging` sector.
+    pub fn add_piece<R: Read>(
+        &self,
+        sector_id: SectorId,
+        source: R,
+        piece_size: UnpaddedBytesAmount,
+    ) -> Result<PieceInfo> {
+        let (_guard, mut sector) = self.acquire(sector_id)?;
+        ensure!(
+            sector.state == SectorState::Staging,
+            "sector {:?} is {}, pieces can only be added while staging",
+            sector_id,
+            sector.state
+        );
+
+        let mut sizes: Vec<UnpaddedBytesAmount> =
+            sector.pieces.iter().map(|piece| piece.size).collect();
+        sizes.push(piece_size);
+        let capacity = UnpaddedBytesAmount::from(self.porep_config);
+        ensure!(
+            sum_piece_bytes_with_alignment(&sizes) <= capacity,
+            "sector {:?} has no room for a piece of {:?}",
+            sector_id,
+            piece_size
+        );
+        sizes.pop();
+
+        let staged_path = self.staged_path(sector_id);
+        let mut staged = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&staged_path)
+            .with_context(|| format!("could not open {:?}", staged_path))?;
+        // Drops whatever an interrupted `add_piece` wrote.
+        staged.set_len(sector.staged_len)?;
+        staged.seek(SeekFrom::End(0))?;
+
+        let (_, commitment) = add_piece(source, &mut staged, piece_size, &sizes)?;
+        staged.sync_all()?;
+
+        let piece = PieceInfo::new(commitment, piece_size)?;
+        sector.pieces.push(piece.clone());
+        sector.staged_len = staged.metadata()?.len();
+        self.update(&sector)?;
+
+        Ok(piece)
+    }
+
+    /// Seals a `Staging` sector with `ticket`, or finishes an interrupted pre-commit.
+    pub fn pre_commit(&self, sector_id: SectorId, ticket: Ticket) -> Result<SealPreCommitOutput> {
+        let (_guard, mut sector) = self.acquire(sector_id)?;
+        match sector.state {
+            SectorState::Staging => {
+                ensure!(
+                    !sector.pieces.is_empty(),
+                    "sector {:?} has no pieces",
+                    sector_id
+                );
+                sector.state = SectorState::PreCommitting { ticket };
+                self.update(&sector)?;
+            }
+            SectorState::PreCommitting { ticket: started }
+            | SectorState::PreCommitPhase1 { ticket: started } => ensure!(
+                started == ticket,
+                "sector {:?} is being pre-committed with another ticket",
+                sector_id
+            ),
+            ref state => bail!(
+                "sector {:?} is {}, it cannot be pre-committed",
+                sector_id,
+                state
+            ),
+        }
+
+        self.run(&mut sector)?;
+        match sector.state {
+            SectorState::PreCommitted { comm_r, comm_d, .. } => {
+                Ok(SealPreCommitOutput { comm_r, comm_d })
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Proves a `PreCommitted` sector with `seed`, or finishes an interrupted commit.
+    pub fn commit(&self, sector_id: SectorId, seed: Ticket) -> Result<SealCommitOutput> {
+        let (_guard, mut sector) = self.acquire(sector_id)?;
+        match sector.state {
+            SectorState::PreCommitted {
+                ticket,
+                comm_r,
+                comm_d,
+            } => {
+                sector.state = SectorState::Committing {
+                    ticket,
+                    seed,
+                    comm_r,
+                    comm_d,
+                };
+                self.update(&sector)?;
+            }
+            SectorState::Committing { seed: started, .. }
+            | SectorState::CommitPhase1 { seed: started, .. } => ensure!(
+                started == seed,
+                "sector {:?} is being committed with another seed",
+                sector_id
+            ),
+            ref state => bail!(
+                "sector {:?} is {}, it cannot be committed",
+                sector_id,
+                state
+            ),
+        }
+
+        self.run(&mut sector)?;
+        match sector.state {
+            SectorState::Proving { proof, .. } => Ok(SealCommitOutput { proof }),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Finishes the steps of all interrupted sectors, returning their ids. Stops at the first
+    /// one which fails again.
+    pub fn resume(&self) -> Result<Vec<SectorId>> {
+        let interrupted: Vec<SectorId> = self
+            .sectors()
+            .into_iter()
+            .filter(|sector| sector.state.is_interrupted())
+            .map(|sector| sector.id)
+            .collect();
+
+        for &sector_id in &interrupted {
+            info!("resuming sector {:?}", sector_id);
+            let (_guard, mut sector) = self.acquire(sector_id)?;
+            self.run(&mut sector)
+                .with_context(|| format!("could not resume sector {:?}", sector_id))?;
+        }
+
+        Ok(interrupted)
+    }
+
+    /// The replica of a `Proving` sector, to generate PoSt with.
+    pub fn private_replica_info(&self, sector_id: SectorId) -> Result<PrivateReplicaInfo<H>> {
+        let sector = self
+            .sector(sector_id)
+            .with_context(|| format!("unknown sector {:?}", sector_id))?;
+        match sector.state {
+            SectorState::Proving { comm_r, .. } => {
+                let sealed_path = self.sealed_path(sector_id);
+                let access = sealed_path
+                    .to_str()
+                    .with_context(|| format!("invalid sealed path {:?}", sealed_path))?
+                    .to_string();
+                PrivateReplicaInfo::new(access, comm_r, self.cache_path(sector_id))
+            }
+            ref state => bail!("sector {:?} is {}, not proving", sector_id, state),
+        }
+    }
+
+    pub fn staged_path(&self, sector_id: SectorId) -> PathBuf {
+        self.root
+            .join("staged")
+            .join(u64::from(sector_id).to_string())
+    }
+
+    pub fn sealed_path(&self, sector_id: SectorId) -> PathBuf {
+        self.root
+            .join("sealed")
+            .join(u64::from(sector_id).to_string())
+    }
+
+    pub fn cache_path(&self, sector_id: SectorId) -> PathBuf {
+        self.root
+            .join("cache")
+            .join(u64::from(sector_id).to_string())
+    }
+
+    /// Advances `sector` through the phases of its current step, persisting each transition.
+    fn run(&self, sector: &mut Sector) -> Result<()> {
+        let sector_id = sector.id;
+        let cache_path = self.cache_path(sector_id);
+        let sealed_path = self.sealed_path(sector_id);
+
+        loop {
+            // The input of the step just done, removed only once the new state is saved.
+            let mut consumed = None;
+
+            sector.state = match sector.state {
+                SectorState::PreCommitting { ticket } => {
+                    // Starts over from the staged file, whatever the previous attempt left.
+                    if cache_path.exists() {
+                        fs::remove_dir_all(&cache_path)?;
+                    }
+                    fs::create_dir_all(&cache_path)?;
+                    File::create(&sealed_path)?;
+
+                    let output = seal_pre_commit_phase1::<H, _, _, _>(
+                        self.porep_config,
+                        &cache_path,
+                        self.staged_path(sector_id),
+                        &sealed_path,
+                        self.prover_id,
+                        sector_id,
+                        ticket,
+                        &sector.pieces,
+                    )?;
+                    write_json(&cache_path.join(PRE_COMMIT_PHASE1_FILE), &output)?;
+
+                    SectorState::PreCommitPhase1 { ticket }
+                }
+                SectorState::PreCommitPhase1 { ticket } => {
+                    let output_path = cache_path.join(PRE_COMMIT_PHASE1_FILE);
+                    let output: SealPreCommitPhase1Output<H> = read_json(&output_path)?;
+                    let SealPreCommitOutput { comm_r, comm_d } = seal_pre_commit_phase2(
+                        self.porep_config,
+                        output,
+                        &cache_path,
+                        &sealed_path,
+                    )?;
+                    consumed = Some(output_path);
+
+                    SectorState::PreCommitted {
+                        ticket,
+                        comm_r,
+                        comm_d,
+                    }
+                }
+                SectorState::Committing {
+                    ticket,
+                    seed,
+                    comm_r,
+                    comm_d,
+                } => {
+                    let output = seal_commit_phase1::<H, _>(
+                        self.porep_config,
+                        &cache_path,
+                        self.prover_id,
+                        sector_id,
+                        ticket,
+                        seed,
+                        SealPreCommitOutput { comm_r, comm_d },
+                        &sector.pieces,
+                    )?;
+                    write_json(&cache_path.join(COMMIT_PHASE1_FILE), &output)?;
+
+                    SectorState::CommitPhase1 {
+                        ticket,
+                        seed,
+                        comm_r,
+                        comm_d,
+                    }
+                }
+                SectorState::CommitPhase1 {
+                    ticket,
+                    seed,
+                    comm_r,
+                    comm_d,
+                } => {
+                    let output_path = cache_path.join(COMMIT_PHASE1_FILE);
+                    let output: SealCommitPhase1Output<H> = read_json(&output_path)?;
+                    let SealCommitOutput { proof } =
+                        seal_commit_phase2(self.porep_config, output, self.prover_id, sector_id)?;
+                    consumed = Some(output_path);
+
+                    SectorState::Proving {
+                        ticket,
+                        seed,
+                        comm_r,
+                        comm_d,
+                        proof,
+                    }
+                }
+                _ => return Ok(()),
+            };
+
+            info!("sector {:?} is {}", sector_id, sector.state);
+            self.update(sector)?;
+
+            if let Some(path) = consumed {
+                // It may already be gone, so re-running the step stays idempotent.
+                match fs::remove_file(&path) {
+                    Err(err) if err.kind() != ErrorKind::NotFound => {
+                        return Err(err).with_context(|| format!("could not remove {:?}", path));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Marks a sector busy, and returns a copy of it to work on.
+    fn acquire(&self, sector_id: SectorId) -> Result<(BusyGuard<'_>, Sector)> {
+        let id = u64::from(sector_id);
+        let mut inner = self.inner.lock().unwrap();
+        let sector = inner
+            .state
+            .sectors
+            .get(&id)
+            .cloned()
+            .with_context(|| format!("unknown sector {:?}", sector_id))?;
+        ensure!(
+            inner.busy.insert(id),
+            "sector {:?} is busy with another call",
+            sector_id
+        );
+
+        let guard = BusyGuard {
+            inner: &self.inner,
+            sector_id: id,
+        };
+        Ok((guard, sector))
+    }
+
+    /// Stores `sector`, keeping its previous state in memory if that can't be persisted.
+    fn update(&self, sector: &Sector) -> Result<()> {
+        let id = u64::from(sector.id);
+        let mut inner = self.inner.lock().unwrap();
+        let previous = inner.state.sectors.insert(id, sector.clone());
+        if let Err(err) = self.persist(&inner.state) {
+            match previous {
+                Some(previous) => inner.state.sectors.insert(id, previous),
+                None => inner.state.sectors.remove(&id),
+            };
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the state file, so that a crash leaves either the old or the new one.
+    fn persist(&self, state: &State) -> Result<()> {
+        write_json(&self.root.join(STATE_FILE), state)
+    }
+}
+
+/// Writes `value` next to `path`, then moves it into place.
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    let mut file = BufWriter::new(
+        File::create(&tmp_path).with_context(|| format!("could not create {:?}", tmp_path))?,
+    );
+    serde_json::to_writer(&mut file, value)?;
+    file.flush()?;
+    file.get_ref().sync_all()?;
+
+    fs::rename(&tmp_path, path).with_context(|| format!("could not replace {:?}", path))?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+    serde_json::from_reader(BufReader::new(file)).with_context(|| format!("invalid {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use crate::api::verify_seal;
+    use crate::constants::{POREP_PARTITIONS, SECTOR_SIZE_2_KIB};
+    use crate::types::{PaddedBytesAmount, PoRepProofPartitions, SectorSize};
+
+    fn porep_config() -> PoRepConfig {
+        PoRepConfig {
+            sector_size: SectorSize(SECTOR_SIZE_2_KIB),
+            partitions: PoRepProofPartitions(
+                *POREP_PARTITIONS
+                    .read()
+                    .unwrap()
+                    .get(&SECTOR_SIZE_2_KIB)
+                    .unwrap(),
+            ),
+        }
+    }
+
+    fn open(dir: &tempfile::TempDir) -> SectorManager {
+        SectorManager::open(dir.path(), porep_config(), [1; 32]).unwrap()
+    }
+
+    fn add_piece(manager: &SectorManager, sector_id: SectorId, size: u64) -> Result<PieceInfo> {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let bytes: Vec<u8> = (0..size).map(|_| rng.gen()).collect();
+        manager.add_piece(sector_id, &bytes[..], UnpaddedBytesAmount(size))
+    }
+
+    #[test]
+    fn test_sector_manager_persists_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let manager = open(&dir);
+        let first = manager.new_sector().unwrap();
+        let piece = add_piece(&manager, first, 127).unwrap();
+        drop(manager);
+
+        let manager = open(&dir);
+        let sector = manager.sector(first).unwrap();
+        assert_eq!(sector.state, SectorState::Staging);
+        assert_eq!(sector.pieces, vec![piece]);
+
+        let second = manager.new_sector().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(manager.sectors().len(), 2);
+    }
+
+    #[test]
+    fn test_sector_manager_locks_its_directory() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let manager = open(&dir);
+        let err = SectorManager::<DefaultTreeHasher>::open(dir.path(), porep_config(), [1; 32])
+            .err()
+            .expect("a second manager opened the same directory");
+        assert!(format!("{:#}", err).contains("in use"), "{:#}", err);
+
+        drop(manager);
+        open(&dir);
+    }
+
+    #[test]
+    fn test_sector_manager_keeps_unpersisted_changes_out_of_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = open(&dir);
+        let sector_id = manager.new_sector().unwrap();
+        add_piece(&manager, sector_id, 127).unwrap();
+
+        // The state file can't be replaced while a directory is in the way.
+        let blocker = dir.path().join(STATE_FILE).with_extension("json.tmp");
+        fs::create_dir(&blocker).unwrap();
+        assert!(manager.new_sector().is_err());
+        assert!(add_piece(&manager, sector_id, 127).is_err());
+        assert_eq!(manager.sectors().len(), 1);
+        assert_eq!(manager.sector(sector_id).unwrap().pieces.len(), 1);
+
+        fs::remove_dir(&blocker).unwrap();
+        let next = manager.new_sector().unwrap();
+        assert_eq!(u64::from(next), u64::from(sector_id) + 1);
+    }
+
+    #[test]
+    fn test_sector_manager_rejects_invalid_transitions() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = open(&dir);
+
+        assert!(manager.pre_commit(SectorId::from(7), [0; 32]).is_err());
+
+        let sector_id = manager.new_sector().unwrap();
+        assert!(manager.commit(sector_id, [0; 32]).is_err());
+        assert!(manager.private_replica_info(sector_id).is_err());
+        assert!(
+            manager.pre_commit(sector_id, [0; 32]).is_err(),
+            "a sector without pieces cannot be sealed"
+        );
+        assert!(
+            add_piece(&manager, sector_id, 4064).is_err(),
+            "a piece larger than the sector cannot be added"
+        );
+        assert_eq!(
+            manager.sector(sector_id).unwrap().state,
+            SectorState::Staging
+        );
+    }
+
+    #[test]
+    fn test_sector_manager_drops_interrupted_pieces() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = open(&dir);
+        let sector_id = manager.new_sector().unwrap();
+        add_piece(&manager, sector_id, 127).unwrap();
+        let staged_len = fs::metadata(manager.staged_path(sector_id)).unwrap().len();
+
+        // What an `add_piece` interrupted halfway through leaves.
+        let mut staged = OpenOptions::new()
+            .append(true)
+            .open(manager.staged_path(sector_id))
+            .unwrap();
+        staged.write_all(&[0xff; 100]).unwrap();
+
+        add_piece(&manager, sector_id, 127).unwrap();
+        assert_eq!(
+            fs::metadata(manager.staged_path(sector_id)).unwrap().len(),
+            2 * staged_len
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_sector_manager_lifecycle() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let ticket = rng.gen();
+        let seed = rng.gen();
+
+        let manager = open(&dir);
+        let sector_id = manager.new_sector()?;
+        let sector_bytes = UnpaddedBytesAmount::from(PaddedBytesAmount(SECTOR_SIZE_2_KIB));
+        add_piece(&manager, sector_id, u64::from(sector_bytes))?;
+
+        let SealPreCommitOutput { comm_r, comm_d } = manager.pre_commit(sector_id, ticket)?;
+        assert!(manager.pre_commit(sector_id, ticket).is_err());
+
+        // Pretend the process died while committing.
+        let mut sector = manager.sector(sector_id).unwrap();
+        sector.state = SectorState::Committing {
+            ticket,
+            seed,
+            comm_r,
+            comm_d,
+        };
+        manager.update(&sector)?;
+        drop(manager);
+
+        let manager = open(&dir);
+        assert_eq!(manager.resume()?, vec![sector_id]);
+        let proof = match manager.sector(sector_id).unwrap().state {
+            SectorState::Proving { proof, .. } => proof,
+            state => panic!("unexpected state {}", state),
+        };
+        assert!(verify_seal::<DefaultTreeHasher>(
+            porep_config(),
+            comm_r,
+            comm_d,
+            [1; 32],
+            sector_id,
+            ticket,
+            seed,
+            &proof,
+        )?);
+
+        assert!(manager.private_replica_info(sector_id).is_ok());
+        assert!(manager.resume()?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use std::io::SeekFrom;
 use std::ops::{Add, Sub};
 
 use serde::{Deserialize, Serialize};
@@ -78,6 +79,12 @@ impl From<UnpaddedByteIndex> for usize {
     }
 }
 
+impl From<UnpaddedByteIndex> for SeekFrom {
+    fn from(n: UnpaddedByteIndex) -> Self {
+        SeekFrom::Start(n.0)
+    }
+}
+
 impl Add for UnpaddedBytesAmount {
     type Output = UnpaddedBytesAmount;
 
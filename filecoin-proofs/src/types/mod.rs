@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use storage_proofs::hasher::Hasher;
 use storage_proofs::stacked;
 
-use crate::constants::{DefaultPieceHasher, DefaultTreeDomain, DefaultTreeHasher};
+use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
 
 mod bytes_amount;
 mod piece_info;
@@ -24,21 +24,26 @@ pub use self::sector_size::*;
 
 pub type Commitment = [u8; 32];
 pub type ChallengeSeed = [u8; 32];
-pub type PersistentAux = stacked::PersistentAux<DefaultTreeDomain>;
-pub type TemporaryAux = stacked::TemporaryAux<DefaultTreeHasher, DefaultPieceHasher>;
+pub type PersistentAux<H = DefaultTreeHasher> = stacked::PersistentAux<<H as Hasher>::Domain>;
+pub type TemporaryAux<H = DefaultTreeHasher> = stacked::TemporaryAux<H, DefaultPieceHasher>;
 pub type ProverId = [u8; 32];
 pub type Ticket = [u8; 32];
 
-pub type Tree = storage_proofs::stacked::QuadTree<DefaultTreeHasher>;
-pub type LCTree = storage_proofs::stacked::QuadLCTree<DefaultTreeHasher>;
+pub type Tree<H = DefaultTreeHasher> = storage_proofs::stacked::QuadTree<H>;
+pub type LCTree<H = DefaultTreeHasher> = storage_proofs::stacked::QuadLCTree<H>;
+pub type OctLCTree<H = DefaultTreeHasher> = storage_proofs::stacked::OctLCTree<H>;
 
-pub type Labels = storage_proofs::stacked::Labels<DefaultTreeHasher>;
+pub type Labels<H = DefaultTreeHasher> = storage_proofs::stacked::Labels<H>;
+pub use storage_proofs::stacked::LabelingMode;
 pub type DataTree = storage_proofs::stacked::BinaryTree<DefaultPieceHasher>;
 
 /// Arity for quad trees, used for comm_r_last.
 pub const QUAD_ARITY: usize = 4;
 
-/// Arity for quad trees, used for comm_r_last.
+/// Arity for oct trees, used for comm_r_last.
+pub const OCT_ARITY: usize = 8;
+
+/// Arity for binary trees, used for comm_d.
 pub const BINARY_ARITY: usize = 2;
 
 #[derive(Debug, Clone)]
@@ -47,14 +52,45 @@ pub struct SealPreCommitOutput {
     pub comm_d: Commitment,
 }
 
-pub type VanillaSealProof = storage_proofs::stacked::Proof<DefaultTreeHasher, DefaultPieceHasher>;
+pub type VanillaSealProof<H = DefaultTreeHasher, U = typenum::U4> =
+    storage_proofs::stacked::Proof<H, DefaultPieceHasher, U>;
+
+/// The vanilla proofs of all partitions, tagged with the arity of `tree_r_last` they were
+/// generated for.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "VanillaSealProof<H, typenum::U4>: Serialize, \
+                 VanillaSealProof<H, typenum::U8>: Serialize",
+    deserialize = "VanillaSealProof<H, typenum::U4>: Deserialize<'de>, \
+                   VanillaSealProof<H, typenum::U8>: Deserialize<'de>"
+))]
+pub enum VanillaSealProofs<H: Hasher = DefaultTreeHasher> {
+    Quad(Vec<Vec<VanillaSealProof<H, typenum::U4>>>),
+    Oct(Vec<Vec<VanillaSealProof<H, typenum::U8>>>),
+}
+
+impl<H: Hasher> From<Vec<Vec<VanillaSealProof<H, typenum::U4>>>> for VanillaSealProofs<H> {
+    fn from(proofs: Vec<Vec<VanillaSealProof<H, typenum::U4>>>) -> Self {
+        VanillaSealProofs::Quad(proofs)
+    }
+}
+
+impl<H: Hasher> From<Vec<Vec<VanillaSealProof<H, typenum::U8>>>> for VanillaSealProofs<H> {
+    fn from(proofs: Vec<Vec<VanillaSealProof<H, typenum::U8>>>) -> Self {
+        VanillaSealProofs::Oct(proofs)
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct SealCommitPhase1Output {
-    pub vanilla_proofs: Vec<Vec<VanillaSealProof>>,
+pub struct SealCommitPhase1Output<H: Hasher = DefaultTreeHasher> {
+    #[serde(bound(
+        serialize = "VanillaSealProofs<H>: Serialize",
+        deserialize = "VanillaSealProofs<H>: Deserialize<'de>"
+    ))]
+    pub vanilla_proofs: VanillaSealProofs<H>,
     pub comm_r: Commitment,
     pub comm_d: Commitment,
-    pub replica_id: <DefaultTreeHasher as Hasher>::Domain,
+    pub replica_id: H::Domain,
     pub seed: Ticket,
     pub ticket: Ticket,
 }
@@ -67,8 +103,12 @@ pub struct SealCommitOutput {
 pub use merkletree::store::StoreConfig;
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct SealPreCommitPhase1Output {
-    pub labels: Labels,
+pub struct SealPreCommitPhase1Output<H: Hasher = DefaultTreeHasher> {
+    #[serde(bound(
+        serialize = "Labels<H>: Serialize",
+        deserialize = "Labels<H>: Deserialize<'de>"
+    ))]
+    pub labels: Labels<H>,
     pub config: StoreConfig,
     pub comm_d: Commitment,
 }
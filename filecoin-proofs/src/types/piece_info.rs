@@ -1,10 +1,11 @@
 use std::fmt;
 
 use anyhow::{ensure, Result};
+use serde::{de, Deserialize, Deserializer, Serialize};
 
 use crate::types::{Commitment, UnpaddedBytesAmount};
 
-#[derive(Clone, Default, PartialEq, Eq)]
+#[derive(Clone, Default, PartialEq, Eq, Serialize)]
 pub struct PieceInfo {
     pub commitment: Commitment,
     pub size: UnpaddedBytesAmount,
@@ -25,3 +26,18 @@ impl PieceInfo {
         Ok(PieceInfo { commitment, size })
     }
 }
+
+impl<'de> Deserialize<'de> for PieceInfo {
+    /// Rejects the all zero commitment, like `PieceInfo::new`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "PieceInfo")]
+        struct Fields {
+            commitment: Commitment,
+            size: UnpaddedBytesAmount,
+        }
+
+        let Fields { commitment, size } = Fields::deserialize(deserializer)?;
+        PieceInfo::new(commitment, size).map_err(de::Error::custom)
+    }
+}
@@ -1,12 +1,15 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
-use paired::bls12_381::Bls12;
+use paired::bls12_381::{Bls12, Fr};
+use storage_proofs::circuit::multi_proof::ProofHeader;
 use storage_proofs::circuit::stacked::{StackedCircuit, StackedCompound};
+use storage_proofs::hasher::{Hasher, PoseidonArity, PoseidonEngine};
 use storage_proofs::parameter_cache::{self, CacheableParameters};
+use storage_proofs::stacked;
 
-use crate::constants::{DefaultPieceHasher, DefaultTreeHasher};
+use crate::constants::DefaultPieceHasher;
 use crate::types::*;
 
 #[derive(Clone, Copy, Debug)]
@@ -47,32 +50,57 @@ impl From<PoRepConfig> for SectorSize {
 }
 
 impl PoRepConfig {
-    /// Returns the cache identifier as used by `storage-proofs::paramater_cache`.
-    pub fn get_cache_identifier(&self) -> Result<String> {
+    /// Returns the cache identifier as used by `storage-proofs::paramater_cache`, for a
+    /// `tree_r_last` built with the hasher `H`.
+    pub fn get_cache_identifier<H: 'static + Hasher>(&self) -> Result<String> {
         let params =
-            crate::parameters::public_params(self.sector_size.into(), self.partitions.into())?;
-
-        Ok(
-            <StackedCompound<DefaultTreeHasher, DefaultPieceHasher> as CacheableParameters<
-                Bls12,
-                StackedCircuit<_, DefaultTreeHasher, DefaultPieceHasher>,
-                _,
-            >>::cache_identifier(&params),
-        )
+            crate::parameters::public_params::<H>(self.sector_size.into(), self.partitions.into())?;
+
+        let id = match crate::parameters::tree_r_last_arity(self.sector_size.into())? {
+            QUAD_ARITY => cache_identifier::<H, typenum::U4>(&params),
+            OCT_ARITY => cache_identifier::<H, typenum::U8>(&params),
+            arity => bail!("unsupported tree_r_last arity: {}", arity),
+        };
+
+        Ok(id)
+    }
+
+    /// Returns the header of enveloped proofs for this config, see
+    /// `storage_proofs::circuit::multi_proof`.
+    pub fn get_proof_header<H: 'static + Hasher>(&self) -> Result<ProofHeader> {
+        Ok(ProofHeader::new(
+            self.get_cache_identifier::<H>()?,
+            self.partitions.into(),
+            vec![H::name(), DefaultPieceHasher::name()],
+        ))
     }
 
-    pub fn get_cache_metadata_path(&self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_metadata_path<H: 'static + Hasher>(&self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_metadata_path(&id))
     }
 
-    pub fn get_cache_verifying_key_path(&self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_verifying_key_path<H: 'static + Hasher>(&self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_verifying_key_path(&id))
     }
 
-    pub fn get_cache_params_path(&self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_params_path<H: 'static + Hasher>(&self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_params_path(&id))
     }
 }
+
+fn cache_identifier<H, U>(params: &stacked::PublicParams<H>) -> String
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    <StackedCompound<H, DefaultPieceHasher, U> as CacheableParameters<
+        Bls12,
+        StackedCircuit<Bls12, H, DefaultPieceHasher, U>,
+        _,
+    >>::cache_identifier(params)
+}
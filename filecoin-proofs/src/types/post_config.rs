@@ -1,12 +1,14 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
-use paired::bls12_381::Bls12;
+use paired::bls12_381::{Bls12, Fr};
 use storage_proofs::circuit::election_post::{ElectionPoStCircuit, ElectionPoStCompound};
+use storage_proofs::circuit::multi_proof::ProofHeader;
+use storage_proofs::election_post;
+use storage_proofs::hasher::{Hasher, PoseidonArity, PoseidonEngine};
 use storage_proofs::parameter_cache::{self, CacheableParameters};
 
-use crate::constants::DefaultTreeHasher;
 use crate::types::*;
 
 #[derive(Clone, Copy, Debug)]
@@ -35,31 +37,57 @@ impl From<PoStConfig> for UnpaddedBytesAmount {
 }
 
 impl PoStConfig {
-    /// Returns the cache identifier as used by `storage-proofs::paramater_cache`.
-    pub fn get_cache_identifier(self) -> Result<String> {
+    /// Returns the cache identifier as used by `storage-proofs::paramater_cache`, for a
+    /// `tree_r_last` built with the hasher `H`.
+    pub fn get_cache_identifier<H: 'static + Hasher>(self) -> Result<String> {
         let params = crate::parameters::post_public_params(self)?;
 
-        Ok(
-            <ElectionPoStCompound<DefaultTreeHasher> as CacheableParameters<
-                Bls12,
-                ElectionPoStCircuit<_, DefaultTreeHasher>,
-                _,
-            >>::cache_identifier(&params),
-        )
+        let id = match crate::parameters::tree_r_last_arity(self.sector_size.into())? {
+            QUAD_ARITY => cache_identifier::<H, typenum::U4>(&params),
+            OCT_ARITY => cache_identifier::<H, typenum::U8>(&params),
+            arity => bail!("unsupported tree_r_last arity: {}", arity),
+        };
+
+        Ok(id)
+    }
+
+    /// Returns the header of enveloped proofs for this config, see
+    /// `storage_proofs::circuit::multi_proof`. Election PoSt proofs have a single
+    /// partition.
+    pub fn get_proof_header<H: 'static + Hasher>(self) -> Result<ProofHeader> {
+        Ok(ProofHeader::new(
+            self.get_cache_identifier::<H>()?,
+            1,
+            vec![H::name()],
+        ))
     }
 
-    pub fn get_cache_metadata_path(self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_metadata_path<H: 'static + Hasher>(self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_metadata_path(&id))
     }
 
-    pub fn get_cache_verifying_key_path(self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_verifying_key_path<H: 'static + Hasher>(self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_verifying_key_path(&id))
     }
 
-    pub fn get_cache_params_path(self) -> Result<PathBuf> {
-        let id = self.get_cache_identifier()?;
+    pub fn get_cache_params_path<H: 'static + Hasher>(self) -> Result<PathBuf> {
+        let id = self.get_cache_identifier::<H>()?;
         Ok(parameter_cache::parameter_cache_params_path(&id))
     }
 }
+
+fn cache_identifier<H, U>(params: &election_post::PublicParams) -> String
+where
+    H: 'static + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    <ElectionPoStCompound<H, U> as CacheableParameters<
+        Bls12,
+        ElectionPoStCircuit<Bls12, H, U>,
+        _,
+    >>::cache_identifier(params)
+}
@@ -0,0 +1,10 @@
+#![no_main]
+
+use filecoin_proofs::deserialize_aux;
+use filecoin_proofs::types::{PersistentAux, TemporaryAux};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize_aux::<PersistentAux>(data);
+    let _ = deserialize_aux::<TemporaryAux>(data);
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use filecoin_proofs::{deserialize_aux, Candidate};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<Candidate>(data);
+    let _ = deserialize_aux::<Candidate>(data);
+});
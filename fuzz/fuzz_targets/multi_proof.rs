@@ -0,0 +1,25 @@
+#![no_main]
+
+use fil_proofs_fuzz::{Input, VERIFYING_KEY};
+use libfuzzer_sys::fuzz_target;
+use storage_proofs::circuit::multi_proof::{MultiProof, ProofHeader};
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Input(data);
+    let partitions = match input.u8() {
+        Some(partitions) => usize::from(partitions % 4 + 1),
+        None => return,
+    };
+    let bytes = input.0;
+
+    let _ = ProofHeader::read(bytes);
+
+    if let Ok(proof) = MultiProof::new_from_reader(Some(partitions), bytes, &VERIFYING_KEY) {
+        // Proofs only decode from their canonical encoding.
+        let encoded = proof.to_vec().expect("failed to write proof");
+        assert_eq!(&bytes[..encoded.len()], &encoded[..]);
+    }
+
+    let expected = ProofHeader::new("fuzz".into(), partitions, vec!["PoseidonHasher".into()]);
+    let _ = MultiProof::new_from_envelope_reader(&expected, bytes, &VERIFYING_KEY);
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use filecoin_proofs::deserialize_aux;
+use filecoin_proofs::types::PieceInfo;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let pieces = vec![
+        serde_json::from_slice::<PieceInfo>(data).ok(),
+        deserialize_aux::<PieceInfo>(data).ok(),
+    ];
+
+    for piece in pieces.into_iter().flatten() {
+        assert_ne!(piece.commitment, [0; 32]);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use filecoin_proofs::deserialize_aux;
+use filecoin_proofs::types::SealCommitPhase1Output;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<SealCommitPhase1Output>(data);
+    let _ = deserialize_aux::<SealCommitPhase1Output>(data);
+});
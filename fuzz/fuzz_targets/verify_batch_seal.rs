@@ -0,0 +1,31 @@
+#![no_main]
+
+use fil_proofs_fuzz::{porep_config, Input, SealInputs};
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::verify_batch_seal;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Input(data);
+    let seals = match input.u8() {
+        Some(count) => (0..count % 4 + 1)
+            .map(|_| SealInputs::read(&mut input))
+            .collect::<Option<Vec<_>>>(),
+        None => None,
+    };
+    let seals = match seals {
+        Some(seals) => seals,
+        None => return,
+    };
+
+    let _ = verify_batch_seal::<DefaultTreeHasher>(
+        porep_config(),
+        &seals.iter().map(|seal| seal.comm_r).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.comm_d).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.prover_id).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.sector_id).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.ticket).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.seed).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.proof).collect::<Vec<_>>(),
+    );
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use fil_proofs_fuzz::{post_config, Input, PoStInputs};
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::verify_post;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(post) = PoStInputs::<DefaultTreeHasher>::read(&mut Input(data)) {
+        let _ = verify_post(
+            post_config(),
+            &post.randomness,
+            post.challenge_count,
+            &post.proofs,
+            &post.replicas,
+            &post.winners,
+            post.prover_id,
+        );
+    }
+});
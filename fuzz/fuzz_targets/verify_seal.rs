@@ -0,0 +1,21 @@
+#![no_main]
+
+use fil_proofs_fuzz::{porep_config, Input, SealInputs};
+use filecoin_proofs::constants::DefaultTreeHasher;
+use filecoin_proofs::verify_seal;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(seal) = SealInputs::read(&mut Input(data)) {
+        let _ = verify_seal::<DefaultTreeHasher>(
+            porep_config(),
+            seal.comm_r,
+            seal.comm_d,
+            seal.prover_id,
+            seal.sector_id,
+            seal.ticket,
+            seal.seed,
+            seal.proof,
+        );
+    }
+});
@@ -0,0 +1,161 @@
+//! Helpers shared by the fuzz targets in `fuzz_targets/`.
+
+use std::collections::BTreeMap;
+
+use bellperson::{groth16, Circuit, ConstraintSystem, SynthesisError};
+use filecoin_proofs::constants::SECTOR_SIZE_2_KIB;
+use filecoin_proofs::types::{Commitment, PoRepConfig, PoStConfig, ProverId, Ticket};
+use filecoin_proofs::{Candidate, PublicReplicaInfo};
+use lazy_static::lazy_static;
+use paired::bls12_381::Bls12;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use storage_proofs::fr32::bytes_into_fr;
+use storage_proofs::hasher::Hasher;
+use storage_proofs::sector::SectorId;
+
+lazy_static! {
+    /// A verifying key to read proofs with. Reading doesn't depend on the key, so the one
+    /// of a trivial circuit does.
+    pub static ref VERIFYING_KEY: groth16::VerifyingKey<Bls12> = {
+        let rng = &mut XorShiftRng::from_seed([0x3d; 16]);
+        groth16::generate_random_parameters::<Bls12, _, _>(Trivial, rng)
+            .expect("failed to generate parameters")
+            .vk
+    };
+}
+
+/// Enforces `x * x = x`.
+struct Trivial;
+
+impl Circuit<Bls12> for Trivial {
+    fn synthesize<CS: ConstraintSystem<Bls12>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let x = cs.alloc(|| "x", || Err(SynthesisError::AssignmentMissing))?;
+        cs.enforce(|| "x * x = x", |lc| lc + x, |lc| lc + x, |lc| lc + x);
+
+        Ok(())
+    }
+}
+
+/// The 2KiB sector configuration all verification targets use.
+pub fn porep_config() -> PoRepConfig {
+    filecoin_proofs::porep_config(SECTOR_SIZE_2_KIB).expect("unknown sector size")
+}
+
+pub fn post_config() -> PoStConfig {
+    PoStConfig {
+        priority: false,
+        ..filecoin_proofs::post_config(SECTOR_SIZE_2_KIB).expect("unknown sector size")
+    }
+}
+
+/// Splits fuzz input into fields, each accessor returning `None` once it runs out.
+pub struct Input<'a>(pub &'a [u8]);
+
+impl<'a> Input<'a> {
+    pub fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+
+        Some(head)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+
+        Some(u64::from_le_bytes(bytes))
+    }
+
+    pub fn array32(&mut self) -> Option<[u8; 32]> {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(self.bytes(32)?);
+
+        Some(bytes)
+    }
+
+    /// A proof prefixed by its u16 LE length.
+    pub fn proof(&mut self) -> Option<&'a [u8]> {
+        let mut len = [0u8; 2];
+        len.copy_from_slice(self.bytes(2)?);
+
+        self.bytes(u16::from_le_bytes(len) as usize)
+    }
+}
+
+/// The arguments of `verify_seal` for a single sector.
+pub struct SealInputs<'a> {
+    pub comm_r: Commitment,
+    pub comm_d: Commitment,
+    pub prover_id: ProverId,
+    pub sector_id: SectorId,
+    pub ticket: Ticket,
+    pub seed: Ticket,
+    pub proof: &'a [u8],
+}
+
+impl<'a> SealInputs<'a> {
+    pub fn read(input: &mut Input<'a>) -> Option<Self> {
+        Some(SealInputs {
+            comm_r: input.array32()?,
+            comm_d: input.array32()?,
+            prover_id: input.array32()?,
+            sector_id: SectorId::from(input.u64()?),
+            ticket: input.array32()?,
+            seed: input.array32()?,
+            proof: input.proof()?,
+        })
+    }
+}
+
+/// The arguments of `verify_post`, for up to four winners.
+pub struct PoStInputs<H: Hasher> {
+    pub randomness: [u8; 32],
+    pub challenge_count: u64,
+    pub proofs: Vec<Vec<u8>>,
+    pub replicas: BTreeMap<SectorId, PublicReplicaInfo<H>>,
+    pub winners: Vec<Candidate>,
+    pub prover_id: ProverId,
+}
+
+impl<H: Hasher> PoStInputs<H> {
+    /// Returns `None` for inputs `verify_post` can't be called with, such as a
+    /// `partial_ticket` that isn't a field element.
+    pub fn read(input: &mut Input) -> Option<Self> {
+        let randomness = input.array32()?;
+        let prover_id = input.array32()?;
+        let challenge_count = u64::from(input.u8()?);
+        let num_winners = input.u8()? % 4 + 1;
+
+        let mut proofs = Vec::new();
+        let mut replicas = BTreeMap::new();
+        let mut winners = Vec::new();
+        for _ in 0..num_winners {
+            let sector_id = SectorId::from(input.u64()?);
+            replicas.insert(sector_id, PublicReplicaInfo::new(input.array32()?).ok()?);
+            winners.push(Candidate {
+                sector_id,
+                partial_ticket: bytes_into_fr::<Bls12>(&input.array32()?).ok()?,
+                ticket: input.array32()?,
+                sector_challenge_index: input.u64()?,
+            });
+            proofs.push(input.proof()?.to_vec());
+        }
+
+        Some(PoStInputs {
+            randomness,
+            challenge_count,
+            proofs,
+            replicas,
+            winners,
+            prover_id,
+        })
+    }
+}
@@ -1,10 +1,14 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, ParameterizedBenchmark};
+use sha2::{Digest, Sha256};
 use storage_proofs::drgraph::{Graph, BASE_DEGREE};
 use storage_proofs::hasher::blake2s::Blake2sHasher;
 use storage_proofs::hasher::pedersen::PedersenHasher;
 use storage_proofs::hasher::sha256::Sha256Hasher;
 use storage_proofs::hasher::Hasher;
-use storage_proofs::stacked::{StackedBucketGraph, EXP_DEGREE};
+use storage_proofs::stacked::{
+    create_key, create_layer_labels_prefetched, PrefetchConfig, StackedBucketGraph, EXP_DEGREE,
+};
+use storage_proofs::util::NODE_SIZE;
 
 #[cfg(feature = "cpu-profile")]
 #[inline(always)]
@@ -74,5 +78,67 @@ fn parents_loop_benchmark(cc: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, parents_loop_benchmark);
+#[allow(clippy::ptr_arg)]
+fn labels_loop<H: Hasher>(
+    graph: &StackedBucketGraph<H>,
+    exp_parents_data: &Vec<u8>,
+    layer_labels: &mut [u8],
+) {
+    let hasher = Sha256::new().chain(&[1u8; 32]);
+    for node in 0..graph.size() {
+        create_key(
+            graph,
+            hasher.clone(),
+            Some(exp_parents_data),
+            layer_labels,
+            node,
+        )
+        .unwrap();
+    }
+}
+
+fn labels_prefetched<H: Hasher>(
+    graph: &StackedBucketGraph<H>,
+    exp_parents_data: &[u8],
+    layer_labels: &mut [u8],
+    config: &PrefetchConfig,
+) {
+    let hasher = Sha256::new().chain(&[1u8; 32]);
+    create_layer_labels_prefetched(graph, &hasher, Some(exp_parents_data), layer_labels, config)
+        .unwrap();
+}
+
+fn labels_benchmark(cc: &mut Criterion) {
+    let sizes = vec![1024, 32 * 1024];
+
+    cc.bench(
+        "labeling a layer",
+        ParameterizedBenchmark::new(
+            "create_key",
+            |b, size| {
+                let graph = pregenerate_graph::<Sha256Hasher>(*size);
+                let exp_parents_data = vec![1u8; *size * NODE_SIZE];
+                let mut layer_labels = vec![0u8; *size * NODE_SIZE];
+                b.iter(|| black_box(labels_loop(&graph, &exp_parents_data, &mut layer_labels)))
+            },
+            sizes,
+        )
+        .with_function("prefetched", |b, size| {
+            let graph = pregenerate_graph::<Sha256Hasher>(*size);
+            let exp_parents_data = vec![1u8; *size * NODE_SIZE];
+            let mut layer_labels = vec![0u8; *size * NODE_SIZE];
+            let config = PrefetchConfig::default();
+            b.iter(|| {
+                black_box(labels_prefetched(
+                    &graph,
+                    &exp_parents_data,
+                    &mut layer_labels,
+                    &config,
+                ))
+            })
+        }),
+    );
+}
+
+criterion_group!(benches, parents_loop_benchmark, labels_benchmark);
 criterion_main!(benches);
@@ -673,6 +673,7 @@ mod tests {
         }
 
         assert!(cs.is_satisfied(), "constraints not satisfied");
+        assert!(cs.is_fully_constrained(), "unconstrained variables");
         assert_eq!(cs.num_inputs(), 18, "wrong number of inputs");
         assert_eq!(cs.num_constraints(), 149607, "wrong number of constraints");
 
@@ -24,8 +24,8 @@ use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::proof::ProofScheme;
 use crate::util::NODE_SIZE;
 
-/// This is the `ElectionPoSt` circuit.
-pub struct ElectionPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
+/// This is the `ElectionPoSt` circuit, over replicas whose `tree_r_last` has arity `U`.
+pub struct ElectionPoStCircuit<'a, E: JubjubEngine, H: Hasher, U = typenum::U4> {
     /// Paramters for the engine.
     pub params: &'a E::Params,
     pub comm_r: Option<E::Fr>,
@@ -39,38 +39,49 @@ pub struct ElectionPoStCircuit<'a, E: JubjubEngine, H: Hasher> {
     pub prover_id: Option<E::Fr>,
     pub sector_id: Option<E::Fr>,
     _h: PhantomData<H>,
+    _u: PhantomData<U>,
 }
 
-pub struct ElectionPoStCompound<H>
+pub struct ElectionPoStCompound<H, U = typenum::U4>
 where
     H: Hasher,
+    U: Unsigned,
 {
     _h: PhantomData<H>,
+    _u: PhantomData<U>,
 }
 
-impl<E: JubjubEngine, C: Circuit<E>, P: ParameterSetMetadata, H: Hasher>
-    CacheableParameters<E, C, P> for ElectionPoStCompound<H>
+impl<E: JubjubEngine, C: Circuit<E>, P: ParameterSetMetadata, H: Hasher, U: Unsigned>
+    CacheableParameters<E, C, P> for ElectionPoStCompound<H, U>
 {
     fn cache_prefix() -> String {
-        format!("proof-of-spacetime-election-{}", H::name())
+        format!(
+            "proof-of-spacetime-election-{}-{}",
+            H::name(),
+            U::to_usize()
+        )
     }
 }
 
 #[derive(Clone, Default)]
 pub struct ComponentPrivateInputs {}
 
-impl<'a, E: JubjubEngine, H: Hasher> CircuitComponent for ElectionPoStCircuit<'a, E, H> {
+impl<'a, E: JubjubEngine, H: Hasher, U> CircuitComponent for ElectionPoStCircuit<'a, E, H, U> {
     type ComponentPrivateInputs = ComponentPrivateInputs;
 }
 
-impl<'a, H> CompoundProof<'a, Bls12, ElectionPoSt<'a, H>, ElectionPoStCircuit<'a, Bls12, H>>
-    for ElectionPoStCompound<H>
+impl<'a, H, U>
+    CompoundProof<'a, Bls12, ElectionPoSt<'a, H, U>, ElectionPoStCircuit<'a, Bls12, H, U>>
+    for ElectionPoStCompound<H, U>
 where
     H: 'a + Hasher,
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
 {
     fn generate_public_inputs(
-        pub_inputs: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicInputs,
-        pub_params: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
+        pub_inputs: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::PublicInputs,
+        pub_params: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::PublicParams,
         _partition_k: Option<usize>,
     ) -> Result<Vec<Fr>> {
         let mut inputs = Vec::new();
@@ -98,7 +109,7 @@ where
                     commitment: None,
                     challenge: challenged_leaf_start as usize + i,
                 };
-                let por_inputs = PoRCompound::<H, typenum::U4>::generate_public_inputs(
+                let por_inputs = PoRCompound::<H, U>::generate_public_inputs(
                     &por_pub_inputs,
                     &por_pub_params,
                     None,
@@ -115,11 +126,11 @@ where
     }
 
     fn circuit(
-        pub_in: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicInputs,
-        _priv_in: <ElectionPoStCircuit<'a, Bls12, H> as CircuitComponent>::ComponentPrivateInputs,
-        vanilla_proof: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::Proof,
-        _pub_params: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
-    ) -> Result<ElectionPoStCircuit<'a, Bls12, H>> {
+        pub_in: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::PublicInputs,
+        _priv_in: <ElectionPoStCircuit<'a, Bls12, H, U> as CircuitComponent>::ComponentPrivateInputs,
+        vanilla_proof: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::Proof,
+        _pub_params: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::PublicParams,
+    ) -> Result<ElectionPoStCircuit<'a, Bls12, H, U>> {
         let comm_r = pub_in.comm_r.into();
         let comm_c = vanilla_proof.comm_c.into();
         let comm_r_last = vanilla_proof.comm_r_last().into();
@@ -157,18 +168,18 @@ where
             prover_id: Some(pub_in.prover_id.into()),
             sector_id: Some(pub_in.sector_id.into()),
             _h: PhantomData,
+            _u: PhantomData,
         })
     }
 
     fn blank_circuit(
-        pub_params: &<ElectionPoSt<'a, H> as ProofScheme<'a>>::PublicParams,
-    ) -> ElectionPoStCircuit<'a, Bls12, H> {
+        pub_params: &<ElectionPoSt<'a, H, U> as ProofScheme<'a>>::PublicParams,
+    ) -> ElectionPoStCircuit<'a, Bls12, H, U> {
         let challenges_count = pub_params.challenged_nodes * pub_params.challenge_count;
-        let height =
-            drgraph::graph_height::<typenum::U4>(pub_params.sector_size as usize / NODE_SIZE);
+        let height = drgraph::graph_height::<U>(pub_params.sector_size as usize / NODE_SIZE);
 
         let leafs = vec![None; challenges_count];
-        let paths = vec![vec![(vec![None; 3], None); height - 1]; challenges_count];
+        let paths = vec![vec![(vec![None; U::to_usize() - 1], None); height - 1]; challenges_count];
 
         ElectionPoStCircuit {
             params: &*JJ_PARAMS,
@@ -182,6 +193,7 @@ where
             prover_id: None,
             sector_id: None,
             _h: PhantomData,
+            _u: PhantomData,
         }
     }
 }
@@ -189,13 +201,15 @@ where
 impl<
         'a,
         E: JubjubEngine
-            + PoseidonEngine<typenum::U4>
+            + PoseidonEngine<U>
             + PoseidonEngine<typenum::U2>
             + PoseidonEngine<PoseidonMDArity>,
         H: Hasher,
-    > Circuit<E> for ElectionPoStCircuit<'a, E, H>
+        U,
+    > Circuit<E> for ElectionPoStCircuit<'a, E, H, U>
 where
-    typenum::U4: PoseidonArity<E>,
+    U: 'static + PoseidonArity<E>,
+    typenum::Add1<U>: generic_array::ArrayLength<E::Fr>,
 {
     fn synthesize<CS: ConstraintSystem<E>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
         let params = self.params;
@@ -253,7 +267,7 @@ where
 
         // 2. Verify Inclusion Paths
         for (i, (leaf, path)) in leafs.iter().zip(paths.iter()).enumerate() {
-            PoRCircuit::<typenum::U4, E, H>::synthesize(
+            PoRCircuit::<U, E, H>::synthesize(
                 cs.namespace(|| format!("challenge_inclusion{}", i)),
                 &params,
                 Root::Val(*leaf),
@@ -352,7 +366,7 @@ mod tests {
     use crate::election_post::{self, ElectionPoSt};
     use crate::fr32::fr_into_bytes;
     use crate::hasher::{Domain, HashFunction, Hasher, PedersenHasher, PoseidonHasher};
-    use crate::merkle::{QuadLCMerkleTree, QuadMerkleTree};
+    use crate::merkle::{LCMerkleTree, MerkleTree, QuadLCMerkleTree, QuadMerkleTree};
     use crate::proof::{NoRequirements, ProofScheme};
     use crate::sector::SectorId;
     use crate::stacked::QUAD_ARITY;
@@ -418,7 +432,7 @@ mod tests {
             trees.insert(i.into(), lctree);
         }
 
-        let candidates = election_post::generate_candidates::<H>(
+        let candidates = election_post::generate_candidates::<H, typenum::U4>(
             &pub_params,
             &sectors,
             &trees,
@@ -487,6 +501,7 @@ mod tests {
             prover_id: Some(prover_id.into()),
             sector_id: Some(candidate.sector_id.into()),
             _h: PhantomData,
+            _u: PhantomData,
         };
 
         instance
@@ -494,6 +509,7 @@ mod tests {
             .expect("failed to synthesize circuit");
 
         assert!(cs.is_satisfied(), "constraints not satisfied");
+        assert!(cs.is_fully_constrained(), "unconstrained variables");
 
         assert_eq!(cs.num_inputs(), 23, "wrong number of inputs");
         assert_eq!(
@@ -524,16 +540,27 @@ mod tests {
     #[ignore] // Slow test – run only when compiled for release.
     #[test]
     fn election_post_test_compound_pedersen() {
-        election_post_test_compound::<PedersenHasher>();
+        election_post_test_compound::<PedersenHasher, typenum::U4>();
     }
 
     #[ignore] // Slow test – run only when compiled for release.
     #[test]
     fn election_post_test_compound_poseidon() {
-        election_post_test_compound::<PoseidonHasher>();
+        election_post_test_compound::<PoseidonHasher, typenum::U4>();
+    }
+
+    #[ignore] // Slow test – run only when compiled for release.
+    #[test]
+    fn election_post_test_compound_poseidon_oct() {
+        election_post_test_compound::<PoseidonHasher, typenum::U8>();
     }
 
-    fn election_post_test_compound<H: Hasher>() {
+    fn election_post_test_compound<H: Hasher, U>()
+    where
+        U: 'static + PoseidonArity<Bls12>,
+        Bls12: PoseidonEngine<U>,
+        typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+    {
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
 
         let leaves = 64;
@@ -560,7 +587,7 @@ mod tests {
         let config = StoreConfig::new(
             &temp_path,
             String::from("test-lc-tree-v1"),
-            StoreConfig::default_cached_above_base_layer(leaves as usize, QUAD_ARITY),
+            StoreConfig::default_cached_above_base_layer(leaves as usize, U::to_usize()),
         );
 
         for i in 0..5 {
@@ -573,7 +600,7 @@ mod tests {
 
             let cur_config =
                 StoreConfig::from_config(&config, format!("test-lc-tree-v1-{}", i), None);
-            let mut tree: QuadMerkleTree<_, _> = graph
+            let mut tree: MerkleTree<_, _, U> = graph
                 .merkle_tree(Some(cur_config.clone()), data.as_slice())
                 .unwrap();
             let c = tree
@@ -581,15 +608,15 @@ mod tests {
                 .unwrap();
             assert_eq!(c, true);
 
-            let lctree: QuadLCMerkleTree<_, _> = graph
+            let lctree: LCMerkleTree<_, _, U> = graph
                 .lcmerkle_tree(Some(cur_config), data.as_slice())
                 .unwrap();
             trees.insert(i.into(), lctree);
         }
 
-        let pub_params = ElectionPoStCompound::<H>::setup(&setup_params).expect("setup failed");
+        let pub_params = ElectionPoStCompound::<H, U>::setup(&setup_params).expect("setup failed");
 
-        let candidates = election_post::generate_candidates::<H>(
+        let candidates = election_post::generate_candidates::<H, U>(
             &pub_params.vanilla_params,
             &sectors,
             &trees,
@@ -613,7 +640,7 @@ mod tests {
             sector_challenge_index: 0,
         };
 
-        let priv_inputs = election_post::PrivateInputs::<H> {
+        let priv_inputs = election_post::PrivateInputs::<H, U> {
             tree,
             comm_c,
             comm_r_last,
@@ -646,7 +673,7 @@ mod tests {
                 ElectionPoStCompound::circuit_for_test(&pub_params, &pub_inputs, &priv_inputs)
                     .unwrap();
             let blank_circuit =
-                ElectionPoStCompound::<H>::blank_circuit(&pub_params.vanilla_params);
+                ElectionPoStCompound::<H, U>::blank_circuit(&pub_params.vanilla_params);
 
             let mut cs_blank = MetricCS::new();
             blank_circuit
@@ -664,7 +691,7 @@ mod tests {
             }
         }
         let blank_groth_params =
-            ElectionPoStCompound::<H>::groth_params(&pub_params.vanilla_params)
+            ElectionPoStCompound::<H, U>::groth_params(&pub_params.vanilla_params)
                 .expect("failed to generate groth params");
 
         let proof = ElectionPoStCompound::prove(
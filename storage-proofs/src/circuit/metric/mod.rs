@@ -1,7 +1,8 @@
 use bellperson::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use paired::Engine;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Clone, Copy)]
 struct OrderedVariable(Variable);
@@ -39,6 +40,14 @@ impl Ord for OrderedVariable {
     }
 }
 
+/// Number of objects allocated under a namespace, see `MetricCS::namespace_metrics`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceMetrics {
+    pub constraints: usize,
+    pub inputs: usize,
+    pub aux: usize,
+}
+
 pub struct MetricCS<E: Engine> {
     named_objects: HashMap<String, NamedObject>,
     current_namespace: Vec<String>,
@@ -66,6 +75,36 @@ impl<E: Engine> MetricCS<E> {
         self.inputs.len()
     }
 
+    pub fn num_aux(&self) -> usize {
+        self.aux.len()
+    }
+
+    /// Counts the constraints, inputs and auxiliary variables allocated under each
+    /// namespace, keeping only the first `depth` levels of the namespace path (anything
+    /// deeper is counted in its ancestor at `depth`). Objects outside of any namespace
+    /// are counted under the empty path.
+    pub fn namespace_metrics(&self, depth: usize) -> BTreeMap<String, NamespaceMetrics> {
+        let mut metrics: BTreeMap<String, NamespaceMetrics> = BTreeMap::new();
+
+        for input in &self.inputs {
+            metrics
+                .entry(namespace_of(input, depth))
+                .or_default()
+                .inputs += 1;
+        }
+        for aux in &self.aux {
+            metrics.entry(namespace_of(aux, depth)).or_default().aux += 1;
+        }
+        for (_, _, _, path) in &self.constraints {
+            metrics
+                .entry(namespace_of(path, depth))
+                .or_default()
+                .constraints += 1;
+        }
+
+        metrics
+    }
+
     pub fn pretty_print_list(&self) -> Vec<String> {
         let mut result = Vec::new();
 
@@ -178,6 +217,16 @@ impl<E: Engine> ConstraintSystem<E> for MetricCS<E> {
     }
 }
 
+// The namespace an object at `path` was allocated in, truncated to `depth` levels.
+fn namespace_of(path: &str, depth: usize) -> String {
+    let mut components: Vec<&str> = path.split('/').collect();
+    // Drop the name of the object itself.
+    components.pop();
+    components.truncate(depth);
+
+    components.join("/")
+}
+
 fn compute_path(ns: &[String], this: &str) -> String {
     if this.chars().any(|a| a == '/') {
         panic!("'/' is not allowed in names");
@@ -197,3 +246,50 @@ fn compute_path(ns: &[String], this: &str) -> String {
 
     name
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+
+    #[test]
+    fn test_namespace_metrics() {
+        let mut cs = MetricCS::<Bls12>::new();
+        let one = MetricCS::<Bls12>::one();
+        let a = cs.alloc(|| "a", || Ok(Fr::one())).unwrap();
+        {
+            let mut cs = cs.namespace(|| "outer");
+            let b = cs.alloc_input(|| "b", || Ok(Fr::one())).unwrap();
+            cs.enforce(|| "a = b", |lc| lc + a, |lc| lc + one, |lc| lc + b);
+
+            let mut cs = cs.namespace(|| "inner");
+            let c = cs.alloc(|| "c", || Ok(Fr::one())).unwrap();
+            cs.enforce(|| "a = c", |lc| lc + a, |lc| lc + one, |lc| lc + c);
+        }
+
+        let metrics = cs.namespace_metrics(1);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(
+            metrics[""],
+            NamespaceMetrics {
+                constraints: 0,
+                inputs: 1,
+                aux: 1,
+            }
+        );
+        assert_eq!(
+            metrics["outer"],
+            NamespaceMetrics {
+                constraints: 2,
+                inputs: 1,
+                aux: 1,
+            }
+        );
+
+        let metrics = cs.namespace_metrics(2);
+        assert_eq!(metrics["outer"].constraints, 1);
+        assert_eq!(metrics["outer/inner"].constraints, 1);
+    }
+}
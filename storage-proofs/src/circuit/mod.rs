@@ -18,4 +18,5 @@ pub mod xor;
 // FIXME: Can we make a config like for test?
 pub mod bench;
 pub mod metric;
+pub mod r1cs;
 pub mod test;
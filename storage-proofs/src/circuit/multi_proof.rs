@@ -1,9 +1,152 @@
+//! Serialization of the Groth16 proofs of all partitions of a compound proof.
+//!
+//! Proofs are written either raw, as the concatenated compressed Groth16 proofs of
+//! each partition, or wrapped in an envelope describing what they prove:
+//!
+//! ```text
+//! magic        4 bytes  b"FILP"
+//! version      u16 LE   MULTI_PROOF_VERSION
+//! circuit id   u16 LE length, then UTF-8 bytes (`CacheableParameters::cache_identifier`)
+//! partitions   u32 LE
+//! hashers      u8 count, then for each a u16 LE length and UTF-8 bytes (`Hasher::name`)
+//! proofs       `partitions` compressed Groth16 proofs
+//! ```
+//!
+//! A raw proof starts with a compressed G1 point, whose first byte always has its
+//! most significant bit set, so it can never be mistaken for the magic.
+
+use std::io::{self, Cursor, Read, Write};
+
+use anyhow::{ensure, Context};
 use bellperson::groth16;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use paired::Engine;
 
 use crate::error::Result;
-use anyhow::Context;
-use paired::Engine;
-use std::io::{self, Read, Write};
+
+/// Magic prefix of an enveloped `MultiProof`.
+pub const MULTI_PROOF_MAGIC: [u8; 4] = *b"FILP";
+
+/// Version of the envelope format written by `MultiProof::write_envelope`.
+pub const MULTI_PROOF_VERSION: u16 = 1;
+
+/// What an enveloped `MultiProof` proves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofHeader {
+    /// The `CacheableParameters::cache_identifier` of the circuit.
+    pub circuit_id: String,
+    pub partitions: usize,
+    /// The `Hasher::name` of every hasher the circuit is generic over, in order.
+    pub hashers: Vec<String>,
+}
+
+impl ProofHeader {
+    pub fn new(circuit_id: String, partitions: usize, hashers: Vec<String>) -> Self {
+        ProofHeader {
+            circuit_id,
+            partitions,
+            hashers,
+        }
+    }
+
+    /// Writes the header, magic and version included.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        ensure!(
+            self.hashers.len() <= u8::max_value() as usize,
+            "too many hashers: {}",
+            self.hashers.len()
+        );
+
+        writer.write_all(&MULTI_PROOF_MAGIC)?;
+        writer.write_u16::<LittleEndian>(MULTI_PROOF_VERSION)?;
+        write_str(&mut writer, &self.circuit_id)?;
+        writer.write_u32::<LittleEndian>(self.partitions as u32)?;
+        writer.write_u8(self.hashers.len() as u8)?;
+        for hasher in &self.hashers {
+            write_str(&mut writer, hasher)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a header, magic and version included.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        ensure!(magic == MULTI_PROOF_MAGIC, "not an enveloped proof");
+
+        Self::read_after_magic(reader)
+    }
+
+    fn read_after_magic<R: Read>(mut reader: R) -> Result<Self> {
+        let version = reader.read_u16::<LittleEndian>()?;
+        ensure!(
+            version == MULTI_PROOF_VERSION,
+            "unsupported proof envelope version {} (expected {})",
+            version,
+            MULTI_PROOF_VERSION
+        );
+
+        let circuit_id = read_str(&mut reader).context("invalid circuit id")?;
+        let partitions = reader.read_u32::<LittleEndian>()? as usize;
+        let num_hashers = reader.read_u8()?;
+        let hashers = (0..num_hashers)
+            .map(|_| read_str(&mut reader).context("invalid hasher name"))
+            .collect::<Result<_>>()?;
+
+        Ok(ProofHeader {
+            circuit_id,
+            partitions,
+            hashers,
+        })
+    }
+
+    /// Fails with a description of the first difference if `self`, as read from a
+    /// proof, doesn't match what the verifier `expected`.
+    pub fn validate(&self, expected: &ProofHeader) -> Result<()> {
+        ensure!(
+            self.circuit_id == expected.circuit_id,
+            "proof is for circuit {}, expected {}",
+            self.circuit_id,
+            expected.circuit_id
+        );
+        ensure!(
+            self.partitions == expected.partitions,
+            "proof has {} partitions, expected {}",
+            self.partitions,
+            expected.partitions
+        );
+        ensure!(
+            self.hashers == expected.hashers,
+            "proof uses hashers {:?}, expected {:?}",
+            self.hashers,
+            expected.hashers
+        );
+
+        Ok(())
+    }
+}
+
+fn write_str<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+    ensure!(
+        s.len() <= u16::max_value() as usize,
+        "string too long: {} bytes",
+        s.len()
+    );
+
+    writer.write_u16::<LittleEndian>(s.len() as u16)?;
+    writer.write_all(s.as_bytes())?;
+
+    Ok(())
+}
+
+fn read_str<R: Read>(reader: &mut R) -> Result<String> {
+    let len = reader.read_u16::<LittleEndian>()? as usize;
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(String::from_utf8(bytes)?)
+}
 
 pub struct MultiProof<'a, E: Engine> {
     pub circuit_proofs: Vec<groth16::Proof<E>>,
@@ -37,6 +180,30 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(Self::new(proofs, verifying_key))
     }
 
+    /// Reads either an enveloped proof, whose header must match `expected`, or a
+    /// raw one of `expected.partitions` partitions.
+    pub fn new_from_envelope_reader<R: Read>(
+        expected: &ProofHeader,
+        mut reader: R,
+        verifying_key: &'a groth16::VerifyingKey<E>,
+    ) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if magic == MULTI_PROOF_MAGIC {
+            let header = ProofHeader::read_after_magic(&mut reader)?;
+            header.validate(expected)?;
+
+            Self::new_from_reader(Some(header.partitions), reader, verifying_key)
+        } else {
+            Self::new_from_reader(
+                Some(expected.partitions),
+                Cursor::new(magic).chain(reader),
+                verifying_key,
+            )
+        }
+    }
+
     pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
         for proof in &self.circuit_proofs {
             proof.write(&mut writer)?
@@ -44,9 +211,114 @@ impl<'a, E: Engine> MultiProof<'a, E> {
         Ok(())
     }
 
+    /// Writes the proofs prefixed by `header`, see the module documentation.
+    pub fn write_envelope<W: Write>(&self, header: &ProofHeader, mut writer: W) -> Result<()> {
+        ensure!(
+            header.partitions == self.circuit_proofs.len(),
+            "header declares {} partitions, but there are {} proofs",
+            header.partitions,
+            self.circuit_proofs.len()
+        );
+
+        header.write(&mut writer)?;
+        self.write(writer)
+    }
+
     pub fn to_vec(&self) -> Result<Vec<u8>> {
         let mut out = Vec::new();
         self.write(&mut out).context("known allocation target")?;
         Ok(out)
     }
+
+    pub fn to_envelope_vec(&self, header: &ProofHeader) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write_envelope(header, &mut out)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Proves knowledge of `x` such that `x * x` is the public input.
+    struct Square(Option<Fr>);
+
+    impl Circuit<Bls12> for Square {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> std::result::Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.0.ok_or(SynthesisError::AssignmentMissing))?;
+            let square = cs.alloc_input(
+                || "square",
+                || {
+                    let mut x = self.0.ok_or(SynthesisError::AssignmentMissing)?;
+                    x.square();
+                    Ok(x)
+                },
+            )?;
+            cs.enforce(|| "x * x", |lc| lc + x, |lc| lc + x, |lc| lc + square);
+
+            Ok(())
+        }
+    }
+
+    fn header(partitions: usize) -> ProofHeader {
+        ProofHeader::new(
+            "stacked-proof-of-replication-test".into(),
+            partitions,
+            vec!["PedersenHasher".into(), "Sha256Hasher".into()],
+        )
+    }
+
+    #[test]
+    fn test_multi_proof_envelope() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let params = groth16::generate_random_parameters::<Bls12, _, _>(Square(None), rng)
+            .expect("failed to generate parameters");
+        let circuit_proofs = (0..2)
+            .map(|_| groth16::create_random_proof(Square(Some(Fr::random(rng))), &params, rng))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .expect("failed to create proofs");
+        let proof = MultiProof::new(circuit_proofs, &params.vk);
+
+        let raw = proof.to_vec().unwrap();
+        let enveloped = proof.to_envelope_vec(&header(2)).unwrap();
+        assert_eq!(&enveloped[..4], &MULTI_PROOF_MAGIC);
+        assert_eq!(&enveloped[enveloped.len() - raw.len()..], &raw[..]);
+
+        // Both the envelope and legacy raw proofs are read back.
+        for bytes in &[&raw, &enveloped] {
+            let read =
+                MultiProof::new_from_envelope_reader(&header(2), &bytes[..], &params.vk).unwrap();
+            assert_eq!(read.circuit_proofs, proof.circuit_proofs);
+        }
+
+        let mut other_circuit = header(2);
+        other_circuit.circuit_id = "proof-of-spacetime-election-test".into();
+        let mut other_hashers = header(2);
+        other_hashers.hashers.pop();
+
+        for expected in &[other_circuit, other_hashers, header(1)] {
+            assert!(
+                MultiProof::new_from_envelope_reader(expected, &enveloped[..], &params.vk).is_err()
+            );
+        }
+
+        let mut bad_version = enveloped.clone();
+        bad_version[4] = 0xff;
+        assert!(
+            MultiProof::new_from_envelope_reader(&header(2), &bad_version[..], &params.vk).is_err()
+        );
+
+        assert!(proof.to_envelope_vec(&header(3)).is_err());
+    }
 }
@@ -0,0 +1,394 @@
+//! A constraint system recording the full R1CS of a circuit, so it can be exported
+//! and inspected outside of this crate.
+//!
+//! Variables (wires) are numbered with all the public inputs first, starting with
+//! the constant `ONE` at index `0`, followed by all the private (auxiliary) inputs:
+//! public input `i` is wire `i` and private input `j` is wire `num_public_inputs + j`.
+//! Every constraint `A * B = C` is stored as three sparse rows of `(wire, coefficient)`
+//! terms, with repeated wires merged and zero coefficients dropped.
+//!
+//! # JSON format
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "field_modulus": "0x73ed...0001",
+//!   "num_public_inputs": 3,
+//!   "num_private_inputs": 10,
+//!   "num_constraints": 12,
+//!   "public_inputs": ["ONE", "path/to/input", ...],
+//!   "private_inputs": ["path/to/aux", ...],
+//!   "constraints": [
+//!     { "name": "path/to/constraint", "a": [[wire, "0x..."], ...], "b": [...], "c": [...] },
+//!     ...
+//!   ]
+//! }
+//! ```
+//!
+//! Field elements (the modulus and the coefficients) are big-endian hex strings of
+//! their canonical representation. Names are the full namespace paths of the objects.
+//!
+//! # Binary format
+//!
+//! All integers are little-endian, field elements are `field_size` bytes long in
+//! little-endian order. Names are not included.
+//!
+//! ```text
+//! magic               4 bytes    "R1CS"
+//! version             u32        1
+//! field_size          u32        number of bytes of a field element
+//! field_modulus       field_size
+//! num_public_inputs   u64        including `ONE`
+//! num_private_inputs  u64
+//! num_constraints     u64
+//! constraints         num_constraints times:
+//!   a, b, c           each:
+//!     num_terms       u64
+//!     terms           num_terms times: wire (u64), coefficient (field_size)
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use bellperson::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use byteorder::{LittleEndian, WriteBytesExt};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+use paired::{Engine, ScalarEngine};
+use serde::Serialize;
+
+use crate::error::Result;
+
+pub const R1CS_MAGIC: &[u8; 4] = b"R1CS";
+pub const R1CS_VERSION: u32 = 1;
+
+/// A sparse row of a constraint, as `(wire, coefficient)` terms.
+pub type R1CSRow<E> = Vec<(usize, <E as ScalarEngine>::Fr)>;
+
+#[derive(Debug)]
+struct RecordedConstraint<E: Engine> {
+    name: String,
+    a: Vec<(Index, E::Fr)>,
+    b: Vec<(Index, E::Fr)>,
+    c: Vec<(Index, E::Fr)>,
+}
+
+#[derive(Debug)]
+pub struct R1CSExportCS<E: Engine> {
+    current_namespace: Vec<String>,
+    constraints: Vec<RecordedConstraint<E>>,
+    inputs: Vec<String>,
+    aux: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JsonR1CS<'a> {
+    version: u32,
+    field_modulus: String,
+    num_public_inputs: usize,
+    num_private_inputs: usize,
+    num_constraints: usize,
+    public_inputs: &'a [String],
+    private_inputs: &'a [String],
+    constraints: Vec<JsonConstraint<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonConstraint<'a> {
+    name: &'a str,
+    a: Vec<(usize, String)>,
+    b: Vec<(usize, String)>,
+    c: Vec<(usize, String)>,
+}
+
+impl<E: Engine> R1CSExportCS<E> {
+    pub fn new() -> Self {
+        R1CSExportCS::default()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Number of public inputs, including `ONE`.
+    pub fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.aux.len()
+    }
+
+    /// Returns the name and the sparse `A`, `B` and `C` rows of every constraint.
+    pub fn constraints(&self) -> Vec<(&str, R1CSRow<E>, R1CSRow<E>, R1CSRow<E>)> {
+        self.constraints
+            .iter()
+            .map(|constraint| {
+                (
+                    constraint.name.as_str(),
+                    self.row(&constraint.a),
+                    self.row(&constraint.b),
+                    self.row(&constraint.c),
+                )
+            })
+            .collect()
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<()> {
+        let hex_row = |row: R1CSRow<E>| -> Vec<(usize, String)> {
+            row.into_iter()
+                .map(|(wire, coeff)| (wire, fr_to_hex::<E>(&coeff)))
+                .collect()
+        };
+
+        let r1cs = JsonR1CS {
+            version: R1CS_VERSION,
+            field_modulus: repr_to_hex(&E::Fr::char())?,
+            num_public_inputs: self.num_inputs(),
+            num_private_inputs: self.num_aux(),
+            num_constraints: self.num_constraints(),
+            public_inputs: &self.inputs,
+            private_inputs: &self.aux,
+            constraints: self
+                .constraints()
+                .into_iter()
+                .map(|(name, a, b, c)| JsonConstraint {
+                    name,
+                    a: hex_row(a),
+                    b: hex_row(b),
+                    c: hex_row(c),
+                })
+                .collect(),
+        };
+
+        serde_json::to_writer(writer, &r1cs)?;
+
+        Ok(())
+    }
+
+    pub fn write_binary<W: Write>(&self, mut writer: W) -> Result<()> {
+        let modulus = E::Fr::char();
+
+        writer.write_all(R1CS_MAGIC)?;
+        writer.write_u32::<LittleEndian>(R1CS_VERSION)?;
+        writer.write_u32::<LittleEndian>(field_size::<E>() as u32)?;
+        modulus.write_le(&mut writer)?;
+        writer.write_u64::<LittleEndian>(self.num_inputs() as u64)?;
+        writer.write_u64::<LittleEndian>(self.num_aux() as u64)?;
+        writer.write_u64::<LittleEndian>(self.num_constraints() as u64)?;
+
+        for (_, a, b, c) in self.constraints() {
+            for row in &[a, b, c] {
+                writer.write_u64::<LittleEndian>(row.len() as u64)?;
+                for (wire, coeff) in row {
+                    writer.write_u64::<LittleEndian>(*wire as u64)?;
+                    coeff.into_repr().write_le(&mut writer)?;
+                }
+            }
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn wire(&self, index: Index) -> usize {
+        match index {
+            Index::Input(i) => i,
+            Index::Aux(i) => self.inputs.len() + i,
+        }
+    }
+
+    fn row(&self, terms: &[(Index, E::Fr)]) -> R1CSRow<E> {
+        let mut row = BTreeMap::new();
+        for &(index, coeff) in terms {
+            row.entry(self.wire(index))
+                .or_insert_with(E::Fr::zero)
+                .add_assign(&coeff);
+        }
+
+        row.into_iter()
+            .filter(|(_, coeff)| !coeff.is_zero())
+            .collect()
+    }
+}
+
+impl<E: Engine> Default for R1CSExportCS<E> {
+    fn default() -> Self {
+        R1CSExportCS {
+            current_namespace: vec![],
+            constraints: vec![],
+            inputs: vec![String::from("ONE")],
+            aux: vec![],
+        }
+    }
+}
+
+impl<E: Engine> ConstraintSystem<E> for R1CSExportCS<E> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // The witness is not needed (nor available for blank circuits).
+        let path = compute_path(&self.current_namespace, &annotation().into());
+        self.aux.push(path);
+
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<E::Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let path = compute_path(&self.current_namespace, &annotation().into());
+        self.inputs.push(path);
+
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LB: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+        LC: FnOnce(LinearCombination<E>) -> LinearCombination<E>,
+    {
+        let name = compute_path(&self.current_namespace, &annotation().into());
+        let terms = |lc: LinearCombination<E>| -> Vec<(Index, E::Fr)> {
+            lc.as_ref()
+                .iter()
+                .map(|(var, coeff)| (var.get_unchecked(), *coeff))
+                .collect()
+        };
+
+        self.constraints.push(RecordedConstraint {
+            name,
+            a: terms(a(LinearCombination::zero())),
+            b: terms(b(LinearCombination::zero())),
+            c: terms(c(LinearCombination::zero())),
+        });
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.current_namespace.push(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        assert!(self.current_namespace.pop().is_some());
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+fn compute_path(ns: &[String], this: &str) -> String {
+    assert!(
+        !this.chars().any(|a| a == '/'),
+        "'/' is not allowed in names"
+    );
+
+    if ns.is_empty() {
+        return this.to_string();
+    }
+
+    format!("{}/{}", ns.join("/"), this)
+}
+
+fn field_size<E: Engine>() -> usize {
+    E::Fr::char().as_ref().len() * 8
+}
+
+fn repr_to_hex<R: PrimeFieldRepr>(repr: &R) -> Result<String> {
+    let mut bytes = Vec::with_capacity(repr.as_ref().len() * 8);
+    repr.write_be(&mut bytes)?;
+
+    Ok(format!("0x{}", hex::encode(bytes)))
+}
+
+fn fr_to_hex<E: Engine>(fr: &E::Fr) -> String {
+    repr_to_hex(&fr.into_repr()).expect("writing to a vec can't fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use paired::bls12_381::{Bls12, Fr};
+
+    // x * y = z, with z public and a duplicated (zero-sum) term in `A`.
+    fn synthesize(cs: &mut R1CSExportCS<Bls12>) {
+        let x = cs.alloc(|| "x", || Ok(Fr::one())).unwrap();
+        let y = cs.alloc(|| "y", || Ok(Fr::one())).unwrap();
+        let z = {
+            let mut cs = cs.namespace(|| "out");
+            cs.alloc_input(|| "z", || Ok(Fr::one())).unwrap()
+        };
+
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        cs.enforce(
+            || "mul",
+            |lc| lc + x + y + (minus_one, y),
+            |lc| lc + y,
+            |lc| lc + z,
+        );
+    }
+
+    #[test]
+    fn test_r1cs_export_rows() {
+        let mut cs = R1CSExportCS::<Bls12>::new();
+        synthesize(&mut cs);
+
+        assert_eq!(cs.num_inputs(), 2);
+        assert_eq!(cs.num_aux(), 2);
+        assert_eq!(cs.num_constraints(), 1);
+
+        let constraints = cs.constraints();
+        let (name, a, b, c) = &constraints[0];
+        assert_eq!(*name, "mul");
+        // Public inputs come first: ONE = 0, z = 1, x = 2, y = 3.
+        assert_eq!(a, &vec![(2, Fr::one())]);
+        assert_eq!(b, &vec![(3, Fr::one())]);
+        assert_eq!(c, &vec![(1, Fr::one())]);
+    }
+
+    #[test]
+    fn test_r1cs_export_formats() {
+        let mut cs = R1CSExportCS::<Bls12>::new();
+        synthesize(&mut cs);
+
+        let mut json = Vec::new();
+        cs.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["num_public_inputs"], 2);
+        assert_eq!(json["num_private_inputs"], 2);
+        assert_eq!(json["public_inputs"][1], "out/z");
+        assert_eq!(json["constraints"][0]["name"], "mul");
+        assert_eq!(
+            json["field_modulus"],
+            "0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001"
+        );
+
+        let mut binary = Vec::new();
+        cs.write_binary(&mut binary).unwrap();
+        assert_eq!(&binary[0..4], R1CS_MAGIC);
+        assert_eq!(LittleEndian::read_u32(&binary[4..8]), R1CS_VERSION);
+        assert_eq!(LittleEndian::read_u32(&binary[8..12]), 32);
+        // Header, then one term in each of the 3 rows.
+        assert_eq!(binary.len(), 12 + 32 + 3 * 8 + 3 * (8 + 8 + 32));
+        assert_eq!(LittleEndian::read_u64(&binary[44..52]), 2);
+        assert_eq!(LittleEndian::read_u64(&binary[60..68]), 1);
+    }
+}
@@ -418,6 +418,7 @@ mod tests {
             .expect("failed to synthesize circuit");
 
         assert!(cs.is_satisfied(), "constraints not satisfied");
+        assert!(cs.is_fully_constrained(), "unconstrained variables");
 
         assert_eq!(cs.num_inputs(), 5, "wrong number of inputs");
         assert_eq!(
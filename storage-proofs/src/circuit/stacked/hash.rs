@@ -86,6 +86,7 @@ mod tests {
             .expect("hash2 function failed");
 
             assert!(cs.is_satisfied(), "constraints not satisfied");
+            assert!(cs.is_fully_constrained(), "unconstrained variables");
             assert_eq!(cs.num_constraints(), 1_371);
 
             let expected: Fr =
@@ -113,6 +114,7 @@ mod tests {
                 .expect("hash_single_column function failed");
 
             assert!(cs.is_satisfied(), "constraints not satisfied");
+            assert!(cs.is_fully_constrained(), "unconstrained variables");
             assert_eq!(cs.num_constraints(), 1_239);
 
             let expected: Fr = vanilla_hash_single_column(&vals).into();
@@ -19,15 +19,24 @@ use crate::stacked::{
 };
 
 #[derive(Debug, Clone)]
-pub struct Proof<H: Hasher, G: Hasher> {
+pub struct Proof<H: Hasher, G: Hasher, U = typenum::U4>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     pub comm_d_proof: InclusionPath<Bls12, G, typenum::U2>,
-    pub comm_r_last_proof: InclusionPath<Bls12, H, typenum::U4>,
+    pub comm_r_last_proof: InclusionPath<Bls12, H, U>,
     pub replica_column_proof: ReplicaColumnProof<H>,
     pub labeling_proofs: Vec<(usize, LabelingProof)>,
     pub encoding_proof: EncodingProof,
 }
 
-impl<H: Hasher, G: Hasher> Proof<H, G> {
+impl<H: Hasher, G: Hasher, U> Proof<H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     /// Create an empty proof, used in `blank_circuit`s.
     pub fn empty(params: &PublicParams<H>) -> Self {
         let layers = params.layer_challenges.layers();
@@ -118,8 +127,12 @@ impl<H: Hasher, G: Hasher> Proof<H, G> {
     }
 }
 
-impl<H: Hasher, G: Hasher> From<VanillaProof<H, G>> for Proof<H, G> {
-    fn from(vanilla_proof: VanillaProof<H, G>) -> Self {
+impl<H: Hasher, G: Hasher, U> From<VanillaProof<H, G, U>> for Proof<H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
+    fn from(vanilla_proof: VanillaProof<H, G, U>) -> Self {
         let VanillaProof {
             comm_d_proofs,
             comm_r_last_proof,
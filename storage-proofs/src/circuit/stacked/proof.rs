@@ -13,7 +13,7 @@ use crate::crypto::pedersen::JJ_PARAMS;
 use crate::drgraph::Graph;
 use crate::error::Result;
 use crate::fr32::fr_into_bytes;
-use crate::hasher::{HashFunction, Hasher};
+use crate::hasher::{HashFunction, Hasher, PoseidonArity, PoseidonEngine};
 use crate::merklepor;
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::proof::ProofScheme;
@@ -25,10 +25,20 @@ use crate::util::bytes_into_boolean_vec_be;
 /// # Fields
 ///
 /// * `params` - parameters for the curve
+/// * `U` - the arity of `tree_r_last`
 ///
-pub struct StackedCircuit<'a, E: JubjubEngine, H: 'static + Hasher, G: 'static + Hasher> {
+pub struct StackedCircuit<
+    'a,
+    E: JubjubEngine,
+    H: 'static + Hasher,
+    G: 'static + Hasher,
+    U = typenum::U4,
+> where
+    U: 'static + PoseidonArity<Bls12>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     params: &'a E::Params,
-    public_params: <StackedDrg<'a, H, G> as ProofScheme<'a>>::PublicParams,
+    public_params: <StackedDrg<'a, H, G, U> as ProofScheme<'a>>::PublicParams,
     replica_id: Option<H::Domain>,
     comm_d: Option<G::Domain>,
     comm_r: Option<H::Domain>,
@@ -36,32 +46,42 @@ pub struct StackedCircuit<'a, E: JubjubEngine, H: 'static + Hasher, G: 'static +
     comm_c: Option<H::Domain>,
 
     // one proof per challenge
-    proofs: Vec<Proof<H, G>>,
+    proofs: Vec<Proof<H, G, U>>,
 
     _e: PhantomData<E>,
 }
 
-impl<'a, E: JubjubEngine, H: Hasher, G: Hasher> CircuitComponent for StackedCircuit<'a, E, H, G> {
+impl<'a, E: JubjubEngine, H: Hasher, G: Hasher, U> CircuitComponent
+    for StackedCircuit<'a, E, H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     type ComponentPrivateInputs = ();
 }
 
-impl<'a, H: Hasher, G: 'static + Hasher> StackedCircuit<'a, Bls12, H, G> {
+impl<'a, H: Hasher, G: 'static + Hasher, U> StackedCircuit<'a, Bls12, H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     #[allow(clippy::too_many_arguments)]
     pub fn synthesize<CS>(
         mut cs: CS,
         params: &'a <Bls12 as JubjubEngine>::Params,
-        public_params: <StackedDrg<'a, H, G> as ProofScheme<'a>>::PublicParams,
+        public_params: <StackedDrg<'a, H, G, U> as ProofScheme<'a>>::PublicParams,
         replica_id: Option<H::Domain>,
         comm_d: Option<G::Domain>,
         comm_r: Option<H::Domain>,
         comm_r_last: Option<H::Domain>,
         comm_c: Option<H::Domain>,
-        proofs: Vec<Proof<H, G>>,
+        proofs: Vec<Proof<H, G, U>>,
     ) -> Result<(), SynthesisError>
     where
         CS: ConstraintSystem<Bls12>,
     {
-        let circuit = StackedCircuit::<'a, Bls12, H, G> {
+        let circuit = StackedCircuit::<'a, Bls12, H, G, U> {
             params,
             public_params,
             replica_id,
@@ -77,7 +97,12 @@ impl<'a, H: Hasher, G: 'static + Hasher> StackedCircuit<'a, Bls12, H, G> {
     }
 }
 
-impl<'a, H: Hasher, G: Hasher> Circuit<Bls12> for StackedCircuit<'a, Bls12, H, G> {
+impl<'a, H: Hasher, G: Hasher, U> Circuit<Bls12> for StackedCircuit<'a, Bls12, H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+{
     fn synthesize<CS: ConstraintSystem<Bls12>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
         let StackedCircuit {
             public_params,
@@ -171,27 +196,43 @@ impl<'a, H: Hasher, G: Hasher> Circuit<Bls12> for StackedCircuit<'a, Bls12, H, G
 }
 
 #[allow(dead_code)]
-pub struct StackedCompound<H: Hasher, G: Hasher> {
+pub struct StackedCompound<H: Hasher, G: Hasher, U: typenum::Unsigned = typenum::U4> {
     partitions: Option<usize>,
     _h: PhantomData<H>,
     _g: PhantomData<G>,
+    _u: PhantomData<U>,
 }
 
-impl<E: JubjubEngine, C: Circuit<E>, P: ParameterSetMetadata, H: Hasher, G: Hasher>
-    CacheableParameters<E, C, P> for StackedCompound<H, G>
+impl<
+        E: JubjubEngine,
+        C: Circuit<E>,
+        P: ParameterSetMetadata,
+        H: Hasher,
+        G: Hasher,
+        U: typenum::Unsigned,
+    > CacheableParameters<E, C, P> for StackedCompound<H, G, U>
 {
     fn cache_prefix() -> String {
-        format!("stacked-proof-of-replication-{}-{}", H::name(), G::name())
+        format!(
+            "stacked-proof-of-replication-{}-{}-{}",
+            H::name(),
+            G::name(),
+            U::to_usize()
+        )
     }
 }
 
-impl<'a, H: 'static + Hasher, G: 'static + Hasher>
-    CompoundProof<'a, Bls12, StackedDrg<'a, H, G>, StackedCircuit<'a, Bls12, H, G>>
-    for StackedCompound<H, G>
+impl<'a, H: 'static + Hasher, G: 'static + Hasher, U>
+    CompoundProof<'a, Bls12, StackedDrg<'a, H, G, U>, StackedCircuit<'a, Bls12, H, G, U>>
+    for StackedCompound<H, G, U>
+where
+    U: 'static + PoseidonArity<Bls12>,
+    Bls12: PoseidonEngine<U>,
+    typenum::Add1<U>: generic_array::ArrayLength<Fr>,
 {
     fn generate_public_inputs(
-        pub_in: &<StackedDrg<H, G> as ProofScheme>::PublicInputs,
-        pub_params: &<StackedDrg<H, G> as ProofScheme>::PublicParams,
+        pub_in: &<StackedDrg<H, G, U> as ProofScheme>::PublicInputs,
+        pub_params: &<StackedDrg<H, G, U> as ProofScheme>::PublicParams,
         k: Option<usize>,
     ) -> Result<Vec<Fr>> {
         let graph = &pub_params.graph;
@@ -218,6 +259,15 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher>
             PoRCompound::<H, typenum::U4>::generate_public_inputs(&pub_inputs, &por_params, k)
         };
 
+        let generate_tree_r_last_inclusion_inputs = |c: usize| {
+            let pub_inputs = merklepor::PublicInputs::<H::Domain> {
+                challenge: c,
+                commitment: None,
+            };
+
+            PoRCompound::<H, U>::generate_public_inputs(&pub_inputs, &por_params, k)
+        };
+
         let all_challenges = pub_in.challenges(&pub_params.layer_challenges, graph.size(), k);
 
         for challenge in all_challenges.into_iter() {
@@ -255,18 +305,18 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher>
             }
 
             // final replica layer
-            inputs.extend(generate_inclusion_inputs(challenge)?);
+            inputs.extend(generate_tree_r_last_inclusion_inputs(challenge)?);
         }
 
         Ok(inputs)
     }
 
     fn circuit<'b>(
-        public_inputs: &'b <StackedDrg<H, G> as ProofScheme>::PublicInputs,
-        _component_private_inputs: <StackedCircuit<'a, Bls12, H, G> as CircuitComponent>::ComponentPrivateInputs,
-        vanilla_proof: &'b <StackedDrg<H, G> as ProofScheme>::Proof,
-        public_params: &'b <StackedDrg<H, G> as ProofScheme>::PublicParams,
-    ) -> Result<StackedCircuit<'a, Bls12, H, G>> {
+        public_inputs: &'b <StackedDrg<H, G, U> as ProofScheme>::PublicInputs,
+        _component_private_inputs: <StackedCircuit<'a, Bls12, H, G, U> as CircuitComponent>::ComponentPrivateInputs,
+        vanilla_proof: &'b <StackedDrg<H, G, U> as ProofScheme>::Proof,
+        public_params: &'b <StackedDrg<H, G, U> as ProofScheme>::PublicParams,
+    ) -> Result<StackedCircuit<'a, Bls12, H, G, U>> {
         assert!(
             !vanilla_proof.is_empty(),
             "Cannot create a circuit with no vanilla proofs"
@@ -295,8 +345,8 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher>
     }
 
     fn blank_circuit(
-        public_params: &<StackedDrg<H, G> as ProofScheme>::PublicParams,
-    ) -> StackedCircuit<'a, Bls12, H, G> {
+        public_params: &<StackedDrg<H, G, U> as ProofScheme>::PublicParams,
+    ) -> StackedCircuit<'a, Bls12, H, G, U> {
         StackedCircuit {
             params: &*JJ_PARAMS,
             public_params: public_params.clone(),
@@ -400,7 +450,8 @@ mod tests {
         // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
         // elements based on the configs stored in TemporaryAux.
         use crate::stacked::TemporaryAuxCache;
-        let t_aux = TemporaryAuxCache::new(&t_aux).expect("failed to restore contents of t_aux");
+        let t_aux = TemporaryAuxCache::<H, Sha256Hasher>::new(&t_aux)
+            .expect("failed to restore contents of t_aux");
 
         let priv_inputs = PrivateInputs::<H, Sha256Hasher> {
             p_aux: p_aux.into(),
@@ -452,6 +503,7 @@ mod tests {
         .expect("failed to synthesize circuit");
 
         assert!(cs.is_satisfied(), "constraints not satisfied");
+        assert!(cs.is_fully_constrained(), "unconstrained variables");
         assert_eq!(cs.num_inputs(), expected_inputs, "wrong number of inputs");
         assert_eq!(
             cs.num_constraints(),
@@ -485,17 +537,28 @@ mod tests {
     #[test]
     #[ignore] // Slow test – run only when compiled for release.
     fn test_stacked_compound_pedersen() {
-        stacked_test_compound::<PedersenHasher>();
+        stacked_test_compound::<PedersenHasher, typenum::U4>(16);
     }
 
     #[test]
     #[ignore] // Slow test – run only when compiled for release.
     fn test_stacked_compound_poseidon() {
-        stacked_test_compound::<PoseidonHasher>();
+        stacked_test_compound::<PoseidonHasher, typenum::U4>(16);
     }
 
-    fn stacked_test_compound<H: 'static + Hasher>() {
-        let nodes = 16;
+    #[test]
+    #[ignore] // Slow test – run only when compiled for release.
+    fn test_stacked_compound_poseidon_oct() {
+        // 8-ary trees need a power of 8 leaves.
+        stacked_test_compound::<PoseidonHasher, typenum::U8>(64);
+    }
+
+    fn stacked_test_compound<H: 'static + Hasher, U>(nodes: usize)
+    where
+        U: 'static + PoseidonArity<Bls12>,
+        Bls12: PoseidonEngine<U>,
+        typenum::Add1<U>: generic_array::ArrayLength<Fr>,
+    {
         let degree = 3;
         let expansion_degree = 2;
         let num_layers = 2;
@@ -534,8 +597,9 @@ mod tests {
             StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
         );
 
-        let public_params = StackedCompound::setup(&setup_params).expect("setup failed");
-        let (tau, (p_aux, t_aux)) = StackedDrg::replicate(
+        let public_params =
+            StackedCompound::<H, Sha256Hasher, U>::setup(&setup_params).expect("setup failed");
+        let (tau, (p_aux, t_aux)) = StackedDrg::<H, Sha256Hasher, U>::replicate(
             &public_params.vanilla_params,
             &replica_id.into(),
             (&mut data_copy[..]).into(),
@@ -558,9 +622,10 @@ mod tests {
         // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
         // elements based on the configs stored in TemporaryAux.
         use crate::stacked::TemporaryAuxCache;
-        let t_aux = TemporaryAuxCache::new(&t_aux).expect("failed to restore contents of t_aux");
+        let t_aux = TemporaryAuxCache::<H, Sha256Hasher, U>::new(&t_aux)
+            .expect("failed to restore contents of t_aux");
 
-        let private_inputs = PrivateInputs::<H, Sha256Hasher> { p_aux, t_aux };
+        let private_inputs = PrivateInputs::<H, Sha256Hasher, U> { p_aux, t_aux };
 
         {
             let (circuit, inputs) =
@@ -588,9 +653,9 @@ mod tests {
             let (circuit1, _inputs) =
                 StackedCompound::circuit_for_test(&public_params, &public_inputs, &private_inputs)
                     .unwrap();
-            let blank_circuit = <StackedCompound<H, Sha256Hasher> as CompoundProof<
+            let blank_circuit = <StackedCompound<H, Sha256Hasher, U> as CompoundProof<
                 _,
-                StackedDrg<H, Sha256Hasher>,
+                StackedDrg<H, Sha256Hasher, U>,
                 _,
             >>::blank_circuit(&public_params.vanilla_params);
 
@@ -610,9 +675,9 @@ mod tests {
             }
         }
 
-        let blank_groth_params = <StackedCompound<H, Sha256Hasher> as CompoundProof<
+        let blank_groth_params = <StackedCompound<H, Sha256Hasher, U> as CompoundProof<
             _,
-            StackedDrg<H, Sha256Hasher>,
+            StackedDrg<H, Sha256Hasher, U>,
             _,
         >>::groth_params(&public_params.vanilla_params)
         .expect("failed to generate groth params");
@@ -15,6 +15,15 @@ enum NamedObject {
     Namespace,
 }
 
+/// Why `TestConstraintSystem::unconstrained_variables` reports a variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unconstrained {
+    /// The variable doesn't appear in any constraint.
+    Unused,
+    /// The variable only appears multiplied by a zero linear combination.
+    Free,
+}
+
 /// Constraint system for testing purposes.
 pub struct TestConstraintSystem<E: Engine> {
     named_objects: HashMap<String, NamedObject>,
@@ -244,6 +253,58 @@ impl<E: Engine> TestConstraintSystem<E> {
         // self.which_is_unsatisfied().is_none()
     }
 
+    /// Lists, by path, every variable (other than `ONE`) the recorded constraints
+    /// leave free to take any value.
+    ///
+    /// A variable is `Unused` if it never appears with a non-zero coefficient, and
+    /// `Free` if it only appears in `A` while `B` is zero (or the other way around),
+    /// so that every constraint mentioning it holds whatever its value is.
+    pub fn unconstrained_variables(&self) -> Vec<(String, Unconstrained)> {
+        let mut inputs = vec![None; self.inputs.len()];
+        let mut aux = vec![None; self.aux.len()];
+
+        for &(ref a, ref b, ref c, _) in &self.constraints {
+            let a = proc_lc::<E>(a.as_ref());
+            let b = proc_lc::<E>(b.as_ref());
+            let c = proc_lc::<E>(c.as_ref());
+
+            let terms = a
+                .keys()
+                .map(|var| (var, !b.is_empty()))
+                .chain(b.keys().map(|var| (var, !a.is_empty())))
+                .chain(c.keys().map(|var| (var, true)));
+
+            for (var, constrained) in terms {
+                let seen = match var.0.get_unchecked() {
+                    Index::Input(i) => &mut inputs[i],
+                    Index::Aux(i) => &mut aux[i],
+                };
+                *seen = Some(seen.unwrap_or(false) || constrained);
+            }
+        }
+
+        let inputs = self.inputs.iter().zip(inputs).skip(1);
+        let aux = self.aux.iter().zip(aux);
+
+        inputs
+            .chain(aux)
+            .filter_map(|(&(_, ref path), seen)| match seen {
+                None => Some((path.clone(), Unconstrained::Unused)),
+                Some(false) => Some((path.clone(), Unconstrained::Free)),
+                Some(true) => None,
+            })
+            .collect()
+    }
+
+    pub fn is_fully_constrained(&self) -> bool {
+        let unconstrained = self.unconstrained_variables();
+        for (path, kind) in &unconstrained {
+            println!("unconstrained ({:?}): {:?}", kind, path);
+        }
+
+        unconstrained.is_empty()
+    }
+
     pub fn num_constraints(&self) -> usize {
         self.constraints.len()
     }
@@ -462,4 +523,44 @@ mod tests {
 
         assert!(cs.get("test1/test2/hehe") == Fr::one());
     }
+
+    #[test]
+    fn test_unconstrained_variables() {
+        use paired::bls12_381::{Bls12, Fr};
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let one = TestConstraintSystem::<Bls12>::one();
+
+        let a = cs.alloc(|| "a", || Ok(Fr::one())).unwrap();
+        let b = cs.alloc(|| "b", || Ok(Fr::one())).unwrap();
+        let c = cs.alloc_input(|| "c", || Ok(Fr::one())).unwrap();
+        let free = cs.alloc(|| "free", || Ok(Fr::one())).unwrap();
+        cs.namespace(|| "unused")
+            .alloc(|| "var", || Ok(Fr::one()))
+            .unwrap();
+
+        cs.enforce(|| "mult", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+        cs.enforce(|| "zero", |lc| lc + free, |lc| lc, |lc| lc);
+        // Cancels out, so `free` is still not constrained by it.
+        cs.enforce(|| "cancel", |lc| lc + free - free, |lc| lc + one, |lc| lc);
+        assert!(cs.is_satisfied());
+
+        assert_eq!(
+            cs.unconstrained_variables(),
+            vec![
+                ("free".to_string(), Unconstrained::Free),
+                ("unused/var".to_string(), Unconstrained::Unused),
+            ]
+        );
+        assert!(!cs.is_fully_constrained());
+
+        cs.enforce(|| "bind", |lc| lc + free, |lc| lc + one, |lc| lc + a);
+        cs.enforce(
+            || "bind unused",
+            |lc| lc + (Fr::one(), Variable::new_unchecked(Index::Aux(3))),
+            |lc| lc + one,
+            |lc| lc + b,
+        );
+        assert!(cs.is_fully_constrained());
+    }
 }
@@ -1,8 +1,13 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
 use anyhow::{ensure, Context};
 use bellperson::{groth16, Circuit};
 use fil_sapling_crypto::jubjub::JubjubEngine;
+use lazy_static::lazy_static;
 use log::info;
 use rand::rngs::OsRng;
 
@@ -11,6 +16,8 @@ use crate::error::Result;
 use crate::parameter_cache::{CacheableParameters, ParameterSetMetadata};
 use crate::partitions;
 use crate::proof::ProofScheme;
+use crate::settings;
+use crate::trace;
 
 #[derive(Clone)]
 pub struct SetupParams<'a, S: ProofScheme<'a>> {
@@ -27,6 +34,29 @@ pub struct PublicParams<'a, S: ProofScheme<'a>> {
     pub priority: bool,
 }
 
+lazy_static! {
+    /// The pools synthesizing circuits, by `num_proving_threads`.
+    static ref PROVING_POOLS: Mutex<HashMap<usize, Arc<ThreadPool>>> = Default::default();
+}
+
+/// Returns the pool of `num_threads` threads synthesizing circuits, started by its first use.
+fn proving_pool(num_threads: usize) -> Result<Arc<ThreadPool>> {
+    let mut pools = PROVING_POOLS.lock().expect("proving pools lock poisoned");
+    if let Some(pool) = pools.get(&num_threads) {
+        return Ok(pool.clone());
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .thread_name(|i| format!("proving-{}", i))
+        .build()
+        .context("could not start the circuit synthesis threads")?;
+    let pool = Arc::new(pool);
+    pools.insert(num_threads, pool.clone());
+
+    Ok(pool)
+}
+
 /// CircuitComponent exists so parent components can pass private inputs to their subcomponents
 /// when calling CompoundProof::circuit directly. In general, there are no internal private inputs,
 /// and a default value will be passed. CompoundProof::circuit implementations should exhibit
@@ -218,18 +248,28 @@ pub trait CompoundProof<
     ) -> Result<Vec<groth16::Proof<E>>> {
         let mut rng = OsRng;
 
-        let circuits = vanilla_proof
-            .into_par_iter()
-            .map(|vanilla_proof| {
-                Self::circuit(
-                    &pub_in,
-                    C::ComponentPrivateInputs::default(),
-                    &vanilla_proof,
-                    &pub_params,
-                )
-            })
-            .collect::<Result<Vec<_>>>()?;
+        let synthesize = move || {
+            vanilla_proof
+                .into_par_iter()
+                .enumerate()
+                .map(|(k, vanilla_proof)| {
+                    let _span = trace::span("synthesize_circuit").arg("partition", k);
+                    Self::circuit(
+                        &pub_in,
+                        C::ComponentPrivateInputs::default(),
+                        &vanilla_proof,
+                        &pub_params,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        let circuits = match settings::current().num_proving_threads() {
+            0 => synthesize()?,
+            num_threads => proving_pool(num_threads)?.install(synthesize)?,
+        };
 
+        let _span = trace::span("groth_prove").arg("partitions", circuits.len());
         let groth_proofs = if priority {
             groth16::create_random_proof_batch_in_priority(circuits, groth_params, &mut rng)?
         } else {
@@ -319,3 +359,16 @@ pub trait CompoundProof<
         Ok((circuit, inputs))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proving_pools_are_reused() {
+        let pool = proving_pool(2).unwrap();
+        assert_eq!(pool.current_num_threads(), 2);
+        assert!(Arc::ptr_eq(&pool, &proving_pool(2).unwrap()));
+        assert!(!Arc::ptr_eq(&pool, &proving_pool(3).unwrap()));
+    }
+}
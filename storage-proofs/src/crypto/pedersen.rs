@@ -10,12 +10,8 @@ use crate::fr32::bytes_into_frs;
 use crate::settings;
 
 lazy_static! {
-    pub static ref JJ_PARAMS: JubjubBls12 = JubjubBls12::new_with_window_size(
-        settings::SETTINGS
-            .lock()
-            .unwrap()
-            .pedersen_hash_exp_window_size
-    );
+    pub static ref JJ_PARAMS: JubjubBls12 =
+        JubjubBls12::new_with_window_size(settings::read_pedersen_hash_exp_window_size());
 }
 
 pub const PEDERSEN_BLOCK_SIZE: usize = 256;
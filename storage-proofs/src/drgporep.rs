@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 use anyhow::{ensure, Context};
 use byteorder::{LittleEndian, WriteBytesExt};
 use generic_array::typenum;
+#[cfg(feature = "prover")]
 use merkletree::store::StoreConfig;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -15,7 +16,9 @@ use crate::fr32::bytes_into_fr_repr_safe;
 use crate::hasher::{Domain, Hasher};
 use crate::merkle::{BinaryMerkleTree, MerkleProof, MerkleTree};
 use crate::parameter_cache::ParameterSetMetadata;
-use crate::porep::{self, Data, PoRep};
+use crate::porep;
+#[cfg(feature = "prover")]
+use crate::porep::{Data, PoRep};
 use crate::proof::{NoRequirements, ProofScheme};
 use crate::util::{data_at_node, data_at_node_offset, NODE_SIZE};
 
@@ -423,6 +426,7 @@ where
     }
 }
 
+#[cfg(feature = "prover")]
 impl<'a, H, G> PoRep<'a, H, H> for DrgPoRep<'a, H, G>
 where
     H: 'a + Hasher,
@@ -20,11 +20,11 @@ use crate::hasher::{
     Domain, HashFunction, Hasher, PoseidonDomain, PoseidonFunction, PoseidonMDArity,
 };
 use crate::measurements::{measure_op, Operation};
-use crate::merkle::{MerkleProof, QuadLCMerkleTree};
+use crate::merkle::{LCMerkleTree, MerkleProof};
+use crate::metrics;
 use crate::parameter_cache::ParameterSetMetadata;
 use crate::proof::{NoRequirements, ProofScheme};
 use crate::sector::*;
-use crate::stacked::QUAD_ARITY;
 use crate::util::NODE_SIZE;
 
 #[derive(Debug, Clone)]
@@ -69,8 +69,8 @@ pub struct PublicInputs<T: Domain> {
 }
 
 #[derive(Debug)]
-pub struct PrivateInputs<H: Hasher> {
-    pub tree: QuadLCMerkleTree<H::Domain, H::Function>,
+pub struct PrivateInputs<H: Hasher, U: Unsigned = typenum::U4> {
+    pub tree: LCMerkleTree<H::Domain, H::Function, U>,
     pub comm_c: H::Domain,
     pub comm_r_last: H::Domain,
 }
@@ -96,17 +96,17 @@ impl fmt::Debug for Candidate {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Proof<H: Hasher> {
+pub struct Proof<H: Hasher, U: Unsigned = typenum::U4> {
     #[serde(bound(
-        serialize = "MerkleProof<H, typenum::U4>: Serialize",
-        deserialize = "MerkleProof<H, typenum::U4>: Deserialize<'de>"
+        serialize = "MerkleProof<H, U>: Serialize",
+        deserialize = "MerkleProof<H, U>: Deserialize<'de>"
     ))]
-    inclusion_proofs: Vec<MerkleProof<H, typenum::U4>>,
+    inclusion_proofs: Vec<MerkleProof<H, U>>,
     pub ticket: [u8; 32],
     pub comm_c: H::Domain,
 }
 
-impl<H: Hasher> Proof<H> {
+impl<H: Hasher, U: Unsigned> Proof<H, U> {
     pub fn leafs(&self) -> Vec<H::Domain> {
         self.inclusion_proofs
             .iter()
@@ -134,18 +134,21 @@ impl<H: Hasher> Proof<H> {
     }
 }
 
+/// Election PoSt over replicas whose `tree_r_last` has arity `U`.
 #[derive(Debug, Clone)]
-pub struct ElectionPoSt<'a, H>
+pub struct ElectionPoSt<'a, H, U = typenum::U4>
 where
     H: 'a + Hasher,
+    U: Unsigned,
 {
     _h: PhantomData<&'a H>,
+    _u: PhantomData<U>,
 }
 
-pub fn generate_candidates<H: Hasher>(
+pub fn generate_candidates<H: Hasher, U: Unsigned>(
     pub_params: &PublicParams,
     challenged_sectors: &[SectorId],
-    trees: &BTreeMap<SectorId, QuadLCMerkleTree<H::Domain, H::Function>>,
+    trees: &BTreeMap<SectorId, LCMerkleTree<H::Domain, H::Function, U>>,
     prover_id: H::Domain,
     randomness: H::Domain,
 ) -> Result<Vec<Candidate>> {
@@ -158,7 +161,7 @@ pub fn generate_candidates<H: Hasher>(
                 None => bail!(Error::MissingPrivateInput("tree", (*sector_id).into())),
             };
 
-            generate_candidate::<H>(
+            generate_candidate::<H, U>(
                 pub_params,
                 tree,
                 prover_id,
@@ -170,9 +173,9 @@ pub fn generate_candidates<H: Hasher>(
         .collect()
 }
 
-fn generate_candidate<H: Hasher>(
+fn generate_candidate<H: Hasher, U: Unsigned>(
     pub_params: &PublicParams,
-    tree: &QuadLCMerkleTree<H::Domain, H::Function>,
+    tree: &LCMerkleTree<H::Domain, H::Function, U>,
     prover_id: H::Domain,
     sector_id: SectorId,
     randomness: H::Domain,
@@ -196,6 +199,10 @@ fn generate_candidate<H: Hasher>(
         .into();
         data.push(val.into());
     }
+    metrics::record_bytes_processed(
+        &Operation::PostReadChallengedRange,
+        pub_params.challenge_count as u64 * NODE_SIZE as u64,
+    );
 
     // pad for md
     let arity = PoseidonMDArity::to_usize();
@@ -311,12 +318,14 @@ pub fn generate_leaf_challenge<T: Domain>(
     Ok(challenged_range_index * pub_params.challenged_nodes as u64)
 }
 
-impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
+impl<'a, H: 'a + Hasher, U: 'a + Unsigned + Sync + Send> ProofScheme<'a>
+    for ElectionPoSt<'a, H, U>
+{
     type PublicParams = PublicParams;
     type SetupParams = SetupParams;
     type PublicInputs = PublicInputs<H::Domain>;
-    type PrivateInputs = PrivateInputs<H>;
-    type Proof = Proof<H>;
+    type PrivateInputs = PrivateInputs<H, U>;
+    type Proof = Proof<H, U>;
     type Requirements = NoRequirements;
 
     fn setup(sp: &Self::SetupParams) -> Result<Self::PublicParams> {
@@ -340,7 +349,7 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
             "Generating proof for tree of len {} with leafs {}, and cached_layers {}",
             tree.len(),
             tree_leafs,
-            StoreConfig::default_cached_above_base_layer(tree_leafs, QUAD_ARITY)
+            StoreConfig::default_cached_above_base_layer(tree_leafs, U::to_usize())
         );
         let inclusion_proofs = measure_op(Operation::PostInclusionProofs, || {
             (0..pub_params.challenge_count)
@@ -360,7 +369,8 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
                             let (proof, _) = tree.gen_proof_and_partial_tree(
                                 challenged_leaf_start as usize + i,
                                 StoreConfig::default_cached_above_base_layer(
-                                    tree_leafs, QUAD_ARITY,
+                                    tree_leafs,
+                                    U::to_usize(),
                                 ),
                             )?;
                             Ok(MerkleProof::new_from_proof(&proof))
@@ -415,7 +425,7 @@ impl<'a, H: 'a + Hasher> ProofScheme<'a> for ElectionPoSt<'a, H> {
 
                 // validate the path length
                 let expected_path_length =
-                    graph_height::<typenum::U4>(pub_params.sector_size as usize / NODE_SIZE) - 1;
+                    graph_height::<U>(pub_params.sector_size as usize / NODE_SIZE) - 1;
                 if expected_path_length != merkle_proof.path().len() {
                     return Ok(false);
                 }
@@ -441,9 +451,9 @@ mod tests {
     use crate::drgraph::{new_seed, BucketGraph, Graph, BASE_DEGREE};
     use crate::fr32::fr_into_bytes;
     use crate::hasher::{PedersenHasher, PoseidonHasher};
-    use crate::merkle::QuadMerkleTree;
+    use crate::merkle::MerkleTree;
 
-    fn test_election_post<H: Hasher>() {
+    fn test_election_post<H: Hasher, U: 'static + Unsigned + Sync + Send>() {
         use merkletree::store::StoreConfigDataVersion;
 
         let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
@@ -464,7 +474,7 @@ mod tests {
         let mut trees = BTreeMap::new();
 
         // arity of the replica
-        let arity = 4;
+        let arity = U::to_usize();
         // Construct and store an MT using a named DiskStore.
         let temp_dir = tempdir::TempDir::new("level_cache_tree_v1").unwrap();
         let temp_path = temp_dir.path();
@@ -484,7 +494,7 @@ mod tests {
 
             let cur_config =
                 StoreConfig::from_config(&config, format!("test-lc-tree-v1-{}", i), None);
-            let mut tree: QuadMerkleTree<_, _> = graph
+            let mut tree: MerkleTree<_, _, U> = graph
                 .merkle_tree(Some(cur_config.clone()), data.as_slice())
                 .unwrap();
             let c = tree
@@ -492,14 +502,15 @@ mod tests {
                 .unwrap();
             assert_eq!(c, true);
 
-            let lctree: QuadLCMerkleTree<_, _> = graph
+            let lctree: LCMerkleTree<_, _, U> = graph
                 .lcmerkle_tree(Some(cur_config), data.as_slice())
                 .unwrap();
             trees.insert(i.into(), lctree);
         }
 
         let candidates =
-            generate_candidates::<H>(&pub_params, &sectors, &trees, prover_id, randomness).unwrap();
+            generate_candidates::<H, U>(&pub_params, &sectors, &trees, prover_id, randomness)
+                .unwrap();
 
         let candidate = &candidates[0];
         let tree = trees.remove(&candidate.sector_id).unwrap();
@@ -516,16 +527,16 @@ mod tests {
             sector_challenge_index: 0,
         };
 
-        let priv_inputs = PrivateInputs::<H> {
+        let priv_inputs = PrivateInputs::<H, U> {
             tree,
             comm_c,
             comm_r_last,
         };
 
-        let proof = ElectionPoSt::<H>::prove(&pub_params, &pub_inputs, &priv_inputs)
+        let proof = ElectionPoSt::<H, U>::prove(&pub_params, &pub_inputs, &priv_inputs)
             .expect("proving failed");
 
-        let is_valid = ElectionPoSt::<H>::verify(&pub_params, &pub_inputs, &proof)
+        let is_valid = ElectionPoSt::<H, U>::verify(&pub_params, &pub_inputs, &proof)
             .expect("verification failed");
 
         assert!(is_valid);
@@ -533,11 +544,21 @@ mod tests {
 
     #[test]
     fn election_post_pedersen() {
-        test_election_post::<PedersenHasher>();
+        test_election_post::<PedersenHasher, typenum::U4>();
     }
 
     #[test]
     fn election_post_poseidon() {
-        test_election_post::<PoseidonHasher>();
+        test_election_post::<PoseidonHasher, typenum::U4>();
+    }
+
+    #[test]
+    fn election_post_pedersen_oct() {
+        test_election_post::<PedersenHasher, typenum::U8>();
+    }
+
+    #[test]
+    fn election_post_poseidon_oct() {
+        test_election_post::<PoseidonHasher, typenum::U8>();
     }
 }
@@ -17,7 +17,7 @@ use super::{Domain, HashFunction, Hasher};
 use crate::crypto::sloth;
 use crate::error::*;
 
-#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Blake2sHasher {}
 
 impl Hasher for Blake2sHasher {
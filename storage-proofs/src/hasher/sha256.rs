@@ -17,7 +17,7 @@ use super::{Domain, HashFunction, Hasher};
 use crate::crypto::sloth;
 use crate::error::*;
 
-#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Sha256Hasher {}
 
 impl Hasher for Sha256Hasher {
@@ -5,6 +5,7 @@
 #[macro_use]
 pub mod test_helper;
 
+#[cfg(feature = "prover")]
 pub mod example_helper;
 
 pub mod circuit;
@@ -20,6 +21,7 @@ pub mod hasher;
 pub mod measurements;
 pub mod merkle;
 pub mod merklepor;
+pub mod metrics;
 pub mod parameter_cache;
 pub mod partitions;
 pub mod pieces;
@@ -29,6 +31,7 @@ pub mod rational_post;
 pub mod sector;
 pub mod settings;
 pub mod stacked;
+pub mod trace;
 pub mod util;
 
 pub(crate) const TEST_SEED: [u8; 16] = [
@@ -2,9 +2,6 @@
 use std::sync::mpsc::{channel, Receiver, Sender};
 #[cfg(feature = "measurements")]
 use std::sync::Mutex;
-#[cfg(not(feature = "measurements"))]
-use std::time::Duration;
-#[cfg(feature = "measurements")]
 use std::time::{Duration, Instant};
 
 #[cfg(feature = "measurements")]
@@ -12,6 +9,9 @@ use cpu_time::ProcessTime;
 
 use serde::Serialize;
 
+use crate::metrics;
+use crate::trace;
+
 #[cfg(feature = "measurements")]
 use lazy_static::lazy_static;
 
@@ -50,11 +50,30 @@ pub enum Operation {
     PostPartialTicketHash,
 }
 
+impl Operation {
+    /// The name of spans of this operation, as it is serialized.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::GenerateTreeC => "generate-tree-c",
+            Operation::GenerateTreeRLast => "generate-tree-r-last",
+            Operation::CommD => "comm-d",
+            Operation::EncodeWindowTimeAll => "encode-window-time-all",
+            Operation::WindowCommLeavesTime => "window-comm-leaves-time",
+            Operation::PorepCommitTime => "porep-commit-time",
+            Operation::PostInclusionProofs => "post-inclusion-proofs",
+            Operation::PostFinalizeTicket => "post-finalize-ticket",
+            Operation::PostReadChallengedRange => "post-read-challenged-range",
+            Operation::PostPartialTicketHash => "post-partial-ticket-hash",
+        }
+    }
+}
+
 #[cfg(feature = "measurements")]
 pub fn measure_op<T, F>(op: Operation, f: F) -> T
 where
     F: FnOnce() -> T,
 {
+    let span = trace::span(op.name());
     let cpu_time_start = ProcessTime::now();
     let wall_start_time = Instant::now();
 
@@ -71,6 +90,10 @@ where
         .unwrap()
         .stop()
         .unwrap();
+    drop(span);
+
+    let wall_time = wall_start_time.elapsed();
+    metrics::record_operation(&op, wall_time);
 
     let opt_tx = OP_MEASUREMENTS
         .0
@@ -82,7 +105,7 @@ where
             .send(OpMeasurement {
                 op,
                 cpu_time: cpu_time_start.elapsed(),
-                wall_time: wall_start_time.elapsed(),
+                wall_time,
             })
             .expect("failed to send to perf channel");
     }
@@ -91,9 +114,14 @@ where
 }
 
 #[cfg(not(feature = "measurements"))]
-pub fn measure_op<T, F>(_: Operation, f: F) -> T
+pub fn measure_op<T, F>(op: Operation, f: F) -> T
 where
     F: FnOnce() -> T,
 {
-    f()
+    let _span = trace::span(op.name());
+    let start = Instant::now();
+    let x = f();
+    metrics::record_operation(&op, start.elapsed());
+
+    x
 }
@@ -32,6 +32,9 @@ pub type BinaryLCMerkleTree<T, A> = LCMerkleTree<T, A, typenum::U2>;
 pub type QuadMerkleTree<T, A> = MerkleTree<T, A, typenum::U4>;
 pub type QuadLCMerkleTree<T, A> = LCMerkleTree<T, A, typenum::U4>;
 
+pub type OctMerkleTree<T, A> = MerkleTree<T, A, typenum::U8>;
+pub type OctLCMerkleTree<T, A> = LCMerkleTree<T, A, typenum::U8>;
+
 pub type MerkleStore<T> = DiskStore<T>;
 
 /// Representation of a merkle proof.
@@ -0,0 +1,360 @@
+//! Counters and histograms of proving operations, rendered in the Prometheus text format.
+//!
+//! Unlike `measurements`, which only sends to a channel when its feature is enabled, the
+//! metrics are always recorded, into a process-wide registry. `render` returns its current
+//! values, e.g. for an HTTP exporter to serve to a Prometheus scraper.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::error::Result;
+use crate::measurements::Operation;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+/// Upper bounds, in seconds, of the duration histogram buckets. Operations range from hashing
+/// a partial ticket to labeling a 32 GiB sector.
+const DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.01, 0.1, 0.5, 1.0, 5.0, 30.0, 60.0, 300.0, 1800.0, 3600.0, 14400.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Counter,
+    Histogram,
+}
+
+/// A metric family, whose series are distinguished by their labels.
+#[derive(Debug, Clone, Copy)]
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: Kind,
+}
+
+const OPERATION_DURATION: Metric = Metric {
+    name: "fil_proofs_operation_duration_seconds",
+    help: "Wall time of each measured operation.",
+    kind: Kind::Histogram,
+};
+
+const BYTES_PROCESSED: Metric = Metric {
+    name: "fil_proofs_processed_bytes_total",
+    help: "Bytes labeled, hashed into trees or read by each operation.",
+    kind: Kind::Counter,
+};
+
+const PARAMETER_CACHE_LOOKUPS: Metric = Metric {
+    name: "fil_proofs_parameter_cache_lookups_total",
+    help: "Lookups of Groth parameters and verifying keys in the memory cache.",
+    kind: Kind::Counter,
+};
+
+const VERIFICATIONS: Metric = Metric {
+    name: "fil_proofs_verifications_total",
+    help: "Proof verifications, by whether the proof was valid, invalid or failed to verify.",
+    kind: Kind::Counter,
+};
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Series {
+    Counter(u64),
+    Histogram {
+        /// Non-cumulative counts per bucket of `DURATION_BUCKETS`, then one for `+Inf`.
+        buckets: Vec<u64>,
+        sum: f64,
+        count: u64,
+    },
+}
+
+/// The values of all metrics. The process-wide one is written by the `record_*` functions.
+#[derive(Debug, Default)]
+pub struct Registry {
+    families: BTreeMap<&'static str, (Metric, BTreeMap<Labels, Series>)>,
+}
+
+impl Registry {
+    fn series(&mut self, metric: Metric, labels: &[(&'static str, &str)]) -> &mut Series {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let (_, series) = self
+            .families
+            .entry(metric.name)
+            .or_insert_with(|| (metric, BTreeMap::new()));
+
+        series.entry(labels).or_insert_with(|| match metric.kind {
+            Kind::Counter => Series::Counter(0),
+            Kind::Histogram => Series::Histogram {
+                buckets: vec![0; DURATION_BUCKETS.len() + 1],
+                sum: 0.,
+                count: 0,
+            },
+        })
+    }
+
+    fn inc(&mut self, metric: Metric, labels: &[(&'static str, &str)], by: u64) {
+        if let Series::Counter(value) = self.series(metric, labels) {
+            *value += by;
+        }
+    }
+
+    fn observe(&mut self, metric: Metric, labels: &[(&'static str, &str)], value: f64) {
+        if let Series::Histogram {
+            buckets,
+            sum,
+            count,
+        } = self.series(metric, labels)
+        {
+            let bucket = DURATION_BUCKETS
+                .iter()
+                .position(|bound| value <= *bound)
+                .unwrap_or(DURATION_BUCKETS.len());
+            buckets[bucket] += 1;
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    pub fn record_operation(&mut self, op: &Operation, duration: Duration) {
+        self.observe(
+            OPERATION_DURATION,
+            &[("operation", op.name())],
+            duration.as_secs_f64(),
+        );
+    }
+
+    pub fn record_bytes_processed(&mut self, op: &Operation, bytes: u64) {
+        self.inc(BYTES_PROCESSED, &[("operation", op.name())], bytes);
+    }
+
+    pub fn record_cache_lookup(&mut self, cache: &str, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.inc(
+            PARAMETER_CACHE_LOOKUPS,
+            &[("cache", cache), ("result", result)],
+            1,
+        );
+    }
+
+    pub fn record_verification(&mut self, proof: &str, result: &Result<bool>) {
+        let result = match result {
+            Ok(true) => "valid",
+            Ok(false) => "invalid",
+            Err(_) => "error",
+        };
+        self.inc(VERIFICATIONS, &[("proof", proof), ("result", result)], 1);
+    }
+
+    /// Renders all series in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (metric, series) in self.families.values() {
+            let kind = match metric.kind {
+                Kind::Counter => "counter",
+                Kind::Histogram => "histogram",
+            };
+            writeln!(out, "# HELP {} {}", metric.name, metric.help).unwrap();
+            writeln!(out, "# TYPE {} {}", metric.name, kind).unwrap();
+
+            for (labels, series) in series {
+                match series {
+                    Series::Counter(value) => {
+                        writeln!(
+                            out,
+                            "{}{} {}",
+                            metric.name,
+                            format_labels(labels, None),
+                            value
+                        )
+                        .unwrap();
+                    }
+                    Series::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let mut cumulative = 0;
+                        for (i, bucket) in buckets.iter().enumerate() {
+                            cumulative += bucket;
+                            let bound = DURATION_BUCKETS
+                                .get(i)
+                                .map(|bound| bound.to_string())
+                                .unwrap_or_else(|| "+Inf".to_string());
+                            writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                metric.name,
+                                format_labels(labels, Some(&bound)),
+                                cumulative
+                            )
+                            .unwrap();
+                        }
+                        let labels = format_labels(labels, None);
+                        writeln!(out, "{}_sum{} {}", metric.name, labels, sum).unwrap();
+                        writeln!(out, "{}_count{} {}", metric.name, labels, count).unwrap();
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn format_labels(labels: &[(&'static str, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn with_registry<F: FnOnce(&mut Registry)>(f: F) {
+    // Metrics must never fail an operation, so a poisoned registry is used as it is.
+    let mut registry = REGISTRY.lock().unwrap_or_else(|err| err.into_inner());
+    f(&mut registry)
+}
+
+/// Records the wall time of one run of `op`.
+pub fn record_operation(op: &Operation, duration: Duration) {
+    with_registry(|registry| registry.record_operation(op, duration))
+}
+
+/// Records that `op` labeled, hashed or read `bytes`.
+pub fn record_bytes_processed(op: &Operation, bytes: u64) {
+    with_registry(|registry| registry.record_bytes_processed(op, bytes))
+}
+
+/// Records a lookup in the parameter memory cache called `cache`.
+pub fn record_cache_lookup(cache: &str, hit: bool) {
+    with_registry(|registry| registry.record_cache_lookup(cache, hit))
+}
+
+/// Runs `verify`, which verifies a `proof` ("seal", "post", ...), and records its outcome.
+pub fn measure_verification<F>(proof: &str, verify: F) -> Result<bool>
+where
+    F: FnOnce() -> Result<bool>,
+{
+    let result = verify();
+    with_registry(|registry| registry.record_verification(proof, &result));
+
+    result
+}
+
+/// Renders the process-wide metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_render_counters() {
+        let mut registry = Registry::default();
+        assert_eq!(registry.render(), "");
+
+        registry.record_cache_lookup("groth", false);
+        registry.record_cache_lookup("groth", true);
+        registry.record_cache_lookup("groth", true);
+        registry.record_verification("seal", &Ok(false));
+        registry.record_verification("seal", &Err(anyhow!("bad proof")));
+        registry.record_bytes_processed(&Operation::CommD, 2048);
+        registry.record_bytes_processed(&Operation::CommD, 2048);
+
+        assert_eq!(
+            registry.render(),
+            "# HELP fil_proofs_parameter_cache_lookups_total \
+             Lookups of Groth parameters and verifying keys in the memory cache.\n\
+             # TYPE fil_proofs_parameter_cache_lookups_total counter\n\
+             fil_proofs_parameter_cache_lookups_total{cache=\"groth\",result=\"hit\"} 2\n\
+             fil_proofs_parameter_cache_lookups_total{cache=\"groth\",result=\"miss\"} 1\n\
+             # HELP fil_proofs_processed_bytes_total \
+             Bytes labeled, hashed into trees or read by each operation.\n\
+             # TYPE fil_proofs_processed_bytes_total counter\n\
+             fil_proofs_processed_bytes_total{operation=\"comm-d\"} 4096\n\
+             # HELP fil_proofs_verifications_total \
+             Proof verifications, by whether the proof was valid, invalid or failed to verify.\n\
+             # TYPE fil_proofs_verifications_total counter\n\
+             fil_proofs_verifications_total{proof=\"seal\",result=\"error\"} 1\n\
+             fil_proofs_verifications_total{proof=\"seal\",result=\"invalid\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn test_render_histogram() {
+        let mut registry = Registry::default();
+        registry.record_operation(&Operation::GenerateTreeC, Duration::from_micros(62_500));
+        registry.record_operation(&Operation::GenerateTreeC, Duration::from_secs(2));
+        registry.record_operation(&Operation::GenerateTreeC, Duration::from_secs(20000));
+
+        let rendered = registry.render();
+        let line = |suffix: &str| {
+            rendered
+                .lines()
+                .find(|line| {
+                    line.starts_with(&format!("fil_proofs_operation_duration_seconds_{}", suffix))
+                })
+                .unwrap_or_else(|| panic!("no {} in {}", suffix, rendered))
+                .to_string()
+        };
+
+        assert!(rendered.contains("# TYPE fil_proofs_operation_duration_seconds histogram\n"));
+        assert_eq!(
+            line("bucket{operation=\"generate-tree-c\",le=\"0.01\"}"),
+            "fil_proofs_operation_duration_seconds_bucket{operation=\"generate-tree-c\",le=\"0.01\"} 0"
+        );
+        assert!(line("bucket{operation=\"generate-tree-c\",le=\"0.1\"}").ends_with(" 1"));
+        assert!(line("bucket{operation=\"generate-tree-c\",le=\"5\"}").ends_with(" 2"));
+        assert!(line("bucket{operation=\"generate-tree-c\",le=\"14400\"}").ends_with(" 2"));
+        assert!(line("bucket{operation=\"generate-tree-c\",le=\"+Inf\"}").ends_with(" 3"));
+        assert_eq!(
+            line("sum"),
+            "fil_proofs_operation_duration_seconds_sum{operation=\"generate-tree-c\"} 20002.0625"
+        );
+        assert_eq!(
+            line("count"),
+            "fil_proofs_operation_duration_seconds_count{operation=\"generate-tree-c\"} 3"
+        );
+    }
+
+    #[test]
+    fn test_escape_label_values() {
+        assert_eq!(
+            format_labels(&[("cache", "a\"b\\c\nd".to_string())], None),
+            "{cache=\"a\\\"b\\\\c\\nd\"}"
+        );
+        assert_eq!(format_labels(&[], Some("+Inf")), "{le=\"+Inf\"}");
+    }
+}
@@ -1,21 +1,20 @@
 use crate::error::*;
+use crate::settings;
 use anyhow::bail;
 use bellperson::groth16::Parameters;
 use bellperson::{groth16, Circuit};
 use fil_sapling_crypto::jubjub::JubjubEngine;
-use fs2::FileExt;
 use itertools::Itertools;
 use log::info;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use std::env;
 use std::fs::{self, create_dir_all, File};
 use std::io::{self, SeekFrom};
 use std::path::{Path, PathBuf};
 
 /// Bump this when circuits change to invalidate the cache.
-pub const VERSION: usize = 23;
+pub const VERSION: usize = 24;
 
 pub const PARAMETER_CACHE_ENV_VAR: &str = "FIL_PROOFS_PARAMETER_CACHE";
 pub const PARAMETER_CACHE_DIR: &str = "/var/tmp/filecoin-proof-parameters/";
@@ -31,7 +30,7 @@ struct LockedFile(File);
 impl LockedFile {
     pub fn open_exclusive_read<P: AsRef<Path>>(p: P) -> io::Result<Self> {
         let f = fs::OpenOptions::new().read(true).open(p)?;
-        f.lock_exclusive()?;
+        lock_exclusive(&f)?;
 
         Ok(LockedFile(f))
     }
@@ -42,12 +41,23 @@ impl LockedFile {
             .write(true)
             .create(true)
             .open(p)?;
-        f.lock_exclusive()?;
+        lock_exclusive(&f)?;
 
         Ok(LockedFile(f))
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn lock_exclusive(f: &File) -> io::Result<()> {
+    fs2::FileExt::lock_exclusive(f)
+}
+
+// wasm32 has no file locks.
+#[cfg(target_arch = "wasm32")]
+fn lock_exclusive(_f: &File) -> io::Result<()> {
+    Ok(())
+}
+
 impl io::Write for LockedFile {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.0.write(buf)
@@ -70,27 +80,20 @@ impl io::Seek for LockedFile {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl Drop for LockedFile {
     fn drop(&mut self) {
-        self.0
-            .unlock()
+        fs2::FileExt::unlock(&self.0)
             .unwrap_or_else(|e| panic!("{}: failed to {:?} unlock file safely", e, &self.0));
     }
 }
 
-fn parameter_cache_dir_name() -> String {
-    match env::var(PARAMETER_CACHE_ENV_VAR) {
-        Ok(dir) => dir,
-        Err(_) => String::from(PARAMETER_CACHE_DIR),
-    }
-}
-
 pub fn parameter_cache_dir() -> PathBuf {
-    Path::new(&parameter_cache_dir_name()).to_path_buf()
+    settings::current().parameter_cache().to_path_buf()
 }
 
 pub fn parameter_cache_params_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    let dir = parameter_cache_dir();
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, GROTH_PARAMETER_EXT
@@ -98,7 +101,7 @@ pub fn parameter_cache_params_path(parameter_set_identifier: &str) -> PathBuf {
 }
 
 pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    let dir = parameter_cache_dir();
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, PARAMETER_METADATA_EXT
@@ -106,7 +109,7 @@ pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf
 }
 
 pub fn parameter_cache_verifying_key_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+    let dir = parameter_cache_dir();
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, VERIFYING_KEY_EXT
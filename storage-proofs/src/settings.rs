@@ -1,47 +1,519 @@
-use std::sync::Mutex;
+//! The settings of the proofs, read from `./rust-fil-proofs.config.toml` and the `FIL_PROOFS_*`
+//! environment variables, e.g. `FIL_PROOFS_MAXIMIZE_CACHING=1`.
+//!
+//! Programs embedding the proofs can replace them with `set`, or override them for the work
+//! done within a closure with `with_settings`, so that two sectors can be sealed with different
+//! settings in one process:
+//!
+//! ```no_run
+//! use storage_proofs::settings::{self, Settings};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let settings = settings::current().to_builder().maximize_caching(true).build()?;
+//! settings::with_settings(settings, || {
+//!     // Seal with the parents cache.
+//! });
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Overrides only apply to the thread calling `with_settings`, which is where the proofs read
+//! the settings they take per call. `pedersen_hash_exp_window_size`, `trace_file` and the memory
+//! cache budgets are process-wide, and are always those of `set` or the configuration; the first
+//! two are read once, at the first Pedersen hash and the first span, after which `set` fails to
+//! change them.
+//!
+//! Keys of the configuration which are not settings are ignored with a warning. This includes
+//! `merkle_tree_path` and `replicated_trees_dir`, which were never read and have been removed.
 
-use config::{Config, ConfigError, Environment, File};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{ensure, Context, Result};
+use config::{Config, Environment, File};
 use lazy_static::lazy_static;
+use log::warn;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 
+use crate::parameter_cache::PARAMETER_CACHE_DIR;
+
 lazy_static! {
-    pub static ref SETTINGS: Mutex<Settings> =
-        Mutex::new(Settings::new().expect("invalid configuration"));
+    static ref SETTINGS: RwLock<Arc<Settings>> =
+        RwLock::new(Arc::new(Settings::load().expect("invalid configuration")));
+}
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Arc<Settings>>> = RefCell::new(None);
 }
 
+/// Set once the process-wide value of the setting has been read for good.
+static PEDERSEN_HASH_EXP_WINDOW_SIZE_READ: AtomicBool = AtomicBool::new(false);
+static TRACE_FILE_READ: AtomicBool = AtomicBool::new(false);
+
 const SETTINGS_PATH: &str = "./rust-fil-proofs.config.toml";
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(default)]
+const KEYS: &[&str] = &[
+    "maximize_caching",
+    "num_proving_threads",
+    "parameter_cache",
+    "pedersen_hash_exp_window_size",
+    "envelope_proofs",
+    "trace_file",
+    "groth_param_cache_max_bytes",
+    "verifying_key_cache_max_bytes",
+];
+
+/// Settings which were never read, and have been removed.
+const REMOVED_KEYS: &[&str] = &["merkle_tree_path", "replicated_trees_dir"];
+
+/// Pedersen hashing precomputes `2^window_size` points per window.
+const MAX_PEDERSEN_HASH_EXP_WINDOW_SIZE: u32 = 24;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawSettings")]
 pub struct Settings {
-    pub maximize_caching: bool,
-    pub merkle_tree_path: String,
-    pub num_proving_threads: usize,
-    pub replicated_trees_dir: String,
-    pub pedersen_hash_exp_window_size: u32,
-    // Generating MTs in parallel optimizes for speed while generating them
-    // in sequence (`false`) optimizes for memory.
+    maximize_caching: bool,
+    num_proving_threads: usize,
+    parameter_cache: PathBuf,
+    pedersen_hash_exp_window_size: u32,
+    envelope_proofs: bool,
+    trace_file: PathBuf,
+    groth_param_cache_max_bytes: u64,
+    verifying_key_cache_max_bytes: u64,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             maximize_caching: false,
-            merkle_tree_path: "/tmp/merkle-trees".into(),
-            num_proving_threads: 1,
-            replicated_trees_dir: "".into(),
+            num_proving_threads: 0,
+            parameter_cache: PARAMETER_CACHE_DIR.into(),
             pedersen_hash_exp_window_size: 16,
+            envelope_proofs: false,
+            trace_file: PathBuf::new(),
+            groth_param_cache_max_bytes: 0,
+            verifying_key_cache_max_bytes: 0,
+        }
+    }
+}
+
+/// The fields of `Settings` as deserialized, before they are validated.
+#[derive(Deserialize)]
+#[serde(default)]
+struct RawSettings {
+    maximize_caching: bool,
+    num_proving_threads: usize,
+    parameter_cache: PathBuf,
+    pedersen_hash_exp_window_size: u32,
+    envelope_proofs: bool,
+    trace_file: PathBuf,
+    groth_param_cache_max_bytes: u64,
+    verifying_key_cache_max_bytes: u64,
+}
+
+impl Default for RawSettings {
+    fn default() -> Self {
+        let Settings {
+            maximize_caching,
+            num_proving_threads,
+            parameter_cache,
+            pedersen_hash_exp_window_size,
+            envelope_proofs,
+            trace_file,
+            groth_param_cache_max_bytes,
+            verifying_key_cache_max_bytes,
+        } = Settings::default();
+
+        RawSettings {
+            maximize_caching,
+            num_proving_threads,
+            parameter_cache,
+            pedersen_hash_exp_window_size,
+            envelope_proofs,
+            trace_file,
+            groth_param_cache_max_bytes,
+            verifying_key_cache_max_bytes,
         }
     }
 }
 
+impl TryFrom<RawSettings> for Settings {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawSettings) -> Result<Self> {
+        let RawSettings {
+            maximize_caching,
+            num_proving_threads,
+            parameter_cache,
+            pedersen_hash_exp_window_size,
+            envelope_proofs,
+            trace_file,
+            groth_param_cache_max_bytes,
+            verifying_key_cache_max_bytes,
+        } = raw;
+
+        let settings = Settings {
+            maximize_caching,
+            num_proving_threads,
+            parameter_cache,
+            pedersen_hash_exp_window_size,
+            envelope_proofs,
+            trace_file,
+            groth_param_cache_max_bytes,
+            verifying_key_cache_max_bytes,
+        };
+        settings.validate()?;
+
+        Ok(settings)
+    }
+}
+
 impl Settings {
-    fn new() -> Result<Settings, ConfigError> {
+    /// Reads the settings file and the environment over the defaults.
+    pub fn load() -> Result<Settings> {
         let mut s = Config::new();
 
         s.merge(File::with_name(SETTINGS_PATH).required(false))?;
         s.merge(Environment::with_prefix("FIL_PROOFS"))?;
 
-        s.try_into()
+        let keys: HashMap<String, IgnoredAny> = s.clone().try_into()?;
+        for key in keys.keys() {
+            if REMOVED_KEYS.contains(&key.as_str()) {
+                warn!("ignoring the {} setting, which has been removed", key);
+            } else if !KEYS.contains(&key.as_str()) {
+                warn!("ignoring the unknown {} setting", key);
+            }
+        }
+
+        // Deserializing validates the settings.
+        Ok(s.try_into()?)
+    }
+
+    /// Returns a builder starting from the defaults.
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::default()
+    }
+
+    /// Returns a builder starting from these settings.
+    pub fn to_builder(&self) -> SettingsBuilder {
+        SettingsBuilder {
+            settings: self.clone(),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            !self.parameter_cache.as_os_str().is_empty(),
+            "parameter_cache must not be empty"
+        );
+        ensure!(
+            self.pedersen_hash_exp_window_size > 0
+                && self.pedersen_hash_exp_window_size <= MAX_PEDERSEN_HASH_EXP_WINDOW_SIZE,
+            "pedersen_hash_exp_window_size must be between 1 and {}, got {}",
+            MAX_PEDERSEN_HASH_EXP_WINDOW_SIZE,
+            self.pedersen_hash_exp_window_size
+        );
+
+        Ok(())
+    }
+
+    /// Whether the settings differ in those which are process-wide.
+    fn process_wide_differ(&self, other: &Settings) -> bool {
+        self.pedersen_hash_exp_window_size != other.pedersen_hash_exp_window_size
+            || self.trace_file != other.trace_file
+            || self.groth_param_cache_max_bytes != other.groth_param_cache_max_bytes
+            || self.verifying_key_cache_max_bytes != other.verifying_key_cache_max_bytes
+    }
+
+    /// Cache the parents of every node of the stacked graph while labeling, which trades up to
+    /// a sector size of memory for a much faster replication.
+    pub fn maximize_caching(&self) -> bool {
+        self.maximize_caching
+    }
+
+    /// The number of threads synthesizing the partition circuits of a proof, on a pool of
+    /// their own. Synthesis uses the global rayon threads if 0.
+    pub fn num_proving_threads(&self) -> usize {
+        self.num_proving_threads
+    }
+
+    /// The directory of the Groth parameters and verifying keys, `FIL_PROOFS_PARAMETER_CACHE`.
+    pub fn parameter_cache(&self) -> &Path {
+        &self.parameter_cache
+    }
+
+    /// The window size of the precomputed Pedersen hash generators. Larger windows hash faster
+    /// with more memory.
+    pub fn pedersen_hash_exp_window_size(&self) -> u32 {
+        self.pedersen_hash_exp_window_size
+    }
+
+    /// Write proofs in the self-describing envelope of `circuit::multi_proof`
+    /// instead of as raw Groth16 proofs. Either kind is always accepted by verifiers.
+    pub fn envelope_proofs(&self) -> bool {
+        self.envelope_proofs
+    }
+
+    /// The Chrome trace-event file the spans of `trace` are written to, if any.
+    pub fn trace_file(&self) -> Option<&Path> {
+        if self.trace_file.as_os_str().is_empty() {
+            None
+        } else {
+            Some(&self.trace_file)
+        }
+    }
+
+    /// Memory budget in bytes of the Groth parameters kept in memory by `filecoin-proofs`,
+    /// beyond which the least recently used are evicted. Unbounded if 0.
+    pub fn groth_param_cache_max_bytes(&self) -> u64 {
+        self.groth_param_cache_max_bytes
+    }
+
+    /// Memory budget in bytes of the cached verifying keys. Unbounded if 0.
+    pub fn verifying_key_cache_max_bytes(&self) -> u64 {
+        self.verifying_key_cache_max_bytes
+    }
+}
+
+/// Builds `Settings`, which are validated by `build`. See `Settings` for the meaning of each.
+#[derive(Clone, Debug, Default)]
+pub struct SettingsBuilder {
+    settings: Settings,
+}
+
+impl SettingsBuilder {
+    pub fn maximize_caching(mut self, maximize_caching: bool) -> Self {
+        self.settings.maximize_caching = maximize_caching;
+        self
+    }
+
+    pub fn num_proving_threads(mut self, num_proving_threads: usize) -> Self {
+        self.settings.num_proving_threads = num_proving_threads;
+        self
+    }
+
+    pub fn parameter_cache<P: Into<PathBuf>>(mut self, parameter_cache: P) -> Self {
+        self.settings.parameter_cache = parameter_cache.into();
+        self
+    }
+
+    pub fn pedersen_hash_exp_window_size(mut self, window_size: u32) -> Self {
+        self.settings.pedersen_hash_exp_window_size = window_size;
+        self
+    }
+
+    pub fn envelope_proofs(mut self, envelope_proofs: bool) -> Self {
+        self.settings.envelope_proofs = envelope_proofs;
+        self
+    }
+
+    /// Sets the trace file, or disables tracing if `None`.
+    pub fn trace_file<P: Into<PathBuf>>(mut self, trace_file: Option<P>) -> Self {
+        self.settings.trace_file = trace_file.map(Into::into).unwrap_or_default();
+        self
+    }
+
+    pub fn groth_param_cache_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.settings.groth_param_cache_max_bytes = max_bytes;
+        self
+    }
+
+    pub fn verifying_key_cache_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.settings.verifying_key_cache_max_bytes = max_bytes;
+        self
+    }
+
+    pub fn build(self) -> Result<Settings> {
+        self.settings.validate().context("invalid settings")?;
+
+        Ok(self.settings)
+    }
+}
+
+/// Returns the settings of the calling thread: those of the innermost `with_settings`, or else
+/// the process-wide ones.
+pub fn current() -> Arc<Settings> {
+    OVERRIDE.with(|o| o.borrow().clone()).unwrap_or_else(global)
+}
+
+/// Returns the process-wide settings, ignoring the overrides of `with_settings`.
+pub fn global() -> Arc<Settings> {
+    SETTINGS.read().expect("settings lock failure").clone()
+}
+
+/// Replaces the process-wide settings. Fails if that changes `pedersen_hash_exp_window_size` or
+/// `trace_file` after they were read.
+pub fn set(settings: Settings) -> Result<()> {
+    let mut global = SETTINGS.write().expect("settings lock failure");
+
+    ensure!(
+        !PEDERSEN_HASH_EXP_WINDOW_SIZE_READ.load(Ordering::SeqCst)
+            || settings.pedersen_hash_exp_window_size == global.pedersen_hash_exp_window_size,
+        "pedersen_hash_exp_window_size was read as {}, it cannot be changed anymore",
+        global.pedersen_hash_exp_window_size
+    );
+    ensure!(
+        !TRACE_FILE_READ.load(Ordering::SeqCst) || settings.trace_file == global.trace_file,
+        "trace_file was read as {:?}, it cannot be changed anymore",
+        global.trace_file
+    );
+
+    *global = Arc::new(settings);
+
+    Ok(())
+}
+
+/// Returns the process-wide `pedersen_hash_exp_window_size`, which `set` keeps from then on.
+pub(crate) fn read_pedersen_hash_exp_window_size() -> u32 {
+    let global = SETTINGS.read().expect("settings lock failure");
+    PEDERSEN_HASH_EXP_WINDOW_SIZE_READ.store(true, Ordering::SeqCst);
+
+    global.pedersen_hash_exp_window_size
+}
+
+/// Returns the process-wide `trace_file`, which `set` keeps from then on.
+pub(crate) fn read_trace_file() -> Option<PathBuf> {
+    let global = SETTINGS.read().expect("settings lock failure");
+    TRACE_FILE_READ.store(true, Ordering::SeqCst);
+
+    global.trace_file().map(Path::to_path_buf)
+}
+
+/// Runs `f` with `settings` as the settings of the calling thread. The process-wide settings
+/// of `settings` are ignored, with a warning if they differ from the current ones.
+pub fn with_settings<T, F: FnOnce() -> T>(settings: Settings, f: F) -> T {
+    if settings.process_wide_differ(&global()) {
+        warn!(
+            "with_settings ignores pedersen_hash_exp_window_size, trace_file and the cache \
+             budgets, which only set changes"
+        );
+    }
+
+    struct Restore(Option<Arc<Settings>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            OVERRIDE.with(|o| *o.borrow_mut() = previous);
+        }
+    }
+
+    let previous = OVERRIDE.with(|o| o.borrow_mut().replace(Arc::new(settings)));
+    let _restore = Restore(previous);
+
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn test_settings_builder_validates() {
+        let settings = Settings::builder()
+            .maximize_caching(true)
+            .num_proving_threads(2)
+            .trace_file(Some("/tmp/trace.json"))
+            .build()
+            .unwrap();
+        assert!(settings.maximize_caching());
+        assert_eq!(settings.num_proving_threads(), 2);
+        assert_eq!(settings.trace_file(), Some(Path::new("/tmp/trace.json")));
+        assert_eq!(settings.parameter_cache(), Path::new(PARAMETER_CACHE_DIR));
+
+        let untraced = settings.to_builder().trace_file(None::<PathBuf>).build();
+        assert_eq!(untraced.unwrap().trace_file(), None);
+
+        assert!(Settings::builder()
+            .pedersen_hash_exp_window_size(0)
+            .build()
+            .is_err());
+        assert!(Settings::builder().parameter_cache("").build().is_err());
+    }
+
+    #[test]
+    fn test_settings_deserialize_validates() {
+        let settings: Settings = serde_json::from_str(r#"{"maximize_caching": true}"#).unwrap();
+        assert_eq!(
+            settings,
+            Settings::builder().maximize_caching(true).build().unwrap()
+        );
+
+        let roundtrip = serde_json::to_string(&settings).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Settings>(&roundtrip).unwrap(),
+            settings
+        );
+
+        assert!(
+            serde_json::from_str::<Settings>(r#"{"pedersen_hash_exp_window_size": 0}"#).is_err()
+        );
+        assert!(serde_json::from_str::<Settings>(r#"{"parameter_cache": ""}"#).is_err());
+    }
+
+    #[test]
+    fn test_with_settings_overrides_calling_thread() {
+        let outer = global()
+            .to_builder()
+            .envelope_proofs(!global().envelope_proofs())
+            .build()
+            .unwrap();
+        let inner = outer.to_builder().num_proving_threads(3).build().unwrap();
+
+        with_settings(outer.clone(), || {
+            assert_eq!(*current(), outer);
+            with_settings(inner.clone(), || assert_eq!(*current(), inner));
+            assert_eq!(*current(), outer);
+
+            // Other threads see the process-wide settings.
+            let other = thread::spawn(current).join().unwrap();
+            assert_eq!(other, global());
+        });
+        assert_eq!(current(), global());
+
+        // Process-wide settings can't be overridden.
+        assert!(outer.process_wide_differ(
+            &outer
+                .to_builder()
+                .groth_param_cache_max_bytes(outer.groth_param_cache_max_bytes() + 1)
+                .build()
+                .unwrap()
+        ));
+        assert!(!outer.process_wide_differ(&inner));
+
+        // The override is restored even if `f` panics.
+        let result = std::panic::catch_unwind(|| with_settings(inner, || panic!("sealing failed")));
+        assert!(result.is_err());
+        assert_eq!(current(), global());
+    }
+
+    #[test]
+    fn test_set_keeps_settings_read_once() {
+        let window_size = read_pedersen_hash_exp_window_size();
+        let changed = global()
+            .to_builder()
+            .pedersen_hash_exp_window_size(window_size % MAX_PEDERSEN_HASH_EXP_WINDOW_SIZE + 1)
+            .build()
+            .unwrap();
+        assert!(set(changed).is_err());
+        assert_eq!(global().pedersen_hash_exp_window_size(), window_size);
+
+        let traced = global()
+            .to_builder()
+            .trace_file(Some("/tmp/other-trace.json"))
+            .build()
+            .unwrap();
+        read_trace_file();
+        assert!(set(traced).is_err());
+
+        // Settings which are read per call can still be set.
+        assert!(set((*global()).clone()).is_ok());
     }
 }
@@ -106,7 +106,7 @@ where
         expansion_degree: usize,
         seed: [u8; 28],
     ) -> Result<Self> {
-        let use_cache = settings::SETTINGS.lock().unwrap().maximize_caching;
+        let use_cache = settings::current().maximize_caching();
 
         let base_graph = match base_graph {
             Some(graph) => graph,
@@ -166,7 +166,9 @@ where
         }
     }
 
-    fn copy_parents_data_inner(
+    /// Copies the data of the given `cache_parents` into `target`, base parents are read
+    /// from `base_data` and expander parents from `exp_data`.
+    pub(crate) fn copy_parents_data_inner(
         &self,
         cache_parents: &[u32],
         base_data: &[u8],
@@ -9,9 +9,14 @@ mod graph;
 pub(crate) mod hash;
 mod labeling_proof;
 mod params;
+#[cfg(feature = "prover")]
 mod porep;
+#[cfg(feature = "prover")]
+mod prefetch;
 mod proof;
 mod proof_scheme;
+#[cfg(feature = "prover")]
+mod replicate;
 
 pub use self::challenges::{ChallengeRequirements, LayerChallenges};
 pub use self::column::Column;
@@ -19,5 +24,7 @@ pub use self::column_proof::ColumnProof;
 pub use self::encoding_proof::EncodingProof;
 pub use self::graph::{StackedBucketGraph, StackedGraph, EXP_DEGREE};
 pub use self::params::*;
+#[cfg(feature = "prover")]
+pub use self::prefetch::create_layer_labels_prefetched;
 pub use self::proof::{create_key, StackedDrg, TOTAL_PARENTS};
 pub use labeling_proof::LabelingProof;
@@ -25,9 +25,12 @@ use crate::util::data_at_node;
 pub type BinaryTree<H> = MerkleTree<<H as Hasher>::Domain, <H as Hasher>::Function, typenum::U2>;
 pub type QuadTree<H> = MerkleTree<<H as Hasher>::Domain, <H as Hasher>::Function, typenum::U4>;
 pub type QuadLCTree<H> = LCMerkleTree<<H as Hasher>::Domain, <H as Hasher>::Function, typenum::U4>;
+pub type OctTree<H> = MerkleTree<<H as Hasher>::Domain, <H as Hasher>::Function, typenum::U8>;
+pub type OctLCTree<H> = LCMerkleTree<<H as Hasher>::Domain, <H as Hasher>::Function, typenum::U8>;
 
 pub const BINARY_ARITY: usize = 2;
 pub const QUAD_ARITY: usize = 4;
+pub const OCT_ARITY: usize = 8;
 
 #[derive(Debug, Copy, Clone)]
 pub enum CacheKey {
@@ -56,6 +59,49 @@ impl CacheKey {
     }
 }
 
+/// Controls how much memory is used while generating the layer labels.
+///
+/// All modes produce byte-identical labels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LabelingMode {
+    /// Keep the current and the previous layer fully in memory (2x sector size).
+    InMemory,
+    /// Memory-map the previous layer from its on-disk store and keep only a window
+    /// of `memory_budget` bytes of the current layer in memory. Parents outside the
+    /// window are read back from disk, trading speed for a bounded memory footprint.
+    Bounded { memory_budget: usize },
+    /// Like `InMemory`, but a producer thread gathers the parents of upcoming nodes
+    /// ahead of the hashing thread, see `create_layer_labels_prefetched`.
+    Prefetch(PrefetchConfig),
+}
+
+impl Default for LabelingMode {
+    fn default() -> Self {
+        LabelingMode::InMemory
+    }
+}
+
+/// Configuration of the prefetching labeling loop, see `LabelingMode::Prefetch`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefetchConfig {
+    /// How many nodes the producer thread may run ahead of the hashing thread.
+    pub lookahead: usize,
+    /// The core to pin the producer thread to, if any.
+    pub producer_core: Option<usize>,
+    /// The core to pin the hashing thread to, if any.
+    pub hasher_core: Option<usize>,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        PrefetchConfig {
+            lookahead: 4096,
+            producer_core: None,
+            hasher_core: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SetupParams {
     // Number of nodes
@@ -143,23 +189,24 @@ impl<T: Domain, S: Domain> PublicInputs<T, S> {
 }
 
 #[derive(Debug)]
-pub struct PrivateInputs<H: Hasher, G: Hasher> {
+pub struct PrivateInputs<H: Hasher, G: Hasher, U: typenum::Unsigned = typenum::U4> {
     pub p_aux: PersistentAux<H::Domain>,
-    pub t_aux: TemporaryAuxCache<H, G>,
+    pub t_aux: TemporaryAuxCache<H, G, U>,
 }
 
+/// A single challenge proof. `U` is the arity of `tree_r_last`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Proof<H: Hasher, G: Hasher> {
+pub struct Proof<H: Hasher, G: Hasher, U: typenum::Unsigned = typenum::U4> {
     #[serde(bound(
         serialize = "MerkleProof<G, typenum::U2>: Serialize",
         deserialize = "MerkleProof<G, typenum::U2>: Deserialize<'de>"
     ))]
     pub comm_d_proofs: MerkleProof<G, typenum::U2>,
     #[serde(bound(
-        serialize = "MerkleProof<H, typenum::U4>: Serialize, ColumnProof<H>: Serialize",
-        deserialize = "MerkleProof<H, typenum::U4>: Deserialize<'de>, ColumnProof<H>: Deserialize<'de>"
+        serialize = "MerkleProof<H, U>: Serialize, ColumnProof<H>: Serialize",
+        deserialize = "MerkleProof<H, U>: Deserialize<'de>, ColumnProof<H>: Deserialize<'de>"
     ))]
-    pub comm_r_last_proof: MerkleProof<H, typenum::U4>,
+    pub comm_r_last_proof: MerkleProof<H, U>,
     #[serde(bound(
         serialize = "ReplicaColumnProof<H>: Serialize",
         deserialize = "ReplicaColumnProof<H>: Deserialize<'de>"
@@ -178,7 +225,7 @@ pub struct Proof<H: Hasher, G: Hasher> {
     pub encoding_proof: EncodingProof<H>,
 }
 
-impl<H: Hasher, G: Hasher> Proof<H, G> {
+impl<H: Hasher, G: Hasher, U: typenum::Unsigned> Proof<H, G, U> {
     pub fn comm_r_last(&self) -> &H::Domain {
         self.comm_r_last_proof.root()
     }
@@ -303,7 +350,7 @@ impl<H: Hasher> ReplicaColumnProof<H> {
     }
 }
 
-impl<H: Hasher, G: Hasher> Proof<H, G> {
+impl<H: Hasher, G: Hasher, U: typenum::Unsigned> Proof<H, G, U> {
     pub fn serialize(&self) -> Vec<u8> {
         unimplemented!();
     }
@@ -332,6 +379,10 @@ pub struct PersistentAux<D> {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemporaryAux<H: Hasher, G: Hasher> {
     /// The encoded nodes for 1..layers.
+    #[serde(bound(
+        serialize = "Labels<H>: Serialize",
+        deserialize = "Labels<H>: Deserialize<'de>"
+    ))]
     pub labels: Labels<H>,
     pub tree_d_config: StoreConfig,
     pub tree_r_last_config: StoreConfig,
@@ -363,8 +414,9 @@ impl<H: Hasher, G: Hasher> TemporaryAux<H, G> {
     }
 
     // "Compact" will discard all persisted data that is no longer
-    // required and compact the remaining "tree_r_last" merkle tree.
-    pub fn compact(t_aux: TemporaryAux<H, G>) -> Result<()> {
+    // required and compact the remaining "tree_r_last" merkle tree,
+    // which has arity `U`.
+    pub fn compact<U: typenum::Unsigned>(t_aux: TemporaryAux<H, G>) -> Result<()> {
         let cached = |config: &StoreConfig| {
             Path::new(&StoreConfig::data_path(&config.path, &config.id)).exists()
         };
@@ -390,11 +442,14 @@ impl<H: Hasher, G: Hasher> TemporaryAux<H, G> {
                 .tree_r_last_config
                 .size
                 .context("tree_r_last config has no size")?;
-            let mut tree_r_last_store: DiskStore<G::Domain> =
-                DiskStore::new_from_disk(tree_r_last_size, QUAD_ARITY, &t_aux.tree_r_last_config)
-                    .context("tree_r_last")?;
+            let mut tree_r_last_store: DiskStore<G::Domain> = DiskStore::new_from_disk(
+                tree_r_last_size,
+                U::to_usize(),
+                &t_aux.tree_r_last_config,
+            )
+            .context("tree_r_last")?;
             tree_r_last_store.compact(
-                QUAD_ARITY,
+                U::to_usize(),
                 t_aux.tree_r_last_config.clone(),
                 StoreConfigDataVersion::One as u32,
             )?;
@@ -429,16 +484,16 @@ impl<H: Hasher, G: Hasher> TemporaryAux<H, G> {
 }
 
 #[derive(Debug)]
-pub struct TemporaryAuxCache<H: Hasher, G: Hasher> {
+pub struct TemporaryAuxCache<H: Hasher, G: Hasher, U: typenum::Unsigned = typenum::U4> {
     /// The encoded nodes for 1..layers.
     pub labels: LabelsCache<H>,
     pub tree_d: BinaryTree<G>,
-    pub tree_r_last: QuadTree<H>,
+    pub tree_r_last: MerkleTree<H::Domain, H::Function, U>,
     pub tree_c: QuadTree<H>,
     pub t_aux: TemporaryAux<H, G>,
 }
 
-impl<H: Hasher, G: Hasher> TemporaryAuxCache<H, G> {
+impl<H: Hasher, G: Hasher, U: typenum::Unsigned> TemporaryAuxCache<H, G, U> {
     pub fn new(t_aux: &TemporaryAux<H, G>) -> Result<Self> {
         let tree_d_size = t_aux.tree_d_config.size.unwrap();
         trace!(
@@ -474,14 +529,14 @@ impl<H: Hasher, G: Hasher> TemporaryAuxCache<H, G> {
         trace!(
             "Instantiating Tree R Last with size {} and leafs {}",
             tree_r_last_size,
-            get_merkle_tree_leafs(tree_r_last_size, QUAD_ARITY)
+            get_merkle_tree_leafs(tree_r_last_size, U::to_usize())
         );
         let tree_r_last_store: DiskStore<H::Domain> =
-            DiskStore::new_from_disk(tree_r_last_size, QUAD_ARITY, &t_aux.tree_r_last_config)
+            DiskStore::new_from_disk(tree_r_last_size, U::to_usize(), &t_aux.tree_r_last_config)
                 .context("tree_r_last_store")?;
-        let tree_r_last: QuadTree<H> = MerkleTree::from_data_store(
+        let tree_r_last: MerkleTree<H::Domain, H::Function, U> = MerkleTree::from_data_store(
             tree_r_last_store,
-            get_merkle_tree_leafs(tree_r_last_size, QUAD_ARITY),
+            get_merkle_tree_leafs(tree_r_last_size, U::to_usize()),
         )
         .context("tree_r_last")?;
 
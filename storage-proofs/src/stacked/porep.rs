@@ -1,3 +1,5 @@
+use generic_array::typenum;
+
 use crate::error::Result;
 use crate::hasher::Hasher;
 use crate::porep::{Data, PoRep};
@@ -8,7 +10,9 @@ use crate::stacked::{
 
 use merkletree::store::StoreConfig;
 
-impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> PoRep<'a, H, G> for StackedDrg<'a, H, G> {
+impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher, U: 'static + typenum::Unsigned + Send + Sync>
+    PoRep<'a, H, G> for StackedDrg<'a, H, G, U>
+{
     type Tau = Tau<<H as Hasher>::Domain, <G as Hasher>::Domain>;
     type ProverAux = (PersistentAux<H::Domain>, TemporaryAux<H, G>);
 
@@ -0,0 +1,219 @@
+use anyhow::anyhow;
+use crossbeam::channel::bounded;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::drgraph::Graph;
+use crate::error::Result;
+use crate::hasher::Hasher;
+use crate::stacked::{
+    graph::StackedBucketGraph,
+    params::PrefetchConfig,
+    proof::{hash_parents_data, store_label, TOTAL_PARENTS},
+};
+use crate::util::{data_at_node_offset, NODE_SIZE};
+
+/// Maximum number of nodes handed from the producer to the hashing thread at once.
+const MAX_BATCH_NODES: usize = 256;
+
+/// A slot of the ring buffer shared between the producer and the hashing thread.
+struct Batch {
+    /// The first node of this batch.
+    start: usize,
+    /// The number of nodes in this batch.
+    len: usize,
+    /// The parent indices, `degree` entries per node.
+    parents: Vec<u32>,
+    /// The labels of the expander parents, `expansion_degree` nodes per node.
+    exp_data: Vec<u8>,
+}
+
+/// Generates the labels of a single layer into `layer_labels`.
+///
+/// A producer thread runs up to `config.lookahead` nodes ahead of the hashing thread,
+/// reading the parent indices and gathering the expander parents from the previous
+/// layer (`exp_parents_data`) into a ring buffer. The base parents live in the current
+/// layer, which is still being written, so the hashing thread copies those itself.
+///
+/// The result is identical to calling `create_key` for every node of the layer.
+pub fn create_layer_labels_prefetched<H: Hasher>(
+    graph: &StackedBucketGraph<H>,
+    base_hasher: &Sha256,
+    exp_parents_data: Option<&[u8]>,
+    layer_labels: &mut [u8],
+    config: &PrefetchConfig,
+) -> Result<()> {
+    let nodes = graph.size();
+    let degree = graph.degree();
+    let base_degree = graph.base_graph().degree();
+    let exp_degree = graph.expansion_degree();
+    let real_parents_count = if exp_parents_data.is_some() {
+        degree
+    } else {
+        base_degree
+    };
+
+    let batch_nodes = std::cmp::min(std::cmp::max(config.lookahead, 1), MAX_BATCH_NODES);
+    let batches = std::cmp::max((config.lookahead + batch_nodes - 1) / batch_nodes, 2);
+    info!("  prefetching {} batches of {} nodes", batches, batch_nodes);
+
+    // Two channels form the ring buffer: empty batches flow from the hashing thread
+    // back to the producer, filled ones from the producer to the hashing thread.
+    let (free_tx, free_rx) = bounded::<Batch>(batches);
+    let (full_tx, full_rx) = bounded::<Batch>(batches);
+
+    for _ in 0..batches {
+        free_tx
+            .send(Batch {
+                start: 0,
+                len: 0,
+                parents: vec![0u32; batch_nodes * degree],
+                exp_data: vec![0u8; batch_nodes * exp_degree * NODE_SIZE],
+            })
+            .expect("the receiver is still in scope");
+    }
+
+    let producer_core = config.producer_core;
+    let hasher_core = config.hasher_core;
+
+    let res = crossbeam::scope(|s| {
+        let producer = s.spawn(move |_| -> Result<()> {
+            if let Some(core) = producer_core {
+                pin_to_core(core);
+            }
+
+            let mut start = 0;
+            while start < nodes {
+                // The hashing thread stopped, nothing left to do.
+                let mut batch = match free_rx.recv() {
+                    Ok(batch) => batch,
+                    Err(_) => return Ok(()),
+                };
+                batch.start = start;
+                batch.len = std::cmp::min(batch_nodes, nodes - start);
+
+                for i in 0..batch.len {
+                    let parents = &mut batch.parents[i * degree..(i + 1) * degree];
+                    graph.parents(start + i, parents)?;
+
+                    if let Some(exp_parents_data) = exp_parents_data {
+                        for (j, parent) in parents.iter().skip(base_degree).enumerate() {
+                            let node_off = data_at_node_offset(*parent as usize);
+                            let off = (i * exp_degree + j) * NODE_SIZE;
+                            batch.exp_data[off..off + NODE_SIZE]
+                                .copy_from_slice(&exp_parents_data[node_off..node_off + NODE_SIZE]);
+                        }
+                    }
+                }
+
+                start += batch.len;
+                if full_tx.send(batch).is_err() {
+                    return Ok(());
+                }
+            }
+
+            Ok(())
+        });
+
+        let consumer = s.spawn(move |_| -> usize {
+            if let Some(core) = hasher_core {
+                pin_to_core(core);
+            }
+
+            let mut inputs = vec![0u8; NODE_SIZE * TOTAL_PARENTS + 8];
+            let mut labeled = 0;
+
+            // Ends once the producer is done (or failed) and dropped its sender.
+            for batch in full_rx.iter() {
+                for i in 0..batch.len {
+                    let node = batch.start + i;
+                    let mut hasher = base_hasher.clone();
+
+                    // hash parents for all non 0 nodes
+                    if node > 0 {
+                        // hash node id
+                        inputs[..8].copy_from_slice(&(node as u64).to_be_bytes());
+
+                        // Base parents
+                        let parents = &batch.parents[i * degree..i * degree + base_degree];
+                        for (j, parent) in parents.iter().enumerate() {
+                            let node_off = data_at_node_offset(*parent as usize);
+                            let off = 8 + j * NODE_SIZE;
+                            inputs[off..off + NODE_SIZE]
+                                .copy_from_slice(&layer_labels[node_off..node_off + NODE_SIZE]);
+                        }
+
+                        // Expander parents
+                        if exp_parents_data.is_some() {
+                            let len = exp_degree * NODE_SIZE;
+                            let off = 8 + base_degree * NODE_SIZE;
+                            inputs[off..off + len]
+                                .copy_from_slice(&batch.exp_data[i * len..(i + 1) * len]);
+                        }
+
+                        hash_parents_data(&mut hasher, &mut inputs, real_parents_count);
+                    } else {
+                        hasher.input(&(node as u64).to_be_bytes()[..]);
+                    }
+
+                    let start = data_at_node_offset(node);
+                    store_label(hasher, &mut layer_labels[start..start + NODE_SIZE]);
+                }
+
+                labeled += batch.len;
+
+                // The producer might already be done, in which case the batch is dropped.
+                let _ = free_tx.send(batch);
+            }
+
+            labeled
+        });
+
+        let labeled = consumer
+            .join()
+            .map_err(|_| anyhow!("hashing thread panicked"))?;
+        producer
+            .join()
+            .map_err(|_| anyhow!("producer thread panicked"))??;
+
+        anyhow::ensure!(
+            labeled == nodes,
+            "only {} of {} nodes were labeled",
+            labeled,
+            nodes
+        );
+
+        Ok(())
+    });
+
+    res.map_err(|_| anyhow!("labeling threads panicked"))?
+}
+
+#[cfg(target_os = "linux")]
+fn pin_to_core(core: usize) {
+    // `CPU_SET` panics on cores beyond the fixed size of a `cpu_set_t`.
+    if core >= libc::CPU_SETSIZE as usize {
+        warn!(
+            "core {} is out of range (at most {}), not pinning",
+            core,
+            libc::CPU_SETSIZE - 1
+        );
+        return;
+    }
+
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            warn!("failed to pin thread to core {}", core);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_core(core: usize) {
+    warn!(
+        "pinning threads is not supported on this platform, not pinning to core {}",
+        core
+    );
+}
@@ -1,56 +1,49 @@
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
-use log::{info, trace};
-use merkletree::merkle::FromIndexedParallelIterator;
-use merkletree::store::{DiskStore, StoreConfig};
+use generic_array::typenum;
+use log::trace;
 use rayon::prelude::*;
 use sha2::{Digest, Sha256};
 
 use crate::drgraph::Graph;
-use crate::encode::{decode, encode};
 use crate::error::Result;
-use crate::hasher::{Domain, HashFunction, Hasher};
-use crate::measurements::{
-    measure_op,
-    Operation::{CommD, EncodeWindowTimeAll, GenerateTreeC, GenerateTreeRLast},
-};
-use crate::merkle::{MerkleProof, MerkleTree, QuadMerkleTree, Store};
-use crate::porep::Data;
-use crate::porep::PoRep;
+use crate::hasher::Hasher;
+use crate::merkle::MerkleProof;
 use crate::stacked::{
     challenges::LayerChallenges,
     column::Column,
     graph::StackedBucketGraph,
-    params::{
-        get_node, BinaryTree, CacheKey, Labels, LabelsCache, PersistentAux, Proof, PublicInputs,
-        PublicParams, ReplicaColumnProof, Tau, TemporaryAux, TemporaryAuxCache, TransformedLayers,
-        BINARY_ARITY, QUAD_ARITY,
-    },
+    params::{PersistentAux, Proof, PublicInputs, ReplicaColumnProof, TemporaryAuxCache},
     EncodingProof, LabelingProof,
 };
+use crate::trace;
 use crate::util::{data_at_node_offset, NODE_SIZE};
 
 pub const TOTAL_PARENTS: usize = 37;
 
+/// Stacked DRG, with a `tree_r_last` of arity `U`.
 #[derive(Debug)]
-pub struct StackedDrg<'a, H: 'a + Hasher, G: 'a + Hasher> {
+pub struct StackedDrg<'a, H: 'a + Hasher, G: 'a + Hasher, U: 'a + typenum::Unsigned = typenum::U4> {
     _a: PhantomData<&'a H>,
     _b: PhantomData<&'a G>,
+    _u: PhantomData<U>,
 }
 
-impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
+impl<'a, H: 'static + Hasher, G: 'static + Hasher, U: 'static + typenum::Unsigned + Send + Sync>
+    StackedDrg<'a, H, G, U>
+{
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn prove_layers(
         graph: &StackedBucketGraph<H>,
         pub_inputs: &PublicInputs<<H as Hasher>::Domain, <G as Hasher>::Domain>,
         p_aux: &PersistentAux<H::Domain>,
-        t_aux: &TemporaryAuxCache<H, G>,
+        t_aux: &TemporaryAuxCache<H, G, U>,
         layer_challenges: &LayerChallenges,
         layers: usize,
         _total_layers: usize,
         partition_count: usize,
-    ) -> Result<Vec<Vec<Proof<H, G>>>> {
+    ) -> Result<Vec<Vec<Proof<H, G, U>>>> {
         assert!(layers > 0);
         assert_eq!(t_aux.labels.len(), layers);
 
@@ -89,6 +82,7 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
         (0..partition_count)
             .map(|k| {
                 trace!("proving partition {}/{}", k + 1, partition_count);
+                let _span = trace::span("prove_partition").arg("partition", k);
 
                 // Derive the set of challenges we are proving over.
                 let challenges = pub_inputs.challenges(layer_challenges, graph_size, Some(k));
@@ -214,376 +208,6 @@ impl<'a, H: 'static + Hasher, G: 'static + Hasher> StackedDrg<'a, H, G> {
             })
             .collect()
     }
-
-    pub(crate) fn extract_and_invert_transform_layers(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
-        replica_id: &<H as Hasher>::Domain,
-        data: &mut [u8],
-        config: StoreConfig,
-    ) -> Result<()> {
-        trace!("extract_and_invert_transform_layers");
-
-        let layers = layer_challenges.layers();
-        assert!(layers > 0);
-
-        // generate labels
-        let (labels, _) = Self::generate_labels(graph, layer_challenges, replica_id, config)?;
-
-        let last_layer_labels = labels.labels_for_last_layer()?;
-        let size = merkletree::store::Store::len(last_layer_labels);
-
-        for (key, encoded_node_bytes) in last_layer_labels
-            .read_range(0..size)?
-            .into_iter()
-            .zip(data.chunks_mut(NODE_SIZE))
-        {
-            let encoded_node = H::Domain::try_from_bytes(encoded_node_bytes)?;
-            let data_node = decode::<H::Domain>(key, encoded_node);
-
-            // store result in the data
-            encoded_node_bytes.copy_from_slice(AsRef::<[u8]>::as_ref(&data_node));
-        }
-
-        Ok(())
-    }
-
-    #[allow(clippy::type_complexity)]
-    fn generate_labels(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
-        replica_id: &<H as Hasher>::Domain,
-        config: StoreConfig,
-    ) -> Result<(LabelsCache<H>, Labels<H>)> {
-        info!("generate labels");
-        let layers = layer_challenges.layers();
-        // For now, we require it due to changes in encodings structure.
-        let mut labels: Vec<DiskStore<H::Domain>> = Vec::with_capacity(layers);
-        let mut label_configs: Vec<StoreConfig> = Vec::with_capacity(layers);
-
-        let layer_size = graph.size() * NODE_SIZE;
-        let mut layer_labels = vec![0u8; layer_size];
-
-        let mut exp_parents_data: Option<Vec<u8>> = None;
-
-        // setup hasher to reuse
-        let mut base_hasher = Sha256::new();
-        // hash replica id
-        base_hasher.input(AsRef::<[u8]>::as_ref(replica_id));
-
-        for layer in 1..=layers {
-            info!("generating layer: {}", layer);
-
-            for node in 0..graph.size() {
-                create_key(
-                    graph,
-                    base_hasher.clone(),
-                    exp_parents_data.as_ref(),
-                    &mut layer_labels,
-                    node,
-                )?;
-            }
-
-            info!("  setting exp parents");
-
-            // NOTE: this means we currently keep 2x sector size around, to improve speed.
-            if let Some(ref mut exp_parents_data) = exp_parents_data {
-                exp_parents_data.copy_from_slice(&layer_labels);
-            } else {
-                exp_parents_data = Some(layer_labels.clone());
-            }
-
-            // Write the result to disk to avoid keeping it in memory all the time.
-            let layer_config =
-                StoreConfig::from_config(&config, CacheKey::label_layer(layer), Some(graph.size()));
-
-            info!("  storing labels on disk");
-            // Construct and persist the layer data.
-            let layer_store: DiskStore<H::Domain> = DiskStore::new_from_slice_with_config(
-                graph.size(),
-                QUAD_ARITY,
-                &layer_labels,
-                layer_config.clone(),
-            )?;
-            info!(
-                "  generated layer {} store with id {}",
-                layer, layer_config.id
-            );
-
-            // Track the layer specific store and StoreConfig for later retrieval.
-            labels.push(layer_store);
-            label_configs.push(layer_config);
-        }
-
-        assert_eq!(
-            labels.len(),
-            layers,
-            "Invalid amount of layers encoded expected"
-        );
-
-        Ok((
-            LabelsCache::<H> {
-                labels,
-                _h: PhantomData,
-            },
-            Labels::<H> {
-                labels: label_configs,
-                _h: PhantomData,
-            },
-        ))
-    }
-
-    fn build_binary_tree<K: Hasher>(
-        tree_data: &[u8],
-        config: StoreConfig,
-    ) -> Result<BinaryTree<K>> {
-        trace!("building tree (size: {})", tree_data.len());
-
-        let leafs = tree_data.len() / NODE_SIZE;
-        assert_eq!(tree_data.len() % NODE_SIZE, 0);
-
-        MerkleTree::from_par_iter_with_config(
-            (0..leafs)
-                .into_par_iter()
-                // TODO: proper error handling instead of `unwrap()`
-                .map(|i| get_node::<K>(tree_data, i).unwrap()),
-            config,
-        )
-    }
-
-    pub(crate) fn transform_and_replicate_layers(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
-        replica_id: &<H as Hasher>::Domain,
-        data: Data,
-        data_tree: Option<BinaryTree<G>>,
-        config: StoreConfig,
-    ) -> Result<TransformedLayers<H, G>> {
-        // Generate key layers.
-        let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(graph, layer_challenges, replica_id, config.clone())
-        })?;
-
-        Self::transform_and_replicate_layers_inner(
-            graph,
-            layer_challenges,
-            data,
-            data_tree,
-            config,
-            labels,
-        )
-    }
-
-    pub(crate) fn transform_and_replicate_layers_inner(
-        graph: &StackedBucketGraph<H>,
-        layer_challenges: &LayerChallenges,
-        mut data: Data,
-        data_tree: Option<BinaryTree<G>>,
-        config: StoreConfig,
-        label_configs: Labels<H>,
-    ) -> Result<TransformedLayers<H, G>> {
-        trace!("transform_and_replicate_layers");
-        let nodes_count = graph.size();
-
-        assert_eq!(data.len(), nodes_count * NODE_SIZE);
-        trace!("nodes count {}, data len {}", nodes_count, data.len());
-
-        let layers = layer_challenges.layers();
-        assert!(layers > 0);
-
-        // Generate all store configs that we need based on the
-        // cache_path in the specified config.
-        let mut tree_d_config = StoreConfig::from_config(
-            &config,
-            CacheKey::CommDTree.to_string(),
-            Some(StoreConfig::default_cached_above_base_layer(
-                data.len(),
-                BINARY_ARITY,
-            )),
-        );
-        let mut tree_r_last_config = StoreConfig::from_config(
-            &config,
-            CacheKey::CommRLastTree.to_string(),
-            Some(StoreConfig::default_cached_above_base_layer(
-                data.len(),
-                QUAD_ARITY,
-            )),
-        );
-        let mut tree_c_config = StoreConfig::from_config(
-            &config,
-            CacheKey::CommCTree.to_string(),
-            Some(StoreConfig::default_cached_above_base_layer(
-                data.len(),
-                QUAD_ARITY,
-            )),
-        );
-
-        let labels = LabelsCache::new(&label_configs)?;
-
-        // Build the tree for CommC
-        let tree_c = measure_op(GenerateTreeC, || {
-            info!("Building column hashes");
-
-            let gsize = graph.size();
-
-            let mut hashes: Vec<H::Domain> = vec![H::Domain::default(); gsize];
-
-            rayon::scope(|s| {
-                // spawn n = num_cpus * 2 threads
-                let n = num_cpus::get() * 2;
-
-                // only split if we have at least two elements per thread
-                let num_chunks = if n > gsize * 2 { 1 } else { n };
-
-                // chunk into n chunks
-                let chunk_size = (gsize as f64 / num_chunks as f64).ceil() as usize;
-
-                // calculate all n chunks in parallel
-                for (chunk, hashes_chunk) in hashes.chunks_mut(chunk_size).enumerate() {
-                    let labels = &labels;
-
-                    s.spawn(move |_| {
-                        for (i, hash) in hashes_chunk.iter_mut().enumerate() {
-                            let data: Vec<_> = (1..=layers)
-                                .map(|layer| {
-                                    let store = labels.labels_for_layer(layer);
-                                    store.read_at(i + chunk * chunk_size).unwrap().into()
-                                })
-                                .collect();
-
-                            *hash = crate::stacked::hash::hash_single_column(&data).into();
-                        }
-                    });
-                }
-            });
-
-            info!("building tree_c");
-            QuadMerkleTree::<_, H::Function>::from_par_iter_with_config(
-                hashes.into_par_iter(),
-                tree_c_config.clone(),
-            )
-        })?;
-        info!("tree_c done");
-
-        // Build the MerkleTree over the original data (if needed).
-        let tree_d = match data_tree {
-            Some(t) => {
-                trace!("using existing original data merkle tree");
-                assert_eq!(t.len(), 2 * (data.len() / NODE_SIZE) - 1);
-
-                t
-            }
-            None => {
-                trace!("building merkle tree for the original data");
-                data.ensure_data()?;
-                measure_op(CommD, || {
-                    Self::build_binary_tree::<G>(data.as_ref(), tree_d_config.clone())
-                })?
-            }
-        };
-
-        // Encode original data into the last layer.
-        info!("building tree_r_last");
-        let tree_r_last = measure_op(GenerateTreeRLast, || {
-            data.ensure_data()?;
-
-            let last_layer_labels = labels.labels_for_last_layer()?;
-            let size = Store::len(last_layer_labels);
-
-            let encoded_data = last_layer_labels
-                .read_range(0..size)?
-                .into_par_iter()
-                .zip(data.as_ref().par_chunks(NODE_SIZE))
-                .map(|(key, data_node_bytes)| {
-                    let data_node = H::Domain::try_from_bytes(data_node_bytes).unwrap();
-                    encode::<H::Domain>(key, data_node)
-                });
-
-            QuadMerkleTree::<_, H::Function>::from_par_iter_with_config(
-                encoded_data,
-                tree_r_last_config.clone(),
-            )
-        })?;
-        info!("tree_r_last done");
-
-        // store encoded data.
-        tree_r_last.read_into(0, data.as_mut())?;
-
-        data.drop_data();
-
-        // comm_r = H(comm_c || comm_r_last)
-        let comm_r: H::Domain = H::Function::hash2(&tree_c.root(), &tree_r_last.root());
-
-        assert_eq!(tree_r_last.len(), tree_c.len());
-
-        tree_d_config.size = Some(tree_d.len());
-        tree_r_last_config.size = Some(tree_r_last.len());
-        tree_c_config.size = Some(tree_c.len());
-
-        trace!("tree d len {}", tree_d.len());
-        trace!("tree c len {}", tree_c.len());
-        trace!("tree r_last len {}", tree_r_last.len());
-
-        Ok((
-            Tau {
-                comm_d: tree_d.root(),
-                comm_r,
-            },
-            PersistentAux {
-                comm_c: tree_c.root(),
-                comm_r_last: tree_r_last.root(),
-            },
-            TemporaryAux {
-                labels: label_configs,
-                tree_d_config,
-                tree_r_last_config,
-                tree_c_config,
-                _g: PhantomData,
-            },
-        ))
-    }
-
-    /// Phase1 of replication.
-    pub fn replicate_phase1(
-        pp: &'a PublicParams<H>,
-        replica_id: &H::Domain,
-        config: StoreConfig,
-    ) -> Result<Labels<H>> {
-        info!("replicate_phase1");
-
-        let (_, labels) = measure_op(EncodeWindowTimeAll, || {
-            Self::generate_labels(&pp.graph, &pp.layer_challenges, replica_id, config)
-        })?;
-
-        Ok(labels)
-    }
-
-    #[allow(clippy::type_complexity)]
-    /// Phase2 of replication.
-    #[allow(clippy::type_complexity)]
-    pub fn replicate_phase2(
-        pp: &'a PublicParams<H>,
-        labels: Labels<H>,
-        data: Data<'a>,
-        data_tree: BinaryTree<G>,
-        config: StoreConfig,
-    ) -> Result<(
-        <Self as PoRep<'a, H, G>>::Tau,
-        <Self as PoRep<'a, H, G>>::ProverAux,
-    )> {
-        info!("replicate_phase2");
-
-        let (tau, paux, taux) = Self::transform_and_replicate_layers_inner(
-            &pp.graph,
-            &pp.layer_challenges,
-            data,
-            Some(data_tree),
-            config,
-            labels,
-        )?;
-
-        Ok((tau, (paux, taux)))
-    }
 }
 
 pub fn create_key<H: Hasher>(
@@ -614,18 +238,7 @@ pub fn create_key<H: Hasher>(
             &mut inputs[8..],
         );
 
-        // Repeat parents
-        {
-            let (source, rest) = inputs.split_at_mut(NODE_SIZE * real_parents_count + 8);
-            let source = &source[8..];
-            debug_assert_eq!(source.len(), NODE_SIZE * real_parents_count);
-
-            for chunk in rest.chunks_mut(source.len()) {
-                chunk.copy_from_slice(&source[..chunk.len()]);
-            }
-        }
-
-        hasher.input(&inputs);
+        hash_parents_data(&mut hasher, &mut inputs, real_parents_count);
     } else {
         hasher.input(&(node as u64).to_be_bytes()[..]);
     }
@@ -633,236 +246,30 @@ pub fn create_key<H: Hasher>(
     // store the newly generated key
     let start = data_at_node_offset(node);
     let end = start + NODE_SIZE;
-    layer_labels[start..end].copy_from_slice(&hasher.result()[..]);
-
-    // strip last two bits, to ensure result is in Fr.
-    layer_labels[end - 1] &= 0b0011_1111;
+    store_label(hasher, &mut layer_labels[start..end]);
 
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use ff::Field;
-    use paired::bls12_381::{Bls12, Fr};
-    use rand::{Rng, SeedableRng};
-    use rand_xorshift::XorShiftRng;
-
-    use crate::drgraph::{new_seed, BASE_DEGREE};
-    use crate::fr32::fr_into_bytes;
-    use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
-    use crate::porep::PoRep;
-    use crate::proof::ProofScheme;
-    use crate::stacked::{PrivateInputs, SetupParams, EXP_DEGREE};
-
-    const DEFAULT_STACKED_LAYERS: usize = 11;
-
-    #[test]
-    fn test_calculate_fixed_challenges() {
-        let layer_challenges = LayerChallenges::new(10, 333);
-        let expected = 333;
-
-        let calculated_count = layer_challenges.challenges_count_all();
-        assert_eq!(expected as usize, calculated_count);
-    }
-
-    #[test]
-    fn extract_all_pedersen() {
-        test_extract_all::<PedersenHasher>();
-    }
-
-    #[test]
-    fn extract_all_sha256() {
-        test_extract_all::<Sha256Hasher>();
-    }
-
-    #[test]
-    fn extract_all_blake2s() {
-        test_extract_all::<Blake2sHasher>();
-    }
-
-    #[test]
-    fn extract_all_poseidon() {
-        test_extract_all::<PoseidonHasher>();
-    }
-
-    fn test_extract_all<H: 'static + Hasher>() {
-        // femme::pretty::Logger::new()
-        //     .start(log::LevelFilter::Trace)
-        //     .ok();
-
-        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
-        let replica_id: H::Domain = H::Domain::random(rng);
-        let nodes = 16;
-
-        let data: Vec<u8> = (0..nodes)
-            .flat_map(|_| {
-                let v: H::Domain = H::Domain::random(rng);
-                v.into_bytes()
-            })
-            .collect();
-        let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
-
-        // create a copy, so we can compare roundtrips
-        let mut data_copy = data.clone();
-
-        let sp = SetupParams {
-            nodes,
-            degree: BASE_DEGREE,
-            expansion_degree: EXP_DEGREE,
-            seed: new_seed(),
-            layer_challenges: challenges.clone(),
-        };
-
-        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
-
-        // MT for original data is always named tree-d, and it will be
-        // referenced later in the process as such.
-        let cache_dir = tempfile::tempdir().unwrap();
-        let config = StoreConfig::new(
-            cache_dir.path(),
-            CacheKey::CommDTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
-        );
-
-        StackedDrg::<H, Blake2sHasher>::replicate(
-            &pp,
-            &replica_id,
-            (&mut data_copy[..]).into(),
-            None,
-            Some(config.clone()),
-        )
-        .expect("replication failed");
-
-        assert_ne!(data, data_copy);
-
-        let decoded_data = StackedDrg::<H, Blake2sHasher>::extract_all(
-            &pp,
-            &replica_id,
-            data_copy.as_mut_slice(),
-            Some(config.clone()),
-        )
-        .expect("failed to extract data");
-
-        assert_eq!(data, decoded_data);
-    }
-
-    fn prove_verify_fixed(n: usize) {
-        let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+/// Repeats the first `real_parents_count` parents in `inputs` (which is prefixed by the
+/// 8 byte node id) until `TOTAL_PARENTS` are filled, and feeds the result into `hasher`.
+pub(crate) fn hash_parents_data(hasher: &mut Sha256, inputs: &mut [u8], real_parents_count: usize) {
+    {
+        let (source, rest) = inputs.split_at_mut(NODE_SIZE * real_parents_count + 8);
+        let source = &source[8..];
+        debug_assert_eq!(source.len(), NODE_SIZE * real_parents_count);
 
-        test_prove_verify::<PedersenHasher>(n, challenges.clone());
-        test_prove_verify::<Sha256Hasher>(n, challenges.clone());
-        test_prove_verify::<Blake2sHasher>(n, challenges.clone());
-        test_prove_verify::<PoseidonHasher>(n, challenges.clone());
-    }
-
-    fn test_prove_verify<H: 'static + Hasher>(n: usize, challenges: LayerChallenges) {
-        // This will be called multiple times, only the first one succeeds, and that is ok.
-        // femme::pretty::Logger::new()
-        //     .start(log::LevelFilter::Trace)
-        //     .ok();
-
-        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
-
-        let degree = BASE_DEGREE;
-        let expansion_degree = EXP_DEGREE;
-        let replica_id: H::Domain = H::Domain::random(rng);
-        let data: Vec<u8> = (0..n)
-            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
-            .collect();
-
-        // create a copy, so we can compare roundtrips
-        let mut data_copy = data.clone();
-        let partitions = 2;
-
-        let sp = SetupParams {
-            nodes: n,
-            degree,
-            expansion_degree,
-            seed: new_seed(),
-            layer_challenges: challenges.clone(),
-        };
-
-        // MT for original data is always named tree-d, and it will be
-        // referenced later in the process as such.
-        let cache_dir = tempfile::tempdir().unwrap();
-        let config = StoreConfig::new(
-            cache_dir.path(),
-            CacheKey::CommDTree.to_string(),
-            StoreConfig::default_cached_above_base_layer(n, BINARY_ARITY),
-        );
-
-        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
-        let (tau, (p_aux, t_aux)) = StackedDrg::<H, Blake2sHasher>::replicate(
-            &pp,
-            &replica_id,
-            (&mut data_copy[..]).into(),
-            None,
-            Some(config),
-        )
-        .expect("replication failed");
-        assert_ne!(data, data_copy);
-
-        let seed = rng.gen();
-
-        let pub_inputs = PublicInputs::<H::Domain, <Blake2sHasher as Hasher>::Domain> {
-            replica_id,
-            seed,
-            tau: Some(tau),
-            k: None,
-        };
-
-        // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
-        // elements based on the configs stored in TemporaryAux.
-        let t_aux = TemporaryAuxCache::<H, Blake2sHasher>::new(&t_aux)
-            .expect("failed to restore contents of t_aux");
-
-        let priv_inputs = PrivateInputs { p_aux, t_aux };
-
-        let all_partition_proofs = &StackedDrg::<H, Blake2sHasher>::prove_all_partitions(
-            &pp,
-            &pub_inputs,
-            &priv_inputs,
-            partitions,
-        )
-        .expect("failed to generate partition proofs");
-
-        let proofs_are_valid = StackedDrg::<H, Blake2sHasher>::verify_all_partitions(
-            &pp,
-            &pub_inputs,
-            all_partition_proofs,
-        )
-        .expect("failed to verify partition proofs");
-
-        assert!(proofs_are_valid);
-    }
-
-    table_tests! {
-        prove_verify_fixed{
-           prove_verify_fixed_64_4(4);
+        for chunk in rest.chunks_mut(source.len()) {
+            chunk.copy_from_slice(&source[..chunk.len()]);
         }
     }
 
-    #[test]
-    // We are seeing a bug, in which setup never terminates for some sector sizes.
-    // This test is to debug that and should remain as a regression teset.
-    fn setup_terminates() {
-        let degree = BASE_DEGREE;
-        let expansion_degree = EXP_DEGREE;
-        let nodes = 1024 * 1024 * 32 * 8; // This corresponds to 8GiB sectors (32-byte nodes)
-        let layer_challenges = LayerChallenges::new(10, 333);
-        let sp = SetupParams {
-            nodes,
-            degree,
-            expansion_degree,
-            seed: new_seed(),
-            layer_challenges: layer_challenges.clone(),
-        };
+    hasher.input(&inputs[..]);
+}
 
-        // When this fails, the call to setup should panic, but seems to actually hang (i.e. neither return nor panic) for some reason.
-        // When working as designed, the call to setup returns without error.
-        let _pp = StackedDrg::<PedersenHasher, Blake2sHasher>::setup(&sp).expect("setup failed");
-    }
+/// Writes the final hash into `label`, stripping the last two bits to ensure the
+/// result is in Fr.
+pub(crate) fn store_label(hasher: Sha256, label: &mut [u8]) {
+    label.copy_from_slice(&hasher.result()[..]);
+    label[NODE_SIZE - 1] &= 0b0011_1111;
 }
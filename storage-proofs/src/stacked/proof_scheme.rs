@@ -1,4 +1,5 @@
 use anyhow::ensure;
+use generic_array::typenum;
 use log::trace;
 use rayon::prelude::*;
 
@@ -13,12 +14,14 @@ use crate::stacked::{
     proof::StackedDrg,
 };
 
-impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher> ProofScheme<'a> for StackedDrg<'c, H, G> {
+impl<'a, 'c, H: 'static + Hasher, G: 'static + Hasher, U: 'static + typenum::Unsigned + Send + Sync>
+    ProofScheme<'a> for StackedDrg<'c, H, G, U>
+{
     type PublicParams = PublicParams<H>;
     type SetupParams = SetupParams;
     type PublicInputs = PublicInputs<<H as Hasher>::Domain, <G as Hasher>::Domain>;
-    type PrivateInputs = PrivateInputs<H, G>;
-    type Proof = Vec<Proof<H, G>>;
+    type PrivateInputs = PrivateInputs<H, G, U>;
+    type Proof = Vec<Proof<H, G, U>>;
     type Requirements = ChallengeRequirements;
 
     fn setup(sp: &Self::SetupParams) -> Result<Self::PublicParams> {
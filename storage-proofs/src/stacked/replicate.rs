@@ -0,0 +1,1196 @@
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+use anyhow::{ensure, Context};
+use generic_array::typenum;
+use log::{info, trace};
+use memmap::{Mmap, MmapOptions};
+use merkletree::merkle::FromIndexedParallelIterator;
+use merkletree::store::{DiskStore, StoreConfig};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::drgraph::Graph;
+use crate::encode::{decode, encode};
+use crate::error::Result;
+use crate::hasher::{Domain, HashFunction, Hasher};
+use crate::measurements::{
+    measure_op,
+    Operation::{CommD, EncodeWindowTimeAll, GenerateTreeC, GenerateTreeRLast},
+};
+use crate::merkle::{MerkleTree, QuadMerkleTree, Store};
+use crate::metrics;
+use crate::porep::{Data, PoRep};
+use crate::stacked::{
+    challenges::LayerChallenges,
+    create_layer_labels_prefetched,
+    graph::StackedBucketGraph,
+    params::{
+        get_node, BinaryTree, CacheKey, LabelingMode, Labels, LabelsCache, PersistentAux,
+        PublicParams, Tau, TemporaryAux, TransformedLayers, BINARY_ARITY, QUAD_ARITY,
+    },
+    proof::{create_key, hash_parents_data, store_label, StackedDrg, TOTAL_PARENTS},
+    PrefetchConfig,
+};
+use crate::trace;
+use crate::util::{data_at_node_offset, NODE_SIZE};
+
+/// The nodes `generate_labels_multi` looks up the parents of at once, before hashing them for
+/// every sector in parallel.
+const MULTI_LABELS_CHUNK_NODES: usize = 4096;
+
+impl<'a, H: 'static + Hasher, G: 'static + Hasher, U: 'static + typenum::Unsigned + Send + Sync>
+    StackedDrg<'a, H, G, U>
+{
+    pub(crate) fn extract_and_invert_transform_layers(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<H as Hasher>::Domain,
+        data: &mut [u8],
+        config: StoreConfig,
+    ) -> Result<()> {
+        trace!("extract_and_invert_transform_layers");
+
+        let layers = layer_challenges.layers();
+        assert!(layers > 0);
+
+        // generate labels
+        let (labels, _) = Self::generate_labels(
+            graph,
+            layer_challenges,
+            replica_id,
+            config,
+            LabelingMode::default(),
+        )?;
+
+        let last_layer_labels = labels.labels_for_last_layer()?;
+        let size = merkletree::store::Store::len(last_layer_labels);
+
+        for (key, encoded_node_bytes) in last_layer_labels
+            .read_range(0..size)?
+            .into_iter()
+            .zip(data.chunks_mut(NODE_SIZE))
+        {
+            let encoded_node = H::Domain::try_from_bytes(encoded_node_bytes)?;
+            let data_node = decode::<H::Domain>(key, encoded_node);
+
+            // store result in the data
+            encoded_node_bytes.copy_from_slice(AsRef::<[u8]>::as_ref(&data_node));
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_labels(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<H as Hasher>::Domain,
+        config: StoreConfig,
+        mode: LabelingMode,
+    ) -> Result<(LabelsCache<H>, Labels<H>)> {
+        info!("generate labels ({:?})", mode);
+
+        let labels = match mode {
+            LabelingMode::InMemory => {
+                Self::generate_labels_in_memory(graph, layer_challenges, replica_id, config, None)
+            }
+            LabelingMode::Prefetch(prefetch) => Self::generate_labels_in_memory(
+                graph,
+                layer_challenges,
+                replica_id,
+                config,
+                Some(&prefetch),
+            ),
+            LabelingMode::Bounded { memory_budget } => Self::generate_labels_bounded(
+                graph,
+                layer_challenges,
+                replica_id,
+                config,
+                memory_budget,
+            ),
+        }?;
+
+        Ok(labels)
+    }
+
+    /// Counts the bytes labeled by replicating `sectors` sectors, which unsealing doesn't.
+    fn record_labeled_bytes(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        sectors: usize,
+    ) {
+        metrics::record_bytes_processed(
+            &EncodeWindowTimeAll,
+            (graph.size() * NODE_SIZE * layer_challenges.layers() * sectors) as u64,
+        );
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_labels_in_memory(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<H as Hasher>::Domain,
+        config: StoreConfig,
+        prefetch: Option<&PrefetchConfig>,
+    ) -> Result<(LabelsCache<H>, Labels<H>)> {
+        let layers = layer_challenges.layers();
+        // For now, we require it due to changes in encodings structure.
+        let mut labels: Vec<DiskStore<H::Domain>> = Vec::with_capacity(layers);
+        let mut label_configs: Vec<StoreConfig> = Vec::with_capacity(layers);
+
+        let layer_size = graph.size() * NODE_SIZE;
+        let mut layer_labels = vec![0u8; layer_size];
+
+        let mut exp_parents_data: Option<Vec<u8>> = None;
+
+        // setup hasher to reuse
+        let mut base_hasher = Sha256::new();
+        // hash replica id
+        base_hasher.input(AsRef::<[u8]>::as_ref(replica_id));
+
+        for layer in 1..=layers {
+            info!("generating layer: {}", layer);
+            let _span = trace::span("label_layer").arg("layer", layer);
+
+            if let Some(prefetch) = prefetch {
+                create_layer_labels_prefetched(
+                    graph,
+                    &base_hasher,
+                    exp_parents_data.as_ref().map(|data| &data[..]),
+                    &mut layer_labels,
+                    prefetch,
+                )?;
+            } else {
+                for node in 0..graph.size() {
+                    create_key(
+                        graph,
+                        base_hasher.clone(),
+                        exp_parents_data.as_ref(),
+                        &mut layer_labels,
+                        node,
+                    )?;
+                }
+            }
+
+            info!("  setting exp parents");
+
+            // NOTE: this means we currently keep 2x sector size around, to improve speed.
+            if let Some(ref mut exp_parents_data) = exp_parents_data {
+                exp_parents_data.copy_from_slice(&layer_labels);
+            } else {
+                exp_parents_data = Some(layer_labels.clone());
+            }
+
+            // Write the result to disk to avoid keeping it in memory all the time.
+            let layer_config =
+                StoreConfig::from_config(&config, CacheKey::label_layer(layer), Some(graph.size()));
+
+            info!("  storing labels on disk");
+            // Construct and persist the layer data.
+            let layer_store: DiskStore<H::Domain> = DiskStore::new_from_slice_with_config(
+                graph.size(),
+                QUAD_ARITY,
+                &layer_labels,
+                layer_config.clone(),
+            )?;
+            info!(
+                "  generated layer {} store with id {}",
+                layer, layer_config.id
+            );
+
+            // Track the layer specific store and StoreConfig for later retrieval.
+            labels.push(layer_store);
+            label_configs.push(layer_config);
+        }
+
+        assert_eq!(
+            labels.len(),
+            layers,
+            "Invalid amount of layers encoded expected"
+        );
+
+        Ok((
+            LabelsCache::<H> {
+                labels,
+                _h: PhantomData,
+            },
+            Labels::<H> {
+                labels: label_configs,
+                _h: PhantomData,
+            },
+        ))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn generate_labels_bounded(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<H as Hasher>::Domain,
+        config: StoreConfig,
+        memory_budget: usize,
+    ) -> Result<(LabelsCache<H>, Labels<H>)> {
+        let layers = layer_challenges.layers();
+        let nodes = graph.size();
+        let layer_size = nodes * NODE_SIZE;
+
+        let mut labels: Vec<DiskStore<H::Domain>> = Vec::with_capacity(layers);
+        let mut label_configs: Vec<StoreConfig> = Vec::with_capacity(layers);
+
+        // The window holds the most recently generated `window_nodes` labels of the
+        // current layer, it always holds at least one node and never more than a layer.
+        let window_nodes = std::cmp::min(std::cmp::max(memory_budget / NODE_SIZE, 1), nodes);
+        let mut window = vec![0u8; window_nodes * NODE_SIZE];
+        info!(
+            "  using a window of {} nodes ({} bytes)",
+            window_nodes,
+            window.len()
+        );
+
+        let base_degree = graph.base_graph().degree();
+        let mut parents = vec![0u32; graph.degree()];
+        let mut inputs = vec![0u8; NODE_SIZE * TOTAL_PARENTS + 8];
+
+        // The previous layer, memory-mapped from its on-disk store.
+        let mut exp_parents_data: Option<Mmap> = None;
+
+        // setup hasher to reuse
+        let mut base_hasher = Sha256::new();
+        // hash replica id
+        base_hasher.input(AsRef::<[u8]>::as_ref(replica_id));
+
+        for layer in 1..=layers {
+            info!("generating layer: {}", layer);
+            let _span = trace::span("label_layer").arg("layer", layer);
+
+            let layer_config =
+                StoreConfig::from_config(&config, CacheKey::label_layer(layer), Some(nodes));
+            let layer_path = StoreConfig::data_path(&layer_config.path, &layer_config.id);
+
+            let mut layer_file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&layer_path)
+                .with_context(|| format!("could not open layer file {:?}", layer_path))?;
+            layer_file.set_len(layer_size as u64)?;
+
+            // Labels which already left the window are read back through this mapping.
+            let layer_data = unsafe {
+                MmapOptions::new()
+                    .map(&layer_file)
+                    .with_context(|| format!("could not mmap layer file {:?}", layer_path))?
+            };
+
+            let real_parents_count = if exp_parents_data.is_some() {
+                graph.degree()
+            } else {
+                base_degree
+            };
+
+            for node in 0..nodes {
+                // Flush the window once it is full, before overwriting its oldest entry.
+                if node > 0 && node % window_nodes == 0 {
+                    let offset = data_at_node_offset(node - window_nodes);
+                    layer_file.seek(SeekFrom::Start(offset as u64))?;
+                    layer_file.write_all(&window)?;
+                }
+
+                let mut hasher = base_hasher.clone();
+
+                // hash parents for all non 0 nodes
+                if node > 0 {
+                    graph.parents(node, &mut parents)?;
+
+                    // hash node id
+                    inputs[..8].copy_from_slice(&(node as u64).to_be_bytes());
+
+                    for (i, parent) in parents.iter().take(real_parents_count).enumerate() {
+                        let parent = *parent as usize;
+                        let off = 8 + i * NODE_SIZE;
+
+                        let source = if i >= base_degree {
+                            // Expander parents come from the previous layer.
+                            let parents_data = exp_parents_data.as_ref().expect("checked above");
+                            &parents_data[data_at_node_offset(parent)..]
+                        } else if parent + window_nodes >= node {
+                            // Base parents still in the window.
+                            &window[data_at_node_offset(parent % window_nodes)..]
+                        } else {
+                            // Base parents already flushed to disk.
+                            &layer_data[data_at_node_offset(parent)..]
+                        };
+
+                        inputs[off..off + NODE_SIZE].copy_from_slice(&source[..NODE_SIZE]);
+                    }
+
+                    hash_parents_data(&mut hasher, &mut inputs, real_parents_count);
+                } else {
+                    hasher.input(&(node as u64).to_be_bytes()[..]);
+                }
+
+                let start = data_at_node_offset(node % window_nodes);
+                store_label(hasher, &mut window[start..start + NODE_SIZE]);
+            }
+
+            // Flush the remainder of the window.
+            let remaining = match nodes % window_nodes {
+                0 => window_nodes,
+                r => r,
+            };
+            let offset = data_at_node_offset(nodes - remaining);
+            layer_file.seek(SeekFrom::Start(offset as u64))?;
+            layer_file.write_all(&window[..remaining * NODE_SIZE])?;
+            layer_file.sync_all()?;
+            drop(layer_data);
+
+            info!("  setting exp parents");
+            exp_parents_data = Some(unsafe {
+                MmapOptions::new()
+                    .map(&layer_file)
+                    .with_context(|| format!("could not mmap layer file {:?}", layer_path))?
+            });
+
+            let layer_store: DiskStore<H::Domain> =
+                DiskStore::new_from_disk(nodes, QUAD_ARITY, &layer_config)?;
+            info!(
+                "  generated layer {} store with id {}",
+                layer, layer_config.id
+            );
+
+            // Track the layer specific store and StoreConfig for later retrieval.
+            labels.push(layer_store);
+            label_configs.push(layer_config);
+        }
+
+        assert_eq!(
+            labels.len(),
+            layers,
+            "Invalid amount of layers encoded expected"
+        );
+
+        Ok((
+            LabelsCache::<H> {
+                labels,
+                _h: PhantomData,
+            },
+            Labels::<H> {
+                labels: label_configs,
+                _h: PhantomData,
+            },
+        ))
+    }
+
+    fn generate_labels_multi(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_ids: &[<H as Hasher>::Domain],
+        configs: Vec<StoreConfig>,
+    ) -> Result<Vec<Labels<H>>> {
+        info!("generate labels for {} sectors", replica_ids.len());
+        ensure!(
+            replica_ids.len() == configs.len(),
+            "expected one config per replica id ({} != {})",
+            replica_ids.len(),
+            configs.len()
+        );
+
+        let lanes = replica_ids.len();
+        let layers = layer_challenges.layers();
+        let mut label_configs: Vec<Vec<StoreConfig>> = vec![Vec::with_capacity(layers); lanes];
+
+        let layer_size = graph.size() * NODE_SIZE;
+        let mut layer_labels = vec![vec![0u8; layer_size]; lanes];
+        let mut exp_parents_data: Vec<Option<Vec<u8>>> = vec![None; lanes];
+
+        // setup hashers to reuse, one per sector
+        let base_hashers: Vec<Sha256> = replica_ids
+            .iter()
+            .map(|replica_id| {
+                let mut hasher = Sha256::new();
+                // hash replica id
+                hasher.input(AsRef::<[u8]>::as_ref(replica_id));
+                hasher
+            })
+            .collect();
+
+        let degree = graph.degree();
+        let mut chunk_parents = vec![0u32; MULTI_LABELS_CHUNK_NODES * degree];
+
+        for layer in 1..=layers {
+            info!("generating layer: {}", layer);
+            let _span = trace::span("label_layer").arg("layer", layer);
+
+            let real_parents_count = if layer > 1 {
+                graph.degree()
+            } else {
+                graph.base_graph().degree()
+            };
+
+            for chunk_start in (0..graph.size()).step_by(MULTI_LABELS_CHUNK_NODES) {
+                let chunk_end = std::cmp::min(chunk_start + MULTI_LABELS_CHUNK_NODES, graph.size());
+                let chunk_parents = &mut chunk_parents[..(chunk_end - chunk_start) * degree];
+
+                // The parents are the same for all sectors, look them up only once.
+                for (node, node_parents) in
+                    (chunk_start..chunk_end).zip(chunk_parents.chunks_mut(degree))
+                {
+                    if node > 0 {
+                        graph.parents(node, node_parents)?;
+                    }
+                }
+                let chunk_parents = &*chunk_parents;
+
+                // Each sector only depends on its own labels, so they are hashed in parallel.
+                layer_labels
+                    .par_iter_mut()
+                    .zip(exp_parents_data.par_iter())
+                    .zip(base_hashers.par_iter())
+                    .for_each(|((labels, exp_parents_data), base_hasher)| {
+                        let mut inputs = vec![0u8; NODE_SIZE * TOTAL_PARENTS + 8];
+
+                        for (node, node_parents) in
+                            (chunk_start..chunk_end).zip(chunk_parents.chunks(degree))
+                        {
+                            let mut hasher = base_hasher.clone();
+
+                            // hash parents for all non 0 nodes
+                            if node > 0 {
+                                // hash node id
+                                inputs[..8].copy_from_slice(&(node as u64).to_be_bytes());
+
+                                graph.copy_parents_data_inner(
+                                    node_parents,
+                                    labels,
+                                    exp_parents_data.as_ref(),
+                                    &mut inputs[8..],
+                                );
+
+                                hash_parents_data(&mut hasher, &mut inputs, real_parents_count);
+                            } else {
+                                hasher.input(&(node as u64).to_be_bytes()[..]);
+                            }
+
+                            let start = data_at_node_offset(node);
+                            store_label(hasher, &mut labels[start..start + NODE_SIZE]);
+                        }
+                    });
+            }
+
+            info!("  setting exp parents");
+
+            for lane in 0..lanes {
+                if let Some(ref mut exp_parents_data) = exp_parents_data[lane] {
+                    exp_parents_data.copy_from_slice(&layer_labels[lane]);
+                } else {
+                    exp_parents_data[lane] = Some(layer_labels[lane].clone());
+                }
+
+                let layer_config = StoreConfig::from_config(
+                    &configs[lane],
+                    CacheKey::label_layer(layer),
+                    Some(graph.size()),
+                );
+
+                info!("  storing labels on disk (sector {})", lane);
+                // Persist the layer data, the store itself is not needed anymore.
+                let _layer_store: DiskStore<H::Domain> = DiskStore::new_from_slice_with_config(
+                    graph.size(),
+                    QUAD_ARITY,
+                    &layer_labels[lane],
+                    layer_config.clone(),
+                )?;
+
+                label_configs[lane].push(layer_config);
+            }
+        }
+
+        Ok(label_configs
+            .into_iter()
+            .map(|labels| Labels::<H> {
+                labels,
+                _h: PhantomData,
+            })
+            .collect())
+    }
+
+    fn build_binary_tree<K: Hasher>(
+        tree_data: &[u8],
+        config: StoreConfig,
+    ) -> Result<BinaryTree<K>> {
+        trace!("building tree (size: {})", tree_data.len());
+
+        let leafs = tree_data.len() / NODE_SIZE;
+        assert_eq!(tree_data.len() % NODE_SIZE, 0);
+
+        MerkleTree::from_par_iter_with_config(
+            (0..leafs)
+                .into_par_iter()
+                // TODO: proper error handling instead of `unwrap()`
+                .map(|i| get_node::<K>(tree_data, i).unwrap()),
+            config,
+        )
+    }
+
+    pub(crate) fn transform_and_replicate_layers(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        replica_id: &<H as Hasher>::Domain,
+        data: Data,
+        data_tree: Option<BinaryTree<G>>,
+        config: StoreConfig,
+    ) -> Result<TransformedLayers<H, G>> {
+        // Generate key layers.
+        let (_, labels) = measure_op(EncodeWindowTimeAll, || {
+            Self::generate_labels(
+                graph,
+                layer_challenges,
+                replica_id,
+                config.clone(),
+                LabelingMode::default(),
+            )
+        })?;
+        Self::record_labeled_bytes(graph, layer_challenges, 1);
+
+        Self::transform_and_replicate_layers_inner(
+            graph,
+            layer_challenges,
+            data,
+            data_tree,
+            config,
+            labels,
+        )
+    }
+
+    pub(crate) fn transform_and_replicate_layers_inner(
+        graph: &StackedBucketGraph<H>,
+        layer_challenges: &LayerChallenges,
+        mut data: Data,
+        data_tree: Option<BinaryTree<G>>,
+        config: StoreConfig,
+        label_configs: Labels<H>,
+    ) -> Result<TransformedLayers<H, G>> {
+        trace!("transform_and_replicate_layers");
+        let nodes_count = graph.size();
+
+        assert_eq!(data.len(), nodes_count * NODE_SIZE);
+        trace!("nodes count {}, data len {}", nodes_count, data.len());
+
+        let layers = layer_challenges.layers();
+        assert!(layers > 0);
+
+        // Generate all store configs that we need based on the
+        // cache_path in the specified config.
+        let mut tree_d_config = StoreConfig::from_config(
+            &config,
+            CacheKey::CommDTree.to_string(),
+            Some(StoreConfig::default_cached_above_base_layer(
+                data.len(),
+                BINARY_ARITY,
+            )),
+        );
+        let mut tree_r_last_config = StoreConfig::from_config(
+            &config,
+            CacheKey::CommRLastTree.to_string(),
+            Some(StoreConfig::default_cached_above_base_layer(
+                data.len(),
+                U::to_usize(),
+            )),
+        );
+        let mut tree_c_config = StoreConfig::from_config(
+            &config,
+            CacheKey::CommCTree.to_string(),
+            Some(StoreConfig::default_cached_above_base_layer(
+                data.len(),
+                QUAD_ARITY,
+            )),
+        );
+
+        let labels = LabelsCache::new(&label_configs)?;
+
+        // Build the tree for CommC
+        let tree_c = measure_op(GenerateTreeC, || {
+            info!("Building column hashes");
+
+            let gsize = graph.size();
+
+            let mut hashes: Vec<H::Domain> = vec![H::Domain::default(); gsize];
+
+            rayon::scope(|s| {
+                // spawn n = num_cpus * 2 threads
+                let n = num_cpus::get() * 2;
+
+                // only split if we have at least two elements per thread
+                let num_chunks = if n > gsize * 2 { 1 } else { n };
+
+                // chunk into n chunks
+                let chunk_size = (gsize as f64 / num_chunks as f64).ceil() as usize;
+
+                // calculate all n chunks in parallel
+                for (chunk, hashes_chunk) in hashes.chunks_mut(chunk_size).enumerate() {
+                    let labels = &labels;
+
+                    s.spawn(move |_| {
+                        for (i, hash) in hashes_chunk.iter_mut().enumerate() {
+                            let data: Vec<_> = (1..=layers)
+                                .map(|layer| {
+                                    let store = labels.labels_for_layer(layer);
+                                    store.read_at(i + chunk * chunk_size).unwrap().into()
+                                })
+                                .collect();
+
+                            *hash = crate::stacked::hash::hash_single_column(&data).into();
+                        }
+                    });
+                }
+            });
+
+            info!("building tree_c");
+            QuadMerkleTree::<_, H::Function>::from_par_iter_with_config(
+                hashes.into_par_iter(),
+                tree_c_config.clone(),
+            )
+        })?;
+        metrics::record_bytes_processed(
+            &GenerateTreeC,
+            (graph.size() * NODE_SIZE * layer_challenges.layers()) as u64,
+        );
+        info!("tree_c done");
+
+        // Build the MerkleTree over the original data (if needed).
+        let tree_d = match data_tree {
+            Some(t) => {
+                trace!("using existing original data merkle tree");
+                assert_eq!(t.len(), 2 * (data.len() / NODE_SIZE) - 1);
+
+                t
+            }
+            None => {
+                trace!("building merkle tree for the original data");
+                data.ensure_data()?;
+                let tree_d = measure_op(CommD, || {
+                    Self::build_binary_tree::<G>(data.as_ref(), tree_d_config.clone())
+                })?;
+                metrics::record_bytes_processed(&CommD, data.len() as u64);
+
+                tree_d
+            }
+        };
+
+        // Encode original data into the last layer.
+        info!("building tree_r_last");
+        let tree_r_last = measure_op(GenerateTreeRLast, || {
+            data.ensure_data()?;
+
+            let last_layer_labels = labels.labels_for_last_layer()?;
+            let size = Store::len(last_layer_labels);
+
+            let encoded_data = last_layer_labels
+                .read_range(0..size)?
+                .into_par_iter()
+                .zip(data.as_ref().par_chunks(NODE_SIZE))
+                .map(|(key, data_node_bytes)| {
+                    let data_node = H::Domain::try_from_bytes(data_node_bytes).unwrap();
+                    encode::<H::Domain>(key, data_node)
+                });
+
+            MerkleTree::<_, H::Function, U>::from_par_iter_with_config(
+                encoded_data,
+                tree_r_last_config.clone(),
+            )
+        })?;
+        metrics::record_bytes_processed(&GenerateTreeRLast, data.len() as u64);
+        info!("tree_r_last done");
+
+        // store encoded data.
+        tree_r_last.read_into(0, data.as_mut())?;
+
+        data.drop_data();
+
+        // comm_r = H(comm_c || comm_r_last)
+        let comm_r: H::Domain = H::Function::hash2(&tree_c.root(), &tree_r_last.root());
+
+        assert_eq!(tree_r_last.leafs(), tree_c.leafs());
+
+        tree_d_config.size = Some(tree_d.len());
+        tree_r_last_config.size = Some(tree_r_last.len());
+        tree_c_config.size = Some(tree_c.len());
+
+        trace!("tree d len {}", tree_d.len());
+        trace!("tree c len {}", tree_c.len());
+        trace!("tree r_last len {}", tree_r_last.len());
+
+        Ok((
+            Tau {
+                comm_d: tree_d.root(),
+                comm_r,
+            },
+            PersistentAux {
+                comm_c: tree_c.root(),
+                comm_r_last: tree_r_last.root(),
+            },
+            TemporaryAux {
+                labels: label_configs,
+                tree_d_config,
+                tree_r_last_config,
+                tree_c_config,
+                _g: PhantomData,
+            },
+        ))
+    }
+
+    /// Phase1 of replication.
+    pub fn replicate_phase1(
+        pp: &'a PublicParams<H>,
+        replica_id: &H::Domain,
+        config: StoreConfig,
+    ) -> Result<Labels<H>> {
+        Self::replicate_phase1_with_mode(pp, replica_id, config, LabelingMode::default())
+    }
+
+    /// Phase1 of replication, generating the labels using the given `LabelingMode`.
+    pub fn replicate_phase1_with_mode(
+        pp: &'a PublicParams<H>,
+        replica_id: &H::Domain,
+        config: StoreConfig,
+        mode: LabelingMode,
+    ) -> Result<Labels<H>> {
+        info!("replicate_phase1");
+
+        let (_, labels) = measure_op(EncodeWindowTimeAll, || {
+            Self::generate_labels(&pp.graph, &pp.layer_challenges, replica_id, config, mode)
+        })?;
+        Self::record_labeled_bytes(&pp.graph, &pp.layer_challenges, 1);
+
+        Ok(labels)
+    }
+
+    /// Phase1 of replication for multiple sectors of the same size at once.
+    ///
+    /// The sectors are labeled in lockstep over the same node order, so the parents of
+    /// each node are only looked up once and shared between all of them, while the labels of
+    /// the sectors are hashed in parallel. The result is identical to calling
+    /// `replicate_phase1` for each `(replica_id, config)` pair.
+    ///
+    /// Two layers of labels are kept in memory per sector, so labeling N sectors at once takes
+    /// N times the memory of labeling one.
+    pub fn replicate_phase1_multi(
+        pp: &'a PublicParams<H>,
+        replica_ids: &[H::Domain],
+        configs: Vec<StoreConfig>,
+    ) -> Result<Vec<Labels<H>>> {
+        info!("replicate_phase1_multi");
+
+        let labels = measure_op(EncodeWindowTimeAll, || {
+            Self::generate_labels_multi(&pp.graph, &pp.layer_challenges, replica_ids, configs)
+        })?;
+        Self::record_labeled_bytes(&pp.graph, &pp.layer_challenges, replica_ids.len());
+
+        Ok(labels)
+    }
+
+    #[allow(clippy::type_complexity)]
+    /// Phase2 of replication.
+    #[allow(clippy::type_complexity)]
+    pub fn replicate_phase2(
+        pp: &'a PublicParams<H>,
+        labels: Labels<H>,
+        data: Data<'a>,
+        data_tree: BinaryTree<G>,
+        config: StoreConfig,
+    ) -> Result<(
+        <Self as PoRep<'a, H, G>>::Tau,
+        <Self as PoRep<'a, H, G>>::ProverAux,
+    )> {
+        info!("replicate_phase2");
+
+        let (tau, paux, taux) = Self::transform_and_replicate_layers_inner(
+            &pp.graph,
+            &pp.layer_challenges,
+            data,
+            Some(data_tree),
+            config,
+            labels,
+        )?;
+
+        Ok((tau, (paux, taux)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use paired::bls12_381::{Bls12, Fr};
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use crate::drgraph::{new_seed, BASE_DEGREE};
+    use crate::fr32::fr_into_bytes;
+    use crate::hasher::{Blake2sHasher, PedersenHasher, PoseidonHasher, Sha256Hasher};
+    use crate::porep::PoRep;
+    use crate::proof::ProofScheme;
+    use crate::stacked::{PrivateInputs, PublicInputs, SetupParams, TemporaryAuxCache, EXP_DEGREE};
+
+    const DEFAULT_STACKED_LAYERS: usize = 11;
+
+    #[test]
+    fn test_calculate_fixed_challenges() {
+        let layer_challenges = LayerChallenges::new(10, 333);
+        let expected = 333;
+
+        let calculated_count = layer_challenges.challenges_count_all();
+        assert_eq!(expected as usize, calculated_count);
+    }
+
+    #[test]
+    fn extract_all_pedersen() {
+        test_extract_all::<PedersenHasher>();
+    }
+
+    #[test]
+    fn extract_all_sha256() {
+        test_extract_all::<Sha256Hasher>();
+    }
+
+    #[test]
+    fn extract_all_blake2s() {
+        test_extract_all::<Blake2sHasher>();
+    }
+
+    #[test]
+    fn extract_all_poseidon() {
+        test_extract_all::<PoseidonHasher>();
+    }
+
+    fn test_extract_all<H: 'static + Hasher>() {
+        // femme::pretty::Logger::new()
+        //     .start(log::LevelFilter::Trace)
+        //     .ok();
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let nodes = 16;
+
+        let data: Vec<u8> = (0..nodes)
+            .flat_map(|_| {
+                let v: H::Domain = H::Domain::random(rng);
+                v.into_bytes()
+            })
+            .collect();
+        let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+
+        // create a copy, so we can compare roundtrips
+        let mut data_copy = data.clone();
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: challenges.clone(),
+        };
+
+        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+        // MT for original data is always named tree-d, and it will be
+        // referenced later in the process as such.
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+        );
+
+        StackedDrg::<H, Blake2sHasher>::replicate(
+            &pp,
+            &replica_id,
+            (&mut data_copy[..]).into(),
+            None,
+            Some(config.clone()),
+        )
+        .expect("replication failed");
+
+        assert_ne!(data, data_copy);
+
+        let decoded_data = StackedDrg::<H, Blake2sHasher>::extract_all(
+            &pp,
+            &replica_id,
+            data_copy.as_mut_slice(),
+            Some(config.clone()),
+        )
+        .expect("failed to extract data");
+
+        assert_eq!(data, decoded_data);
+    }
+
+    #[test]
+    fn labeling_modes_match_pedersen() {
+        test_labeling_modes_match::<PedersenHasher>();
+    }
+
+    #[test]
+    fn labeling_modes_match_poseidon() {
+        test_labeling_modes_match::<PoseidonHasher>();
+    }
+
+    fn test_labeling_modes_match<H: 'static + Hasher>() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let nodes = 64;
+        let layer_challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: layer_challenges.clone(),
+        };
+        let pp = StackedDrg::<H, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+        let generate = |mode: LabelingMode| {
+            let cache_dir = tempfile::tempdir().unwrap();
+            let config = StoreConfig::new(
+                cache_dir.path(),
+                CacheKey::CommDTree.to_string(),
+                StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+            );
+            let (labels, _) = StackedDrg::<H, Blake2sHasher>::generate_labels(
+                &pp.graph,
+                &layer_challenges,
+                &replica_id,
+                config,
+                mode,
+            )
+            .expect("failed to generate labels");
+
+            (1..=layer_challenges.layers())
+                .map(|layer| {
+                    labels
+                        .labels_for_layer(layer)
+                        .read_range(0..nodes)
+                        .expect("failed to read labels")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let expected = generate(LabelingMode::InMemory);
+
+        // Windows smaller than, not dividing, and larger than the layer.
+        for memory_budget in &[0, 3 * NODE_SIZE, 7 * NODE_SIZE, 1024 * NODE_SIZE] {
+            let bounded = generate(LabelingMode::Bounded {
+                memory_budget: *memory_budget,
+            });
+            assert_eq!(expected, bounded, "budget {}", memory_budget);
+        }
+
+        // Lookaheads smaller than, not dividing, and larger than the layer.
+        for lookahead in &[0, 1, 7, 1024] {
+            let prefetched = generate(LabelingMode::Prefetch(PrefetchConfig {
+                lookahead: *lookahead,
+                ..Default::default()
+            }));
+            assert_eq!(expected, prefetched, "lookahead {}", lookahead);
+        }
+    }
+
+    #[test]
+    fn replicate_phase1_multi_matches_single() {
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+        let nodes = 64;
+        let layer_challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+
+        let sp = SetupParams {
+            nodes,
+            degree: BASE_DEGREE,
+            expansion_degree: EXP_DEGREE,
+            seed: new_seed(),
+            layer_challenges: layer_challenges.clone(),
+        };
+        let pp = StackedDrg::<PoseidonHasher, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+        let replica_ids: Vec<<PoseidonHasher as Hasher>::Domain> =
+            (0..3).map(|_| Domain::random(rng)).collect();
+        let cache_dirs: Vec<_> = (0..replica_ids.len() * 2)
+            .map(|_| tempfile::tempdir().unwrap())
+            .collect();
+        let configs: Vec<_> = cache_dirs
+            .iter()
+            .map(|dir| {
+                StoreConfig::new(
+                    dir.path(),
+                    CacheKey::CommDTree.to_string(),
+                    StoreConfig::default_cached_above_base_layer(nodes, BINARY_ARITY),
+                )
+            })
+            .collect();
+        let (single_configs, multi_configs) = configs.split_at(replica_ids.len());
+
+        let read_labels = |labels: &Labels<PoseidonHasher>| {
+            (1..=layer_challenges.layers())
+                .map(|layer| {
+                    labels
+                        .labels_for_layer(layer)
+                        .and_then(|store| store.read_range(0..nodes))
+                        .expect("failed to read labels")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let multi = StackedDrg::<PoseidonHasher, Blake2sHasher>::replicate_phase1_multi(
+            &pp,
+            &replica_ids,
+            multi_configs.to_vec(),
+        )
+        .expect("multi labeling failed");
+        assert_eq!(multi.len(), replica_ids.len());
+
+        for ((replica_id, config), multi_labels) in replica_ids
+            .iter()
+            .zip(single_configs.iter())
+            .zip(multi.iter())
+        {
+            let single = StackedDrg::<PoseidonHasher, Blake2sHasher>::replicate_phase1(
+                &pp,
+                replica_id,
+                config.clone(),
+            )
+            .expect("labeling failed");
+
+            assert_eq!(read_labels(&single), read_labels(multi_labels));
+        }
+    }
+
+    fn prove_verify_fixed(n: usize) {
+        let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+
+        test_prove_verify::<PedersenHasher, typenum::U4>(n, challenges.clone());
+        test_prove_verify::<Sha256Hasher, typenum::U4>(n, challenges.clone());
+        test_prove_verify::<Blake2sHasher, typenum::U4>(n, challenges.clone());
+        test_prove_verify::<PoseidonHasher, typenum::U4>(n, challenges.clone());
+    }
+
+    #[test]
+    fn prove_verify_oct_tree_r_last() {
+        // 8-ary trees need a power of 8 leaves, tree_c a power of 4.
+        let challenges = LayerChallenges::new(DEFAULT_STACKED_LAYERS, 5);
+
+        test_prove_verify::<PedersenHasher, typenum::U8>(64, challenges.clone());
+        test_prove_verify::<PoseidonHasher, typenum::U8>(64, challenges);
+    }
+
+    fn test_prove_verify<H: 'static + Hasher, U: 'static + typenum::Unsigned + Send + Sync>(
+        n: usize,
+        challenges: LayerChallenges,
+    ) {
+        // This will be called multiple times, only the first one succeeds, and that is ok.
+        // femme::pretty::Logger::new()
+        //     .start(log::LevelFilter::Trace)
+        //     .ok();
+
+        let rng = &mut XorShiftRng::from_seed(crate::TEST_SEED);
+
+        let degree = BASE_DEGREE;
+        let expansion_degree = EXP_DEGREE;
+        let replica_id: H::Domain = H::Domain::random(rng);
+        let data: Vec<u8> = (0..n)
+            .flat_map(|_| fr_into_bytes::<Bls12>(&Fr::random(rng)))
+            .collect();
+
+        // create a copy, so we can compare roundtrips
+        let mut data_copy = data.clone();
+        let partitions = 2;
+
+        let sp = SetupParams {
+            nodes: n,
+            degree,
+            expansion_degree,
+            seed: new_seed(),
+            layer_challenges: challenges.clone(),
+        };
+
+        // MT for original data is always named tree-d, and it will be
+        // referenced later in the process as such.
+        let cache_dir = tempfile::tempdir().unwrap();
+        let config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            StoreConfig::default_cached_above_base_layer(n, BINARY_ARITY),
+        );
+
+        let pp = StackedDrg::<H, Blake2sHasher, U>::setup(&sp).expect("setup failed");
+        let (tau, (p_aux, t_aux)) = StackedDrg::<H, Blake2sHasher, U>::replicate(
+            &pp,
+            &replica_id,
+            (&mut data_copy[..]).into(),
+            None,
+            Some(config),
+        )
+        .expect("replication failed");
+        assert_ne!(data, data_copy);
+
+        let seed = rng.gen();
+
+        let pub_inputs = PublicInputs::<H::Domain, <Blake2sHasher as Hasher>::Domain> {
+            replica_id,
+            seed,
+            tau: Some(tau),
+            k: None,
+        };
+
+        // Convert TemporaryAux to TemporaryAuxCache, which instantiates all
+        // elements based on the configs stored in TemporaryAux.
+        let t_aux = TemporaryAuxCache::<H, Blake2sHasher, U>::new(&t_aux)
+            .expect("failed to restore contents of t_aux");
+
+        let priv_inputs = PrivateInputs { p_aux, t_aux };
+
+        let all_partition_proofs = &StackedDrg::<H, Blake2sHasher, U>::prove_all_partitions(
+            &pp,
+            &pub_inputs,
+            &priv_inputs,
+            partitions,
+        )
+        .expect("failed to generate partition proofs");
+
+        let proofs_are_valid = StackedDrg::<H, Blake2sHasher, U>::verify_all_partitions(
+            &pp,
+            &pub_inputs,
+            all_partition_proofs,
+        )
+        .expect("failed to verify partition proofs");
+
+        assert!(proofs_are_valid);
+    }
+
+    table_tests! {
+        prove_verify_fixed{
+           prove_verify_fixed_64_4(4);
+        }
+    }
+
+    #[test]
+    // We are seeing a bug, in which setup never terminates for some sector sizes.
+    // This test is to debug that and should remain as a regression teset.
+    fn setup_terminates() {
+        let degree = BASE_DEGREE;
+        let expansion_degree = EXP_DEGREE;
+        let nodes = 1024 * 1024 * 32 * 8; // This corresponds to 8GiB sectors (32-byte nodes)
+        let layer_challenges = LayerChallenges::new(10, 333);
+        let sp = SetupParams {
+            nodes,
+            degree,
+            expansion_degree,
+            seed: new_seed(),
+            layer_challenges: layer_challenges.clone(),
+        };
+
+        // When this fails, the call to setup should panic, but seems to actually hang (i.e. neither return nor panic) for some reason.
+        // When working as designed, the call to setup returns without error.
+        let _pp = StackedDrg::<PedersenHasher, Blake2sHasher>::setup(&sp).expect("setup failed");
+    }
+}
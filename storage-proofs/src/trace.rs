@@ -0,0 +1,262 @@
+//! Spans for the stages of sealing and PoSt, written to a Chrome trace-event file.
+//!
+//! Tracing is off unless `FIL_PROOFS_TRACE_FILE` (or `trace_file` in the settings file) names a
+//! file, or `start` is called. Every `Span` dropped while tracing is on is then appended to that
+//! file as a complete ("X") event with its thread and arguments, so that a seal can be loaded
+//! into `chrome://tracing` or Perfetto. Spans on the same thread nest by time.
+//!
+//! Events are flushed as they are written, and the file is a JSON array which `finish`
+//! terminates. Trace viewers also load files of processes which never called `finish`.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde_json::{json, Map, Value};
+
+use crate::settings;
+
+lazy_static! {
+    static ref TRACER: Mutex<Option<Tracer>> = Mutex::new(None);
+    static ref EPOCH: Instant = Instant::now();
+}
+
+static INIT: Once = Once::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// Small sequential ids, which trace viewers show more readably than `ThreadId`s.
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+struct Tracer {
+    writer: BufWriter<File>,
+    events: usize,
+    named_threads: HashSet<u64>,
+}
+
+impl Tracer {
+    fn write(&mut self, event: &Value) -> Result<()> {
+        if self.events > 0 {
+            self.writer.write_all(b",\n")?;
+        }
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.flush()?;
+        self.events += 1;
+
+        Ok(())
+    }
+
+    fn write_span(&mut self, span: &Span, start: Instant, tid: u64) -> Result<()> {
+        if self.named_threads.insert(tid) {
+            if let Some(name) = thread::current().name() {
+                self.write(&json!({
+                    "name": "thread_name",
+                    "ph": "M",
+                    "pid": std::process::id(),
+                    "tid": tid,
+                    "args": { "name": name },
+                }))?;
+            }
+        }
+
+        self.write(&json!({
+            "name": span.name,
+            "cat": "fil-proofs",
+            "ph": "X",
+            "ts": micros(start.duration_since(*EPOCH)),
+            "dur": micros(start.elapsed()),
+            "pid": std::process::id(),
+            "tid": tid,
+            "args": span.args,
+        }))
+    }
+}
+
+fn micros(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+/// Starts writing spans to `path`, replacing it. Finishes the current trace file, if any.
+pub fn start<P: AsRef<Path>>(path: P) -> Result<()> {
+    // An explicit trace file takes precedence over the settings, which are not read anymore.
+    INIT.call_once(|| {
+        settings::read_trace_file();
+    });
+    start_tracer(path.as_ref())
+}
+
+fn start_tracer(path: &Path) -> Result<()> {
+    lazy_static::initialize(&EPOCH);
+
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("could not create trace file {:?}", path))?,
+    );
+    writer.write_all(b"[\n")?;
+
+    let mut tracer = TRACER.lock().expect("trace lock poisoned");
+    if let Some(previous) = tracer.take() {
+        finish_tracer(previous)?;
+    }
+    *tracer = Some(Tracer {
+        writer,
+        events: 0,
+        named_threads: HashSet::new(),
+    });
+    ENABLED.store(true, Ordering::SeqCst);
+    info!("writing trace events to {:?}", path);
+
+    Ok(())
+}
+
+/// Stops tracing, and terminates the trace file.
+pub fn finish() -> Result<()> {
+    let mut tracer = TRACER.lock().expect("trace lock poisoned");
+    ENABLED.store(false, Ordering::SeqCst);
+    match tracer.take() {
+        Some(tracer) => finish_tracer(tracer),
+        None => Ok(()),
+    }
+}
+
+fn finish_tracer(mut tracer: Tracer) -> Result<()> {
+    tracer.writer.write_all(b"\n]\n")?;
+    tracer.writer.flush()?;
+
+    Ok(())
+}
+
+/// Returns true if spans are being written.
+pub fn is_enabled() -> bool {
+    INIT.call_once(|| {
+        if let Some(path) = settings::read_trace_file() {
+            if let Err(err) = start_tracer(&path) {
+                warn!("tracing disabled: {:#}", err);
+            }
+        }
+    });
+
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Opens a span called `name`, which lasts until it is dropped.
+pub fn span(name: &'static str) -> Span {
+    let start = if is_enabled() {
+        Some(Instant::now())
+    } else {
+        None
+    };
+
+    Span {
+        name,
+        args: Map::new(),
+        start,
+    }
+}
+
+/// A traced stage. Does nothing unless tracing was enabled when it was opened.
+#[must_use = "a span ends when it is dropped"]
+#[derive(Debug)]
+pub struct Span {
+    name: &'static str,
+    args: Map<String, Value>,
+    start: Option<Instant>,
+}
+
+impl Span {
+    /// Adds an argument, such as a sector id or partition, shown with the span.
+    pub fn arg<V: Into<Value>>(mut self, key: &str, value: V) -> Self {
+        if self.start.is_some() {
+            self.args.insert(key.to_string(), value.into());
+        }
+
+        self
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+
+        let tid = THREAD_ID.with(|id| *id);
+        let mut tracer = match TRACER.lock() {
+            Ok(tracer) => tracer,
+            Err(_) => return,
+        };
+        let result = match tracer.as_mut() {
+            Some(t) => t.write_span(self, start, tid),
+            None => return,
+        };
+        if let Err(err) = result {
+            warn!("tracing disabled, could not write trace event: {:#}", err);
+            ENABLED.store(false, Ordering::SeqCst);
+            *tracer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_spans() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.json");
+
+        start(&path).unwrap();
+        {
+            let _outer = span("test_outer").arg("sector_id", 7u64);
+            thread::Builder::new()
+                .name("test-worker".into())
+                .spawn(|| {
+                    let _inner = span("test_inner").arg("partition", 1usize);
+                })
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+        finish().unwrap();
+        // Spans outside a trace are not recorded.
+        drop(span("test_after"));
+
+        let events: Vec<Value> =
+            serde_json::from_reader(File::open(&path).unwrap()).expect("valid JSON");
+        let find = |name: &str| {
+            events
+                .iter()
+                .find(|event| event["name"] == name)
+                .unwrap_or_else(|| panic!("no {} event", name))
+        };
+
+        let outer = find("test_outer");
+        let inner = find("test_inner");
+        assert_eq!(outer["ph"], "X");
+        assert_eq!(outer["args"]["sector_id"], 7);
+        assert_eq!(inner["args"]["partition"], 1);
+        assert_ne!(outer["tid"], inner["tid"]);
+
+        let outer_start = outer["ts"].as_f64().unwrap();
+        let outer_end = outer_start + outer["dur"].as_f64().unwrap();
+        let inner_start = inner["ts"].as_f64().unwrap();
+        assert!(outer_start <= inner_start);
+        assert!(inner_start + inner["dur"].as_f64().unwrap() <= outer_end);
+
+        assert!(events.iter().any(|event| event["ph"] == "M"
+            && event["tid"] == inner["tid"]
+            && event["args"]["name"] == "test-worker"));
+        assert!(events.iter().all(|event| event["name"] != "test_after"));
+    }
+}